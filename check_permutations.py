@@ -0,0 +1,117 @@
+#!/usr/bin/env python3
+"""
+RISC Zero Auction Permutation Check
+
+This script generates random auction scenarios and runs each one through the
+host's --check-determinism mode, which executes the guest (no proving) on the
+scenario as given and on shuffled copies of its participants and requires
+byte-identical journals. A failing scenario is kept for reproduction.
+
+Usage:
+    python3 check_permutations.py [count] [seed]
+
+    count: Number of random scenarios to check (default: 50)
+    seed:  Seed of the scenario generator (default: 0)
+"""
+
+import json
+import random
+import subprocess
+import sys
+import tempfile
+from pathlib import Path
+from typing import Any, Dict
+
+ALGORITHMS = ["UniformPrice", "Merge", "McAfee", "Greedy"]
+PRICING_RULES = ["MidPoint", "BuyerBid", "SellerAsk", "SecondPrice"]
+TIE_BREAKS = ["ById", "ByQuantityDesc", "ByQuantityAsc", "Seeded"]
+
+
+def random_scenario(rng: random.Random, index: int) -> Dict[str, Any]:
+    """Build a valid random book with at least one buyer and one seller."""
+    count = rng.randint(2, 12)
+    roles = [0, 1] + [rng.choice([0, 1]) for _ in range(count - 2)]
+    participants = []
+    for participant_id, role in enumerate(roles):
+        quantity = rng.randint(1, 20)
+        participants.append({
+            "id": participant_id,
+            "role": role,
+            # Few distinct prices, so ties exercise the tie-break
+            "price": rng.choice([30, 40, 50, 60, 70] if role == 0 else [10, 20, 30, 40, 50]),
+            "quantity": quantity,
+            "in_coin": rng.choice([0, 200, 1000, 2000]) if role == 0 else 0,
+            "in_energy": rng.randint(0, quantity) if role == 1 else 0,
+        })
+
+    algorithm = rng.choice(ALGORITHMS)
+    config = {
+        "algorithm": algorithm,
+        "pricing_rule": rng.choice(PRICING_RULES),
+        "tie_break": rng.choice(TIE_BREAKS),
+        "seed": [rng.randint(0, 255) for _ in range(32)],
+    }
+    if algorithm != "Greedy":
+        config["allocation_rule"] = rng.choice(["Priority", "ProRata"])
+    if algorithm == "McAfee":
+        config["operator_id"] = 0
+
+    return {
+        "scenario_name": f"Random permutation check {index}",
+        "description": "Generated by check_permutations.py",
+        "config": config,
+        "participants": participants,
+    }
+
+
+def check_scenario(path: Path) -> bool:
+    """Run the host's determinism check on one scenario file."""
+    result = subprocess.run(
+        ["cargo", "run", "--release", "--quiet", "--bin", "host", "--",
+         str(path), "--check-determinism"],
+        capture_output=True,
+        text=True,
+    )
+    if result.returncode != 0:
+        print(result.stdout[-2000:])
+        print(result.stderr[-2000:])
+    return result.returncode == 0
+
+
+def main():
+    count = int(sys.argv[1]) if len(sys.argv) > 1 else 50
+    seed = int(sys.argv[2]) if len(sys.argv) > 2 else 0
+    rng = random.Random(seed)
+
+    print("═══════════════════════════════════════════════")
+    print("  Permutation Check")
+    print("═══════════════════════════════════════════════")
+    print(f"  Scenarios: {count} (generator seed {seed})\n")
+
+    failures = []
+    with tempfile.TemporaryDirectory() as work_dir:
+        for index in range(count):
+            scenario = random_scenario(rng, index)
+            path = Path(work_dir) / f"permutation_{index}.json"
+            with open(path, 'w') as f:
+                json.dump(scenario, f, indent=2)
+
+            if check_scenario(path):
+                print(f"  ✓ {index + 1}/{count}")
+                continue
+
+            kept = Path(f"permutation_failure_{seed}_{index}.json")
+            with open(kept, 'w') as f:
+                json.dump(scenario, f, indent=2)
+            print(f"  ✗ {index + 1}/{count}: journal depends on input order, saved to {kept}")
+            failures.append(kept)
+
+    print()
+    if failures:
+        print(f"✗ {len(failures)} of {count} scenarios failed")
+        sys.exit(1)
+    print(f"✓ All {count} scenarios are permutation invariant")
+
+
+if __name__ == "__main__":
+    main()
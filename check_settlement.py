@@ -0,0 +1,136 @@
+#!/usr/bin/env python3
+"""
+RISC Zero Auction Settlement Check
+
+This script generates random auction scenarios with trade fees, delivery
+losses and mixed initial balances (buyers holding energy, sellers coin) and
+runs each one through the host's --check-determinism mode. Every journal
+passes the guest's check_journal before it is committed, which rejects one
+whose coin or energy totals differ, so a run that exits with code 0 settled
+every trade without creating or destroying coin or energy.
+A scenario that does not exit cleanly is kept for reproduction.
+
+Usage:
+    python3 check_settlement.py [count] [seed]
+
+    count: Number of random scenarios to check (default: 50)
+    seed:  Seed of the scenario generator (default: 0)
+"""
+
+import json
+import random
+import subprocess
+import sys
+import tempfile
+from pathlib import Path
+from typing import Any, Dict
+
+ALGORITHMS = ["UniformPrice", "Merge", "McAfee"]
+PRICING_RULES = ["MidPoint", "BuyerBid", "SellerAsk", "SecondPrice"]
+# Odd rates so almost every fee rounds
+FEE_BPS = [0, 1, 3, 250, 333, 5000, 9999, 10000]
+LOSS_BPS = [0, 1, 333, 2500]
+
+
+def random_scenario(rng: random.Random, index: int) -> Dict[str, Any]:
+    """Build a valid random book plus a zero-quantity operator row."""
+    count = rng.randint(2, 12)
+    roles = [0, 1] + [rng.choice([0, 1]) for _ in range(count - 2)]
+    participants = []
+    for participant_id, role in enumerate(roles):
+        quantity = rng.randint(1, 50)
+        # Half the rows also hold the asset they are not trading
+        other = rng.choice([0, 1])
+        participants.append({
+            "id": participant_id,
+            "role": role,
+            # Odd prices, so notionals and mid-points rarely divide evenly
+            "price": rng.randint(1, 999) | 1,
+            "quantity": quantity,
+            "in_coin": rng.randint(0, 30000) if role == 0 else other * rng.randint(1, 500),
+            "in_energy": rng.randint(0, quantity) if role == 1 else other * rng.randint(1, 50),
+        })
+    # Collects the spread and fees (coin) and the delivery losses (energy)
+    operator_id = count
+    participants.append({
+        "id": operator_id,
+        "role": 1,
+        "price": 0,
+        "quantity": 0,
+        "in_coin": 0,
+        "in_energy": 0,
+    })
+
+    algorithm = rng.choice(ALGORITHMS)
+    config = {
+        "algorithm": algorithm,
+        "pricing_rule": rng.choice(PRICING_RULES),
+        "allocation_rule": rng.choice(["Priority", "ProRata"]),
+        "fee_bps": rng.choice(FEE_BPS),
+        "loss_bps": rng.choice(LOSS_BPS),
+        "operator_id": operator_id,
+        "loss_sink_id": operator_id,
+    }
+    if algorithm != "McAfee":
+        config["settlement"] = rng.choice(["Uniform", "PayAsBid"])
+
+    return {
+        "scenario_name": f"Random settlement check {index}",
+        "description": "Generated by check_settlement.py",
+        "config": config,
+        "participants": participants,
+    }
+
+
+def check_scenario(path: Path) -> bool:
+    """Execute one scenario file and require a clean exit."""
+    result = subprocess.run(
+        ["cargo", "run", "--release", "--quiet", "--bin", "host", "--",
+         str(path), "--check-determinism"],
+        capture_output=True,
+        text=True,
+    )
+    if result.returncode != 0 or "(Halted(0))" not in result.stdout:
+        print(result.stdout[-2000:])
+        print(result.stderr[-2000:])
+        return False
+    return True
+
+
+def main():
+    count = int(sys.argv[1]) if len(sys.argv) > 1 else 50
+    seed = int(sys.argv[2]) if len(sys.argv) > 2 else 0
+    rng = random.Random(seed)
+
+    print("═══════════════════════════════════════════════")
+    print("  Settlement Check")
+    print("═══════════════════════════════════════════════")
+    print(f"  Scenarios: {count} (generator seed {seed})\n")
+
+    failures = []
+    with tempfile.TemporaryDirectory() as work_dir:
+        for index in range(count):
+            scenario = random_scenario(rng, index)
+            path = Path(work_dir) / f"settlement_{index}.json"
+            with open(path, 'w') as f:
+                json.dump(scenario, f, indent=2)
+
+            if check_scenario(path):
+                print(f"  ✓ {index + 1}/{count}")
+                continue
+
+            kept = Path(f"settlement_failure_{seed}_{index}.json")
+            with open(kept, 'w') as f:
+                json.dump(scenario, f, indent=2)
+            print(f"  ✗ {index + 1}/{count}: settlement did not exit cleanly, saved to {kept}")
+            failures.append(kept)
+
+    print()
+    if failures:
+        print(f"✗ {len(failures)} of {count} scenarios failed")
+        sys.exit(1)
+    print(f"✓ All {count} scenarios conserve coin and energy")
+
+
+if __name__ == "__main__":
+    main()
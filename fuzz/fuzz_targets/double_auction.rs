@@ -0,0 +1,341 @@
+// ═══════════════════════════════════════════════════════════════════════════
+// Fuzz Target - run_double_auction on Arbitrary Books
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// The guest source is compiled natively (include!), so the fuzzer calls the
+// same run_double_auction the zkVM proves. Every book the guest's
+// validate_input accepts must clear without a panic (cargo fuzz builds with
+// overflow checks, so an output balance that underflows panics) and:
+//   • Clear to Ok, or stop with Overflow / TooManyOrders
+//   • Pass check_journal: version, shape, ids, block counts, input
+//     balances, order, Σ in == Σ out, fill counts summing to the blocks
+//     and an allocation every row's balances agree with
+//   • Pass check_rationality: nobody settles past its own bid or ask
+//   • Pass check_feasibility: every row reachable from its inputs by the
+//     allocation its energy column records
+//   • Pass check_audit: the ranked bids and asks an audit journal commits
+//     line up with the buyer and seller blocks, in price priority
+//   • Move energy one way: buyers never lose it, sellers never gain it,
+//     and without delivery losses Σ bought == Σ sold
+//   • Commit the volume its rows move: traded_volume == Σ sold (no prosumers)
+//   • Commit no surplus without a trade
+//
+// A failing book is printed as a scenario file; save it under
+// tests/fuzz_regressions/ (see the README there).
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+#![no_main]
+#![allow(dead_code)]
+
+use arbitrary::Arbitrary;
+
+include!("../../methods/guest/src/main.rs");
+
+/// Orders read per fuzzed book (further orders ignored)
+const MAX_FUZZ_ORDERS: usize = 16;
+
+/// A u64 that is mostly small, so books cross, and sometimes at the edge of its range
+#[derive(Clone, Copy, Debug)]
+struct Amount(u64);
+
+impl<'a> Arbitrary<'a> for Amount {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Amount(match u.int_in_range(0..=15u8)? {
+            0 => u64::MAX,
+            1 => u.arbitrary()?,
+            2..=4 => u.arbitrary::<u16>()? as u64,
+            _ => u.arbitrary::<u8>()? as u64,
+        }))
+    }
+}
+
+impl Amount {
+    fn value(self) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+struct FuzzOrder {
+    role: u8, // 255 exercises InvalidRole
+    price: Amount,
+    quantity: Amount,
+    in_coin: Amount,
+    in_energy: Amount,
+    reserve_price: Option<Amount>,
+    all_or_nothing: bool,
+    min_fill: u8,
+    steps: Vec<(Amount, Amount)>,
+    sell_price: Amount,
+    sell_quantity: Amount,
+    priority: bool,
+    flexible: bool,
+    valid_until_round: u8,
+    display_quantity: Option<u8>,
+    owner: Option<u8>,
+    fixed_cost: u16,
+    budget: Amount,
+    max_position_energy: Option<u8>,
+}
+
+#[derive(Arbitrary, Debug)]
+struct FuzzBook {
+    orders: Vec<FuzzOrder>,
+    plain: u8, // Most books leave the optional order and config features off
+    algorithm: u8,
+    pricing_rule: u8,
+    k: (u8, u8),
+    settlement: u8,
+    allocation_rule: u8,
+    tie_break: u8,
+    seed: [u8; 32],
+    objective: u8,
+    operator: Option<u8>, // Index into the orders
+    clock: (u8, u8),
+    price_band: (Option<u16>, Option<u16>),
+    band_no_trade: bool,
+    fee_bps: u16,
+    grid: (u8, u8),
+    round_to_grid: bool,
+    max_traded_volume: Option<u16>,
+    loss_bps: u16,
+    current_round: u8,
+    net_owners: bool,
+    congestion_rent: bool,
+    max_position_energy: Option<u8>,
+    effective_crossing: bool,
+    max_price: Option<Amount>,
+}
+
+impl FuzzBook {
+    /// Single-book input: no rounds, periods, commodities, zones, carbon or bundles
+    fn into_input(self) -> AuctionInput {
+        let exotic = self.plain % 4 == 0;
+        let mut participants: Vec<Participant> = self
+            .orders
+            .iter()
+            .take(MAX_FUZZ_ORDERS)
+            .enumerate()
+            .map(|(index, order)| Participant {
+                id: index as u32,
+                role: if order.role == 255 { 3 } else { (order.role % 3) as u32 },
+                price: order.price.value(),
+                quantity: order.quantity.value(),
+                in_coin: order.in_coin.value(),
+                in_energy: order.in_energy.value(),
+                capacity: 0,
+                in_capacity: 0,
+                reserve_price: order.reserve_price.map(Amount::value),
+                all_or_nothing: order.all_or_nothing,
+                min_fill: order.min_fill as u64,
+                steps: order
+                    .steps
+                    .iter()
+                    .take(MAX_STEPS)
+                    .map(|(price, quantity)| (price.value(), quantity.value()))
+                    .collect(),
+                commodity: 0,
+                sell_price: order.sell_price.value(),
+                sell_quantity: order.sell_quantity.value(),
+                zone: 0,
+                priority: order.priority,
+                flexible: order.flexible,
+                carbon_per_unit: 0,
+                carbon_budget: None,
+                valid_until_round: order.valid_until_round as u32,
+                display_quantity: order.display_quantity.map(|q| q as u64),
+                owner: order.owner.map(|owner| (owner % 4) as u32),
+                fixed_cost: order.fixed_cost as u64,
+                budget: order.budget.value(),
+                max_position_energy: order.max_position_energy.map(|q| q as u64),
+                periods: Vec::new(),
+                salt: [0; 32],
+            })
+            .collect();
+        if !exotic {
+            for p in &mut participants {
+                p.reserve_price = None;
+                p.all_or_nothing = false;
+                p.min_fill = 0;
+                p.steps.clear();
+                p.priority = false;
+                p.flexible = false;
+                p.valid_until_round = u32::MAX;
+                p.display_quantity = None;
+                p.owner = None;
+                p.fixed_cost = 0;
+                p.budget = 0;
+                p.max_position_energy = None;
+                if p.role != 2 {
+                    p.sell_price = 0;
+                    p.sell_quantity = 0;
+                }
+            }
+        }
+
+        let pick = |choice: u8, count: u8| choice % count;
+        let operator_id = self
+            .operator
+            .map(|index| index as u32)
+            .filter(|&id| (id as usize) < participants.len());
+        let config = AuctionConfig {
+            algorithm: match pick(self.algorithm, 6) {
+                0 => Algorithm::UniformPrice,
+                1 => Algorithm::McAfee,
+                2 => Algorithm::DutchClock,
+                3 => Algorithm::EnglishClock,
+                4 => Algorithm::Greedy,
+                _ => Algorithm::Merge,
+            },
+            pricing_rule: match pick(self.pricing_rule, 5) {
+                0 => PricingRule::MidPoint,
+                1 => PricingRule::BuyerBid,
+                2 => PricingRule::SellerAsk,
+                3 => PricingRule::KDouble,
+                _ => PricingRule::SecondPrice,
+            },
+            k_numerator: self.k.0.min(self.k.1) as u64,
+            k_denominator: self.k.1.max(1) as u64,
+            settlement: match pick(self.settlement, 3) {
+                0 => Settlement::Uniform,
+                1 => Settlement::PayAsBid,
+                _ => Settlement::Vcg,
+            },
+            operator_id,
+            clock_increment: self.clock.0 as u64,
+            clock_tick: self.clock.1.max(1) as u64,
+            price_floor: self.price_band.0.map(|p| p as u64),
+            price_cap: self.price_band.1.map(|p| p as u64),
+            band_policy: if self.band_no_trade {
+                BandPolicy::NoTrade
+            } else {
+                BandPolicy::Clamp
+            },
+            fee_bps: (self.fee_bps % 10_001) as u64,
+            price_tick: self.grid.0.max(1) as u64,
+            quantity_lot: self.grid.1.max(1) as u64,
+            round_to_grid: self.round_to_grid,
+            max_traded_volume: self.max_traded_volume.map_or(u64::MAX, |v| v as u64),
+            loss_bps: (self.loss_bps % 10_001) as u64,
+            loss_sink_id: operator_id,
+            allocation_rule: match pick(self.allocation_rule, 3) {
+                0 => AllocationRule::Priority,
+                1 => AllocationRule::ProRata,
+                _ => AllocationRule::MaxMin,
+            },
+            tie_break: match pick(self.tie_break, 4) {
+                0 => TieBreak::ById,
+                1 => TieBreak::ByQuantityDesc,
+                2 => TieBreak::ByQuantityAsc,
+                _ => TieBreak::Seeded,
+            },
+            seed: self.seed,
+            objective: match pick(self.objective, 3) {
+                0 => Objective::FirstCrossing,
+                1 => Objective::MaxVolume,
+                _ => Objective::MaxSurplus,
+            },
+            current_round: self.current_round as u32,
+            net_owners: self.net_owners,
+            congestion_rent: self.congestion_rent,
+            max_position_energy: self.max_position_energy.map(|q| q as u64),
+            effective_crossing: self.effective_crossing,
+            max_price: self.max_price.map_or(DEFAULT_MAX_PRICE, Amount::value),
+            ..AuctionConfig::default()
+        };
+        let config = if exotic {
+            config
+        } else {
+            AuctionConfig {
+                settlement: Settlement::Uniform,
+                fee_bps: 0,
+                loss_bps: 0,
+                price_floor: None,
+                price_cap: None,
+                price_tick: 1,
+                quantity_lot: 1,
+                max_traded_volume: u64::MAX,
+                current_round: 0,
+                net_owners: false,
+                congestion_rent: false,
+                max_position_energy: None,
+                effective_crossing: false,
+                ..config
+            }
+        };
+
+        AuctionInput {
+            participants,
+            config,
+            rounds: Vec::new(),
+            commitments: Vec::new(),
+            output_salt: [0; 32],
+        }
+    }
+}
+
+/// The input as a scenario file the host can load
+fn scenario_json(input: &AuctionInput) -> String {
+    let scenario = serde_json::json!({
+        "scenario_name": "Fuzz regression",
+        "description": "Found by fuzz/fuzz_targets/double_auction.rs",
+        "config": input.config,
+        "participants": input.participants,
+    });
+    serde_json::to_string_pretty(&scenario).unwrap()
+}
+
+/// Check every invariant of one cleared book, panicking with the scenario on failure
+fn check_book(input: &AuctionInput) {
+    let fail = |what: String| -> ! {
+        panic!("{}\nscenario:\n{}", what, scenario_json(input));
+    };
+    if validate_input(input).is_err() {
+        return; // Rejected before clearing: an input error, not a finding
+    }
+    let journal = match run_double_auction(input) {
+        Ok(journal) => journal,
+        Err(AuctionError::Overflow) | Err(AuctionError::TooManyOrders(_)) => return,
+        Err(error) => fail(format!("clearing failed: {:?}", error)),
+    };
+    if let Err(error) = check_journal(&journal, &input.participants, &input.config) {
+        fail(format!("journal rejected: {:?}", error));
+    }
+    if let Err(error) = check_rationality(&journal, &input.participants, &input.config) {
+        fail(format!("settled past a quote: {:?}", error));
+    }
+    if let Err(error) = check_feasibility(&journal, &input.participants, &input.config) {
+        fail(format!("row out of reach: {:?}", error));
+    }
+    if let Err(error) = check_audit(&journal, &audit_journal(input), &input.participants) {
+        fail(format!("audit journal rejected: {:?}", error));
+    }
+
+    let rows = protocol_order(&input.participants, &input.config);
+    let (mut bought, mut sold) = (0u128, 0u128);
+    for (row, p) in rows.iter().enumerate() {
+        if Some(p.id) == input.config.loss_sink_id || p.role == 2 {
+            continue;
+        }
+        let (before, after) = (journal.in_energy[row], journal.out_energy[row]);
+        match p.role {
+            0 if after < before => fail(format!("buyer {} lost energy", p.id)),
+            1 if after > before => fail(format!("seller {} gained energy", p.id)),
+            0 => bought += (after - before) as u128,
+            _ => sold += (before - after) as u128,
+        }
+    }
+    let netted = rows.iter().any(|p| p.role == 2);
+    if input.config.loss_bps == 0 && !netted && bought != sold {
+        fail(format!("{} units bought but {} sold", bought, sold));
+    }
+    if !netted && journal.traded_volume as u128 != sold {
+        fail(format!("traded_volume {} but {} sold", journal.traded_volume, sold));
+    }
+    if journal.traded_volume == 0 && journal.surplus != 0 {
+        fail(format!("surplus {} without a trade", journal.surplus));
+    }
+}
+
+libfuzzer_sys::fuzz_target!(|book: FuzzBook| check_book(&book.into_input()));
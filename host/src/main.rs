@@ -3,41 +3,40 @@
 // This program runs the double auction guest program in the RISC Zero zkVM
 // and generates a cryptographic receipt proving correct execution.
 
-use methods::DOUBLE_AUCTION_GUEST_ELF;
-use risc0_zkvm::{default_prover, ExecutorEnv, InnerReceipt, ProverOpts, recursion::identity_p254};
+use methods::{DOUBLE_AUCTION_GUEST_ELF, DOUBLE_AUCTION_GUEST_ID};
+use risc0_zkvm::{
+    default_executor, default_prover, recursion::identity_p254, sha::Digest, ExecutorEnv, ExitCode,
+    InnerReceipt, ProverOpts,
+};
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs;
 use std::time::Instant;
+use types::*;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Participant {
-    pub id: u32,
-    pub role: u32,
-    pub price: u64,
-    pub quantity: u64,
-    pub in_coin: u64,
-    pub in_energy: u64,
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct AuctionInput {
-    pub participants: Vec<Participant>,
-}
+/// Journal rows printed with the results (journal.json holds every row)
+const MAX_PRINTED_ROWS: usize = 32;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AuctionScenario {
     pub scenario_name: String,
     pub description: String,
     pub participants: Vec<Participant>,
+    #[serde(default)]
+    pub config: AuctionConfig,
+    #[serde(default)]
+    pub rounds: Vec<RoundInput>,
+    #[serde(default)]
+    pub commitments: Vec<Digest>,
+    #[serde(default)]
+    pub output_salt: [u8; 32],
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct PublicJournal {
-    pub in_coin: Vec<u64>,
-    pub in_energy: Vec<u64>,
-    pub out_coin: Vec<u64>,
-    pub out_energy: Vec<u64>,
+pub struct PrivateOutputsFile {
+    pub outputs_digest: String, // Hex, as committed in the PrivateJournal
+    pub salt: String,           // Hex output_salt, which opens the digest
+    pub rows: Vec<PrivateRow>,  // Protocol order, as in PublicJournal
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -52,6 +51,28 @@ pub struct BenchmarkResult {
     pub total_time_ms: u64,
     pub receipt_size_bytes: usize,
     pub journal_size_bytes: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub round_cycles: Vec<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub period_cycles: Vec<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub separate_period_cycles: Vec<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guest_exit_code: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clearing_price: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traded_volume: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub journal_status: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trade_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fees_collected: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub surplus: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill_counts: Option<FillCounts>,
     pub timestamp: String,
 }
 
@@ -64,9 +85,20 @@ fn main() {
 
     // Parse command line arguments
     let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("seal") => return seal_scenario(&args[2..]),
+        Some("verify-outputs") => return verify_outputs(&args[2..]),
+        _ => {}
+    }
     let mut scenario_file = "auction_scenario.json";
     let mut benchmark_mode = false;
     let mut benchmark_output = String::new();
+    let mut ignore_participant_limit = false;
+    let mut skip_validation = false;
+    let mut check_determinism = false;
+    let mut expected_mechanism = ExpectedMechanism::default();
+    let mut borsh_format = false;
+    let mut round_id = None; // --round-id, written over the scenario's config.round_id
 
     let mut i = 1;
     while i < args.len() {
@@ -78,6 +110,37 @@ fn main() {
                     i += 1;
                 }
             }
+            "--ignore-participant-limit" => ignore_participant_limit = true,
+            "--skip-validation" => skip_validation = true,
+            "--check-determinism" => check_determinism = true,
+            "--expect-algorithm-id" if i + 1 < args.len() => {
+                let id = args[i + 1]
+                    .parse()
+                    .expect("--expect-algorithm-id takes a u32");
+                expected_mechanism.algorithm_id = Some(id);
+                i += 1;
+            }
+            "--journal-format" if i + 1 < args.len() => {
+                borsh_format = match args[i + 1].as_str() {
+                    "json" => false,
+                    "borsh" => true,
+                    other => panic!("--journal-format takes json or borsh, not {}", other),
+                };
+                i += 1;
+            }
+            "--expect-config-digest" if i + 1 < args.len() => {
+                expected_mechanism.config_digest = Some(args[i + 1].to_lowercase());
+                i += 1;
+            }
+            "--round-id" if i + 1 < args.len() => {
+                round_id = Some(args[i + 1].parse().expect("--round-id takes a u64"));
+                i += 1;
+            }
+            "--expect-round-id" if i + 1 < args.len() => {
+                let id = args[i + 1].parse().expect("--expect-round-id takes a u64");
+                expected_mechanism.round_id = Some(id);
+                i += 1;
+            }
             arg if !arg.starts_with("--") => {
                 scenario_file = arg;
             }
@@ -87,19 +150,274 @@ fn main() {
     }
 
     // Load scenario
-    let scenario = load_scenario(scenario_file).expect("Failed to load scenario");
+    let mut scenario = load_scenario(scenario_file).expect("Failed to load scenario");
+    if let Some(id) = round_id {
+        scenario.config.round_id = id;
+    }
+    // A proof for another round would be refused after proving: refuse it before
+    if let Some(id) = expected_mechanism.round_id {
+        if id != scenario.config.round_id {
+            println!(
+                "✗ Refusing to prove: the scenario's round id is {}, --expect-round-id gives {} (set it with --round-id)",
+                scenario.config.round_id, id
+            );
+            std::process::exit(1);
+        }
+    }
+    if skip_validation {
+        println!(
+            "  Scenario validation: skipped (the guest checks ids, roles, limits, the settlement bound, commitments and carbon or secondary-round combinations; the host's other feature rules are not applied)"
+        );
+    } else {
+        validate_scenario(&scenario).expect("Invalid scenario");
+    }
     println!("✓ Loaded scenario: {}", scenario.scenario_name);
-    println!("  Participants: {}\n", scenario.participants.len());
+    println!("  Participants: {}", scenario.participants.len());
+    if !scenario.rounds.is_empty() {
+        println!("  Rounds: {}", scenario.rounds.len());
+    }
+    if ignore_participant_limit {
+        println!(
+            "  Participant limit: not checked on the host (guest limit {})",
+            MAX_PARTICIPANTS
+        );
+    } else {
+        check_participant_limit(&scenario).expect("Refusing to prove");
+    }
+    let multi_period = is_multi_period(&scenario.participants);
+    if multi_period {
+        println!(
+            "  Periods: {} (cleared in order, coin shared)",
+            period_count(&scenario.participants)
+        );
+    }
+    let multi_commodity = is_multi_commodity(&scenario.participants);
+    if multi_commodity {
+        println!("  Commodities: multiple (cleared in ascending id order)");
+    }
+    assert!(
+        !borsh_format || (scenario.rounds.is_empty() && !multi_period && !multi_commodity),
+        "--journal-format borsh writes one PublicJournal: not supported with rounds, periods or commodities"
+    );
+    assert!(
+        !borsh_format
+            || !matches!(
+                scenario.config.journal_variant,
+                JournalVariant::Private | JournalVariant::Quantized(_)
+            ),
+        "--journal-format borsh writes the exact rows: not supported with the Private or Quantized journal"
+    );
+    let zonal = is_zonal(&scenario.participants);
+    if zonal {
+        println!(
+            "  Zones: multiple, {} transfer link(s)",
+            scenario.config.transfer_capacities.len()
+        );
+    }
+    println!("  Algorithm: {:?}", scenario.config.algorithm);
+    println!("  Pricing rule: {:?}", scenario.config.pricing_rule);
+    if scenario.config.pricing_rule == PricingRule::KDouble {
+        println!(
+            "  k: {}/{}",
+            scenario.config.k_numerator, scenario.config.k_denominator
+        );
+    }
+    match scenario.config.algorithm {
+        Algorithm::DutchClock => println!(
+            "  Clock: ask + {}, tick {}",
+            scenario.config.clock_increment, scenario.config.clock_tick
+        ),
+        Algorithm::EnglishClock => println!("  Clock: ask, tick {}", scenario.config.clock_tick),
+        _ => {}
+    }
+    if scenario.config.bundles {
+        println!("  Bundles: energy + capacity");
+    }
+    let carbon_priced = is_carbon_priced(&scenario.participants);
+    if carbon_priced {
+        println!("  Carbon: budgets and intensities co-cleared");
+    }
+    if scenario.config.price_floor.is_some() || scenario.config.price_cap.is_some() {
+        println!(
+            "  Price band: [{}, {}] ({:?})",
+            scenario.config.price_floor.unwrap_or(0),
+            scenario
+                .config
+                .price_cap
+                .map_or("∞".to_string(), |cap| cap.to_string()),
+            scenario.config.band_policy
+        );
+    }
+    if scenario.config.price_offset > 0 {
+        println!(
+            "  Price offset: {} (encoded price − offset = real price)",
+            scenario.config.price_offset
+        );
+    }
+    println!("  Settlement: {:?}", scenario.config.settlement);
+    if scenario.config.objective != Objective::FirstCrossing {
+        println!("  Objective: {:?}", scenario.config.objective);
+    }
+    if scenario.config.current_round > 0 {
+        println!("  Current round: {}", scenario.config.current_round);
+    }
+    if scenario.config.net_owners {
+        println!("  Owner netting: enabled");
+    }
+    if scenario.config.allocation_rule != AllocationRule::Priority {
+        println!("  Allocation: {:?}", scenario.config.allocation_rule);
+    }
+    if scenario.config.tie_break != TieBreak::ById {
+        println!("  Tie-break: {:?}", scenario.config.tie_break);
+    }
+    if scenario.config.tie_break == TieBreak::Seeded {
+        let seed: String = scenario
+            .config
+            .seed
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        println!("  Seed: {seed}");
+    }
+    if scenario.config.fee_bps > 0 {
+        println!("  Fee: {} bps per side", scenario.config.fee_bps);
+    }
+    if scenario.config.price_tick > 1 || scenario.config.quantity_lot > 1 {
+        println!(
+            "  Grid: price tick {}, quantity lot {}{}",
+            scenario.config.price_tick,
+            scenario.config.quantity_lot,
+            if scenario.config.round_to_grid {
+                " (rounding)"
+            } else {
+                ""
+            }
+        );
+    }
+    if scenario.config.loss_bps > 0 {
+        println!(
+            "  Delivery loss: {} bps (sink: participant {})",
+            scenario.config.loss_bps,
+            scenario
+                .config
+                .loss_sink_id
+                .map_or("none".to_string(), |id| id.to_string())
+        );
+    }
+    if scenario.config.max_traded_volume < u64::MAX {
+        println!("  Max traded volume: {}", scenario.config.max_traded_volume);
+    }
+    if scenario.config.congestion_rent {
+        println!("  Congestion rent: to the operator");
+    }
+    if scenario.config.effective_crossing {
+        println!("  Crossing search: effective caps");
+    }
+    if scenario.config.max_price != DEFAULT_MAX_PRICE {
+        println!("  Max price: {}", scenario.config.max_price);
+    }
+    if !scenario.config.check_rationality {
+        println!("  Rationality check: off");
+    }
+    if !scenario.config.strict {
+        println!("  Strict mode: off (a book failing a check settles as no trade)");
+        let above = prices_above_max(&scenario);
+        if !above.is_empty() {
+            println!(
+                "  Kept out of the book (price above max_price): participants {}",
+                above.join(", ")
+            );
+        }
+    }
+    if let Some(index) = scenario.config.settlement_price {
+        println!("  Settlement price: {} (external index)", index);
+    }
+    if scenario.config.quantity_scale > 1 {
+        println!(
+            "  Quantity scale: {} raw units per energy unit",
+            scenario.config.quantity_scale
+        );
+    }
+    if let Some(concession) = scenario.config.secondary_concession {
+        println!("  Secondary round: concession {}", concession);
+    }
+    if let Some(limit) = scenario.config.max_position_energy {
+        println!("  Position limit: {}", limit);
+    }
+    if scenario.config.curtailment_comp_per_unit > 0 || scenario.config.funding_fee_bps > 0 {
+        println!(
+            "  Curtailment: {} per unit, funded by {} bps",
+            scenario.config.curtailment_comp_per_unit, scenario.config.funding_fee_bps
+        );
+    }
+    if let Some(operator_id) = scenario.config.operator_id {
+        println!("  Operator: participant {}", operator_id);
+    }
+    if !scenario.commitments.is_empty() {
+        println!(
+            "  Sealed bids: {} commitments, opened in the guest",
+            scenario.commitments.len()
+        );
+    }
+    if scenario.config.audit {
+        println!("  Audit: the ranked bids and asks committed after the journal");
+    }
+    // A scenario without a salt of its own gets a fresh one, kept in private_outputs.json
+    let mut output_salt = scenario.output_salt;
+    if scenario.config.journal_variant == JournalVariant::Private {
+        if output_salt == [0; 32] {
+            output_salt = random_bytes();
+        }
+        println!("  Private outputs: aggregates in the journal, rows in private_outputs.json");
+    }
+    if scenario.config.keccak_digest {
+        println!("  Keccak digest: keccak256 of the packed journal, committed last");
+    }
+    if let JournalVariant::Quantized(scale) = scenario.config.journal_variant {
+        println!("  Quantized journal: balances in units of {}", scale);
+    }
+    if scenario.config.journal_variant == JournalVariant::Slim {
+        println!("  Slim journal: no input balances, re-derived from this scenario");
+    }
+    if scenario.config.round_id != 0 {
+        println!(
+            "  Round id: {} (echoed into the journal)",
+            scenario.config.round_id
+        );
+    }
+    println!();
+
+    if check_determinism {
+        println!("▸ Checking determinism (executor only, no proof)...");
+        let (runs, exit_code) = check_reproducible(&scenario).expect("Determinism check failed");
+        println!(
+            "✓ {} executions committed byte-identical journals ({:?})",
+            runs, exit_code
+        );
+        return;
+    }
 
-    // Prepare input for guest (only id, role, price, quantity, in_coin, in_energy)
+    // Prepare input for guest (participants plus auction config)
     let guest_input = AuctionInput {
         participants: scenario.participants.clone(),
+        config: scenario.config.clone(),
+        rounds: scenario.rounds.clone(),
+        commitments: scenario.commitments.clone(),
+        output_salt,
     };
 
-    // Build executor environment
+    // The journal must commit the digest of exactly the words written here
+    let input_words = risc0_zkvm::serde::to_vec(&guest_input).expect("Failed to serialize input");
+    let expected_digest = input_digest(&guest_input);
+    // ...and the digest of the config inside them, with the algorithm that cleared it
+    let expected_config_digest = config_digest(&guest_input.config);
+    let mut algorithm_id = None;
+
+    // Build executor environment (guest stdout carries per-round cycle counts)
+    let mut guest_stdout: Vec<u8> = Vec::new();
     let env = ExecutorEnv::builder()
-        .write(&guest_input)
-        .unwrap()
+        .write_slice(&input_words)
+        .stdout(&mut guest_stdout)
         .build()
         .unwrap();
 
@@ -109,9 +427,19 @@ fn main() {
     let prover = default_prover();
     let opts = ProverOpts::succinct();
 
-    let prove_info = prover
-        .prove_with_opts(env, DOUBLE_AUCTION_GUEST_ELF, &opts)
-        .expect("Failed to generate proof");
+    // A guest panic aborts the session without an exit code or a journal: the host exits with 1
+    let prove_info = match prover.prove_with_opts(env, DOUBLE_AUCTION_GUEST_ELF, &opts) {
+        Ok(prove_info) => prove_info,
+        Err(e) => {
+            println!(
+                "✗ Guest execution failed after {:?}: {}",
+                exec_start.elapsed(),
+                e
+            );
+            println!("  The guest panicked rather than exiting with an AuctionError (a guest bug)");
+            std::process::exit(1);
+        }
+    };
 
     let proving_time = exec_start.elapsed();
     let receipt = prove_info.receipt;
@@ -131,12 +459,497 @@ fn main() {
         println!("  Proving Time: {:?}\n", proving_time);
     }
 
-    // Decode journal
-    let journal: PublicJournal = receipt.journal.decode().expect("Failed to decode journal");
+    // A guest that stops early (e.g. at the participant limit) commits only its AuctionError,
+    // and the host exits with the guest's code
+    let claim = receipt
+        .claim()
+        .expect("Failed to read receipt claim")
+        .value()
+        .expect("Receipt claim is pruned");
+    match claim.exit_code {
+        ExitCode::Halted(0) => {}
+        ExitCode::Halted(code) => {
+            println!(
+                "✗ Guest exited with code {} ({}): {}",
+                code,
+                exit_code_name(code),
+                describe_exit_code(code)
+            );
+            println!(
+                "  Stopped after {} user cycles in {} segment(s)",
+                user_cycles, segments
+            );
+            match receipt.journal.decode::<AuctionError>() {
+                Ok(error) => println!("  Error: {:?} ({})", error, describe_error(&error)),
+                Err(e) => println!("  Error: not decodable from the journal ({})", e),
+            }
+            if benchmark_mode {
+                let receipt_json =
+                    serde_json::to_string(&receipt).expect("Failed to serialize receipt");
+                let benchmark_result = BenchmarkResult {
+                    participant_count: scenario.participants.len(),
+                    scenario_name: scenario.scenario_name.clone(),
+                    user_cycles,
+                    total_cycles,
+                    session_segments: segments,
+                    executor_time_ms: 0,
+                    proving_time_ms: proving_time.as_millis() as u64,
+                    total_time_ms: start_time.elapsed().as_millis() as u64,
+                    receipt_size_bytes: receipt_json.len(),
+                    journal_size_bytes: receipt.journal.bytes.len(),
+                    round_cycles: Vec::new(),
+                    period_cycles: Vec::new(),
+                    separate_period_cycles: Vec::new(),
+                    guest_exit_code: Some(code),
+                    clearing_price: None,
+                    traded_volume: None,
+                    journal_status: None,
+                    trade_count: None,
+                    fees_collected: None,
+                    surplus: None,
+                    fill_counts: None,
+                    timestamp: chrono::Utc::now().to_rfc3339(),
+                };
+                save_benchmark_result(&benchmark_result, &benchmark_output);
+            }
+            std::process::exit(code as i32);
+        }
+        other => panic!("Guest did not halt: {:?}", other),
+    }
+
+    let stage_cycles: Vec<u64> = if scenario.rounds.is_empty() && !multi_period {
+        Vec::new()
+    } else {
+        risc0_zkvm::serde::from_slice(&guest_stdout).expect("Failed to decode stage cycles")
+    };
+    if benchmark_mode && !stage_cycles.is_empty() {
+        let stage = if multi_period { "period" } else { "round" };
+        println!("  Cycles per {}: {:?}\n", stage, stage_cycles);
+    }
+    let (round_cycles, period_cycles) = if multi_period {
+        (Vec::new(), stage_cycles)
+    } else {
+        (stage_cycles, Vec::new())
+    };
+
+    // The comparison multi-period bidding is for: one proof versus one per period
+    let separate_period_cycles = if benchmark_mode && multi_period {
+        benchmark_separate_periods(&scenario)
+    } else {
+        Vec::new()
+    };
+    if !separate_period_cycles.is_empty() {
+        println!(
+            "  Separate proofs: {} total cycles over {} proofs (one proof: {})\n",
+            separate_period_cycles.iter().sum::<u64>(),
+            separate_period_cycles.len(),
+            total_cycles
+        );
+    }
 
-    // Display results
-    println!("▸ Auction Results:");
-    println!("  Participants: {}", journal.in_coin.len());
+    // Decode journal (bundle mode appends a capacity section, rounds use their own layout)
+    let mut capacity_journal = None;
+    let mut carbon_journal = None;
+    let mut objective_journal = None;
+    let mut secondary_journal = None;
+    let mut trades_journal = None;
+    let mut commitments_journal = None;
+    let mut audit_journal = None;
+    let mut outputs_file = None;
+    let mut private_outputs = None;
+    let mut absolute_journal = None;
+    let mut clearing = None; // Single-book and zonal journals commit one clearing
+    let mut fees_collected = None; // And the fees it charged
+    let mut surplus = None; // And the surplus its matched units realized
+    let mut fill_counts = None; // And how fully its buyer and seller rows traded
+    let mut journal_status = None; // And the status it ended with
+    let mut borsh_journal = None; // The checked PublicJournal, for journal.borsh
+    let mut keccak_digest = None; // The journal's keccak256, for journal_digest.hex
+    let journal_json = if !scenario.rounds.is_empty() {
+        check_journal_version(&receipt.journal, ROUNDS_JOURNAL_VERSION);
+        let journal: RoundsJournal = receipt.journal.decode().expect("Failed to decode journal");
+        for (round, input) in journal.rounds.iter().zip(&scenario.rounds) {
+            check_input_digest(&round.input_digest, &expected_digest);
+            algorithm_id = Some(check_mechanism(
+                round.algorithm_id,
+                round.round_id,
+                &round.config_digest,
+                &expected_config_digest,
+                &expected_mechanism,
+            ));
+            check_row_blocks(round, prosumer_count(&input.participants, |_| true));
+        }
+        println!("▸ Auction Results:");
+        println!("  Rounds: {}", journal.rounds.len());
+        println!("  Participants: {}", journal.ids.len());
+        serde_json::to_string_pretty(&journal).expect("Failed to serialize journal")
+    } else if multi_period {
+        check_journal_version(&receipt.journal, PERIODS_JOURNAL_VERSION);
+        let journal: PeriodsJournal = receipt.journal.decode().expect("Failed to decode journal");
+        check_input_digest(&journal.input_digest, &expected_digest);
+        algorithm_id = Some(check_mechanism(
+            journal.algorithm_id,
+            journal.round_id,
+            &journal.config_digest,
+            &expected_config_digest,
+            &expected_mechanism,
+        ));
+        println!("▸ Auction Results:");
+        println!("  Periods: {}", journal.counts.len());
+        println!("  Final coin: {:?}", journal.final_coin);
+        serde_json::to_string_pretty(&journal).expect("Failed to serialize journal")
+    } else if multi_commodity {
+        check_journal_version(&receipt.journal, COMMODITY_JOURNAL_VERSION);
+        let journal: CommodityJournal = receipt.journal.decode().expect("Failed to decode journal");
+        for (market, &commodity) in journal.markets.iter().zip(&journal.commodities) {
+            let prosumers = prosumer_count(&scenario.participants, |p| p.commodity == commodity);
+            check_input_digest(&market.input_digest, &expected_digest);
+            algorithm_id = Some(check_mechanism(
+                market.algorithm_id,
+                market.round_id,
+                &market.config_digest,
+                &expected_config_digest,
+                &expected_mechanism,
+            ));
+            check_row_blocks(market, prosumers);
+        }
+        println!("▸ Auction Results:");
+        println!("  Commodities: {:?}", journal.commodities);
+        println!("  Rows per commodity: {:?}", journal.counts);
+        serde_json::to_string_pretty(&journal).expect("Failed to serialize journal")
+    } else if zonal {
+        check_journal_version(&receipt.journal, ZONAL_JOURNAL_VERSION);
+        let journal: ZonalJournal = receipt.journal.decode().expect("Failed to decode journal");
+        check_input_digest(&journal.journal.input_digest, &expected_digest);
+        algorithm_id = Some(check_mechanism(
+            journal.journal.algorithm_id,
+            journal.journal.round_id,
+            &journal.journal.config_digest,
+            &expected_config_digest,
+            &expected_mechanism,
+        ));
+        check_row_blocks(
+            &journal.journal,
+            prosumer_count(&scenario.participants, |_| true),
+        );
+        println!("▸ Auction Results:");
+        println!("  Zones: {:?}", journal.zones);
+        println!("  Prices per zone: {:?}", journal.prices);
+        println!("  Flows per link: {:?}", journal.flows);
+        println!("  Traded volume: {}", journal.journal.traded_volume);
+        print_journal_status(journal.journal.status);
+        journal_status = Some(journal.journal.status);
+        borsh_journal = Some(borsh::to_vec(&journal.journal).expect("Failed to encode journal"));
+        clearing = Some((
+            journal.journal.clearing_price,
+            journal.journal.traded_volume,
+        ));
+        fees_collected = Some(journal.journal.fees_collected);
+        surplus = Some(journal.journal.surplus);
+        fill_counts = Some(journal.journal.fill_counts);
+        serde_json::to_string_pretty(&journal).expect("Failed to serialize journal")
+    } else if scenario.config.journal_variant != JournalVariant::Full {
+        // decode_any_journal picks the layout from the version tag the guest commits first
+        let bytes = &receipt.journal.bytes;
+        let bytes = match scenario.config.keccak_digest {
+            true => &bytes[..bytes.len().saturating_sub(32)], // The keccak256 follows the ABI bytes
+            false => &bytes[..],
+        };
+        let decoded = decode_any_journal(bytes).unwrap_or_else(|e| {
+            panic!(
+                "Unsupported journal: {} (the guest commits a layout this host does not decode)",
+                e
+            )
+        });
+        assert!(
+            decoded.variant == scenario.config.journal_variant,
+            "Journal variant mismatch: the journal commits {:?}, the scenario configures {:?}",
+            decoded.variant,
+            scenario.config.journal_variant
+        );
+        check_input_digest(&decoded.input_digest, &expected_digest);
+        algorithm_id = Some(check_mechanism(
+            decoded.algorithm_id,
+            decoded.round_id,
+            &decoded.config_digest,
+            &expected_config_digest,
+            &expected_mechanism,
+        ));
+        println!("▸ Auction Results:");
+        println!("  Participants: {}", decoded.row_count);
+        println!(
+            "  Buyers: {}, sellers: {}",
+            decoded.buyer_count, decoded.seller_count
+        );
+        if let Some(status) = decoded.market_status {
+            println!(
+                "  Market status: {:?} ({})",
+                status,
+                describe_status(status)
+            );
+        }
+        println!(
+            "  Clearing price: {}, traded volume: {}",
+            decoded.clearing_price, decoded.traded_volume
+        );
+        print_journal_status(decoded.status);
+        journal_status = Some(decoded.status);
+        clearing = Some((decoded.clearing_price, decoded.traded_volume));
+        fees_collected = Some(decoded.fees_collected);
+        surplus = Some(decoded.surplus);
+        fill_counts = Some(decoded.fill_counts);
+        let prosumers = prosumer_count(&scenario.participants, |_| true);
+        match &decoded.committed {
+            CommittedJournal::Full(_) => {
+                unreachable!("JournalVariant::Full takes the branch below")
+            }
+            CommittedJournal::Abi(journal) => {
+                // Raw abi.encodePacked bytes: fixed offsets, no serde words and no market status
+                assert!(
+                    abi_journal_bytes(journal) == *bytes,
+                    "ABI journal mismatch: the committed bytes are not the native encoding of their own fields"
+                );
+                check_row_blocks(journal, prosumers);
+                if scenario.config.keccak_digest {
+                    keccak_digest = Some(check_journal_keccak(&receipt.journal.bytes, journal));
+                }
+                println!(
+                    "  ABI journal: {} bytes, equal to the native abi.encodePacked encoding",
+                    bytes.len()
+                );
+                print_rows(journal, scenario.config.quantity_scale);
+                borsh_journal = Some(borsh::to_vec(journal).expect("Failed to encode journal"));
+            }
+            CommittedJournal::Private(private) => {
+                // Only aggregates are proven in the clear; the rows come over the guest's stdout
+                let rows: PublicJournal = risc0_zkvm::serde::from_slice(&guest_stdout)
+                    .expect("Failed to decode journal rows");
+                check_row_blocks(&rows, prosumers);
+                let file = private_outputs_file(&rows, &output_salt);
+                check_private_outputs(private, &file).expect("The rows the guest wrote");
+                println!("  Turnover: {}", private.turnover);
+                println!(
+                    "  Outputs digest: {} over {} rows (not printed)",
+                    private.outputs_digest, private.row_count
+                );
+                private_outputs = Some(file);
+            }
+            CommittedJournal::Delta(delta) => {
+                // Delta journals carry net changes only; the scenario supplies the input balances
+                let journal = absolute_outputs(delta, &scenario.participants)
+                    .expect("Cannot rebuild the outputs");
+                check_row_blocks(&journal, prosumers);
+                println!("  Delta journal: outputs rebuilt as in + delta from the scenario");
+                print_rows(&journal, scenario.config.quantity_scale);
+                borsh_journal = Some(borsh::to_vec(&journal).expect("Failed to encode journal"));
+                absolute_journal = Some(journal);
+            }
+            CommittedJournal::Quantized(quantized) => {
+                // Balances in units of the scale; the clearing fields stay exact
+                let JournalVariant::Quantized(scale) = scenario.config.journal_variant else {
+                    unreachable!("the variants matched above");
+                };
+                check_quantized(quantized, &scenario.participants, scale)
+                    .expect("Quantized journal refused");
+                println!(
+                    "  Quantized journal: balances in units of {}, each short of the exact balance by less than one unit",
+                    quantized.scale
+                );
+                print_quantized_rows(quantized);
+            }
+            CommittedJournal::Slim(slim) => {
+                // Slim journals leave out the input balances; the digest binds the scenario's
+                let journal = full_journal(slim, &scenario.participants)
+                    .expect("Cannot re-derive the inputs");
+                check_row_blocks(&journal, prosumers);
+                println!(
+                    "  Slim journal: input balances re-derived from the scenario the input digest binds"
+                );
+                print_rows(&journal, scenario.config.quantity_scale);
+                borsh_journal = Some(borsh::to_vec(&journal).expect("Failed to encode journal"));
+                absolute_journal = Some(journal);
+            }
+            CommittedJournal::Compact(compact) => {
+                // The rows behind outputs_root come over the guest's stdout, outside the proof
+                let rows: PublicJournal = risc0_zkvm::serde::from_slice(&guest_stdout)
+                    .expect("Failed to decode journal rows");
+                let outputs = outputs_file_of(compact, &rows)
+                    .unwrap_or_else(|e| panic!("The rows the guest wrote {}", e));
+                check_row_blocks(&rows, prosumers);
+                println!(
+                    "  Outputs root: {} over {} rows (coin {}, energy {})",
+                    compact.outputs_root,
+                    compact.row_count,
+                    compact.total_coin,
+                    compact.total_energy
+                );
+                // Check every path as a participant would, against the committed root alone
+                for leaf in &outputs.leaves {
+                    assert!(
+                        verify_merkle_path(&leaf.leaf, &leaf.path, &compact.outputs_root),
+                        "Merkle path of row {} does not lead to the committed outputs_root",
+                        leaf.row
+                    );
+                }
+                println!(
+                    "  ✓ Merkle paths of all {} rows verify against the root",
+                    outputs.leaves.len()
+                );
+                print_rows(&rows, scenario.config.quantity_scale);
+                borsh_journal = Some(borsh::to_vec(&rows).expect("Failed to encode journal"));
+                outputs_file = Some(outputs);
+            }
+        }
+        serde_json::to_string_pretty(&decoded.committed).expect("Failed to serialize journal")
+    } else {
+        // Single-book journals end with the market status
+        check_journal_version(&receipt.journal, JOURNAL_VERSION);
+        let (journal, status): (PublicJournal, MarketStatus) = if scenario.config.bundles {
+            let (journal, capacity, status): (PublicJournal, CapacityJournal, MarketStatus) =
+                receipt.journal.decode().expect("Failed to decode journal");
+            capacity_journal = Some(capacity);
+            (journal, status)
+        } else if carbon_priced {
+            let (journal, carbon, status): (PublicJournal, CarbonJournal, MarketStatus) =
+                receipt.journal.decode().expect("Failed to decode journal");
+            println!("  Total carbon: {}", carbon.total_carbon);
+            carbon_journal = Some(carbon);
+            (journal, status)
+        } else if scenario.config.objective != Objective::FirstCrossing {
+            let (journal, summary, status): (PublicJournal, ObjectiveJournal, MarketStatus) =
+                receipt.journal.decode().expect("Failed to decode journal");
+            println!(
+                "  Clearing price: {}, volume: {}, surplus: {}",
+                summary.clearing_price, summary.volume, summary.surplus
+            );
+            objective_journal = Some(summary);
+            (journal, status)
+        } else if scenario.config.secondary_concession.is_some() {
+            let (journal, summary, status): (PublicJournal, SecondaryJournal, MarketStatus) =
+                receipt.journal.decode().expect("Failed to decode journal");
+            println!(
+                "  Primary price: {}, secondary price: {}, secondary volume: {}",
+                summary.primary_price, summary.secondary_price, summary.secondary_volume
+            );
+            secondary_journal = Some(summary);
+            (journal, status)
+        } else if let Some(max_trades) = scenario.config.max_trades {
+            let (journal, trades, status): (PublicJournal, TradesJournal, MarketStatus) =
+                receipt.journal.decode().expect("Failed to decode journal");
+            println!(
+                "  Trades: {} (max_trades {}), reproducing every row",
+                trades.trades.len(),
+                max_trades
+            );
+            trades_journal = Some(trades);
+            (journal, status)
+        } else if !scenario.commitments.is_empty() {
+            let (journal, sealed, status): (PublicJournal, CommitmentsJournal, MarketStatus) =
+                receipt.journal.decode().expect("Failed to decode journal");
+            assert!(
+                sealed.commitments == scenario.commitments,
+                "Commitments mismatch: the journal does not commit the scenario's sealed bids"
+            );
+            println!(
+                "  Sealed bids: {} commitments, each opened by its bid",
+                sealed.commitments.len()
+            );
+            commitments_journal = Some(sealed);
+            (journal, status)
+        } else if scenario.config.audit {
+            let (journal, audit, status): (PublicJournal, AuditJournal, MarketStatus) =
+                receipt.journal.decode().expect("Failed to decode journal");
+            check_audit(&journal, &audit, &scenario.participants).expect("Audit journal refused");
+            println!(
+                "  Audit journal: {} bids and {} asks, each its row's stated quote",
+                audit.bid_prices.len(),
+                audit.ask_prices.len()
+            );
+            audit_journal = Some(audit);
+            (journal, status)
+        } else {
+            receipt.journal.decode().expect("Failed to decode journal")
+        };
+        check_input_digest(&journal.input_digest, &expected_digest);
+        algorithm_id = Some(check_mechanism(
+            journal.algorithm_id,
+            journal.round_id,
+            &journal.config_digest,
+            &expected_config_digest,
+            &expected_mechanism,
+        ));
+        check_row_blocks(&journal, prosumer_count(&scenario.participants, |_| true));
+        if scenario.config.keccak_digest {
+            keccak_digest = Some(check_journal_keccak(&receipt.journal.bytes, &journal));
+        }
+        println!("▸ Auction Results:");
+        println!("  Participants: {}", journal.in_coin.len());
+        println!(
+            "  Buyers: {}, sellers: {}",
+            journal.buyer_count, journal.seller_count
+        );
+        println!(
+            "  Market status: {:?} ({})",
+            status,
+            describe_status(status)
+        );
+        println!(
+            "  Clearing price: {}, traded volume: {}",
+            journal.clearing_price, journal.traded_volume
+        );
+        print_journal_status(journal.status);
+        journal_status = Some(journal.status);
+        clearing = Some((journal.clearing_price, journal.traded_volume));
+        fees_collected = Some(journal.fees_collected);
+        surplus = Some(journal.surplus);
+        fill_counts = Some(journal.fill_counts);
+        print_rows(&journal, scenario.config.quantity_scale);
+        if let Some(audit) = &audit_journal {
+            print_supply_demand(audit, journal.clearing_price);
+        }
+        borsh_journal = Some(borsh::to_vec(&journal).expect("Failed to encode journal"));
+        serde_json::to_string_pretty(&journal).expect("Failed to serialize journal")
+    };
+    let fees_collected = fees_collected.filter(|_| scenario.config.fee_bps > 0);
+    if let Some(fees) = fees_collected {
+        println!(
+            "  Fees collected: {} at {} bps, the operator's coin gain from fees",
+            fees, scenario.config.fee_bps
+        );
+    }
+    if let Some(digest) = keccak_digest {
+        println!(
+            "  Journal keccak256: 0x{} (matches the packed journal)",
+            hex::encode(digest)
+        );
+    }
+    if let Some(surplus) = surplus {
+        println!(
+            "  Surplus: {} (Σ bid − ask over the matched units)",
+            surplus
+        );
+    }
+    if let Some(fills) = fill_counts {
+        println!(
+            "  Fills: buyers {} filled, {} partial, {} unfilled; sellers {} filled, {} partial, {} unfilled",
+            fills.buyers_filled,
+            fills.buyers_partial,
+            fills.buyers_unfilled,
+            fills.sellers_filled,
+            fills.sellers_partial,
+            fills.sellers_unfilled
+        );
+    }
+    println!(
+        "  Input digest: {} (matches the input written)",
+        expected_digest
+    );
+    if let Some(algorithm_id) = algorithm_id {
+        println!(
+            "  Algorithm id: {}, config digest: {} (matches the config written)",
+            algorithm_id, expected_config_digest
+        );
+    }
     println!();
 
     // Verify receipt (optional but recommended)
@@ -170,11 +983,100 @@ fn main() {
     println!("✓ Saved risc0_receipt.json");
 
     // Save journal for verification
-    let journal_json = serde_json::to_string_pretty(&journal).expect("Failed to serialize journal");
     let journal_size = journal_json.len();
     fs::write("journal.json", &journal_json).expect("Failed to write journal");
     println!("✓ Saved journal.json");
 
+    if borsh_format {
+        // Set by every branch --journal-format borsh admits
+        let bytes = borsh_journal.expect("No PublicJournal to encode");
+        fs::write("journal.borsh", &bytes).expect("Failed to write Borsh journal");
+        println!("✓ Saved journal.borsh ({} bytes)", bytes.len());
+    }
+
+    if let Some(digest) = keccak_digest {
+        fs::write("journal_digest.hex", hex::encode(digest) + "\n")
+            .expect("Failed to write journal digest");
+        println!("✓ Saved journal_digest.hex");
+    }
+
+    if let Some(journal) = &absolute_journal {
+        let journal_json =
+            serde_json::to_string_pretty(journal).expect("Failed to serialize absolute journal");
+        fs::write("absolute_journal.json", journal_json).expect("Failed to write absolute journal");
+        println!("✓ Saved absolute_journal.json");
+    }
+
+    if let Some(outputs) = &outputs_file {
+        let outputs_json =
+            serde_json::to_string_pretty(outputs).expect("Failed to serialize outputs");
+        fs::write("outputs.json", outputs_json).expect("Failed to write outputs");
+        println!("✓ Saved outputs.json ({} leaves)", outputs.leaves.len());
+    }
+
+    if let Some(file) = &private_outputs {
+        let file_json =
+            serde_json::to_string_pretty(file).expect("Failed to serialize private outputs");
+        fs::write("private_outputs.json", file_json).expect("Failed to write private outputs");
+        println!(
+            "✓ Saved private_outputs.json ({} rows, for authorized distribution only)",
+            file.rows.len()
+        );
+    }
+
+    if let Some(trades) = &trades_journal {
+        let trades_json =
+            serde_json::to_string_pretty(&trades.trades).expect("Failed to serialize trades");
+        fs::write("trades.json", trades_json).expect("Failed to write trades");
+        println!("✓ Saved trades.json ({} trades)", trades.trades.len());
+    }
+
+    if let Some(audit) = &audit_journal {
+        let audit_json =
+            serde_json::to_string_pretty(audit).expect("Failed to serialize audit journal");
+        fs::write("audit_journal.json", audit_json).expect("Failed to write audit journal");
+        println!("✓ Saved audit_journal.json");
+    }
+
+    if let Some(sealed) = &commitments_journal {
+        let sealed_json =
+            serde_json::to_string_pretty(sealed).expect("Failed to serialize commitments journal");
+        fs::write("commitments_journal.json", sealed_json)
+            .expect("Failed to write commitments journal");
+        println!("✓ Saved commitments_journal.json");
+    }
+
+    if let Some(capacity) = &capacity_journal {
+        let capacity_json =
+            serde_json::to_string_pretty(capacity).expect("Failed to serialize capacity journal");
+        fs::write("capacity_journal.json", capacity_json)
+            .expect("Failed to write capacity journal");
+        println!("✓ Saved capacity_journal.json");
+    }
+
+    if let Some(carbon) = &carbon_journal {
+        let carbon_json =
+            serde_json::to_string_pretty(carbon).expect("Failed to serialize carbon journal");
+        fs::write("carbon_journal.json", carbon_json).expect("Failed to write carbon journal");
+        println!("✓ Saved carbon_journal.json");
+    }
+
+    if let Some(summary) = &objective_journal {
+        let summary_json =
+            serde_json::to_string_pretty(summary).expect("Failed to serialize objective journal");
+        fs::write("objective_journal.json", summary_json)
+            .expect("Failed to write objective journal");
+        println!("✓ Saved objective_journal.json");
+    }
+
+    if let Some(summary) = &secondary_journal {
+        let summary_json =
+            serde_json::to_string_pretty(summary).expect("Failed to serialize secondary journal");
+        fs::write("secondary_journal.json", summary_json)
+            .expect("Failed to write secondary journal");
+        println!("✓ Saved secondary_journal.json");
+    }
+
     // Save benchmark results if in benchmark mode
     if benchmark_mode {
         let total_time = start_time.elapsed();
@@ -191,23 +1093,641 @@ fn main() {
             total_time_ms: total_time.as_millis() as u64,
             receipt_size_bytes: receipt_json.len(),
             journal_size_bytes: journal_size,
+            round_cycles,
+            period_cycles,
+            separate_period_cycles,
+            guest_exit_code: None,
+            clearing_price: clearing.map(|(price, _)| price),
+            traded_volume: clearing.map(|(_, volume)| volume),
+            journal_status,
+            trade_count: trades_journal.as_ref().map(|list| list.trades.len()),
+            fees_collected,
+            surplus,
+            fill_counts,
             timestamp: chrono::Utc::now().to_rfc3339(),
         };
+        save_benchmark_result(&benchmark_result, &benchmark_output);
+    }
+
+    println!("\n✓ RISC Zero proof generation complete");
+}
+
+/// Write a benchmark result to the --benchmark file, or print it without one
+fn save_benchmark_result(result: &BenchmarkResult, output: &str) {
+    let benchmark_json =
+        serde_json::to_string_pretty(result).expect("Failed to serialize benchmark result");
+
+    if !output.is_empty() {
+        fs::write(output, &benchmark_json).expect("Failed to write benchmark results");
+        println!("✓ Saved benchmark results to {}", output);
+    } else {
+        println!("\n▸ Benchmark Results (JSON):");
+        println!("{}", benchmark_json);
+    }
+}
+
+/// AuctionError variant a guest exit code stands for
+fn exit_code_name(code: u32) -> &'static str {
+    match u8::try_from(code) {
+        Ok(EXIT_DUPLICATE_ID) => "DuplicateId",
+        Ok(EXIT_UNKNOWN_ROLE) => "InvalidRole",
+        Ok(EXIT_PARTICIPANT_LIMIT) => "TooManyParticipants",
+        Ok(EXIT_TOO_MANY_ORDERS) => "TooManyOrders",
+        Ok(EXIT_OVERFLOW) => "Overflow",
+        Ok(EXIT_INSUFFICIENT_BALANCE) => "InsufficientCoin / InsufficientEnergy",
+        Ok(EXIT_SETTLEMENT_BOUND) => "SettlementBound",
+        Ok(EXIT_INTERNAL) => "Internal",
+        Ok(EXIT_MALFORMED_JOURNAL) => "MalformedJournal",
+        Ok(EXIT_PRICE_ABOVE_MAX) => "PriceAboveMax",
+        Ok(EXIT_IRRATIONAL) => "Irrational",
+        Ok(EXIT_INFEASIBLE) => "Infeasible",
+        Ok(EXIT_EMPTY_MARKET) => "EmptyMarket",
+        Ok(EXIT_TOO_MANY_TRADES) => "TooManyTrades",
+        Ok(EXIT_COMMITMENT_MISMATCH) => "CommitmentMismatch",
+        Ok(EXIT_UNSUPPORTED_MODE) => "UnsupportedMode",
+        Ok(EXIT_INVALID_K_DOUBLE) => "InvalidKDouble",
+        _ => "unknown",
+    }
+}
+
+/// Explanation of a guest exit code (see the EXIT_* constants)
+fn describe_exit_code(code: u32) -> &'static str {
+    match u8::try_from(code) {
+        Ok(EXIT_DUPLICATE_ID) => "a participant id appears twice in one book",
+        Ok(EXIT_UNKNOWN_ROLE) => "a participant has a role other than 0, 1 or 2",
+        Ok(EXIT_PARTICIPANT_LIMIT) => "the input exceeds the guest's participant limit",
+        Ok(EXIT_TOO_MANY_ORDERS) => "step and prosumer expansion exceeds the guest's order limit",
+        Ok(EXIT_OVERFLOW) => "a payment or balance left its integer range during settlement",
+        Ok(EXIT_INSUFFICIENT_BALANCE) => "an allocation exceeds a participant's coin or energy",
+        Ok(EXIT_SETTLEMENT_BOUND) => "a book could settle more coin than 128-bit settlement holds",
+        Ok(EXIT_INTERNAL) => "a guest invariant failed (a guest bug, not an input error)",
+        Ok(EXIT_MALFORMED_JOURNAL) => "the journal failed its structural checks (a guest bug)",
+        Ok(EXIT_PRICE_ABOVE_MAX) => "an order quotes a price above max_price",
+        Ok(EXIT_IRRATIONAL) => "a trader settled past its own bid or ask (a pricing bug)",
+        Ok(EXIT_INFEASIBLE) => "a journal row is out of reach of its allocation (a guest bug)",
+        Ok(EXIT_EMPTY_MARKET) => "the market is empty: no participants to clear",
+        Ok(EXIT_TOO_MANY_TRADES) => "the trade list is longer than max_trades",
+        Ok(EXIT_COMMITMENT_MISMATCH) => "a bid and its salt do not open the published commitment",
+        Ok(EXIT_UNSUPPORTED_MODE) => {
+            "the input combines features no clearing mode settles together"
+        }
+        Ok(EXIT_INVALID_K_DOUBLE) => "the k-double weight k lies outside [0, 1]",
+        _ => "unknown exit code",
+    }
+}
 
-        let benchmark_json = serde_json::to_string_pretty(&benchmark_result)
-            .expect("Failed to serialize benchmark result");
+/// Explanation of a committed AuctionError, naming the participant it concerns
+fn describe_error(error: &AuctionError) -> String {
+    match error {
+        AuctionError::DuplicateId(id) => format!("participant {}: id listed twice", id),
+        AuctionError::InvalidRole(id) => format!("participant {}: unknown role", id),
+        AuctionError::TooManyParticipants(rows) => {
+            format!("{} rows (max {})", rows, MAX_PARTICIPANTS)
+        }
+        AuctionError::TooManyOrders(orders) => {
+            format!(
+                "{} orders after expansion (max {})",
+                orders, MAX_EXPANDED_ORDERS
+            )
+        }
+        AuctionError::Overflow => "settlement overflow".to_string(),
+        AuctionError::InsufficientCoin(id, allocation) => format!(
+            "participant {}: insufficient coin for allocation {}",
+            id, allocation
+        ),
+        AuctionError::InsufficientEnergy(id, allocation) => format!(
+            "participant {}: insufficient energy for allocation {}",
+            id, allocation
+        ),
+        AuctionError::SettlementBound => "largest possible settlement exceeds i128".to_string(),
+        AuctionError::Internal(id) => format!("participant {}: internal invariant failed", id),
+        AuctionError::MalformedJournal(row) => {
+            format!("journal row {}: wrong length, id, balances or order", row)
+        }
+        AuctionError::PriceAboveMax(id) => format!("participant {}: price above max_price", id),
+        AuctionError::Irrational(id) => {
+            format!(
+                "participant {}: paid above its bid or received below its ask",
+                id
+            )
+        }
+        AuctionError::Infeasible(id) => {
+            format!(
+                "participant {}: output balances its allocation cannot reach",
+                id
+            )
+        }
+        AuctionError::EmptyMarket => "no participants".to_string(),
+        AuctionError::TooManyTrades(trades) => format!("{} trades, above max_trades", trades),
+        AuctionError::CommitmentMismatch(position) => {
+            format!(
+                "commitment {} (ascending id): the bid does not open it",
+                position
+            )
+        }
+        AuctionError::UnsupportedMode => "features no clearing mode settles together".to_string(),
+        AuctionError::InvalidKDouble => "k-double weight outside [0, 1]".to_string(),
+    }
+}
+
+/// Rows the guest counts against MAX_PARTICIPANTS
+fn participant_rows(scenario: &AuctionScenario) -> usize {
+    scenario.participants.len()
+        + scenario
+            .rounds
+            .iter()
+            .map(|round| round.participants.len())
+            .sum::<usize>()
+}
+
+/// Refuse inputs the guest would reject at its participant limit
+fn check_participant_limit(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let rows = participant_rows(scenario);
+    if rows > MAX_PARTICIPANTS {
+        return Err(format!(
+            "{} participant rows exceed the guest limit of {} (the guest would exit with code {}); pass --ignore-participant-limit to run it anyway",
+            rows, MAX_PARTICIPANTS, EXIT_PARTICIPANT_LIMIT
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Padded cycles of executing each period as its own single-auction proof
+///
+/// Every period keeps the initial coin balances, so the books match the
+/// combined run in size but not in carried budget; only cycles are compared.
+fn benchmark_separate_periods(scenario: &AuctionScenario) -> Vec<u64> {
+    let executor = default_executor();
+    (0..period_count(&scenario.participants))
+        .map(|period| {
+            let input = AuctionInput {
+                participants: period_participants(&scenario.participants, period),
+                config: scenario.config.clone(),
+                rounds: Vec::new(),
+                commitments: Vec::new(),
+                output_salt: [0; 32],
+            };
+            let env = ExecutorEnv::builder()
+                .write(&input)
+                .unwrap()
+                .build()
+                .unwrap();
+            let session = executor
+                .execute(env, DOUBLE_AUCTION_GUEST_ELF)
+                .expect("Failed to execute period");
+            session
+                .segments
+                .iter()
+                .map(|segment| 1u64 << segment.po2)
+                .sum()
+        })
+        .collect()
+}
 
-        if !benchmark_output.is_empty() {
-            fs::write(&benchmark_output, &benchmark_json)
-                .expect("Failed to write benchmark results");
-            println!("✓ Saved benchmark results to {}", benchmark_output);
+/// Shuffled copies of the book checked by --check-determinism
+const DETERMINISM_SHUFFLES: u8 = 4;
+
+/// Execute the guest on the scenario repeatedly and compare the journals
+///
+/// The same input runs twice, then with participants (and each round's
+/// bids) shuffled; the guest sorts every book itself, so all runs must
+/// commit the same bytes and end with the same exit code. A shuffled input
+/// hashes differently, so its journal is compared with its input digest
+/// replaced by the reference's, and without the keccak_digest that covers
+/// it too. Returns the number of executions and the common exit code.
+fn check_reproducible(scenario: &AuctionScenario) -> Result<(usize, ExitCode), String> {
+    let input = AuctionInput {
+        participants: scenario.participants.clone(),
+        config: scenario.config.clone(),
+        rounds: scenario.rounds.clone(),
+        commitments: scenario.commitments.clone(),
+        output_salt: scenario.output_salt,
+    };
+    let serialize = |input: &AuctionInput| {
+        risc0_zkvm::serde::to_vec(input).map_err(|e| format!("Failed to serialize input: {}", e))
+    };
+    let reference_digest = input_digest(&input);
+    let execute = |input: &AuctionInput| {
+        let words = serialize(input)?;
+        let mut guest_stdout: Vec<u8> = Vec::new();
+        let env = ExecutorEnv::builder()
+            .write_slice(&words)
+            .stdout(&mut guest_stdout)
+            .build()
+            .unwrap();
+        let session = default_executor()
+            .execute(env, DOUBLE_AUCTION_GUEST_ELF)
+            .map_err(|e| format!("Guest execution failed: {}", e))?;
+        let mut bytes = session.journal.bytes.clone();
+        if input.config.keccak_digest && session.exit_code == ExitCode::Halted(0) {
+            bytes.truncate(bytes.len().saturating_sub(32));
+        }
+        let journal = rebind(&bytes, &input_digest(input), &reference_digest);
+        Ok::<_, String>((session.exit_code, journal))
+    };
+
+    let reference = execute(&input)?;
+    if execute(&input)? != reference {
+        return Err("two executions of the same input committed different journals".into());
+    }
+    for seed in 1..=DETERMINISM_SHUFFLES {
+        let shuffled_input = AuctionInput {
+            participants: shuffled(&input.participants, seed),
+            rounds: input
+                .rounds
+                .iter()
+                .map(|round| RoundInput {
+                    participants: shuffled(&round.participants, seed),
+                })
+                .collect(),
+            ..input.clone()
+        };
+        if execute(&shuffled_input)? != reference {
+            return Err(format!(
+                "shuffling the participants (seed {}) changed the journal",
+                seed
+            ));
+        }
+    }
+    Ok((2 + DETERMINISM_SHUFFLES as usize, reference.0))
+}
+
+/// Journal bytes with every committed copy of one input digest replaced by another
+fn rebind(journal: &[u8], from: &Digest, to: &Digest) -> Vec<u8> {
+    let (from, to) = (from.as_bytes(), to.as_bytes());
+    let mut bytes = journal.to_vec();
+    let mut i = 0;
+    while i + from.len() <= bytes.len() {
+        if &bytes[i..i + from.len()] == from {
+            bytes[i..i + from.len()].copy_from_slice(to);
+            i += from.len();
         } else {
-            println!("\n▸ Benchmark Results (JSON):");
-            println!("{}", benchmark_json);
+            i += 4; // Digests are committed word-aligned
         }
     }
+    bytes
+}
 
-    println!("\n✓ RISC Zero proof generation complete");
+/// Participants reordered by ascending SHA-256(seed || index)
+fn shuffled(participants: &[Participant], seed: u8) -> Vec<Participant> {
+    use sha2::{Digest, Sha256};
+    let mut keyed: Vec<([u8; 32], &Participant)> = participants
+        .iter()
+        .enumerate()
+        .map(|(index, p)| {
+            let mut hasher = Sha256::new();
+            hasher.update([seed]);
+            hasher.update((index as u32).to_le_bytes());
+            (hasher.finalize().into(), p)
+        })
+        .collect();
+    keyed.sort_by_key(|(key, _)| *key);
+    keyed.into_iter().map(|(_, p)| p.clone()).collect()
+}
+
+/// Formats a raw quantity as a decimal in energy units, e.g. 1500 at scale 1000 is "1.500".
+fn format_scaled(raw: u64, scale: u64) -> String {
+    if scale <= 1 {
+        return raw.to_string();
+    }
+    format!(
+        "{}.{:0width$}",
+        raw / scale,
+        raw % scale,
+        width = scale.ilog10() as usize
+    )
+}
+
+/// Refuse a journal layout this host does not decode before reading past its version
+///
+/// Every journal commits its version word first, so no other field is
+/// decoded under the wrong layout.
+fn check_journal_version(journal: &risc0_zkvm::Journal, expected: u32) {
+    let found: u32 = journal.decode().expect("Failed to decode journal version");
+    assert!(
+        found == expected,
+        "Unsupported journal version: expected {}, found {} (the guest commits a layout this host does not decode)",
+        expected,
+        found
+    );
+}
+
+/// Check a quantized journal against the scenario it was proven for
+///
+/// The scale must be the configured one, every column one unit count per
+/// id, and each input column the scenario's balance of that id rounded down
+/// to units. The outputs are bounded by conservation only: per column,
+/// Σ out_units and Σ in_units differ by less than the row count, since each
+/// row loses less than one unit to each of its two floors.
+fn check_quantized(
+    quantized: &QuantizedJournal,
+    participants: &[Participant],
+    configured: u64,
+) -> Result<(), String> {
+    let scale = quantized.scale;
+    if scale != configured {
+        return Err(format!(
+            "scale {} committed, {} configured",
+            scale, configured
+        ));
+    }
+    let rows = quantized.ids.len();
+    let columns = [
+        &quantized.in_coin,
+        &quantized.in_energy,
+        &quantized.out_coin,
+        &quantized.out_energy,
+    ];
+    if columns.iter().any(|column| column.len() != rows) {
+        return Err(format!("{} ids but a column of another length", rows));
+    }
+    for (row, &id) in quantized.ids.iter().enumerate() {
+        let p = participants
+            .iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| format!("row {}: participant {} is not in the scenario", row, id))?;
+        let units = (p.in_coin / scale, p.in_energy / scale);
+        if units
+            != (
+                quantized.in_coin[row] as u64,
+                quantized.in_energy[row] as u64,
+            )
+        {
+            return Err(format!(
+                "row {}: participant {} holds {} coin and {} energy units, the journal commits {} and {}",
+                row, id, units.0, units.1, quantized.in_coin[row], quantized.in_energy[row]
+            ));
+        }
+    }
+    let total = |column: &[u32]| column.iter().map(|&units| units as i128).sum::<i128>();
+    for (name, ins, outs) in [
+        ("coin", &quantized.in_coin, &quantized.out_coin),
+        ("energy", &quantized.in_energy, &quantized.out_energy),
+    ] {
+        let drift = total(outs) - total(ins);
+        if drift.unsigned_abs() >= rows.max(1) as u128 {
+            return Err(format!(
+                "{} units move by {} in total, beyond the rounding of {} rows",
+                name, drift, rows
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The keccak256 a journal commits last must hash its own fields' packed bytes
+///
+/// Serde words or raw ABI bytes alike, the digest is the journal's last 32
+/// bytes; the host packs the decoded fields itself (abi_journal_bytes).
+fn check_journal_keccak(journal_bytes: &[u8], journal: &PublicJournal) -> [u8; 32] {
+    let committed = &journal_bytes[journal_bytes.len().saturating_sub(32)..];
+    let expected = keccak256(&abi_journal_bytes(journal));
+    assert!(
+        committed == expected,
+        "Keccak digest mismatch: the journal commits 0x{}, its packed fields hash to 0x{}",
+        hex::encode(committed),
+        hex::encode(expected)
+    );
+    expected
+}
+
+/// A journal must commit the digest of the input this host wrote, or the receipt
+/// proves the clearing of some other book
+fn check_input_digest(committed: &Digest, expected: &Digest) {
+    assert!(
+        committed == expected,
+        "Input digest mismatch: the journal commits {}, the input written hashes to {}",
+        committed,
+        expected
+    );
+}
+
+/// What --expect-algorithm-id, --expect-config-digest and --expect-round-id pin (None: not checked)
+#[derive(Default)]
+struct ExpectedMechanism {
+    algorithm_id: Option<u32>,
+    config_digest: Option<String>, // Lowercase hex, as a Digest prints
+    round_id: Option<u64>,
+}
+
+/// A journal must commit the digest of the config this host wrote and, when the
+/// command line names them, the expected algorithm id, config digest and round
+/// id; a verifier that pins all three knows which mechanism cleared the book and
+/// for which settlement round. Returns the committed algorithm id
+fn check_mechanism(
+    algorithm_id: u32,
+    round_id: u64,
+    committed: &Digest,
+    written: &Digest,
+    expected: &ExpectedMechanism,
+) -> u32 {
+    assert!(
+        committed == written,
+        "Config digest mismatch: the journal commits {}, the config written hashes to {}",
+        committed,
+        written
+    );
+    if let Some(hex) = &expected.config_digest {
+        assert!(
+            committed.to_string() == *hex,
+            "Config digest mismatch: the journal commits {}, --expect-config-digest gives {}",
+            committed,
+            hex
+        );
+    }
+    if let Some(id) = expected.algorithm_id {
+        assert!(
+            algorithm_id == id,
+            "Algorithm id mismatch: the journal commits {}, --expect-algorithm-id gives {}",
+            algorithm_id,
+            id
+        );
+    }
+    if let Err(e) = check_round_id(round_id, expected.round_id) {
+        println!("✗ {}", e);
+        std::process::exit(1);
+    }
+    algorithm_id
+}
+
+/// A journal must commit the round id --expect-round-id gives, when it gives one
+///
+/// The guest echoes config.round_id verbatim, so a receipt proven for another
+/// round, replayed against this one, fails here; the host exits with 1.
+fn check_round_id(committed: u64, expected: Option<u64>) -> Result<(), String> {
+    match expected {
+        Some(id) if committed != id => Err(format!(
+            "Round id mismatch: the journal commits {}, --expect-round-id gives {}",
+            committed, id
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Prosumers among the participants a journal's rows come from
+fn prosumer_count(participants: &[Participant], keep: impl Fn(&Participant) -> bool) -> usize {
+    participants
+        .iter()
+        .filter(|p| p.role == 2 && keep(p))
+        .count()
+}
+
+/// A journal's buyer and seller blocks must cover every row but the trailing prosumers
+fn check_row_blocks(journal: &PublicJournal, prosumers: usize) {
+    let blocks = journal.buyer_count as usize + journal.seller_count as usize;
+    assert_eq!(
+        blocks + prosumers,
+        journal.in_coin.len(),
+        "Journal blocks ({} buyers, {} sellers, {} prosumers) do not cover its {} rows",
+        journal.buyer_count,
+        journal.seller_count,
+        prosumers,
+        journal.in_coin.len()
+    );
+}
+
+/// Explanation of a committed MarketStatus for the console
+fn describe_status(status: MarketStatus) -> &'static str {
+    match status {
+        MarketStatus::TwoSided => "both sides quoted, the auction ran",
+        MarketStatus::Empty => "no participants, nothing to clear",
+        MarketStatus::ZeroQuantity => "no order quotes a positive quantity, balances unchanged",
+        MarketStatus::NoBuyers => "no buyer quotes a positive quantity, balances unchanged",
+        MarketStatus::NoSellers => "no seller quotes a positive quantity, balances unchanged",
+    }
+}
+
+/// Symbolic name of a committed PublicJournal.status (see the guest's STATUS_* constants)
+fn journal_status_name(status: u32) -> &'static str {
+    match status {
+        STATUS_CLEARED => "Cleared",
+        STATUS_NO_CROSSING => "NoCrossing",
+        STATUS_ZERO_VOLUME => "ZeroVolume",
+        STATUS_NO_SETTLEMENT_ACCOUNT => "NoSettlementAccount",
+        STATUS_SETTLED_AS_NO_TRADE => "SettledAsNoTrade",
+        _ => "unknown",
+    }
+}
+
+/// Explanation of a committed PublicJournal.status
+fn describe_journal_status(status: u32) -> &'static str {
+    match status {
+        STATUS_CLEARED => "energy traded, safe to settle",
+        STATUS_NO_CROSSING => "no bid reaches an ask, balances unchanged",
+        STATUS_ZERO_VOLUME => {
+            "the book crosses but no unit trades at the price, balances unchanged"
+        }
+        STATUS_NO_SETTLEMENT_ACCOUNT => {
+            "the operator or loss sink the settlement needs is missing, balances unchanged"
+        }
+        STATUS_SETTLED_AS_NO_TRADE => "the clearing failed and strict is off, settled as no trade",
+        _ => "unknown status",
+    }
+}
+
+fn print_journal_status(status: u32) {
+    println!(
+        "  Status: {} = {} ({})",
+        status,
+        journal_status_name(status),
+        describe_journal_status(status)
+    );
+}
+
+fn print_rows(journal: &PublicJournal, scale: u64) {
+    // The journal stays in raw units; only this table is scaled
+    for (row, id) in journal.ids.iter().enumerate().take(MAX_PRINTED_ROWS) {
+        println!(
+            "  Row {}: participant {}, coin {} -> {}, energy {} -> {}, allocated {}",
+            row,
+            id,
+            journal.in_coin[row],
+            journal.out_coin[row],
+            format_scaled(journal.in_energy[row], scale),
+            format_scaled(journal.out_energy[row], scale),
+            format_scaled(journal.allocation[row], scale)
+        );
+    }
+    if journal.ids.len() > MAX_PRINTED_ROWS {
+        println!(
+            "  ... {} more rows in journal.json",
+            journal.ids.len() - MAX_PRINTED_ROWS
+        );
+    }
+}
+
+/// Print a quantized journal's rows in its units, as committed
+fn print_quantized_rows(quantized: &QuantizedJournal) {
+    for (row, id) in quantized.ids.iter().enumerate().take(MAX_PRINTED_ROWS) {
+        println!(
+            "  Row {}: participant {}, coin {} -> {}, energy {} -> {} (units of {})",
+            row,
+            id,
+            quantized.in_coin[row],
+            quantized.out_coin[row],
+            quantized.in_energy[row],
+            quantized.out_energy[row],
+            quantized.scale
+        );
+    }
+    if quantized.ids.len() > MAX_PRINTED_ROWS {
+        println!(
+            "  ... {} more rows in journal.json",
+            quantized.ids.len() - MAX_PRINTED_ROWS
+        );
+    }
+}
+
+/// Print the supply and demand an audit journal commits, at every quoted price
+///
+/// Demand at a price is what the bids at or above it ask for, supply what
+/// the asks at or below it offer, both from the committed vectors alone. A
+/// clearing price between two quotes gets a row of its own.
+fn print_supply_demand(audit: &AuditJournal, clearing_price: u64) {
+    let mut prices: Vec<u64> = audit
+        .bid_prices
+        .iter()
+        .chain(&audit.ask_prices)
+        .copied()
+        .collect();
+    if clearing_price > 0 {
+        prices.push(clearing_price);
+    }
+    prices.sort_unstable();
+    prices.dedup();
+    let total = |prices: &[u64], quantities: &[u64], side: &dyn Fn(u64) -> bool| {
+        prices
+            .iter()
+            .zip(quantities)
+            .filter(|(&price, _)| side(price))
+            .map(|(_, &quantity)| quantity as u128)
+            .sum::<u128>()
+    };
+    println!("  Supply and demand (audit journal):");
+    for &price in prices.iter().take(MAX_PRINTED_ROWS) {
+        let demand = total(&audit.bid_prices, &audit.bid_quantities, &|bid| {
+            bid >= price
+        });
+        let supply = total(&audit.ask_prices, &audit.ask_quantities, &|ask| {
+            ask <= price
+        });
+        let marker = if price == clearing_price {
+            "  <- clearing price"
+        } else {
+            ""
+        };
+        println!(
+            "    Price {}: demand {}, supply {}{}",
+            price, demand, supply, marker
+        );
+    }
+    if prices.len() > MAX_PRINTED_ROWS {
+        println!(
+            "    ... {} more prices in audit_journal.json",
+            prices.len() - MAX_PRINTED_ROWS
+        );
+    }
 }
 
 fn load_scenario(filename: &str) -> Result<AuctionScenario, Box<dyn std::error::Error>> {
@@ -215,3 +1735,1328 @@ fn load_scenario(filename: &str) -> Result<AuctionScenario, Box<dyn std::error::
     let scenario: AuctionScenario = serde_json::from_str(&content)?;
     Ok(scenario)
 }
+
+fn validate_scenario(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    if scenario.participants.is_empty() {
+        return Err(format!(
+            "Empty market: the scenario has no participants, so there is nothing to clear (the guest would exit with code {})",
+            EXIT_EMPTY_MARKET
+        )
+        .into());
+    }
+    let config = &scenario.config;
+    if config.pricing_rule == PricingRule::KDouble
+        && (config.k_denominator == 0 || config.k_numerator > config.k_denominator)
+    {
+        return Err(format!(
+            "Invalid k-double parameter: k = {}/{} must lie in [0, 1] (the guest would exit with code {})",
+            config.k_numerator, config.k_denominator, EXIT_INVALID_K_DOUBLE
+        )
+        .into());
+    }
+    if let (Some(floor), Some(cap)) = (config.price_floor, config.price_cap) {
+        if floor > cap {
+            return Err(format!(
+                "Invalid price band: price_floor {} exceeds price_cap {}",
+                floor, cap
+            )
+            .into());
+        }
+    }
+    validate_unique_ids(scenario)?;
+    validate_roles(scenario)?;
+    validate_max_price(scenario)?;
+    validate_settlement_bound(scenario)?;
+    validate_grid(scenario)?;
+    validate_steps(scenario)?;
+    validate_rounds(scenario)?;
+    validate_commodities(scenario)?;
+    validate_prosumers(scenario)?;
+    validate_zones(scenario)?;
+    validate_dispatch(scenario)?;
+    validate_carbon(scenario)?;
+    validate_objective(scenario)?;
+    validate_expiry(scenario)?;
+    validate_icebergs(scenario)?;
+    validate_owners(scenario)?;
+    validate_net_owners(scenario)?;
+    validate_fixed_costs(scenario)?;
+    validate_budgets(scenario)?;
+    validate_congestion_rent(scenario)?;
+    validate_effective_crossing(scenario)?;
+    validate_strict(scenario)?;
+    validate_compact(scenario)?;
+    validate_trade_list(scenario)?;
+    validate_commitments(scenario)?;
+    validate_audit(scenario)?;
+    validate_keccak_digest(scenario)?;
+    validate_curtailment(scenario)?;
+    validate_position_limits(scenario)?;
+    validate_secondary(scenario)?;
+    validate_settlement_price(scenario)?;
+    validate_quantity_scale(scenario)?;
+    validate_periods(scenario)?;
+    let settles_per_fill = matches!(config.algorithm, Algorithm::DutchClock | Algorithm::Greedy);
+    if config.price_offset > 0
+        && (settles_per_fill || config.settlement == Settlement::Vcg || config.bundles)
+    {
+        return Err(format!(
+            "price_offset is not supported with {:?} / {:?} settlement or bundles",
+            config.algorithm, config.settlement
+        )
+        .into());
+    }
+    let is_clock = matches!(
+        config.algorithm,
+        Algorithm::DutchClock | Algorithm::EnglishClock
+    );
+    if is_clock && config.clock_tick == 0 {
+        return Err(format!("{:?} requires clock_tick > 0", config.algorithm).into());
+    }
+    let prices_from_book = matches!(config.algorithm, Algorithm::UniformPrice | Algorithm::Merge);
+    if !prices_from_book && config.settlement != Settlement::Uniform {
+        return Err(format!(
+            "{:?} sets its own prices and requires Uniform settlement, found {:?}",
+            config.algorithm, config.settlement
+        )
+        .into());
+    }
+    if config.max_traded_volume < u64::MAX && (settles_per_fill || config.bundles) {
+        return Err(format!(
+            "max_traded_volume is not supported with {:?} or bundles",
+            config.algorithm
+        )
+        .into());
+    }
+    if config.loss_bps > 10_000 {
+        return Err(format!(
+            "Invalid delivery loss: {} bps exceeds 10000",
+            config.loss_bps
+        )
+        .into());
+    }
+    if config.loss_bps > 0 {
+        if settles_per_fill
+            || config.settlement == Settlement::Vcg
+            || config.bundles
+            || is_zonal(&scenario.participants)
+        {
+            return Err(format!(
+                "loss_bps is not supported with {:?} / {:?} settlement, bundles or zones",
+                config.algorithm, config.settlement
+            )
+            .into());
+        }
+        let sink_found = config.loss_sink_id.is_some_and(|id| {
+            scenario
+                .participants
+                .iter()
+                .any(|p| p.id == id && p.role <= 1)
+        });
+        if !sink_found {
+            return Err(
+                "loss_bps requires loss_sink_id naming a buyer or seller participant".into(),
+            );
+        }
+    }
+    if config.allocation_rule != AllocationRule::Priority {
+        if settles_per_fill || config.bundles || config.quantity_lot > 1 {
+            return Err(format!(
+                "{:?} allocation is not supported with {:?}, bundles or a quantity lot",
+                config.allocation_rule, config.algorithm
+            )
+            .into());
+        }
+        if scenario
+            .participants
+            .iter()
+            .chain(scenario.rounds.iter().flat_map(|round| &round.participants))
+            .any(|p| p.priority || p.flexible)
+        {
+            return Err(format!(
+                "priority / flexible dispatch requires Priority allocation, found {:?}",
+                config.allocation_rule
+            )
+            .into());
+        }
+    }
+    if config.fee_bps > 10_000 {
+        return Err(format!("Invalid fee: {} bps exceeds 10000", config.fee_bps).into());
+    }
+    if config.fee_bps > 0
+        && (settles_per_fill || config.settlement == Settlement::Vcg || config.bundles)
+    {
+        return Err(format!(
+            "fee_bps is not supported with {:?} / {:?} settlement or bundles",
+            config.algorithm, config.settlement
+        )
+        .into());
+    }
+    if config.settlement != Settlement::Uniform
+        || config.algorithm == Algorithm::McAfee
+        || config.fee_bps > 0
+        || config.congestion_rent
+        || config.funding_fee_bps > 0
+    {
+        let operator_found = config.operator_id.is_some_and(|id| {
+            scenario
+                .participants
+                .iter()
+                .any(|p| p.id == id && p.role <= 1)
+        });
+        if !operator_found {
+            return Err(format!(
+                "{:?} / {:?} requires operator_id naming a buyer or seller participant",
+                config.algorithm, config.settlement
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+fn validate_grid(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if config.price_tick == 0 || config.quantity_lot == 0 {
+        return Err("price_tick and quantity_lot must be > 0".into());
+    }
+    if config.price_tick == 1 && config.quantity_lot == 1 {
+        return Ok(());
+    }
+    if !matches!(config.algorithm, Algorithm::UniformPrice | Algorithm::Merge) || config.bundles {
+        return Err(format!(
+            "price_tick / quantity_lot are only supported with UniformPrice or Merge, found {:?}{}",
+            config.algorithm,
+            if config.bundles { " with bundles" } else { "" }
+        )
+        .into());
+    }
+    for (name, edge) in [
+        ("price_floor", config.price_floor),
+        ("price_cap", config.price_cap),
+    ] {
+        if edge.is_some_and(|price| !price.is_multiple_of(config.price_tick)) {
+            return Err(format!("{} is not on the {} price tick", name, config.price_tick).into());
+        }
+    }
+    if !config.round_to_grid {
+        let offending: Vec<u32> = scenario
+            .participants
+            .iter()
+            .filter(|p| Some(p.id) != config.operator_id)
+            .filter(|p| {
+                !p.price.is_multiple_of(config.price_tick)
+                    || !p.quantity.is_multiple_of(config.quantity_lot)
+            })
+            .map(|p| p.id)
+            .collect();
+        if !offending.is_empty() {
+            return Err(format!(
+                "Participants {:?} are off the price tick {} / quantity lot {} (set round_to_grid to round them)",
+                offending, config.price_tick, config.quantity_lot
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+fn validate_steps(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    let stepped: Vec<&Participant> = scenario
+        .participants
+        .iter()
+        .filter(|p| !p.steps.is_empty())
+        .collect();
+    if stepped.is_empty() {
+        return Ok(());
+    }
+    if !matches!(config.algorithm, Algorithm::UniformPrice | Algorithm::Merge)
+        || config.settlement != Settlement::Uniform
+        || config.bundles
+    {
+        return Err("Stepped bids require UniformPrice or Merge with Uniform settlement".into());
+    }
+    if let Some(p) = stepped.iter().find(|p| p.steps.len() > MAX_STEPS) {
+        return Err(format!(
+            "Participant {} has {} steps (max {})",
+            p.id,
+            p.steps.len(),
+            MAX_STEPS
+        )
+        .into());
+    }
+    let expanded: usize = scenario
+        .participants
+        .iter()
+        .map(|p| if p.role == 2 { 2 } else { p.steps.len().max(1) })
+        .sum();
+    if expanded > MAX_EXPANDED_ORDERS {
+        return Err(format!(
+            "{} orders after step expansion (max {})",
+            expanded, MAX_EXPANDED_ORDERS
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn validate_prosumers(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    let prosumers: Vec<&Participant> = scenario
+        .participants
+        .iter()
+        .chain(scenario.rounds.iter().flat_map(|round| &round.participants))
+        .filter(|p| p.role == 2)
+        .collect();
+    if prosumers.is_empty() {
+        return Ok(());
+    }
+    let clears_uniformly = matches!(
+        config.algorithm,
+        Algorithm::UniformPrice | Algorithm::McAfee | Algorithm::EnglishClock | Algorithm::Merge
+    );
+    if !clears_uniformly || config.settlement == Settlement::Vcg || config.bundles {
+        return Err(format!(
+            "Prosumers are not supported with {:?} / {:?} settlement or bundles",
+            config.algorithm, config.settlement
+        )
+        .into());
+    }
+    for p in prosumers {
+        if !p.steps.is_empty() {
+            return Err(format!("Prosumer {} cannot place stepped bids", p.id).into());
+        }
+        if p.sell_price <= p.price {
+            return Err(format!(
+                "Prosumer {} would self-match: sell_price {} must exceed price {}",
+                p.id, p.sell_price, p.price
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+fn validate_dispatch(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    let flagged: Vec<&Participant> = scenario
+        .participants
+        .iter()
+        .chain(scenario.rounds.iter().flat_map(|round| &round.participants))
+        .filter(|p| p.priority || p.flexible)
+        .collect();
+    if flagged.is_empty() {
+        return Ok(());
+    }
+    if let Some(buyer) = flagged.iter().find(|p| p.priority && p.role == 0) {
+        return Err(format!(
+            "Participant {} is a buyer: priority applies to sellers",
+            buyer.id
+        )
+        .into());
+    }
+    if let Some(seller) = flagged.iter().find(|p| p.flexible && p.role == 1) {
+        return Err(format!(
+            "Participant {} is a seller: flexible applies to buyers",
+            seller.id
+        )
+        .into());
+    }
+    if matches!(config.algorithm, Algorithm::DutchClock | Algorithm::Greedy) || config.bundles {
+        return Err(format!(
+            "priority / flexible dispatch is not supported with {:?} or bundles",
+            config.algorithm
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn validate_carbon(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    let round_carbon = scenario
+        .rounds
+        .iter()
+        .any(|round| is_carbon_priced(&round.participants));
+    if !is_carbon_priced(&scenario.participants) && !round_carbon {
+        return Ok(());
+    }
+    if config.algorithm != Algorithm::UniformPrice
+        || config.settlement == Settlement::Vcg
+        || config.loss_bps > 0
+        || config.price_tick > 1
+        || config.quantity_lot > 1
+    {
+        return Err(format!(
+            "Carbon co-clearing requires UniformPrice with Uniform or PayAsBid settlement and no loss or grid, found {:?} / {:?}",
+            config.algorithm, config.settlement
+        )
+        .into());
+    }
+    if config.bundles
+        || round_carbon
+        || !scenario.rounds.is_empty()
+        || is_multi_commodity(&scenario.participants)
+        || is_zonal(&scenario.participants)
+    {
+        return Err(
+            "Carbon co-clearing does not support bundles, rounds, commodities or zones".into(),
+        );
+    }
+    for p in &scenario.participants {
+        if p.role > 1 || !p.steps.is_empty() {
+            return Err(format!(
+                "Participant {} must be a plain buyer or seller under carbon co-clearing",
+                p.id
+            )
+            .into());
+        }
+        if p.role == 0 && p.carbon_per_unit > 0 {
+            return Err(format!("Buyer {} declares carbon_per_unit", p.id).into());
+        }
+        if p.role == 1 && p.carbon_budget.is_some() {
+            return Err(format!("Seller {} declares carbon_budget", p.id).into());
+        }
+    }
+    Ok(())
+}
+
+fn validate_objective(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if config.objective == Objective::FirstCrossing {
+        return Ok(());
+    }
+    if config.algorithm != Algorithm::UniformPrice || config.settlement == Settlement::Vcg {
+        return Err(format!(
+            "{:?} requires UniformPrice with Uniform or PayAsBid settlement, found {:?} / {:?}",
+            config.objective, config.algorithm, config.settlement
+        )
+        .into());
+    }
+    if config.bundles
+        || !scenario.rounds.is_empty()
+        || is_multi_commodity(&scenario.participants)
+        || is_zonal(&scenario.participants)
+        || is_carbon_priced(&scenario.participants)
+    {
+        return Err(format!(
+            "{:?} does not support bundles, rounds, commodities, zones or carbon",
+            config.objective
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn validate_expiry(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    let last_round = config
+        .current_round
+        .saturating_add(scenario.rounds.len().saturating_sub(1) as u32);
+    let expiring: Vec<&Participant> = scenario
+        .participants
+        .iter()
+        .chain(scenario.rounds.iter().flat_map(|round| &round.participants))
+        .filter(|p| p.valid_until_round < last_round)
+        .collect();
+    if expiring.is_empty() {
+        return Ok(());
+    }
+    if config.bundles {
+        return Err("valid_until_round is not supported with bundles".into());
+    }
+    let account = |id: Option<u32>| id.is_some_and(|id| expiring.iter().any(|p| p.id == id));
+    if account(config.operator_id) || account(config.loss_sink_id) {
+        return Err(
+            "The operator and the loss sink are settlement accounts and cannot expire".into(),
+        );
+    }
+    Ok(())
+}
+
+fn validate_icebergs(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    let icebergs: Vec<&Participant> = scenario
+        .participants
+        .iter()
+        .chain(scenario.rounds.iter().flat_map(|round| &round.participants))
+        .filter(|p| p.display_quantity.is_some())
+        .collect();
+    if icebergs.is_empty() {
+        return Ok(());
+    }
+    if let Some(p) = icebergs.iter().find(|p| p.display_quantity == Some(0)) {
+        return Err(format!(
+            "Participant {} displays 0: display_quantity must be > 0",
+            p.id
+        )
+        .into());
+    }
+    if matches!(config.algorithm, Algorithm::DutchClock | Algorithm::Greedy)
+        || config.bundles
+        || config.allocation_rule != AllocationRule::Priority
+    {
+        return Err(format!(
+            "display_quantity requires Priority allocation and is not supported with {:?} or bundles",
+            config.algorithm
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn validate_owners(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    let owned = scenario
+        .participants
+        .iter()
+        .chain(scenario.rounds.iter().flat_map(|round| &round.participants))
+        .any(|p| p.owner.is_some());
+    if !owned {
+        return Ok(());
+    }
+    if config.settlement == Settlement::Vcg
+        || config.bundles
+        || is_zonal(&scenario.participants)
+        || is_carbon_priced(&scenario.participants)
+    {
+        return Err(format!(
+            "Self-trade prevention (owner) is not supported with {:?} settlement, bundles, zones or carbon",
+            config.settlement
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn validate_net_owners(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if !config.net_owners {
+        return Ok(());
+    }
+    if matches!(config.algorithm, Algorithm::DutchClock | Algorithm::Greedy) {
+        return Err(format!(
+            "Owner netting needs a price-based algorithm, not {:?}",
+            config.algorithm
+        )
+        .into());
+    }
+    if scenario
+        .participants
+        .iter()
+        .chain(scenario.rounds.iter().flat_map(|round| &round.participants))
+        .any(|p| !p.steps.is_empty() || p.sell_quantity > 0)
+    {
+        return Err("Owner netting is not supported with step bids or prosumers".into());
+    }
+    Ok(())
+}
+
+fn validate_fixed_costs(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    let committed: Vec<&Participant> = scenario
+        .participants
+        .iter()
+        .chain(scenario.rounds.iter().flat_map(|round| &round.participants))
+        .filter(|p| p.fixed_cost > 0)
+        .collect();
+    if committed.is_empty() {
+        return Ok(());
+    }
+    if let Some(p) = committed
+        .iter()
+        .find(|p| p.role != 1 || !p.steps.is_empty())
+    {
+        return Err(format!(
+            "Participant {} has a fixed_cost: only sellers without steps may have one",
+            p.id
+        )
+        .into());
+    }
+    if !matches!(config.algorithm, Algorithm::UniformPrice | Algorithm::Merge)
+        || config.settlement != Settlement::Uniform
+        || config.fee_bps > 0
+        || config.price_offset > 0
+        || config.net_owners
+        || config.bundles
+        || is_zonal(&scenario.participants)
+    {
+        return Err(
+            "fixed_cost requires UniformPrice or Merge with Uniform settlement, no fee, no price_offset, and no owner netting, bundles or zones"
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+fn validate_budgets(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    let budgeted: Vec<&Participant> = scenario
+        .participants
+        .iter()
+        .chain(scenario.rounds.iter().flat_map(|round| &round.participants))
+        .filter(|p| p.budget > 0)
+        .collect();
+    if budgeted.is_empty() {
+        return Ok(());
+    }
+    if let Some(p) = budgeted
+        .iter()
+        .find(|p| p.role != 0 || p.quantity != 0 || !p.steps.is_empty())
+    {
+        return Err(format!(
+            "Participant {} has a budget: budget bids are buyers with quantity 0 and no steps",
+            p.id
+        )
+        .into());
+    }
+    if config.algorithm != Algorithm::UniformPrice
+        || config.settlement != Settlement::Uniform
+        || config.price_offset > 0
+        || config.net_owners
+        || config.bundles
+    {
+        return Err(
+            "Budget bids require UniformPrice with Uniform settlement, no price_offset, and no owner netting or bundles"
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+fn validate_congestion_rent(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if !config.congestion_rent {
+        return Ok(());
+    }
+    if config.algorithm != Algorithm::UniformPrice
+        || config.settlement != Settlement::Uniform
+        || config.max_traded_volume == u64::MAX
+        || config.price_offset > 0
+        || config.bundles
+        || is_zonal(&scenario.participants)
+    {
+        return Err(
+            "congestion_rent requires UniformPrice with Uniform settlement, a max_traded_volume, no price_offset, and no bundles or zones"
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+fn validate_effective_crossing(
+    scenario: &AuctionScenario,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if !config.effective_crossing {
+        return Ok(());
+    }
+    if config.algorithm != Algorithm::UniformPrice || config.objective != Objective::FirstCrossing {
+        return Err("effective_crossing requires UniformPrice with FirstCrossing".into());
+    }
+    Ok(())
+}
+
+fn validate_curtailment(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if config.curtailment_comp_per_unit == 0 && config.funding_fee_bps == 0 {
+        return Ok(());
+    }
+    if config.fee_bps.saturating_add(config.funding_fee_bps) > 10_000 {
+        return Err(format!(
+            "Invalid funding fee: fee_bps + funding_fee_bps = {} bps exceeds 10000",
+            config.fee_bps.saturating_add(config.funding_fee_bps)
+        )
+        .into());
+    }
+    if matches!(config.algorithm, Algorithm::DutchClock | Algorithm::Greedy)
+        || config.settlement == Settlement::Vcg
+        || config.price_offset > 0
+        || config.bundles
+        || is_zonal(&scenario.participants)
+    {
+        return Err(format!(
+            "Curtailment compensation is not supported with {:?} / {:?} settlement, price_offset, bundles or zones",
+            config.algorithm, config.settlement
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn validate_position_limits(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    let limited = config.max_position_energy.is_some()
+        || scenario
+            .participants
+            .iter()
+            .chain(scenario.rounds.iter().flat_map(|round| &round.participants))
+            .any(|p| p.max_position_energy.is_some());
+    if limited
+        && (matches!(config.algorithm, Algorithm::DutchClock | Algorithm::Greedy) || config.bundles)
+    {
+        return Err(format!(
+            "max_position_energy is not supported with {:?} or bundles",
+            config.algorithm
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn validate_secondary(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if config.secondary_concession.is_none() {
+        return Ok(());
+    }
+    if config.algorithm != Algorithm::UniformPrice
+        || config.settlement != Settlement::Uniform
+        || config.objective != Objective::FirstCrossing
+        || config.fee_bps > 0
+        || config.loss_bps > 0
+        || config.price_offset > 0
+        || config.congestion_rent
+        || config.funding_fee_bps > 0
+        || config.bundles
+        || !scenario.rounds.is_empty()
+    {
+        return Err(
+            "secondary_concession requires UniformPrice with Uniform settlement and FirstCrossing, and no fees, losses, price_offset, congestion rent, bundles or rounds"
+                .into(),
+        );
+    }
+    if is_multi_commodity(&scenario.participants)
+        || is_zonal(&scenario.participants)
+        || is_carbon_priced(&scenario.participants)
+        || scenario
+            .participants
+            .iter()
+            .any(|p| p.role > 1 || !p.steps.is_empty() || p.budget > 0)
+    {
+        return Err(
+            "secondary_concession is not supported with commodities, zones, carbon, prosumers, steps or budget bids"
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+fn validate_settlement_price(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if config.settlement_price.is_none() {
+        return Ok(());
+    }
+    if !matches!(config.algorithm, Algorithm::UniformPrice | Algorithm::Merge)
+        || config.settlement != Settlement::Uniform
+        || config.objective != Objective::FirstCrossing
+        || config.price_floor.is_some()
+        || config.price_cap.is_some()
+        || config.congestion_rent
+        || config.secondary_concession.is_some()
+        || config.bundles
+        || is_multi_commodity(&scenario.participants)
+        || is_zonal(&scenario.participants)
+    {
+        return Err(
+            "settlement_price requires UniformPrice or Merge with Uniform settlement and FirstCrossing, and no price band, congestion rent, secondary round, bundles, commodities or zones"
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+fn validate_quantity_scale(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    // A power of ten keeps the scaled display an exact decimal
+    let scale = scenario.config.quantity_scale;
+    if scale == 0 || 10u64.pow(scale.ilog10()) != scale {
+        return Err("quantity_scale must be a power of ten (1, 10, 100, ...)".into());
+    }
+    Ok(())
+}
+
+fn validate_periods(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    if !is_multi_period(&scenario.participants) {
+        return Ok(());
+    }
+    let config = &scenario.config;
+    if !scenario.rounds.is_empty()
+        || config.bundles
+        || config.secondary_concession.is_some()
+        || config.objective != Objective::FirstCrossing
+        || is_multi_commodity(&scenario.participants)
+        || is_zonal(&scenario.participants)
+        || is_carbon_priced(&scenario.participants)
+    {
+        return Err(
+            "Multi-period bids do not support rounds, bundles, a secondary round, objectives, commodities, zones or carbon"
+                .into(),
+        );
+    }
+    for p in &scenario.participants {
+        if p.periods.len() > MAX_PERIODS {
+            return Err(format!(
+                "Participant {} bids {} periods (max {})",
+                p.id,
+                p.periods.len(),
+                MAX_PERIODS
+            )
+            .into());
+        }
+        if !p.periods.is_empty() && (p.role > 1 || !p.steps.is_empty()) {
+            return Err(format!(
+                "Participant {} combines periods with steps or a prosumer role",
+                p.id
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// The guest exits with code 1 on a repeated id; name every offender first
+///
+/// A multi-commodity book may list an id once per commodity.
+fn validate_unique_ids(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    use std::collections::BTreeMap;
+
+    let books = std::iter::once(("participants".to_string(), &scenario.participants)).chain(
+        scenario
+            .rounds
+            .iter()
+            .enumerate()
+            .map(|(round, input)| (format!("round {}", round), &input.participants)),
+    );
+    for (book, participants) in books {
+        let mut roles: BTreeMap<(u32, u32), Vec<u32>> = BTreeMap::new();
+        for p in participants {
+            roles.entry((p.id, p.commodity)).or_default().push(p.role);
+        }
+        let duplicates: Vec<String> = roles
+            .iter()
+            .filter(|(_, roles)| roles.len() > 1)
+            .map(|((id, _), roles)| format!("{} (roles {:?})", id, roles))
+            .collect();
+        if !duplicates.is_empty() {
+            return Err(format!(
+                "Duplicate participant ids in {}: {}",
+                book,
+                duplicates.join(", ")
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// The guest exits with code 2 on a role other than 0 / 1 / 2
+fn validate_roles(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let unknown: Vec<String> = scenario
+        .participants
+        .iter()
+        .chain(scenario.rounds.iter().flat_map(|round| &round.participants))
+        .filter(|p| p.role > 2)
+        .map(|p| format!("{} (role {})", p.id, p.role))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(format!(
+            "Unknown roles (0 = buy, 1 = sell, 2 = prosumer): participants {}",
+            unknown.join(", ")
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// The guest exits with code 10 when an order quotes a price above max_price
+///
+/// Without strict the guest keeps such participants out of the book instead.
+fn validate_max_price(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let above = prices_above_max(scenario);
+    if scenario.config.strict && !above.is_empty() {
+        return Err(format!(
+            "Prices above max_price {}: participants {}",
+            scenario.config.max_price,
+            above.join(", ")
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Participants quoting above max_price, as "id (price highest)"
+fn prices_above_max(scenario: &AuctionScenario) -> Vec<String> {
+    scenario
+        .participants
+        .iter()
+        .chain(scenario.rounds.iter().flat_map(|round| &round.participants))
+        .filter_map(|p| {
+            let price = quoted_prices(p).max()?;
+            (price > scenario.config.max_price).then(|| format!("{} (price {})", p.id, price))
+        })
+        .collect()
+}
+
+/// Lenient mode needs the guest's clear_book, which zones, carbon, bundles and the secondary round bypass
+fn validate_strict(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if !config.strict
+        && (is_zonal(&scenario.participants)
+            || is_carbon_priced(&scenario.participants)
+            || config.bundles
+            || config.secondary_concession.is_some())
+    {
+        return Err(
+            "strict: false is not supported with zones, carbon pricing, bundles or secondary_concession"
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+/// Whether the guest clears the scenario as one book committing only its PublicJournal
+///
+/// Only such a book may take the other journal variants and the optional
+/// sections (a trade list, sealed bids, the audit journal, the keccak
+/// digest): rounds, periods, commodities, zones, carbon pricing, bundles, a
+/// secondary round and an objective each commit sections of their own.
+fn is_plain_single_book(scenario: &AuctionScenario) -> bool {
+    let config = &scenario.config;
+    scenario.rounds.is_empty()
+        && !is_multi_period(&scenario.participants)
+        && !is_multi_commodity(&scenario.participants)
+        && !is_zonal(&scenario.participants)
+        && !is_carbon_priced(&scenario.participants)
+        && !config.bundles
+        && config.secondary_concession.is_none()
+        && config.objective == Objective::FirstCrossing
+}
+
+/// Refuse a feature the guest supports on a plain single book only (see is_plain_single_book)
+fn require_plain_single_book(
+    scenario: &AuctionScenario,
+    feature: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if is_plain_single_book(scenario) {
+        return Ok(());
+    }
+    Err(format!(
+        "{} needs a plain single book: not supported with rounds, periods, commodities, zones, carbon pricing, bundles, secondary_concession or an objective other than FirstCrossing",
+        feature
+    )
+    .into())
+}
+
+/// The guest commits a journal_variant other than Full for a plain single book only
+fn validate_compact(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if config.journal_variant == JournalVariant::Quantized(0) {
+        return Err(
+            "the Quantized journal's scale must be positive (the guest divides every balance by it)"
+                .into(),
+        );
+    }
+    if config.journal_variant != JournalVariant::Full {
+        require_plain_single_book(scenario, "A journal_variant other than Full")?;
+    }
+    Ok(())
+}
+
+/// The guest commits a keccak digest as the last section of the plain Full or ABI journal only
+fn validate_keccak_digest(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if !config.keccak_digest {
+        return Ok(());
+    }
+    require_plain_single_book(scenario, "keccak_digest")?;
+    if !scenario.commitments.is_empty()
+        || !matches!(
+            config.journal_variant,
+            JournalVariant::Full | JournalVariant::Abi
+        )
+    {
+        return Err(
+            "keccak_digest is not supported with commitments or a journal_variant other than Full or Abi"
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+/// The guest lists trades for Greedy's single-book journal only
+fn validate_trade_list(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if config.max_trades.is_none() {
+        return Ok(());
+    }
+    if config.algorithm != Algorithm::Greedy {
+        return Err(format!(
+            "max_trades lists bilateral trades: algorithm {:?} does not record any (use Greedy)",
+            config.algorithm
+        )
+        .into());
+    }
+    require_plain_single_book(scenario, "max_trades")?;
+    if config.journal_variant != JournalVariant::Full
+        || config.audit
+        || config.keccak_digest
+        || !scenario.commitments.is_empty()
+    {
+        return Err(
+            "max_trades commits its trades in place of the optional sections: not supported with audit, keccak_digest, commitments or a journal_variant other than Full"
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+/// The guest commits the audit journal after the plain single-book journal only
+fn validate_audit(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if !config.audit {
+        return Ok(());
+    }
+    require_plain_single_book(scenario, "audit")?;
+    if !scenario.commitments.is_empty()
+        || config.keccak_digest
+        || config.journal_variant != JournalVariant::Full
+    {
+        return Err(
+            "audit is not supported with commitments, keccak_digest or a journal_variant other than Full"
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+/// The guest opens sealed bids for the plain single-book journal only
+///
+/// Every commitment must open, in ascending id, or the guest exits with
+/// code 15; name the first that does not.
+fn validate_commitments(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if scenario.commitments.is_empty() {
+        return Ok(());
+    }
+    require_plain_single_book(scenario, "commitments")?;
+    if config.journal_variant != JournalVariant::Full {
+        return Err("commitments are not supported with a journal_variant other than Full".into());
+    }
+    if scenario.commitments.len() != scenario.participants.len() {
+        return Err(format!(
+            "commitments lists {} entries for {} participants (the guest would exit with code {})",
+            scenario.commitments.len(),
+            scenario.participants.len(),
+            EXIT_COMMITMENT_MISMATCH
+        )
+        .into());
+    }
+    for (position, (p, commitment)) in sealed_order(&scenario.participants)
+        .into_iter()
+        .zip(&scenario.commitments)
+        .enumerate()
+    {
+        let opened = bid_commitment(p);
+        if opened != *commitment {
+            return Err(format!(
+                "Commitment {} does not open: participant {}'s bid and salt hash to {}, not {} (the guest would exit with code {})",
+                position, p.id, opened, commitment, EXIT_COMMITMENT_MISMATCH
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Participants in the order of the commitments list: ascending (id, commodity)
+fn sealed_order(participants: &[Participant]) -> Vec<&Participant> {
+    let mut sorted: Vec<&Participant> = participants.iter().collect();
+    sorted.sort_by_key(|p| (p.id, p.commodity));
+    sorted
+}
+
+/// `host seal <scenario.json> [out.json]`: salt every bid and list its commitment
+///
+/// Participants without a salt (or with the all-zero default) get 32 bytes
+/// from /dev/urandom; every other field is kept as read (keys sorted) and
+/// the scenario written to out.json or over the input, with commitments in
+/// the order the guest checks them.
+fn seal_scenario(args: &[String]) {
+    let input = args
+        .first()
+        .expect("Usage: host seal <scenario.json> [out.json]");
+    let output = args.get(1).unwrap_or(input);
+    let content = fs::read_to_string(input).expect("Failed to read scenario");
+    let mut value: serde_json::Value = serde_json::from_str(&content).expect("Invalid scenario");
+    let rows = value["participants"]
+        .as_array_mut()
+        .expect("The scenario has no participants list");
+    let mut fresh = 0;
+    for row in rows {
+        let salted = row
+            .get("salt")
+            .and_then(|salt| serde_json::from_value::<[u8; 32]>(salt.clone()).ok())
+            .is_some_and(|salt| salt != [0; 32]);
+        if !salted {
+            row["salt"] = serde_json::json!(random_bytes());
+            fresh += 1;
+        }
+    }
+    let scenario: AuctionScenario =
+        serde_json::from_value(value.clone()).expect("Invalid scenario");
+    let commitments: Vec<Digest> = sealed_order(&scenario.participants)
+        .into_iter()
+        .map(bid_commitment)
+        .collect();
+    value["commitments"] = serde_json::json!(commitments);
+    let sealed = serde_json::to_string_pretty(&value).expect("Failed to serialize scenario");
+    fs::write(output, sealed + "\n").expect("Failed to write scenario");
+    println!(
+        "✓ Sealed {} bids ({} new salts), commitments written to {}",
+        commitments.len(),
+        fresh,
+        output
+    );
+}
+
+/// 32 bytes from /dev/urandom, for salts the scenario leaves to the host
+fn random_bytes() -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    std::io::Read::read_exact(
+        &mut fs::File::open("/dev/urandom").expect("Failed to open /dev/urandom"),
+        &mut bytes,
+    )
+    .expect("Failed to read /dev/urandom");
+    bytes
+}
+
+/// private_outputs.json of the rows the guest wrote, under the salt the host wrote
+fn private_outputs_file(rows: &PublicJournal, salt: &[u8; 32]) -> PrivateOutputsFile {
+    let rows = private_rows(rows);
+    PrivateOutputsFile {
+        outputs_digest: private_outputs_digest(salt, &rows).to_string(),
+        salt: hex::encode(salt),
+        rows,
+    }
+}
+
+/// An outputs file must open the committed digest and agree with the committed aggregates
+///
+/// The digest alone binds the rows; the row count, conservation and
+/// turnover are checked as well so that an error names what differs.
+fn check_private_outputs(
+    journal: &PrivateJournal,
+    file: &PrivateOutputsFile,
+) -> Result<(), String> {
+    let salt = hex::decode(&file.salt).map_err(|e| format!("salt is not hex: {}", e))?;
+    let digest = private_outputs_digest(&salt, &file.rows);
+    if digest != journal.outputs_digest {
+        return Err(format!(
+            "Outputs digest mismatch: the rows hash to {}, the journal commits {}",
+            digest, journal.outputs_digest
+        ));
+    }
+    if file.rows.len() != journal.row_count as usize {
+        return Err(format!(
+            "{} rows, the journal commits {}",
+            file.rows.len(),
+            journal.row_count
+        ));
+    }
+    let total = |column: fn(&PrivateRow) -> u64| -> u128 {
+        file.rows.iter().map(|row| column(row) as u128).sum()
+    };
+    if total(|row| row.in_coin) != total(|row| row.out_coin)
+        || total(|row| row.in_energy) != total(|row| row.out_energy)
+    {
+        return Err("the rows do not conserve coin and energy".into());
+    }
+    let turnover = total(|row| row.in_coin.saturating_sub(row.out_coin));
+    if turnover != journal.turnover {
+        return Err(format!(
+            "the rows move {} coin, the journal commits a turnover of {}",
+            turnover, journal.turnover
+        ));
+    }
+    Ok(())
+}
+
+/// `host verify-outputs <risc0_receipt.json> <private_outputs.json> [--expect-round-id <n>]`:
+/// check a distributed outputs file against the PrivateJournal in a receipt
+/// and, when given, the round the receipt was proven for (checked on any
+/// journal variant, before the variant itself)
+///
+/// The receipt is verified against the guest's image id first, so a journal
+/// edited after proving, or proven by another program, is refused before
+/// any of its fields is trusted.
+fn verify_outputs(args: &[String]) {
+    let usage = "Usage: host verify-outputs <risc0_receipt.json> <private_outputs.json> [--expect-round-id <n>]";
+    let (Some(receipt_file), Some(outputs_file)) = (args.first(), args.get(1)) else {
+        panic!("{}", usage);
+    };
+    let expected_round_id = match &args[2..] {
+        [] => None,
+        [flag, id] if flag == "--expect-round-id" => {
+            Some(id.parse().expect("--expect-round-id takes a u64"))
+        }
+        _ => panic!("{}", usage),
+    };
+    let receipt: risc0_zkvm::Receipt =
+        serde_json::from_str(&fs::read_to_string(receipt_file).expect("Failed to read receipt"))
+            .expect("Invalid receipt");
+    receipt
+        .verify(DOUBLE_AUCTION_GUEST_ID)
+        .unwrap_or_else(|e| panic!("Receipt verification failed: {}", e));
+    let decoded = decode_any_journal(&receipt.journal.bytes)
+        .unwrap_or_else(|e| panic!("Unsupported journal: {}", e));
+    if let Err(e) = check_round_id(decoded.round_id, expected_round_id) {
+        println!("✗ {}", e);
+        std::process::exit(1);
+    }
+    let CommittedJournal::Private(journal) = decoded.committed else {
+        panic!(
+            "The receipt does not carry a private journal (version {}): it commits the {:?} variant",
+            PRIVATE_JOURNAL_VERSION, decoded.variant
+        );
+    };
+    let file: PrivateOutputsFile = serde_json::from_str(
+        &fs::read_to_string(outputs_file).expect("Failed to read outputs file"),
+    )
+    .expect("Invalid outputs file");
+    check_private_outputs(&journal, &file).expect("Outputs file refused");
+    println!(
+        "✓ {} rows open the committed outputs digest {} (turnover {})",
+        file.rows.len(),
+        journal.outputs_digest,
+        journal.turnover
+    );
+}
+
+/// The guest exits with code 7 when a book's largest possible settlement exceeds i128
+fn validate_settlement_bound(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let books = std::iter::once(("participants".to_string(), &scenario.participants)).chain(
+        scenario
+            .rounds
+            .iter()
+            .enumerate()
+            .map(|(round, input)| (format!("round {}", round), &input.participants)),
+    );
+    for (book, participants) in books {
+        match settlement_bound(participants, &scenario.config) {
+            Some(bound) if bound <= i128::MAX as u128 => {}
+            Some(bound) => {
+                return Err(format!(
+                    "Largest possible settlement in {} is {} coin, above the i128 limit {}",
+                    book,
+                    bound,
+                    i128::MAX
+                )
+                .into())
+            }
+            None => {
+                return Err(
+                    format!("Largest possible settlement in {} overflows u128", book).into(),
+                )
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_zones(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    let config = &scenario.config;
+    if !is_zonal(&scenario.participants) {
+        if !config.transfer_capacities.is_empty() {
+            return Err("transfer_capacities require participants in more than one zone".into());
+        }
+        return Ok(());
+    }
+    if config.algorithm != Algorithm::UniformPrice
+        || config.settlement != Settlement::Uniform
+        || config.fee_bps > 0
+        || config.price_offset > 0
+    {
+        return Err(
+            "Zonal auctions require UniformPrice with Uniform settlement, no fee and no price_offset"
+                .into(),
+        );
+    }
+    if config.bundles || !scenario.rounds.is_empty() || is_multi_commodity(&scenario.participants) {
+        return Err("Zonal auctions do not support bundles, rounds or multiple commodities".into());
+    }
+    for p in &scenario.participants {
+        if !p.steps.is_empty() {
+            return Err(format!(
+                "Participant {} cannot place stepped bids in a zonal auction",
+                p.id
+            )
+            .into());
+        }
+    }
+    let has_zone = |zone: u32| scenario.participants.iter().any(|p| p.zone == zone);
+    for (i, &(from, to, _)) in config.transfer_capacities.iter().enumerate() {
+        if from == to || !has_zone(from) || !has_zone(to) {
+            return Err(format!(
+                "Invalid transfer link {} -> {}: zones must differ and have participants",
+                from, to
+            )
+            .into());
+        }
+        if config.transfer_capacities[..i]
+            .iter()
+            .any(|&(f, t, _)| (f, t) == (from, to))
+        {
+            return Err(format!("Transfer link {} -> {} is listed twice", from, to).into());
+        }
+    }
+    Ok(())
+}
+
+fn validate_rounds(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    if scenario.rounds.is_empty() {
+        return Ok(());
+    }
+    if scenario.config.bundles {
+        return Err("Multi-round auctions do not support bundles".into());
+    }
+    for (round, input) in scenario.rounds.iter().enumerate() {
+        let unknown: Vec<u32> = input
+            .participants
+            .iter()
+            .filter(|bid| !scenario.participants.iter().any(|p| p.id == bid.id))
+            .map(|bid| bid.id)
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!(
+                "Round {} bids from participants {:?} without an initial balance",
+                round, unknown
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+fn validate_commodities(scenario: &AuctionScenario) -> Result<(), Box<dyn std::error::Error>> {
+    if !is_multi_commodity(&scenario.participants) {
+        return Ok(());
+    }
+    if !scenario.rounds.is_empty() || scenario.config.bundles {
+        return Err("Multi-commodity auctions do not support rounds or bundles".into());
+    }
+    for (i, p) in scenario.participants.iter().enumerate() {
+        for other in &scenario.participants[..i] {
+            if other.id != p.id {
+                continue;
+            }
+            if other.in_coin != p.in_coin {
+                return Err(format!(
+                    "Participant {} shares one coin account across commodities but lists in_coin {} and {}",
+                    p.id, other.in_coin, p.in_coin
+                )
+                .into());
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,397 @@
+// Host integration tests
+//
+// The host binary is included whole so that its private helpers can be
+// driven directly. Tests that need a proof use dev-mode fake receipts.
+
+#![allow(dead_code)]
+
+include!("../src/main.rs");
+
+use risc0_zkvm::{FakeReceipt, Receipt, ReceiptClaim};
+
+/// risc0 serde words of a committed value, as the guest's env::commit writes them
+fn journal_bytes<T: Serialize>(value: &T) -> Vec<u8> {
+    risc0_zkvm::serde::to_vec(value)
+        .expect("Failed to encode journal")
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect()
+}
+
+/// A dev-mode receipt of the guest committing `journal`
+fn fake_receipt(journal: Vec<u8>) -> Receipt {
+    let claim = ReceiptClaim::ok(DOUBLE_AUCTION_GUEST_ID, journal.clone());
+    Receipt::new(InnerReceipt::Fake(FakeReceipt::new(claim)), journal)
+}
+
+/// Absolute path of a scenario in the scenarios directory
+fn scenario_path(name: &str) -> String {
+    format!("{}/../scenarios/{}.json", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+/// False under RISC0_SKIP_BUILD, where the guest ELF is empty: tests that
+/// execute the guest return early
+fn guest_built() -> bool {
+    !DOUBLE_AUCTION_GUEST_ELF.is_empty()
+}
+
+/// Executes the guest on a scenario's input, as the host writes it
+fn execute(scenario: &AuctionScenario) -> risc0_zkvm::SessionInfo {
+    let input = AuctionInput {
+        participants: scenario.participants.clone(),
+        config: scenario.config.clone(),
+        rounds: scenario.rounds.clone(),
+        commitments: scenario.commitments.clone(),
+        output_salt: scenario.output_salt,
+    };
+    let env = ExecutorEnv::builder()
+        .write(&input)
+        .unwrap()
+        .build()
+        .unwrap();
+    default_executor()
+        .execute(env, DOUBLE_AUCTION_GUEST_ELF)
+        .expect("Guest execution failed")
+}
+
+/// A private journal of two rows (buyer 0 pays 30 for seller 1's 2 units)
+/// and the outputs file that opens it
+fn private_round() -> (PrivateJournal, PrivateOutputsFile) {
+    let salt = [7u8; 32];
+    let rows = vec![
+        PrivateRow {
+            id: 0,
+            in_coin: 100,
+            in_energy: 0,
+            out_coin: 70,
+            out_energy: 2,
+        },
+        PrivateRow {
+            id: 1,
+            in_coin: 0,
+            in_energy: 2,
+            out_coin: 30,
+            out_energy: 0,
+        },
+    ];
+    let journal = PrivateJournal {
+        version: PRIVATE_JOURNAL_VERSION,
+        input_digest: Digest::ZERO,
+        algorithm_id: 0,
+        config_digest: Digest::ZERO,
+        outputs_digest: private_outputs_digest(&salt, &rows),
+        row_count: 2,
+        buyer_count: 1,
+        seller_count: 1,
+        turnover: 30,
+        status: STATUS_CLEARED,
+        clearing_price: 15,
+        traded_volume: 2,
+        fees_collected: 0,
+        surplus: 0,
+        fill_counts: FillCounts {
+            buyers_filled: 1,
+            buyers_partial: 0,
+            buyers_unfilled: 0,
+            sellers_filled: 1,
+            sellers_partial: 0,
+            sellers_unfilled: 0,
+        },
+        round_id: 42,
+    };
+    let file = PrivateOutputsFile {
+        outputs_digest: journal.outputs_digest.to_string(),
+        salt: hex::encode(salt),
+        rows,
+    };
+    (journal, file)
+}
+
+/// Writes a receipt and an outputs file to the temp dir and returns them as
+/// verify-outputs arguments
+fn verify_outputs_args(name: &str, receipt: &Receipt, file: &PrivateOutputsFile) -> Vec<String> {
+    let dir = env::temp_dir();
+    let receipt_file = dir.join(format!("{}_receipt.json", name));
+    let outputs_file = dir.join(format!("{}_outputs.json", name));
+    fs::write(&receipt_file, serde_json::to_string(receipt).unwrap()).unwrap();
+    fs::write(&outputs_file, serde_json::to_string(file).unwrap()).unwrap();
+    vec![
+        receipt_file.display().to_string(),
+        outputs_file.display().to_string(),
+    ]
+}
+
+/// An untouched dev-mode receipt verifies and its outputs file is accepted
+#[test]
+fn verify_outputs_accepts_a_verified_receipt() {
+    env::set_var("RISC0_DEV_MODE", "1");
+    let (journal, file) = private_round();
+    let receipt = fake_receipt(journal_bytes(&(journal, MarketStatus::TwoSided)));
+    receipt
+        .verify(DOUBLE_AUCTION_GUEST_ID)
+        .expect("Receipt should verify");
+    verify_outputs(&verify_outputs_args(
+        "verify_outputs_accepts",
+        &receipt,
+        &file,
+    ));
+}
+
+/// A journal edited after proving (the clearing price raised by one) no
+/// longer matches the receipt's claim and is refused before it is decoded
+#[test]
+#[should_panic(expected = "Receipt verification failed")]
+fn verify_outputs_refuses_a_tampered_journal() {
+    env::set_var("RISC0_DEV_MODE", "1");
+    let (journal, file) = private_round();
+    let mut receipt = fake_receipt(journal_bytes(&(journal.clone(), MarketStatus::TwoSided)));
+    let tampered = PrivateJournal {
+        clearing_price: journal.clearing_price + 1,
+        ..journal
+    };
+    receipt.journal.bytes = journal_bytes(&(tampered, MarketStatus::TwoSided));
+    verify_outputs(&verify_outputs_args(
+        "verify_outputs_tampered",
+        &receipt,
+        &file,
+    ));
+}
+
+/// A guest that halts with an AuctionError makes the host process exit with
+/// the guest's code: max_price_rejected_N2 under --skip-validation halts with
+/// PriceAboveMax (10) in the executor, and so does the host binary proving it
+#[test]
+#[ignore = "proves through the host binary in dev mode; run with --ignored"]
+fn host_exits_with_the_guest_exit_code() {
+    let name = "max_price_rejected_N2";
+    let session = execute(&load_scenario(&scenario_path(name)).unwrap());
+    let code = EXIT_PRICE_ABOVE_MAX as u32;
+    assert_eq!(session.exit_code, ExitCode::Halted(code));
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_host"))
+        .args([scenario_path(name).as_str(), "--skip-validation"])
+        .env("RISC0_DEV_MODE", "1")
+        .current_dir(env::temp_dir())
+        .status()
+        .expect("Failed to run the host");
+    assert_eq!(status.code(), Some(code as i32));
+}
+
+/// Every AuctionError an input can cause halts the guest with the error's
+/// exit code and commits the error alone. Codes 6, 8, 9, 11 and 12 come only
+/// from a broken clearing algorithm or journal builder, so no input reaches
+/// them (the property tests cover them natively)
+#[test]
+fn guest_halts_with_each_input_error() {
+    if !guest_built() {
+        return;
+    }
+    let load = |name: &str| load_scenario(&scenario_path(name)).unwrap();
+    let mut lossy_secondary = load("secondary_round_N4");
+    lossy_secondary.config.loss_bps = 10;
+    let mut k_above_one = load("kdouble_k1_4_N4");
+    (
+        k_above_one.config.k_numerator,
+        k_above_one.config.k_denominator,
+    ) = (3, 2);
+    let cases = [
+        (
+            "duplicate_id_N3",
+            load("duplicate_id_N3"),
+            AuctionError::DuplicateId(1),
+        ),
+        (
+            "unknown_role_N3",
+            load("unknown_role_N3"),
+            AuctionError::InvalidRole(2),
+        ),
+        (
+            "participant_limit_over_N1025",
+            load("participant_limit_over_N1025"),
+            AuctionError::TooManyParticipants(1025),
+        ),
+        (
+            "expanded_orders_over_N129",
+            load("expanded_orders_over_N129"),
+            AuctionError::TooManyOrders(1032),
+        ),
+        (
+            "overflow_rejected_N2",
+            load("overflow_rejected_N2"),
+            AuctionError::Overflow,
+        ),
+        (
+            "overflow_greedy_N3",
+            load("overflow_greedy_N3"),
+            AuctionError::Overflow,
+        ),
+        (
+            "saturation_joint_over_N2",
+            load("saturation_joint_over_N2"),
+            AuctionError::SettlementBound,
+        ),
+        (
+            "max_price_rejected_N2",
+            load("max_price_rejected_N2"),
+            AuctionError::PriceAboveMax(0),
+        ),
+        (
+            "degenerate_empty_N0",
+            load("degenerate_empty_N0"),
+            AuctionError::EmptyMarket,
+        ),
+        (
+            "trade_limit_N4",
+            load("trade_limit_N4"),
+            AuctionError::TooManyTrades(3),
+        ),
+        (
+            "sealed_wrong_salt_N4",
+            load("sealed_wrong_salt_N4"),
+            AuctionError::CommitmentMismatch(2),
+        ),
+        (
+            "secondary_round_N4, loss_bps 10",
+            lossy_secondary,
+            AuctionError::UnsupportedMode,
+        ),
+        (
+            "kdouble_k1_4_N4, k 3/2",
+            k_above_one,
+            AuctionError::InvalidKDouble,
+        ),
+    ];
+    for (name, scenario, error) in cases {
+        let session = execute(&scenario);
+        assert_eq!(
+            session.exit_code,
+            ExitCode::Halted(error.exit_code() as u32),
+            "{}",
+            name
+        );
+        let committed: AuctionError = session
+            .journal
+            .decode()
+            .expect("Journal is not an AuctionError");
+        assert_eq!(committed, error, "{}", name);
+    }
+}
+
+/// --check-determinism passes on a single book, a multi-round book, a
+/// keccak-bound journal and a book the guest refuses, each reporting the
+/// exit code every run ended with
+#[test]
+fn check_reproducible_on_scenarios() {
+    if !guest_built() {
+        return;
+    }
+    for (name, code) in [
+        ("marginal_inside_ask_N4", 0),
+        ("multi_round_N4", 0),
+        ("periods_N2", 0),
+        ("keccak_digest_N4", 0),
+        ("duplicate_id_N3", EXIT_DUPLICATE_ID as u32),
+    ] {
+        let scenario = load_scenario(&scenario_path(name)).unwrap();
+        let (runs, exit_code) =
+            check_reproducible(&scenario).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        assert_eq!(runs, 2 + DETERMINISM_SHUFFLES as usize, "{}", name);
+        assert_eq!(exit_code, ExitCode::Halted(code), "{}", name);
+    }
+}
+
+/// The guest settles the mixed_balances_*.json books on top of the coin and
+/// energy each row already holds, as the properties crate computes natively
+#[test]
+fn guest_settles_mixed_initial_balances() {
+    if !guest_built() {
+        return;
+    }
+    for (name, out_coin, out_energy) in [
+        (
+            "mixed_balances_N5",
+            vec![399, 300, 42, 169, 25],
+            vec![9, 9, 3, 4, 8],
+        ),
+        (
+            "mixed_balances_greedy_N4",
+            vec![400, 300, 170, 25],
+            vec![9, 9, 4, 8],
+        ),
+        (
+            "mixed_balances_dutch_N3",
+            vec![1100, 500, 930],
+            vec![16, 2, 2],
+        ),
+    ] {
+        let session = execute(&load_scenario(&scenario_path(name)).unwrap());
+        let decoded = decode_any_journal(&session.journal.bytes).unwrap();
+        let CommittedJournal::Full(journal) = decoded.committed else {
+            panic!("{}: not a full journal", name);
+        };
+        assert_eq!(journal.out_coin, out_coin, "{}", name);
+        assert_eq!(journal.out_energy, out_energy, "{}", name);
+    }
+}
+
+/// check_round_id passes without --expect-round-id or on the same id, and
+/// names both ids otherwise
+#[test]
+fn check_round_id_refuses_another_round() {
+    assert_eq!(check_round_id(42, None), Ok(()));
+    assert_eq!(check_round_id(42, Some(42)), Ok(()));
+    assert_eq!(
+        check_round_id(42, Some(43)),
+        Err("Round id mismatch: the journal commits 42, --expect-round-id gives 43".to_string())
+    );
+}
+
+/// verify-outputs exits with 1 on a receipt for another round whatever its
+/// variant: the private journal of round 42, and a compact journal of the
+/// same round, refused for its round before its variant
+#[test]
+fn verify_outputs_exits_on_another_round() {
+    let (journal, file) = private_round();
+    let compact = CompactJournal {
+        version: COMPACT_JOURNAL_VERSION,
+        input_digest: journal.input_digest,
+        algorithm_id: journal.algorithm_id,
+        config_digest: journal.config_digest,
+        outputs_root: Digest::ZERO,
+        row_count: journal.row_count,
+        buyer_count: journal.buyer_count,
+        seller_count: journal.seller_count,
+        total_coin: 100,
+        total_energy: 2,
+        status: journal.status,
+        clearing_price: journal.clearing_price,
+        traded_volume: journal.traded_volume,
+        fees_collected: journal.fees_collected,
+        surplus: journal.surplus,
+        fill_counts: journal.fill_counts,
+        round_id: journal.round_id,
+    };
+    for (name, bytes) in [
+        ("private", journal_bytes(&(journal, MarketStatus::TwoSided))),
+        ("compact", journal_bytes(&(compact, MarketStatus::TwoSided))),
+    ] {
+        let args = verify_outputs_args(
+            &format!("verify_outputs_round_{}", name),
+            &fake_receipt(bytes),
+            &file,
+        );
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_host"))
+            .arg("verify-outputs")
+            .args(&args)
+            .args(["--expect-round-id", "43"])
+            .env("RISC0_DEV_MODE", "1")
+            .output()
+            .expect("Failed to run the host");
+        assert_eq!(output.status.code(), Some(1), "{}", name);
+        assert!(
+            String::from_utf8_lossy(&output.stdout)
+                .contains("Round id mismatch: the journal commits 42, --expect-round-id gives 43"),
+            "{}",
+            name
+        );
+    }
+}
@@ -8,13 +8,15 @@
 //   by modifying only the MODULAR ALGORITHM section below.
 //
 // MODULARITY POINT:
-//   Replace run_double_auction() function body (line 107) with your algorithm.
+//   Replace run_double_auction() function body with your algorithm.
 //
 // CONSTRAINTS:
-//   • Input:  AuctionInput  (participants with bids/balances)
+//   • Input:  AuctionInput  (participants with bids/balances, auction config)
 //   • Output: PublicJournal (sorted: buyers DESC, sellers ASC by price)
 //   • Law:    Σ in_coin == Σ out_coin, Σ in_energy == Σ out_energy
 //   • Must be deterministic (no external I/O, randomness, or time)
+//   • Order-free: shuffling the rows of a book never changes the journal
+//   • Errors: an AuctionError is committed alone and sets the exit code
 //
 // EXAMPLES OF ALTERNATIVE ALGORITHMS:
 //   • Vickrey auction (second-price sealed bid)
@@ -25,74 +27,4262 @@
 // ═══════════════════════════════════════════════════════════════════════════
 
 use risc0_zkvm::guest::env;
-use serde::{Deserialize, Serialize};
+use risc0_zkvm::sha::{Digest, Impl, Sha256};
+use types::*;
 
 // ═══════════════════════════════════════════════════════════════════════════
 // DATA STRUCTURES (DO NOT MODIFY)
 // ═══════════════════════════════════════════════════════════════════════════
+//
+// The input, the journals, the exit codes and the limits are defined once in
+// the types crate (types/src/lib.rs), which the host, the property tests and
+// the fuzz target share, with every function the host must compute alike
+// (mode selection, the settlement bound, the digests, keccak256 and the
+// audit check); only what this implementation commits on its own account
+// is defined here.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Identifier of the clearing implementation, committed in every PublicJournal
+///
+/// The image id changes on every rebuild, so a verifier tells mechanisms
+/// apart by this constant and the committed config_digest, whose config
+/// selects the algorithm and its parameters. 1 is this reference
+/// implementation (every config.algorithm); a replacement of
+/// run_double_auction must commit an id of its own.
+const ALGORITHM_ID: u32 = 1;
+
+/// Price and volume of one clearing: (clearing_price, traded_volume)
+///
+/// The price is the uniform price buyers pay, in encoded units (see
+/// price_offset): McAfee and congestion rent pay the sellers less, PayAsBid
+/// and Vcg settle each order on its own. The Dutch clock and greedy
+/// matching commit the last price that traded, bundles the lowest accepted
+/// bid and zonal books 0 (see ZonalJournal.prices). The volume is Σ seller
+/// allocations, losses included; both are 0 when nothing trades.
+type Clearing = (u64, u64);
+
+/// Clearing of a book where nothing trades
+const NO_TRADE: Clearing = (0, 0);
+
+// ═══════════════════════════════════════════════════════════════════════════
+// ENTRY POINT (DO NOT MODIFY)
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn main() {
+    let auction_input: AuctionInput = env::read();
+    if let Err(error) = run(&auction_input) {
+        // Only the error is committed: nothing is committed before a branch succeeds
+        env::commit(&error);
+        env::exit(error.exit_code());
+    }
+}
+
+/// Validate the input, clear it in the mode its fields select and commit the journal
+///
+/// Every committed journal carries the input's digest (see input_digest),
+/// its config's (see config_digest) and config.round_id, each round's and
+/// each market's too.
+fn run(auction_input: &AuctionInput) -> Result<(), AuctionError> {
+    validate_input(auction_input)?;
+    let (book, config) = (&auction_input.participants, &auction_input.config);
+    let digest = input_digest(auction_input);
+    let config_digest = config_digest(config);
+    if !auction_input.rounds.is_empty() {
+        let (mut journal, round_cycles) = run_rounds(auction_input)?;
+        for round in &mut journal.rounds {
+            round.bind(digest, config_digest, config.round_id);
+        }
+        env::commit(&journal);
+        env::write(&round_cycles); // Benchmark side channel, not part of the proof
+    } else if is_multi_period(&auction_input.participants) {
+        let (mut journal, period_cycles) = run_periods(auction_input)?;
+        journal.input_digest = digest;
+        journal.config_digest = config_digest;
+        journal.round_id = config.round_id;
+        env::commit(&journal);
+        env::write(&period_cycles); // Benchmark side channel, not part of the proof
+    } else if is_multi_commodity(&auction_input.participants) {
+        let mut journal = run_commodities(auction_input)?;
+        for market in &mut journal.markets {
+            market.bind(digest, config_digest, config.round_id);
+        }
+        env::commit(&journal);
+    } else if is_zonal(&auction_input.participants) {
+        let mut journal = run_zones(auction_input)?;
+        check_journal(&journal.journal, book, config)?;
+        check_feasibility(&journal.journal, book, config)?;
+        journal.journal.bind(digest, config_digest, config.round_id);
+        env::commit(&journal);
+    } else if is_carbon_priced(&auction_input.participants) {
+        let (mut journal, carbon) = run_carbon_auction(auction_input)?;
+        check_journal(&journal, book, config)?;
+        check_feasibility(&journal, book, config)?;
+        journal.bind(digest, config_digest, config.round_id);
+        env::commit(&journal);
+        env::commit(&carbon);
+        env::commit(&market_status(auction_input));
+    } else if auction_input.config.bundles {
+        let (mut journal, capacity) = run_bundle_auction(auction_input)?;
+        check_journal(&journal, book, config)?;
+        check_feasibility(&journal, book, config)?;
+        journal.bind(digest, config_digest, config.round_id);
+        env::commit(&journal);
+        env::commit(&capacity);
+        env::commit(&market_status(auction_input));
+    } else if auction_input.config.secondary_concession.is_some() {
+        let (mut journal, secondary) = run_secondary_round(auction_input)?;
+        check_journal(&journal, book, config)?;
+        check_feasibility(&journal, book, config)?;
+        journal.bind(digest, config_digest, config.round_id);
+        env::commit(&journal);
+        env::commit(&secondary);
+        env::commit(&market_status(auction_input));
+    } else if let Some(max_trades) = auction_input.config.max_trades {
+        let (mut journal, trades) = clear_with_trades(auction_input, max_trades)?;
+        journal.bind(digest, config_digest, config.round_id);
+        env::commit(&journal);
+        env::commit(&trades);
+        env::commit(&market_status(auction_input));
+    } else {
+        let mut journal = clear_book(auction_input)?;
+        journal.bind(digest, config_digest, config.round_id);
+        match config.journal_variant {
+            JournalVariant::Full => {
+                env::commit(&journal);
+                if config.objective != Objective::FirstCrossing {
+                    env::commit(&build_objective_journal(auction_input, &journal));
+                }
+                if !auction_input.commitments.is_empty() {
+                    let commitments = auction_input.commitments.clone(); // Checked by validate_input
+                    env::commit(&CommitmentsJournal { commitments });
+                }
+                if config.audit {
+                    let audit = audit_journal(auction_input);
+                    check_audit(&journal, &audit, book)?;
+                    env::commit(&audit);
+                }
+                env::commit(&market_status(auction_input));
+                if config.keccak_digest {
+                    env::commit(&journal_keccak(&journal));
+                }
+            }
+            JournalVariant::Compact => {
+                env::commit(&compact_journal(&journal));
+                env::write(&journal); // The rows behind outputs_root, for the host's outputs.json
+                env::commit(&market_status(auction_input));
+            }
+            JournalVariant::Delta => {
+                let delta = delta_journal(&journal)?;
+                check_deltas(&delta)?;
+                env::commit(&delta);
+                env::commit(&market_status(auction_input));
+            }
+            JournalVariant::Abi => {
+                let bytes = abi_journal_bytes(&journal);
+                env::commit_slice(&bytes); // Raw bytes: no serde words, no market status
+                if config.keccak_digest {
+                    env::commit_slice(&keccak256(&bytes)); // 32 raw bytes after the packed journal
+                }
+            }
+            JournalVariant::Private => {
+                env::commit(&private_journal(&journal, &auction_input.output_salt));
+                env::write(&journal); // The rows behind outputs_digest, for the host's private_outputs.json
+                env::commit(&market_status(auction_input));
+            }
+            JournalVariant::Quantized(scale) => {
+                let quantized = quantized_journal(&journal, scale)?;
+                check_quantized(&journal, &quantized)?;
+                env::commit(&quantized);
+                env::commit(&market_status(auction_input));
+            }
+            JournalVariant::Slim => {
+                env::commit(&slim_journal(&journal));
+                env::commit(&market_status(auction_input));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reject inputs no clearing mode can settle, before any clearing work
+///
+/// An input without participants is refused outright: its journal would
+/// have no rows, which no verifier sized for a fixed N can consume. The
+/// participant limit is checked next, so an oversized input ends in a
+/// short session instead of running out of segments or memory mid-proof.
+/// Every check exits in both modes except the price bound, which
+/// config.strict alone enforces (see `price_above_max`).
+fn validate_input(input: &AuctionInput) -> Result<(), AuctionError> {
+    if input.participants.is_empty() {
+        return Err(AuctionError::EmptyMarket);
+    }
+    let rows = input_books(input).map(Vec::len).sum::<usize>();
+    if rows > MAX_PARTICIPANTS {
+        return Err(AuctionError::TooManyParticipants(rows));
+    }
+    if let Some(id) = duplicate_id(input) {
+        return Err(AuctionError::DuplicateId(id));
+    }
+    if let Some(id) = unknown_role(input) {
+        return Err(AuctionError::InvalidRole(id));
+    }
+    if unsupported_mode(input) {
+        return Err(AuctionError::UnsupportedMode);
+    }
+    let config = &input.config;
+    if config.pricing_rule == PricingRule::KDouble
+        && (config.k_denominator == 0 || config.k_numerator > config.k_denominator)
+    {
+        return Err(AuctionError::InvalidKDouble);
+    }
+    if let Some(id) = price_above_max(input).filter(|_| input.config.strict) {
+        return Err(AuctionError::PriceAboveMax(id));
+    }
+    let within_bound = |participants: &Vec<Participant>| {
+        settlement_bound(participants, &input.config)
+            .is_some_and(|bound| bound <= i128::MAX as u128)
+    };
+    if !input_books(input).all(within_bound) {
+        return Err(AuctionError::SettlementBound);
+    }
+    check_commitments(input)
+}
+
+/// Whether the input combines features its clearing mode would settle wrongly or ignore
+///
+/// run picks one mode per input, so a feature another mode owns would be
+/// dropped silently: carbon budgets only trim a plain uniform-price book
+/// (see `run_carbon_auction`), and the secondary round carries each
+/// primary row's energy change over as its traded quantity, which losses,
+/// prosumers, steps or budget bids break (see `run_secondary_round`).
+fn unsupported_mode(input: &AuctionInput) -> bool {
+    let config = &input.config;
+    let participants = &input.participants;
+    let carbon = input_books(input).any(|book| is_carbon_priced(book));
+    let unsupported_secondary = config.secondary_concession.is_some()
+        && (config.algorithm != Algorithm::UniformPrice
+            || config.settlement != Settlement::Uniform
+            || config.objective != Objective::FirstCrossing
+            || config.fee_bps > 0
+            || config.loss_bps > 0
+            || config.price_offset > 0
+            || config.congestion_rent
+            || config.funding_fee_bps > 0
+            || config.bundles
+            || carbon
+            || !input.rounds.is_empty()
+            || is_multi_period(participants)
+            || is_multi_commodity(participants)
+            || is_zonal(participants)
+            || participants
+                .iter()
+                .any(|p| p.role > 1 || !p.steps.is_empty() || p.budget > 0));
+    let unsupported_carbon = carbon
+        && (config.algorithm != Algorithm::UniformPrice
+            || config.settlement == Settlement::Vcg
+            || config.loss_bps > 0
+            || config.price_tick > 1
+            || config.quantity_lot > 1
+            || config.bundles
+            || !input.rounds.is_empty()
+            || is_multi_period(participants)
+            || is_multi_commodity(participants)
+            || is_zonal(participants)
+            || participants.iter().any(|p| {
+                p.role > 1
+                    || !p.steps.is_empty()
+                    || (p.role == 0 && p.carbon_per_unit > 0)
+                    || (p.role == 1 && p.carbon_budget.is_some())
+            }));
+    unsupported_secondary || unsupported_carbon
+}
+
+/// Treat a failed checked operation as an overflow
+fn checked<T>(value: Option<T>) -> Result<T, AuctionError> {
+    value.ok_or(AuctionError::Overflow)
+}
+
+/// Smallest id listed twice in input.participants or in one round's book
+///
+/// Allocations and outputs are keyed by id, so a repeated id would merge or
+/// misattribute balances. A multi-commodity book may list an id once per
+/// commodity (one shared coin account).
+fn duplicate_id(input: &AuctionInput) -> Option<u32> {
+    use std::collections::BTreeSet;
+
+    input_books(input).find_map(|participants| {
+        let mut seen = BTreeSet::new();
+        participants
+            .iter()
+            .filter(|p| !seen.insert((p.id, p.commodity)))
+            .map(|p| p.id)
+            .min()
+    })
+}
+
+/// Smallest id in the first book with a role other than buyer, seller or prosumer
+///
+/// Such a row would fall out of every side of the book and out of the
+/// journal with its balances, so the input is rejected rather than passed
+/// through. Both checks report the smallest id rather than the first row,
+/// so the committed error does not depend on the order of the rows.
+fn unknown_role(input: &AuctionInput) -> Option<u32> {
+    input_books(input).find_map(|participants| {
+        participants
+            .iter()
+            .filter(|p| p.role > 2)
+            .map(|p| p.id)
+            .min()
+    })
+}
+
+/// Smallest id in the first book quoting a price above config.max_price
+///
+/// Every quoted price (see `quoted_prices`) enters the crossing grid and the
+/// pricing rules, so one absurd bid such as u64::MAX becomes a grid price
+/// and, as the marginal bid, the clearing price. The bound rejects the
+/// input instead (without config.strict the participant places no orders,
+/// see `expand_orders`); it may be raised up to u64::MAX, which the
+/// settlement arithmetic still handles exactly (see `settlement_bound`).
+fn price_above_max(input: &AuctionInput) -> Option<u32> {
+    input_books(input).find_map(|participants| {
+        participants
+            .iter()
+            .filter(|p| !within_max_price(p, &input.config))
+            .map(|p| p.id)
+            .min()
+    })
+}
+
+/// Whether every price a participant quotes is at most config.max_price
+fn within_max_price(p: &Participant, config: &AuctionConfig) -> bool {
+    quoted_prices(p).all(|price| price <= config.max_price)
+}
+
+/// Classify a single book before it clears (see MarketStatus)
+///
+/// Looks at the live orders after step and prosumer expansion, without the
+/// operator. A budget bid counts as a positive quantity.
+fn market_status(input: &AuctionInput) -> MarketStatus {
+    if input.participants.is_empty() {
+        return MarketStatus::Empty;
+    }
+    let orders = expand_orders(&input.participants, &input.config);
+    let quoting: Vec<&Participant> = orders
+        .iter()
+        .filter(|o| Some(o.id) != input.config.operator_id)
+        .filter(|o| quotes_quantity(o))
+        .collect();
+    let buyers = quoting.iter().any(|o| o.role == 0);
+    let sellers = quoting.iter().any(|o| o.role == 1);
+    match (buyers, sellers) {
+        (true, true) => MarketStatus::TwoSided,
+        (false, false) => MarketStatus::ZeroQuantity,
+        (false, true) => MarketStatus::NoBuyers,
+        (true, false) => MarketStatus::NoSellers,
+    }
+}
+
+/// input.participants followed by each round's book
+fn input_books(input: &AuctionInput) -> impl Iterator<Item = &Vec<Participant>> {
+    core::iter::once(&input.participants)
+        .chain(input.rounds.iter().map(|round| &round.participants))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// MODULAR ALGORITHM INTERFACE
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// DEVELOPER CUSTOMIZATION POINT:
+//   Replace the function body below with your custom auction algorithm.
+//   Keep the function signature unchanged.
+//
+// INPUT:
+//   • participants: Vec<Participant> with id, role, price, quantity, balances
+//   • config:       AuctionConfig with mechanism parameters (pricing rule, ...)
+//
+// OUTPUT:
+//   • Ok(PublicJournal) with computed allocations (a no-trade journal when
+//     nothing clears), or Err(AuctionError) when the book cannot be settled
+//   • MUST preserve protocol ordering (buyers DESC, sellers ASC by price)
+//   • MUST satisfy conservation: Σ in == Σ out (both coin and energy)
+//
+// CURRENT IMPLEMENTATION:
+//   Uniform-price double auction with marginal pricing (default), McAfee
+//   trade reduction, a single-seller Dutch/English clock, greedy
+//   bilateral matching, or a merge walk of the sorted book, selected by
+//   config.algorithm
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+fn run_double_auction(input: &AuctionInput) -> Result<PublicJournal, AuctionError> {
+    let participants = &input.participants;
+
+    // Separate and sort participants (protocol requirement)
+    let (buyers, sellers) = separate_and_sort(participants, &input.config);
+
+    // ─────────────────────────────────────────────────────────────────────────
+    // AUCTION ALGORITHM (CUSTOMIZE THIS SECTION)
+    // ─────────────────────────────────────────────────────────────────────────
+
+    // Coin imbalances (spreads, VCG, trade reduction) must land on the operator
+    if needs_operator(&input.config) && !has_participant(participants, input.config.operator_id) {
+        return Ok(PublicJournal {
+            status: STATUS_NO_SETTLEMENT_ACCOUNT,
+            ..build_journal(participants, &buyers, &sellers)
+        });
+    }
+
+    // Delivery losses must land on the sink to conserve energy
+    if input.config.loss_bps > 0 && !has_participant(participants, input.config.loss_sink_id) {
+        return Ok(PublicJournal {
+            status: STATUS_NO_SETTLEMENT_ACCOUNT,
+            ..build_journal(participants, &buyers, &sellers)
+        });
+    }
+
+    // Stepped bids and prosumers become virtual orders sharing the participant id
+    let orders = expand_orders(participants, &input.config);
+    if orders.len() > MAX_EXPANDED_ORDERS {
+        return Err(AuctionError::TooManyOrders(orders.len()));
+    }
+
+    // Orders off the price tick / quantity lot grid are rounded or dropped
+    let orders = conform_to_grid(&orders, &input.config);
+    let (order_buyers, order_sellers) = separate_and_sort(&orders, &input.config);
+
+    // Settle once per participant side (first surviving order carries the balances)
+    let accounts = settlement_accounts(&orders);
+
+    // The operator is a settlement account, not an order: keep it out of the book.
+    // Orders that cannot trade pass through the same way (see is_tradable).
+    let operator_id = input.config.operator_id;
+    let book_buyers: Vec<&Participant> = order_buyers
+        .iter()
+        .copied()
+        .filter(|b| Some(b.id) != operator_id && is_tradable(b))
+        .collect();
+    let book_sellers: Vec<&Participant> = order_sellers
+        .iter()
+        .copied()
+        .filter(|s| Some(s.id) != operator_id && is_tradable(s))
+        .collect();
+
+    let book = (&book_buyers[..], &book_sellers[..]);
+    let outputs = match input.config.algorithm {
+        Algorithm::UniformPrice
+        | Algorithm::McAfee
+        | Algorithm::EnglishClock
+        | Algorithm::Merge => clear_and_settle(&accounts, book, &input.config)?,
+        // Fills settle without fees, so nothing is collected
+        Algorithm::DutchClock => match run_dutch_clock(book, &input.config)? {
+            Some((fills, clearing)) => {
+                let allocations = checked(fill_allocations(&fills))?;
+                let surplus = checked(matched_surplus(book.0, book.1, &allocations))?;
+                Some((
+                    compute_fill_outputs(&accounts, &fills)?,
+                    clearing,
+                    0,
+                    surplus,
+                    allocations,
+                ))
+            }
+            None => None,
+        },
+        Algorithm::Greedy => {
+            let trades = run_greedy_matching(book);
+            let fills = checked(fills_from_trades(&trades))?;
+            let clearing = checked(trades_clearing(&trades))?;
+            let allocations = checked(fill_allocations(&fills))?;
+            let surplus = checked(matched_surplus(book.0, book.1, &allocations))?;
+            Some((
+                compute_fill_outputs(&accounts, &fills)?,
+                clearing,
+                0,
+                surplus,
+                allocations,
+            ))
+        }
+    };
+
+    let (mut outputs, clearing, fees_collected, surplus, allocations) = match outputs {
+        Some(outputs) => outputs,
+        None => {
+            return Ok(PublicJournal {
+                status: no_trade_status(book),
+                ..build_journal(participants, &buyers, &sellers)
+            });
+        }
+    };
+    let status = match clearing {
+        (_, 0) => no_trade_status(book),
+        _ => STATUS_CLEARED,
+    };
+
+    // ─────────────────────────────────────────────────────────────────────────
+    // END CUSTOMIZABLE SECTION
+    // ─────────────────────────────────────────────────────────────────────────
+
+    // Format journal in protocol order (DO NOT MODIFY)
+    let wanted = |p: &Participant| order_quantity(p, clearing.0, &input.config);
+    Ok(PublicJournal {
+        status,
+        fees_collected,
+        surplus,
+        fill_counts: fill_counts((&buyers, &sellers), &orders, &allocations, wanted),
+        allocation: allocation_column(participants, (&buyers, &sellers), &allocations),
+        ..build_journal_with_outputs(participants, &buyers, &sellers, &mut outputs, clearing)
+    })
+}
+
+/// Status of a book that traded nothing: whether its best bid reached its best ask
+///
+/// The book is run_double_auction's (tradable orders, no operator), each
+/// side in price priority.
+fn no_trade_status((buyers, sellers): (&[&Participant], &[&Participant])) -> u32 {
+    match (buyers.first(), sellers.first()) {
+        (Some(bid), Some(ask)) if bid.price >= ask.price => STATUS_ZERO_VOLUME,
+        _ => STATUS_NO_CROSSING,
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// REFERENCE IMPLEMENTATION: Uniform-Price Double Auction
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// The functions below implement the default auction mechanism.
+// You may DELETE or REPLACE these when implementing a custom algorithm.
+//
+// ALGORITHM:
+//   1. Find clearing price p* where supply(p) >= demand(p), or the grid
+//      price optimizing config.objective (volume or surplus)
+//   2. Set price from marginal bid/ask per config.pricing_rule (default mid-point)
+//   3. Allocate based on effective caps (min of quantity, balance constraint)
+//   4. Ration the long side per config.allocation_rule (price priority
+//      by default, or pro-rata to effective caps)
+//   5. Settle at the clearing price, per order (pay-as-bid), or by VCG
+//      externality, with any coin imbalance credited to the operator
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Settled balances: (participant_id, out_coin, out_energy)
+type Outputs = Vec<(u32, u64, u64)>;
+
+/// A settled book: (outputs, clearing, fees_collected, surplus, allocations)
+type Settled = (Outputs, Clearing, u64, u64, Vec<(OrderKey, u64)>);
+
+/// Run a price-based clearing algorithm and settle its allocations
+///
+/// Returns: Ok(Some((outputs, clearing, fees_collected, surplus, allocations))), Ok(None)
+/// when nothing clears (or the coin imbalance cannot be settled),
+/// Err(Overflow) when a settlement step leaves its integer range or the
+/// surplus does not fit u64 (see `matched_surplus`). Vcg charges no fees.
+fn clear_and_settle(
+    participants: &[Participant],
+    (buyers, sellers): (&[&Participant], &[&Participant]),
+    config: &AuctionConfig,
+) -> Result<Option<Settled>, AuctionError> {
+    let clearing = if config.net_owners {
+        clear_netted_book((buyers, sellers), config)?
+    } else {
+        price_book((buyers, sellers), config)?
+    };
+    let Some((prices, mut allocations)) = clearing else {
+        return Ok(None);
+    };
+    net_owner_trades(buyers, sellers, &mut allocations);
+    let prices = if config.congestion_rent {
+        congestion_prices((buyers, sellers), &allocations, prices.0, config)
+    } else {
+        prices
+    };
+    let clearing = match allocated_volume(&allocations) {
+        0 => NO_TRADE, // A crossing whose allocations all round to 0
+        volume => (prices.0, volume),
+    };
+    let surplus = checked(matched_surplus(buyers, sellers, &allocations))?;
+
+    if config.settlement == Settlement::Vcg {
+        let outputs = compute_vcg_outputs(participants, (buyers, sellers), &allocations, config)?;
+        Ok(outputs.map(|outputs| (outputs, clearing, 0, surplus, allocations)))
+    } else {
+        let Some((mut outputs, fees)) =
+            compute_outputs(participants, &allocations, prices, config)?
+        else {
+            return Ok(None);
+        };
+        checked(settle_curtailment(
+            &mut outputs,
+            sellers,
+            &allocations,
+            prices.1,
+            config,
+        ))?;
+        Ok(Some((outputs, clearing, fees, surplus, allocations)))
+    }
+}
+
+/// Find uniform clearing price, honouring reserve prices, AON orders and startup costs
+///
+/// The crossing is computed on the active book. Any allocated participant
+/// whose reserve is violated by the resulting price (seller: price below its
+/// reserve, buyer: price above its ceiling) is excluded for good and the
+/// crossing is recomputed, since removing orders can move the marginal
+/// price. Once no reserve is violated, the lowest-priority all-or-nothing
+/// order filled for less than its effective cap (latest in book order,
+/// buyers DESC then sellers ASC) is excluded and the crossing recomputed.
+/// Once no AON order is partial, the committed seller with the largest
+/// startup shortfall (see `commitment_shortfall`, ties to the latest in book
+/// order) is excluded and the crossing recomputed.
+/// Exclusion is monotone, so the loop stops after at most N rounds. It ends
+/// at the first allocation that violates none of the rules; an excluded
+/// participant is not re-admitted even if a later price would satisfy it.
+///
+/// Returns: Option<(clearing_price, Vec<(order_key, allocation)>)>
+fn find_clearing_price(
+    buyers: &[&Participant],
+    sellers: &[&Participant],
+    config: &AuctionConfig,
+) -> Option<(u64, Vec<(OrderKey, u64)>)> {
+    let mut active_buyers: Vec<&Participant> = buyers.to_vec();
+    let mut active_sellers: Vec<&Participant> = sellers.to_vec();
+
+    loop {
+        let (price, allocations) = find_crossing(&active_buyers, &active_sellers, config)?;
+        let violates =
+            |p: &Participant| allocation_of(&allocations, p) > 0 && !reserve_satisfied(p, price);
+        if active_buyers
+            .iter()
+            .chain(active_sellers.iter())
+            .any(|p| violates(p))
+        {
+            active_buyers.retain(|b| !violates(b));
+            active_sellers.retain(|s| !violates(s));
+            continue;
+        }
+
+        // Fill-or-kill: drop one partially filled AON order, lowest priority first
+        let partial_aon = active_buyers
+            .iter()
+            .chain(active_sellers.iter())
+            .rev()
+            .find(|p| {
+                let allocated = allocation_of(&allocations, p);
+                p.all_or_nothing && allocated > 0 && allocated < effective_cap(p, price, config)
+            })
+            .map(|p| order_key(p));
+        if let Some(dropped) = partial_aon {
+            active_buyers.retain(|b| order_key(b) != dropped);
+            active_sellers.retain(|s| order_key(s) != dropped);
+            continue;
+        }
+
+        // Unit commitment: drop the seller whose startup cost is least covered
+        let uneconomic = active_sellers
+            .iter()
+            .map(|s| {
+                (
+                    commitment_shortfall(s, price, allocation_of(&allocations, s)),
+                    s,
+                )
+            })
+            .filter(|(shortfall, _)| *shortfall > 0)
+            .max_by_key(|(shortfall, _)| *shortfall)
+            .map(|(_, s)| order_key(s));
+        let Some(dropped) = uneconomic else {
+            return Some((price, allocations));
+        };
+        active_sellers.retain(|s| order_key(s) != dropped);
+    }
+}
+
+/// How far a seller's revenue falls short of its startup plus marginal cost
+///
+/// A seller allocated `q` units at `price` is committed only if
+/// `price * q >= fixed_cost + ask * q`; the shortfall is 0 when it is met,
+/// when the seller has no fixed cost or when it is not allocated. The fixed
+/// cost only gates commitment and is never charged to the balances.
+fn commitment_shortfall(seller: &Participant, price: u64, allocated: u64) -> u128 {
+    if seller.fixed_cost == 0 || allocated == 0 {
+        return 0;
+    }
+    let revenue = price as u128 * allocated as u128;
+    let cost = seller.fixed_cost as u128 + seller.price as u128 * allocated as u128;
+    cost.saturating_sub(revenue)
+}
+
+/// Price and allocate a sorted book with the configured price-based algorithm
+///
+/// Returns: Ok(None) when nothing clears, Err(Overflow) when the English
+/// clock's price leaves the u64 range
+fn price_book(
+    (buyers, sellers): (&[&Participant], &[&Participant]),
+    config: &AuctionConfig,
+) -> Result<Option<SidePriceClearing>, AuctionError> {
+    Ok(match config.algorithm {
+        Algorithm::McAfee => run_mcafee(buyers, sellers, config),
+        Algorithm::EnglishClock => find_english_clock_price(buyers, sellers, config)?
+            .map(|(price, allocations)| ((price, price), allocations)),
+        _ => find_clearing_price(buyers, sellers, config)
+            .map(|(price, allocations)| ((price, price), allocations)),
+    })
+}
+
+/// Orders of one owner and side merged into a single synthetic order
+struct OwnerGroup<'a> {
+    order: Participant,           // Synthetic order keyed by the first member
+    members: Vec<&'a Participant>, // Original orders, in book order
+}
+
+/// Merge each owner's orders on one side (book order) into a synthetic order
+///
+/// The synthetic order takes the best price of the group (its first member
+/// in book order), the summed quantity and the summed balances; order
+/// conditions (reserve, fill limits, dispatch flags, icebergs) do not carry
+/// over. Orders without an owner form a group of their own. Sums saturate at
+/// u64::MAX, which can only understate the group, and its members' own caps
+/// still bound what they receive.
+fn owner_groups<'a>(orders: &[&'a Participant]) -> Vec<OwnerGroup<'a>> {
+    let mut groups: Vec<OwnerGroup<'a>> = Vec::new();
+    for &order in orders {
+        let group = order
+            .owner
+            .and_then(|owner| groups.iter_mut().find(|g| g.order.owner == Some(owner)));
+        match group {
+            Some(group) => {
+                group.order.quantity = group.order.quantity.saturating_add(order.quantity);
+                group.order.in_coin = group.order.in_coin.saturating_add(order.in_coin);
+                group.order.in_energy = group.order.in_energy.saturating_add(order.in_energy);
+                group.members.push(order);
+            }
+            None if order.owner.is_none() => groups.push(OwnerGroup {
+                order: order.clone(),
+                members: vec![order],
+            }),
+            None => groups.push(OwnerGroup {
+                order: Participant {
+                    reserve_price: None,
+                    all_or_nothing: false,
+                    min_fill: 0,
+                    priority: false,
+                    flexible: false,
+                    display_quantity: None,
+                    ..order.clone()
+                },
+                members: vec![order],
+            }),
+        }
+    }
+    groups
+}
+
+/// Owner-level netting: clear one synthetic order per owner and side, then fan out
+///
+/// The netted book is priced and allocated as usual. Each group's
+/// allocation is shared among its members in proportion to their effective
+/// caps at the buyer price (largest remainder, ties by id), which is
+/// integer-exact. When a member's own balance binds more tightly than the
+/// group's pooled balance, the shortfall is withdrawn from the other side,
+/// lowest-priority orders first, so both sides still trade the same volume.
+fn clear_netted_book(
+    (buyers, sellers): (&[&Participant], &[&Participant]),
+    config: &AuctionConfig,
+) -> Result<Option<SidePriceClearing>, AuctionError> {
+    let buyer_groups = owner_groups(buyers);
+    let seller_groups = owner_groups(sellers);
+    let netted: Vec<Participant> = buyer_groups
+        .iter()
+        .chain(seller_groups.iter())
+        .map(|g| g.order.clone())
+        .collect();
+    let (netted_buyers, netted_sellers) = separate_and_sort(&netted, config);
+    let Some((prices, netted_allocations)) = price_book((&netted_buyers, &netted_sellers), config)?
+    else {
+        return Ok(None);
+    };
+
+    let mut allocations = Vec::new();
+    for group in buyer_groups.iter().chain(seller_groups.iter()) {
+        let caps = shared_caps(&group.members, prices.0, config);
+        let allocated = allocation_of(&netted_allocations, &group.order);
+        allocate_pro_rata(&mut allocations, &group.members, &caps, allocated);
+    }
+
+    let bought = traded_volume(buyers, &allocations);
+    let sold = traded_volume(sellers, &allocations);
+    withdraw_allocation(&mut allocations, buyers, bought.saturating_sub(sold));
+    withdraw_allocation(&mut allocations, sellers, sold.saturating_sub(bought));
+    Ok(Some((prices, allocations)))
+}
+
+/// Congestion pricing: split the uniform price when the volume cap binds
+///
+/// The cap binds when the traded volume reaches max_traded_volume while
+/// orders on both sides that accept the clearing price still have effective
+/// cap left. Buyers then pay the lowest bid and sellers receive the highest
+/// ask among the orders that trade, and compute_outputs credits the wedge
+/// (bid − ask) × volume to the operator. Every trading order still clears
+/// within its limit, and buyer caps are taken at their own bid so the
+/// higher buyer price stays affordable.
+fn congestion_prices(
+    (buyers, sellers): (&[&Participant], &[&Participant]),
+    allocations: &[(OrderKey, u64)],
+    clearing_price: u64,
+    config: &AuctionConfig,
+) -> (u64, u64) {
+    let unserved =
+        |p: &Participant| effective_cap(p, clearing_price, config) > allocation_of(allocations, p);
+    let binds = traded_volume(buyers, allocations) >= config.max_traded_volume
+        && buyers
+            .iter()
+            .any(|b| b.price >= clearing_price && unserved(b))
+        && sellers
+            .iter()
+            .any(|s| s.price <= clearing_price && unserved(s));
+    let traded = |p: &Participant| allocation_of(allocations, p) > 0;
+    let buyer_price = buyers.iter().filter(|b| traded(b)).map(|b| b.price).min();
+    let seller_price = sellers.iter().filter(|s| traded(s)).map(|s| s.price).max();
+    match (binds, buyer_price, seller_price) {
+        (true, Some(bid), Some(ask)) => (bid, ask),
+        _ => (clearing_price, clearing_price),
+    }
+}
+
+/// Self-trade prevention for pooled clearing: net each owner's buys against its sells
+///
+/// For an owner allocated B units on buy orders and S units on sell orders,
+/// the wash volume min(B, S) is withdrawn from both sides, lowest-priority
+/// orders first: the smaller side no longer trades and the larger side keeps
+/// only the owner's net position. Both sides of the market shrink by the
+/// same amount, so the price and every other allocation stand (min_fill and
+/// all_or_nothing are not re-checked).
+fn net_owner_trades(
+    buyers: &[&Participant],
+    sellers: &[&Participant],
+    allocations: &mut [(OrderKey, u64)],
+) {
+    let mut owners: Vec<u32> = buyers
+        .iter()
+        .filter_map(|b| b.owner)
+        .filter(|&owner| sellers.iter().any(|s| s.owner == Some(owner)))
+        .collect();
+    owners.sort();
+    owners.dedup();
+
+    for owner in owners {
+        let buys: Vec<&Participant> = buyers
+            .iter()
+            .copied()
+            .filter(|b| b.owner == Some(owner))
+            .collect();
+        let sells: Vec<&Participant> = sellers
+            .iter()
+            .copied()
+            .filter(|s| s.owner == Some(owner))
+            .collect();
+        let bought: u64 = buys.iter().map(|b| allocation_of(allocations, b)).sum();
+        let sold: u64 = sells.iter().map(|s| allocation_of(allocations, s)).sum();
+        let wash = bought.min(sold);
+        withdraw_allocation(allocations, &buys, wash);
+        withdraw_allocation(allocations, &sells, wash);
+    }
+}
+
+/// Take `amount` back from the orders' allocations, lowest priority (last) first
+fn withdraw_allocation(allocations: &mut [(OrderKey, u64)], orders: &[&Participant], amount: u64) {
+    let mut left = amount;
+    for order in orders.iter().rev() {
+        if left == 0 {
+            break;
+        }
+        if let Some((_, allocated)) = allocations
+            .iter_mut()
+            .find(|(key, _)| *key == order_key(order))
+        {
+            let take = (*allocated).min(left);
+            *allocated -= take;
+            left -= take;
+        }
+    }
+}
+
+/// Whether two orders belong to the same owner (and so must not trade)
+fn same_owner(a: &Participant, b: &Participant) -> bool {
+    a.owner.is_some() && a.owner == b.owner
+}
+
+/// Whether a clearing price respects a participant's reserve price
+fn reserve_satisfied(p: &Participant, price: u64) -> bool {
+    match (p.role, p.reserve_price) {
+        (_, None) => true,
+        (0, Some(ceiling)) => price <= ceiling,
+        (_, Some(floor)) => price >= floor,
+    }
+}
+
+/// Uniform clearing: (clearing_price, allocations)
+type PriceClearing = (u64, Vec<(OrderKey, u64)>);
+
+/// Find uniform clearing price using supply-demand crossing
+///
+/// FirstCrossing takes the lowest grid price where supply >= demand, or the
+/// highest grid price if demand exceeds supply at all of them. That price is
+/// then the top bid, at or above every ask, so the book still trades. Both
+/// qualification filters are inclusive, so an order whose price equals p*
+/// always qualifies (a lone bid and ask at the same price trade). The
+/// other objectives clear the book at every grid price instead and keep the
+/// outcome with the largest matched volume or surplus; ties go to the lower
+/// clearing price, then to the lower grid price (scan order).
+///
+/// Returns: Option<(clearing_price, Vec<(order_key, allocation)>)>
+fn find_crossing(
+    buyers: &[&Participant],
+    sellers: &[&Participant],
+    config: &AuctionConfig,
+) -> Option<(u64, Vec<(OrderKey, u64)>)> {
+    if buyers.is_empty() || sellers.is_empty() {
+        return None;
+    }
+
+    // Build price grid from all bids and asks
+    let mut prices: Vec<u64> = buyers.iter().map(|b| b.price).collect();
+    prices.extend(sellers.iter().map(|s| s.price));
+    prices.sort();
+    prices.dedup();
+
+    if config.algorithm == Algorithm::Merge {
+        return merge_crossing(buyers, sellers, config);
+    }
+    if config.objective == Objective::FirstCrossing {
+        // Find p* where supply >= demand; excess demand at every grid price
+        // still clears at the top one, with buyers rationed
+        let p_star = prices
+            .iter()
+            .copied()
+            .find(|&p| {
+                let (demand, supply) = demand_supply_at(buyers, sellers, p, config);
+                supply >= demand
+            })
+            .or(prices.last().copied())?;
+        return clear_at(buyers, sellers, p_star, config);
+    }
+
+    let mut best: Option<(i128, PriceClearing)> = None;
+    for p in prices {
+        let Some((price, allocations)) = clear_at(buyers, sellers, p, config) else {
+            continue;
+        };
+        // A surplus beyond i128 cannot be compared, so that price is skipped
+        let value = match config.objective {
+            Objective::MaxSurplus => {
+                let Some(surplus) = realized_surplus(buyers, sellers, &allocations) else {
+                    continue;
+                };
+                surplus
+            }
+            _ => traded_volume(buyers, &allocations) as i128,
+        };
+        let better = best.as_ref().is_none_or(|(best_value, (best_price, _))| {
+            value > *best_value || (value == *best_value && price < *best_price)
+        });
+        if better {
+            best = Some((value, (price, allocations)));
+        }
+    }
+    best.map(|(_, clearing)| clearing)
+}
+
+/// Clear the book at grid price p_star: qualify orders, settle the price, allocate
+///
+/// Returns: Option<(clearing_price, Vec<(order_key, allocation)>)>
+fn clear_at(
+    buyers: &[&Participant],
+    sellers: &[&Participant],
+    p_star: u64,
+    config: &AuctionConfig,
+) -> Option<(u64, Vec<(OrderKey, u64)>)> {
+    // Identify qualified participants at p*
+    let qualified_buyers: Vec<&Participant> = buyers
+        .iter()
+        .copied()
+        .filter(|b| b.price >= p_star)
+        .collect();
+    let qualified_sellers: Vec<&Participant> = sellers
+        .iter()
+        .copied()
+        .filter(|s| s.price <= p_star)
+        .collect();
+
+    if qualified_buyers.is_empty() || qualified_sellers.is_empty() {
+        return None;
+    }
+
+    // First excluded bid/ask at p* (second-price reference)
+    let b_next = buyers.iter().find(|b| b.price < p_star).map(|b| b.price);
+    let a_next = sellers.iter().find(|s| s.price > p_star).map(|s| s.price);
+    let marginal = marginal_pair(&qualified_buyers, &qualified_sellers, p_star, config);
+    price_qualified(
+        (buyers, sellers),
+        (qualified_buyers, qualified_sellers),
+        (marginal, (b_next, a_next)),
+        config,
+    )
+}
+
+/// Marginal bid and ask at p*: the orders holding the last matched unit
+///
+/// The crossing quantity is min(qualified demand, qualified supply), and the
+/// marginal order on each side is the one whose cumulative quantity (in
+/// priority order) first reaches it. Qualified orders beyond it, such as
+/// asks below p* that the excess supply leaves unmatched, do not set the
+/// price. Without a matched unit the last qualified orders stand in.
+fn marginal_pair(
+    qualified_buyers: &[&Participant],
+    qualified_sellers: &[&Participant],
+    p_star: u64,
+    config: &AuctionConfig,
+) -> (u64, u64) {
+    let (demand, supply) = demand_supply_at(qualified_buyers, qualified_sellers, p_star, config);
+    let crossing = demand.min(supply);
+    let marginal = |side: &[&Participant]| {
+        let mut cumulative = 0u128;
+        let order = side
+            .iter()
+            .find(|p| {
+                cumulative += crossing_quantity(p, p_star, config) as u128;
+                crossing > 0 && cumulative >= crossing
+            })
+            .or(side.last());
+        order.map_or(0, |p| p.price)
+    };
+    (marginal(qualified_buyers), marginal(qualified_sellers))
+}
+
+/// Merge matching: walk buyers DESC and sellers ASC together
+///
+/// Each step matches min(remaining quantities) of the current bid and ask
+/// and advances past whichever order is exhausted, while bid >= ask. The
+/// orders touched before the walk stops form the qualified prefixes, and
+/// their last bid and ask are the marginal pair priced per pricing_rule
+/// (SecondPrice refers to the next bid and ask in book order). One pass
+/// over the sorted book instead of a demand/supply sum per grid price.
+///
+/// Returns: Option<(clearing_price, Vec<(order_key, allocation)>)>
+fn merge_crossing(
+    buyers: &[&Participant],
+    sellers: &[&Participant],
+    config: &AuctionConfig,
+) -> Option<PriceClearing> {
+    let (mut i, mut j) = (0, 0);
+    let mut touched = None;
+    let (mut left_bid, mut left_ask) = (buyers.first()?.quantity, sellers.first()?.quantity);
+    while i < buyers.len() && j < sellers.len() && buyers[i].price >= sellers[j].price {
+        touched = Some((i, j));
+        let matched = left_bid.min(left_ask);
+        left_bid -= matched;
+        left_ask -= matched;
+        if left_bid == 0 {
+            i += 1;
+            left_bid = buyers.get(i).map_or(0, |b| b.quantity);
+        }
+        if left_ask == 0 {
+            j += 1;
+            left_ask = sellers.get(j).map_or(0, |s| s.quantity);
+        }
+    }
+    let (last_bid, last_ask) = touched?;
+
+    price_qualified(
+        (buyers, sellers),
+        (buyers[..=last_bid].to_vec(), sellers[..=last_ask].to_vec()),
+        (
+            (buyers[last_bid].price, sellers[last_ask].price),
+            (
+                buyers.get(last_bid + 1).map(|b| b.price),
+                sellers.get(last_ask + 1).map(|s| s.price),
+            ),
+        ),
+        config,
+    )
+}
+
+/// Price the qualified orders from their marginal bid and ask, then allocate
+///
+/// b_marg / a_marg are the bid and ask of the last matched unit, and
+/// b_next / a_next the first excluded bid and ask (None falls back to
+/// the marginal ones). A price outside the regulated band is rejected, or
+/// clamped with the whole book re-qualified at the band edge. An external
+/// config.settlement_price replaces the pricing rule: it is only verified to
+/// lie within [a_marg, b_marg], and the book does not trade otherwise.
+/// A clearing price of 0 (free energy, e.g. donated solar) still allocates:
+/// no coin moves and buyers are never budget-limited.
+///
+/// Returns: Option<(clearing_price, Vec<(order_key, allocation)>)>
+fn price_qualified<'a>(
+    (buyers, sellers): (&[&'a Participant], &[&'a Participant]),
+    (mut qualified_buyers, mut qualified_sellers): (Vec<&'a Participant>, Vec<&'a Participant>),
+    ((b_marg, a_marg), (b_next, a_next)): ((u64, u64), (Option<u64>, Option<u64>)),
+    config: &AuctionConfig,
+) -> Option<PriceClearing> {
+    let (b_next, a_next) = (b_next.unwrap_or(b_marg), a_next.unwrap_or(a_marg));
+    let mut clearing_price = match config.settlement_price {
+        Some(index) if (a_marg..=b_marg).contains(&index) => index,
+        Some(_) => return None,
+        None => round_to_tick(
+            settle_price((b_marg, a_marg), (b_next, a_next), config)?,
+            config,
+        ),
+    };
+    // Qualified orders beyond the marginal pair may not accept that price
+    qualified_buyers.retain(|b| b.price >= clearing_price);
+    qualified_sellers.retain(|s| s.price <= clearing_price);
+
+    // Regulated price band: reject, or clamp and re-qualify the whole book
+    let banded = clamp_to_band(clearing_price, config);
+    if banded != clearing_price {
+        if config.band_policy == BandPolicy::NoTrade {
+            return None;
+        }
+        clearing_price = banded;
+        qualified_buyers = buyers
+            .iter()
+            .copied()
+            .filter(|b| b.price >= banded)
+            .collect();
+        qualified_sellers = sellers
+            .iter()
+            .copied()
+            .filter(|s| s.price <= banded)
+            .collect();
+        if qualified_buyers.is_empty() || qualified_sellers.is_empty() {
+            return None;
+        }
+    }
+
+    let allocations = allocate_with_min_fill(
+        &qualified_buyers,
+        &qualified_sellers,
+        clearing_price,
+        config,
+    );
+    Some((clearing_price, allocations))
+}
+
+/// Allocate at a fixed price, enforcing per-order minimum fills
+///
+/// Post-pass over allocate_at_price: every allocation strictly between 0
+/// and min_fill is rounded down to 0 and its participant knocked out, then
+/// the allocation is re-run at the same price without them. Freed volume is
+/// thereby re-offered down the priority order on the long side, or withdrawn
+/// from the lowest-priority counterparties when the short side shrinks.
+/// That can push another order below its minimum (cascade), so the pass
+/// repeats; each round knocks out at least one participant, so it ends
+/// after at most N rounds with both sides still summing to the same volume.
+///
+/// Returns: Vec<(order_key, allocation)>
+fn allocate_with_min_fill(
+    qualified_buyers: &[&Participant],
+    qualified_sellers: &[&Participant],
+    clearing_price: u64,
+    config: &AuctionConfig,
+) -> Vec<(OrderKey, u64)> {
+    let mut buyers: Vec<&Participant> = qualified_buyers.to_vec();
+    let mut sellers: Vec<&Participant> = qualified_sellers.to_vec();
+
+    loop {
+        let allocations = allocate_at_price(&buyers, &sellers, clearing_price, config);
+        let below_min = |p: &Participant| {
+            let allocated = allocation_of(&allocations, p);
+            allocated > 0 && allocated < p.min_fill
+        };
+        if !buyers.iter().chain(sellers.iter()).any(|p| below_min(p)) {
+            return allocations;
+        }
+        buyers.retain(|b| !below_min(b));
+        sellers.retain(|s| !below_min(s));
+    }
+}
+
+/// Allocate volume between qualified participants at a given buyer price
+///
+/// Effective caps are min(quantity, affordable at the price) for buyers and
+/// min(quantity, energy balance) for sellers. The short side is filled
+/// completely and the long side is rationed per config.allocation_rule. When
+/// the matched volume exceeds config.max_traded_volume, both sides are
+/// rationed up to the cap instead, so the price is unchanged and still
+/// individually rational for every order that trades. Whenever sellers are
+/// rationed, priority sellers (renewables) are served before all others;
+/// whenever buyers are rationed, flexible buyers are served only after all
+/// inflexible ones. Each group keeps book order. Virtual orders from a
+/// stepped participant share its balance and are summed per id.
+/// Caps are summed in u128; since max_traded_volume is at most u64::MAX,
+/// a book whose matched volume exceeds u64 is rationed like a capped one,
+/// so every side's total allocation fits u64.
+///
+/// Returns: Vec<(order_key, allocation)>
+fn allocate_at_price(
+    qualified_buyers: &[&Participant],
+    qualified_sellers: &[&Participant],
+    clearing_price: u64,
+    config: &AuctionConfig,
+) -> Vec<(OrderKey, u64)> {
+    // Compute effective caps (budget and inventory constraints)
+    let buyer_caps = shared_caps(qualified_buyers, clearing_price, config);
+    let seller_caps = shared_caps(qualified_sellers, clearing_price, config);
+    let (dispatch_buyers, dispatch_buyer_caps) =
+        dispatch_order(qualified_buyers, &buyer_caps, |b| !b.flexible);
+    let (dispatch_sellers, dispatch_seller_caps) =
+        dispatch_order(qualified_sellers, &seller_caps, |s| s.priority);
+    let eff_demand: u128 = buyer_caps.iter().map(|&cap| cap as u128).sum();
+    let eff_supply: u128 = seller_caps.iter().map(|&cap| cap as u128).sum();
+
+    let matched = eff_demand.min(eff_supply);
+    let traded_total = matched.min(config.max_traded_volume as u128) as u64;
+    if traded_total == 0 {
+        return Vec::new();
+    }
+
+    // Allocate based on binding constraint
+    let mut allocations: Vec<(OrderKey, u64)> = Vec::new();
+
+    if (traded_total as u128) < matched {
+        // Volume-capped: ration both sides up to the cap
+        ration(
+            &mut allocations,
+            &dispatch_sellers,
+            &dispatch_seller_caps,
+            traded_total,
+            config,
+        );
+        ration(
+            &mut allocations,
+            &dispatch_buyers,
+            &dispatch_buyer_caps,
+            traded_total,
+            config,
+        );
+    } else if eff_demand >= eff_supply {
+        // Supply-constrained: fill all sellers, ration buyers
+        for (seller, cap) in qualified_sellers.iter().zip(seller_caps) {
+            add_allocation(&mut allocations, seller, cap);
+        }
+        ration(
+            &mut allocations,
+            &dispatch_buyers,
+            &dispatch_buyer_caps,
+            traded_total,
+            config,
+        );
+    } else {
+        // Demand-constrained: fill all buyers, ration sellers
+        for (buyer, cap) in qualified_buyers.iter().zip(buyer_caps) {
+            add_allocation(&mut allocations, buyer, cap);
+        }
+        ration(
+            &mut allocations,
+            &dispatch_sellers,
+            &dispatch_seller_caps,
+            traded_total,
+            config,
+        );
+    }
+
+    allocations
+}
+
+/// Orders in dispatch order: those `served_first` ahead, price/id order kept
+///
+/// Caps are computed in book order beforehand, so the reorder (a stable
+/// sort) does not change how stepped orders share a balance.
+fn dispatch_order<'a>(
+    orders: &[&'a Participant],
+    caps: &[u64],
+    served_first: impl Fn(&Participant) -> bool,
+) -> (Vec<&'a Participant>, Vec<u64>) {
+    let mut dispatch: Vec<(&Participant, u64)> =
+        orders.iter().copied().zip(caps.iter().copied()).collect();
+    dispatch.sort_by_key(|(order, _)| !served_first(order));
+    dispatch.into_iter().unzip()
+}
+
+/// Ration `total` units between orders per config.allocation_rule
+fn ration(
+    allocations: &mut Vec<(OrderKey, u64)>,
+    orders: &[&Participant],
+    caps: &[u64],
+    total: u64,
+    config: &AuctionConfig,
+) {
+    match config.allocation_rule {
+        AllocationRule::Priority => allocate_by_priority(allocations, orders, caps, total),
+        AllocationRule::ProRata => allocate_pro_rata(allocations, orders, caps, total),
+        AllocationRule::MaxMin => allocate_max_min(allocations, orders, caps, total),
+    }
+}
+
+/// Share `total` units max-min fairly (water-filling)
+///
+/// Closed form of handing out one unit at a time to the order with the
+/// smallest allocation so far (ties by id) within its cap: every order is
+/// filled up to a common level L (or its cap, if lower), where L is the
+/// highest level that fits, and the units left over (fewer than the orders
+/// above L) go one each to the orders above L in ascending id. O(n log n)
+/// regardless of the volume.
+fn allocate_max_min(
+    allocations: &mut Vec<(OrderKey, u64)>,
+    orders: &[&Participant],
+    caps: &[u64],
+    total: u64,
+) {
+    let mut by_cap: Vec<usize> = (0..caps.len()).collect();
+    by_cap.sort_by_key(|&i| caps[i]);
+
+    // Raise the level through the sorted caps until the volume runs out
+    let mut remaining = total as u128;
+    let mut level = 0u64;
+    for (filled, &i) in by_cap.iter().enumerate() {
+        let open = (by_cap.len() - filled) as u128;
+        let step = (caps[i] - level) as u128;
+        if step * open <= remaining {
+            remaining -= step * open;
+            level = caps[i];
+        } else {
+            level += (remaining / open) as u64;
+            remaining %= open;
+            break;
+        }
+    }
+
+    let mut shares: Vec<u64> = caps.iter().map(|&cap| cap.min(level)).collect();
+    let mut above: Vec<usize> = (0..caps.len()).filter(|&i| caps[i] > level).collect();
+    above.sort_by_key(|&i| orders[i].id);
+    for &i in above.iter().take(remaining as usize) {
+        shares[i] += 1;
+    }
+
+    for (order, share) in orders.iter().zip(shares) {
+        if share > 0 {
+            add_allocation(allocations, order, share);
+        }
+    }
+}
+
+/// Share `total` units in proportion to effective caps
+///
+/// Each order first gets floor(cap × total / Σ cap); the units left over
+/// (fewer than the number of orders) go one each to the largest remainders,
+/// ties by id. An order with a remainder is below its cap, so no cap is
+/// exceeded and the shares sum to exactly min(total, Σ cap).
+fn allocate_pro_rata(
+    allocations: &mut Vec<(OrderKey, u64)>,
+    orders: &[&Participant],
+    caps: &[u64],
+    total: u64,
+) {
+    let total_cap: u128 = caps.iter().map(|&cap| cap as u128).sum();
+    if total_cap == 0 {
+        return;
+    }
+    let total = (total as u128).min(total_cap);
+
+    let mut shares: Vec<(usize, u64, u128)> = caps
+        .iter()
+        .enumerate()
+        .map(|(i, &cap)| {
+            let scaled = cap as u128 * total;
+            (i, (scaled / total_cap) as u64, scaled % total_cap)
+        })
+        .collect();
+    let assigned: u128 = shares.iter().map(|(_, share, _)| *share as u128).sum();
+
+    // Largest remainder first, ties by id
+    let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+    by_remainder.sort_by(|&a, &b| {
+        shares[b]
+            .2
+            .cmp(&shares[a].2)
+            .then(orders[a].id.cmp(&orders[b].id))
+    });
+    for &i in by_remainder.iter().take((total - assigned) as usize) {
+        shares[i].1 += 1;
+    }
+
+    for (i, share, _) in shares {
+        if share > 0 {
+            add_allocation(allocations, orders[i], share);
+        }
+    }
+}
+
+/// Serve orders in priority order up to `total`, each at most its cap
+///
+/// Iceberg orders (display_quantity set) are served one displayed slice per
+/// pass: each pass walks the orders in priority order, giving an iceberg at
+/// most its slice and any other order its whole remaining cap, so a large
+/// hidden quantity cannot starve the orders behind it. After
+/// MAX_ICEBERG_PASSES passes a final pass serves the remaining caps in
+/// priority order, so both sides still reach the same total.
+fn allocate_by_priority(
+    allocations: &mut Vec<(OrderKey, u64)>,
+    orders: &[&Participant],
+    caps: &[u64],
+    total: u64,
+) {
+    let mut remaining = total;
+    let mut left = caps.to_vec();
+    if orders.iter().any(|o| o.display_quantity.is_some()) {
+        for _ in 0..MAX_ICEBERG_PASSES {
+            if remaining == 0 {
+                break;
+            }
+            for (order, cap) in orders.iter().zip(left.iter_mut()) {
+                let take = order
+                    .display_quantity
+                    .unwrap_or(*cap)
+                    .min(*cap)
+                    .min(remaining);
+                if take > 0 {
+                    add_allocation(allocations, order, take);
+                    *cap -= take;
+                    remaining -= take;
+                }
+            }
+        }
+    }
+    for (order, &cap) in orders.iter().zip(&left) {
+        if remaining == 0 {
+            break;
+        }
+        let take = cap.min(remaining);
+        if take > 0 {
+            add_allocation(allocations, order, take);
+            remaining -= take;
+        }
+    }
+}
+
+/// Effective caps of orders in priority order
+///
+/// Virtual orders sharing an id draw on one budget (buyers) or energy
+/// balance (sellers), so earlier steps are capped first.
+fn shared_caps(orders: &[&Participant], clearing_price: u64, config: &AuctionConfig) -> Vec<u64> {
+    let mut used: Vec<(OrderKey, u64)> = Vec::new();
+    orders
+        .iter()
+        .map(|p| {
+            let used_by_id = allocation_of(&used, p);
+            let balance_left = balance_cap(p, clearing_price, config).saturating_sub(used_by_id);
+            let lot = config.quantity_lot.max(1);
+            let cap = order_quantity(p, clearing_price, config).min(balance_left) / lot * lot;
+            add_allocation(&mut used, p, cap);
+            cap
+        })
+        .collect()
+}
+
+/// Add volume to an order's allocation entry, creating it if needed
+fn add_allocation(allocations: &mut Vec<(OrderKey, u64)>, p: &Participant, amount: u64) {
+    match allocations.iter_mut().find(|(key, _)| *key == order_key(p)) {
+        Some((_, allocated)) => *allocated += amount,
+        None => allocations.push((order_key(p), amount)),
+    }
+}
+
+/// Round a clearing price down onto the price tick (toward the seller side)
+///
+/// Asks are on the tick, so the result stays within [a_marg, b_marg].
+fn round_to_tick(price: u64, config: &AuctionConfig) -> u64 {
+    let tick = config.price_tick.max(1);
+    price / tick * tick
+}
+
+/// Clamp a clearing price into the configured [price_floor, price_cap] band
+fn clamp_to_band(price: u64, config: &AuctionConfig) -> u64 {
+    let floor = config.price_floor.unwrap_or(0);
+    let cap = config.price_cap.unwrap_or(u64::MAX);
+    price.max(floor).min(cap)
+}
+
+/// Effective cap of a participant at a clearing price
+///
+/// min(quantity, balance cap), rounded down to the quantity lot so every
+/// allocation is a whole lot.
+fn effective_cap(p: &Participant, clearing_price: u64, config: &AuctionConfig) -> u64 {
+    let lot = config.quantity_lot.max(1);
+    let quantity = order_quantity(p, clearing_price, config);
+    quantity.min(balance_cap(p, clearing_price, config)) / lot * lot
+}
+
+/// Whether an order is a budget bid: a buyer with quantity 0 and a budget
+fn is_budget_bid(p: &Participant) -> bool {
+    p.role == 0 && p.quantity == 0 && p.budget > 0
+}
+
+/// Whether an order asks for a positive quantity (a budget bid does)
+fn quotes_quantity(p: &Participant) -> bool {
+    p.quantity > 0 || is_budget_bid(p)
+}
+
+/// Whether an order can trade at all: it quotes a quantity and, as a seller, holds energy
+///
+/// Other orders keep their balances as settlement accounts but stay out of
+/// the book, so they add no grid price and are never the marginal order.
+fn is_tradable(p: &Participant) -> bool {
+    quotes_quantity(p) && (p.role == 0 || p.in_energy > 0)
+}
+
+/// Quantity an order asks for at a clearing price
+///
+/// A budget bid buys as much as min(budget, in_coin) affords at the price,
+/// fee included (the coin limit is applied by `balance_cap`); a price of 0
+/// counts as 1 so the demand stays finite. Other orders ask for `quantity`.
+fn order_quantity(p: &Participant, clearing_price: u64, config: &AuctionConfig) -> u64 {
+    if is_budget_bid(p) {
+        affordable_units(p.budget, clearing_price.max(1), config)
+    } else {
+        p.quantity
+    }
+}
+
+/// Units a participant's balance supports: affordable units (buyers) or energy (sellers)
+///
+/// At a non-positive price a buyer is never budget-limited; at a negative
+/// price a seller must also afford paying to offload. Either way the
+/// participant's position limit applies on top.
+fn balance_cap(p: &Participant, clearing_price: u64, config: &AuctionConfig) -> u64 {
+    // Under congestion pricing a buyer may pay up to its own bid
+    let quoted = if config.congestion_rent && p.role == 0 {
+        p.price
+    } else {
+        clearing_price
+    };
+    let price = real_price(unit_price(p, quoted, config), config);
+    let cap = if p.role == 0 {
+        if price > 0 {
+            affordable_units(p.in_coin, price as u64, config)
+        } else {
+            u64::MAX
+        }
+    } else if price < 0 {
+        let afford = affordable_units(p.in_coin, price.unsigned_abs() as u64, config);
+        p.in_energy.min(afford)
+    } else {
+        p.in_energy
+    };
+    cap.min(position_cap(p, price, config))
+}
+
+/// Units a participant may trade under its position limit
+///
+/// The limit is the participant's max_position_energy, or the config default.
+/// A buyer's energy after the trade (in_energy + q) and a seller's coin after
+/// the trade (in_coin + price × q, at a positive price) must not exceed it;
+/// a balance already above the limit allows no trade at all.
+fn position_cap(p: &Participant, price: i128, config: &AuctionConfig) -> u64 {
+    let Some(limit) = p.max_position_energy.or(config.max_position_energy) else {
+        return u64::MAX;
+    };
+    if p.role == 0 {
+        limit.saturating_sub(p.in_energy)
+    } else if price > 0 {
+        limit.saturating_sub(p.in_coin) / price as u64
+    } else {
+        u64::MAX
+    }
+}
+
+/// Signed price per unit of an offset-encoded price (encoded − price_offset)
+fn real_price(encoded: u64, config: &AuctionConfig) -> i128 {
+    encoded as i128 - config.price_offset as i128
+}
+
+/// Derive the uniform clearing price from the marginal bid and ask
+///
+/// KDouble rounding: the weighted sum is floored, i.e. rounded toward the
+/// seller's ask (b_marg >= a_marg). With k = 1/2 this equals MidPoint exactly.
+/// The price is an integer, so conservation holds regardless of rounding.
+///
+/// SecondPrice uses the first excluded bid and ask instead of the marginal
+/// included ones, clamped so no qualified participant trades past its limit.
+///
+/// Returns: None if k is not a weight in [0, 1] (validate_input refuses it)
+fn settle_price(
+    (b_marg, a_marg): (u64, u64),
+    (b_next, a_next): (u64, u64),
+    config: &AuctionConfig,
+) -> Option<u64> {
+    match config.pricing_rule {
+        PricingRule::MidPoint => Some(b_marg.midpoint(a_marg)), // floor((b + a) / 2), no overflow
+        PricingRule::BuyerBid => Some(b_marg),
+        PricingRule::SellerAsk => Some(a_marg),
+        PricingRule::KDouble => {
+            let k_num = config.k_numerator as u128;
+            let k_den = config.k_denominator as u128;
+            let bid_weight = k_num.checked_mul(b_marg as u128)?;
+            let ask_weight = k_den.checked_sub(k_num)?.checked_mul(a_marg as u128)?;
+            let weighted = bid_weight.checked_add(ask_weight)?;
+            u64::try_from(weighted.checked_div(k_den)?).ok()
+        }
+        PricingRule::SecondPrice => Some(b_next.midpoint(a_next).clamp(a_marg, b_marg)),
+    }
+}
+
+/// Clearing with separate prices per side: ((buyer_price, seller_price), allocations)
+type SidePriceClearing = ((u64, u64), Vec<(OrderKey, u64)>);
+
+/// McAfee (1992) trade-reduction double auction
+///
+/// Each order counts as one trader. With buyers DESC and sellers ASC (ties by
+/// id), k is the number of leading pairs with b_i >= a_i. If the (k+1)-th pair
+/// exists and its candidate price p0 = (b_k+1 + a_k+1) / 2 lies in [a_k, b_k],
+/// the top k pairs trade at p0. Otherwise the k-th pair is dropped: the top
+/// k-1 pairs trade with buyers paying b_k and sellers receiving a_k, and the
+/// spread goes to the operator (k = 1 therefore leaves no trade). Volume
+/// between the admitted orders is allocated as in the uniform mechanism.
+///
+/// Returns: Option<((buyer_price, seller_price), Vec<(order_key, allocation)>)>
+fn run_mcafee(
+    buyers: &[&Participant],
+    sellers: &[&Participant],
+    config: &AuctionConfig,
+) -> Option<SidePriceClearing> {
+    let k = buyers
+        .iter()
+        .zip(sellers.iter())
+        .take_while(|(b, s)| b.price >= s.price)
+        .count();
+    if k == 0 {
+        return None;
+    }
+
+    let (b_k, a_k) = (buyers[k - 1].price, sellers[k - 1].price);
+    let candidate = buyers
+        .get(k)
+        .zip(sellers.get(k))
+        .map(|(b, s)| b.price.midpoint(s.price));
+
+    let (traders, buyer_price, seller_price) = match candidate {
+        Some(p0) if a_k <= p0 && p0 <= b_k => (k, p0, p0),
+        _ => (k - 1, b_k, a_k), // Trade reduction
+    };
+    if traders == 0 {
+        return None;
+    }
+
+    let allocations =
+        allocate_at_price(&buyers[..traders], &sellers[..traders], buyer_price, config);
+    Some(((buyer_price, seller_price), allocations))
+}
+
+/// Compute aggregate demand and supply at given price
+///
+/// Budget bids demand what their budget affords at that price, so demand
+/// stays non-increasing in the price and the crossing is still well defined.
+/// With effective_crossing every order counts its effective cap at that
+/// price instead (coin for buyers, energy for sellers), so a quantity no
+/// balance backs cannot place p* where nothing trades.
+/// Summed in u128, so any number of u64 quantities compares exactly.
+fn demand_supply_at(
+    buyers: &[&Participant],
+    sellers: &[&Participant],
+    price: u64,
+    config: &AuctionConfig,
+) -> (u128, u128) {
+    let demand: u128 = buyers
+        .iter()
+        .filter(|b| b.price >= price)
+        .map(|b| crossing_quantity(b, price, config) as u128)
+        .sum();
+
+    let supply: u128 = sellers
+        .iter()
+        .filter(|s| s.price <= price)
+        .map(|s| crossing_quantity(s, price, config) as u128)
+        .sum();
+
+    (demand, supply)
+}
+
+/// Quantity an order contributes to the crossing search at a price
+fn crossing_quantity(p: &Participant, price: u64, config: &AuctionConfig) -> u64 {
+    if config.effective_crossing {
+        effective_cap(p, price, config)
+    } else {
+        order_quantity(p, price, config)
+    }
+}
+
+/// Price per unit a matched participant pays (buyer) or receives (seller)
+///
+/// VCG transfers are only known after allocation but never exceed the bid,
+/// so the bid bounds the buyer's affordability cap.
+fn unit_price(p: &Participant, clearing_price: u64, config: &AuctionConfig) -> u64 {
+    match config.settlement {
+        Settlement::Uniform => clearing_price,
+        Settlement::PayAsBid | Settlement::Vcg => p.price,
+    }
+}
+
+/// Fee charged on one side of a trade: floor(notional × fee_bps / 10_000)
+///
+/// Returns: None if the product overflows u128
+fn trade_fee(notional: u128, fee_bps: u64) -> Option<u128> {
+    Some(notional.checked_mul(fee_bps as u128)? / BPS_DENOMINATOR)
+}
+
+/// Coin moved when `quantity` units trade at one unit price:
+/// (buyer_pays, seller_gets, dust)
+///
+/// The dust policy of every settlement path. Unit prices are whole coin, so
+/// the notional price × quantity is exact and the only rounding is the fee
+/// on each side, floored so nobody is charged a fraction of a coin. Dust is
+/// exactly buyer_pays − seller_gets (both fees, 0 without fees); callers
+/// credit it to the settlement account (config.operator_id) through
+/// settle_imbalance, so no trade creates or destroys coin. Each floor drops
+/// less than one coin, so over n trades the dust falls short of the exact
+/// fees by less than 2n. Negative prices charge the fees on |notional|.
+///
+/// Returns: None if the notional or a fee leaves its integer range
+fn settle(price: i128, quantity: u64, fee_bps: u64) -> Option<(i128, i128, i128)> {
+    let notional = price.checked_mul(quantity as i128)?;
+    let fee = i128::try_from(trade_fee(notional.unsigned_abs(), fee_bps)?).ok()?;
+    let buyer_pays = notional.checked_add(fee)?;
+    let seller_gets = notional.checked_sub(fee)?;
+    let dust = buyer_pays.checked_sub(seller_gets)?;
+    Some((buyer_pays, seller_gets, dust))
+}
+
+/// Units a buyer can afford at a unit price, buyer-side fee included
+///
+/// Uses the unrounded fee, so the bound is exact without fees and at most
+/// conservative by a unit with them. Free units (price 0) are unlimited.
+fn affordable_units(coin: u64, price: u64, config: &AuctionConfig) -> u64 {
+    if price == 0 {
+        return u64::MAX;
+    }
+    let gross_price = price as u128 * (BPS_DENOMINATOR + config.fee_bps as u128);
+    (coin as u128 * BPS_DENOMINATOR / gross_price) as u64
+}
+
+/// Displayed-slice passes of priority allocation before caps are served in full
+const MAX_ICEBERG_PASSES: usize = 8;
+
+/// Expand stepped bids and prosumers into virtual orders sharing the parent id
+///
+/// Each of the first MAX_STEPS steps becomes an order with the step's price
+/// and quantity and the parent's balances; all_or_nothing does not apply to
+/// stepped participants. A prosumer (role 2) becomes a buy order at price /
+/// quantity and a sell order at sell_price / sell_quantity. Self-matching is
+/// forbidden: a prosumer whose ask does not exceed its bid could cross its
+/// own order, so it places neither. Expired participants place no orders,
+/// nor (reachable only without config.strict) participants quoting above
+/// config.max_price. Other participants are kept as they are.
+fn expand_orders(participants: &[Participant], config: &AuctionConfig) -> Vec<Participant> {
+    let mut orders = Vec::new();
+    for p in participants {
+        if !is_live(p, config) || !within_max_price(p, config) {
+            continue;
+        }
+        if p.role == 2 {
+            if p.sell_price > p.price {
+                orders.push(Participant {
+                    role: 0,
+                    steps: Vec::new(),
+                    ..p.clone()
+                });
+                orders.push(Participant {
+                    role: 1,
+                    price: p.sell_price,
+                    quantity: p.sell_quantity,
+                    steps: Vec::new(),
+                    ..p.clone()
+                });
+            }
+            continue;
+        }
+        if p.steps.is_empty() {
+            orders.push(p.clone());
+            continue;
+        }
+        for &(price, quantity) in p.steps.iter().take(MAX_STEPS) {
+            orders.push(Participant {
+                price,
+                quantity,
+                all_or_nothing: false,
+                steps: Vec::new(),
+                ..p.clone()
+            });
+        }
+    }
+    orders
+}
+
+/// Keep the first order per order key (one settlement row per participant side)
+fn settlement_accounts(orders: &[Participant]) -> Vec<Participant> {
+    let mut accounts: Vec<Participant> = Vec::new();
+    for order in orders {
+        if !accounts.iter().any(|a| order_key(a) == order_key(order)) {
+            accounts.push(order.clone());
+        }
+    }
+    accounts
+}
+
+/// Whether an order is still valid in config.current_round
+///
+/// The operator and the loss sink are settlement accounts and never expire.
+fn is_live(p: &Participant, config: &AuctionConfig) -> bool {
+    p.valid_until_round >= config.current_round
+        || Some(p.id) == config.operator_id
+        || Some(p.id) == config.loss_sink_id
+}
+
+/// Whether an order's price is on the tick and its quantity a whole lot
+fn is_on_grid(p: &Participant, config: &AuctionConfig) -> bool {
+    p.price.is_multiple_of(config.price_tick.max(1))
+        && p.quantity.is_multiple_of(config.quantity_lot.max(1))
+}
+
+/// Normalize orders to the configured price tick and quantity lot
+///
+/// With config.round_to_grid, bids round down and asks round up to the tick
+/// (never past the participant's limit) and quantities round down to the
+/// lot; otherwise non-conforming orders are dropped. The operator row is a
+/// settlement account and is always kept unchanged.
+fn conform_to_grid(participants: &[Participant], config: &AuctionConfig) -> Vec<Participant> {
+    let tick = config.price_tick.max(1);
+    let lot = config.quantity_lot.max(1);
+    participants
+        .iter()
+        .filter_map(|p| {
+            if Some(p.id) == config.operator_id || is_on_grid(p, config) {
+                return Some(p.clone());
+            }
+            if !config.round_to_grid {
+                return None;
+            }
+            let mut order = p.clone();
+            order.price = if p.role == 0 {
+                p.price / tick * tick
+            } else {
+                p.price.div_ceil(tick).saturating_mul(tick)
+            };
+            order.quantity = p.quantity / lot * lot;
+            Some(order)
+        })
+        .collect()
+}
+
+/// Whether the configured mechanism can leave a coin imbalance for the operator
+fn needs_operator(config: &AuctionConfig) -> bool {
+    config.settlement != Settlement::Uniform
+        || config.algorithm == Algorithm::McAfee
+        || config.fee_bps > 0
+        || config.congestion_rent
+        || config.funding_fee_bps > 0
+}
+
+/// Internal order key: participant id plus the side the order trades on
+///
+/// Allocations are booked per order rather than per participant, so a
+/// prosumer's buy and sell orders stay separate until settlement nets them.
+type OrderKey = (u32, u32);
+
+/// Order key of a book entry: (participant_id, role)
+fn order_key(p: &Participant) -> OrderKey {
+    (p.id, p.role)
+}
+
+/// Look up an order's allocation (0 when unallocated)
+fn allocation_of(allocations: &[(OrderKey, u64)], p: &Participant) -> u64 {
+    allocations
+        .iter()
+        .find(|(key, _)| *key == order_key(p))
+        .map(|(_, amount)| *amount)
+        .unwrap_or(0)
+}
+
+/// Volume one side of the book is allocated (Σ allocations of its order keys)
+///
+/// The steps of a stepped order share their participant's key and so one
+/// allocation entry, which is counted once, not once per step.
+fn traded_volume(orders: &[&Participant], allocations: &[(OrderKey, u64)]) -> u64 {
+    allocations
+        .iter()
+        .filter(|(key, _)| orders.iter().any(|p| order_key(p) == *key))
+        .map(|(_, amount)| amount)
+        .sum()
+}
+
+/// Value of one side's allocation: Σ quote × units over its orders
+///
+/// A stepped participant's summed allocation is attributed to its orders
+/// in book order (its best step first), each up to its quantity, the last
+/// taking the rest, so every step is valued at its own price for the units
+/// it traded rather than for the participant's whole allocation.
+///
+/// Returns: None if the value overflows u128
+fn side_value(orders: &[&Participant], allocations: &[(OrderKey, u64)]) -> Option<u128> {
+    let mut attributed: Vec<(OrderKey, u64)> = Vec::new();
+    orders.iter().enumerate().try_fold(0u128, |total, (i, p)| {
+        let left = allocation_of(allocations, p).saturating_sub(allocation_of(&attributed, p));
+        let last = !orders[i + 1..].iter().any(|q| order_key(q) == order_key(p));
+        let amount = if last { left } else { left.min(p.quantity) };
+        add_allocation(&mut attributed, p, amount);
+        total.checked_add(p.price as u128 * amount as u128) // Fits: u64 × u64
+    })
+}
+
+/// Energy an allocation delivers: Σ buyer-side entries
+///
+/// Counts every entry, so stepped orders sharing a key are each counted once.
+/// One clearing allocates at most u64::MAX (see `allocate_at_price`).
+fn allocated_volume(allocations: &[(OrderKey, u64)]) -> u64 {
+    allocations
+        .iter()
+        .filter(|((_, role), _)| *role == 0)
+        .map(|(_, amount)| amount)
+        .sum()
+}
+
+/// Realized surplus of an allocation: Σ bid × qty (buyers) − Σ ask × qty (sellers)
+///
+/// Each side is valued step by step (see `side_value`).
+///
+/// Returns: None if a side's value or the difference leaves the i128 range
+fn realized_surplus(
+    buyers: &[&Participant],
+    sellers: &[&Participant],
+    allocations: &[(OrderKey, u64)],
+) -> Option<i128> {
+    let bought = i128::try_from(side_value(buyers, allocations)?).ok()?;
+    let sold = i128::try_from(side_value(sellers, allocations)?).ok()?;
+    bought.checked_sub(sold)
+}
+
+/// Realized social surplus committed in the journal: Σ (bid − ask) over the matched units
+///
+/// Unlike `realized_surplus`, a stepped participant's summed allocation is
+/// attributed to its orders in book order (its best step first), each up to
+/// its quantity, the last taking the rest. Sides are valued in u128; every
+/// matched bid reaches its ask, so the surplus is never negative.
+///
+/// Returns: None if a side's value overflows u128 or the surplus is beyond u64
+fn matched_surplus(
+    buyers: &[&Participant],
+    sellers: &[&Participant],
+    allocations: &[(OrderKey, u64)],
+) -> Option<u64> {
+    let side_value = |orders: &[&Participant]| {
+        let mut attributed: Vec<(OrderKey, u64)> = Vec::new();
+        orders.iter().enumerate().try_fold(0u128, |total, (i, p)| {
+            let left = allocation_of(allocations, p).saturating_sub(allocation_of(&attributed, p));
+            let last = !orders[i + 1..].iter().any(|q| order_key(q) == order_key(p));
+            let amount = if last { left } else { left.min(p.quantity) };
+            add_allocation(&mut attributed, p, amount);
+            total.checked_add(p.price as u128 * amount as u128) // Fits: u64 × u64
+        })
+    };
+    u64::try_from(side_value(buyers)?.checked_sub(side_value(sellers)?)?).ok()
+}
+
+/// Classify the journal's buyer and seller rows by their allocation
+///
+/// A row is filled when its allocation reaches what its orders want (Σ
+/// `wanted` over the orders sharing its key, every step of a stepped bid),
+/// partial when it is positive but short of that, whether rationed at the
+/// margin or capped by the balance it holds, and unfilled at 0. A row with
+/// no order in the book (the operator, an untradable order) is unfilled.
+fn fill_counts(
+    (buyers, sellers): (&[&Participant], &[&Participant]),
+    orders: &[Participant],
+    allocations: &[(OrderKey, u64)],
+    wanted: impl Fn(&Participant) -> u64,
+) -> FillCounts {
+    let classify = |rows: &[&Participant]| {
+        rows.iter().fold([0u32; 3], |mut counts, row| {
+            let allocated = allocation_of(allocations, row);
+            let quantity = orders
+                .iter()
+                .filter(|o| order_key(o) == order_key(row))
+                .fold(0u64, |total, o| total.saturating_add(wanted(o)));
+            match allocated {
+                0 => counts[2] += 1,
+                allocated if allocated >= quantity => counts[0] += 1,
+                _ => counts[1] += 1,
+            }
+            counts
+        })
+    };
+    let [buyers_filled, buyers_partial, buyers_unfilled] = classify(buyers);
+    let [sellers_filled, sellers_partial, sellers_unfilled] = classify(sellers);
+    FillCounts {
+        buyers_filled,
+        buyers_partial,
+        buyers_unfilled,
+        sellers_filled,
+        sellers_partial,
+        sellers_unfilled,
+    }
+}
+
+/// Units each journal row was allocated, in protocol order
+///
+/// A row sums every order sharing its id, so a stepped bid counts all of its
+/// steps and a prosumer both of its sides; the operator and rows that did
+/// not trade get 0.
+fn allocation_column(
+    participants: &[Participant],
+    (buyers, sellers): (&[&Participant], &[&Participant]),
+    allocations: &[(OrderKey, u64)],
+) -> Vec<u64> {
+    let prosumers = sorted_prosumers(participants);
+    buyers
+        .iter()
+        .chain(sellers)
+        .chain(&prosumers)
+        .map(|row| {
+            allocations
+                .iter()
+                .filter(|((id, _), _)| *id == row.id)
+                .fold(0u64, |total, (_, amount)| total.saturating_add(*amount))
+        })
+        .collect()
+}
+
+/// Check whether an optional participant id refers to a journal row
+fn has_participant(participants: &[Participant], id: Option<u32>) -> bool {
+    id.is_some_and(|id| participants.iter().any(|p| p.id == id && p.role <= 1))
+}
+
+/// Apply allocations to compute final balances
+///
+/// Buyers settle at buyer_price and sellers at seller_price (equal for a
+/// uniform clearing price) unless the settlement rule prices per order.
+/// With config.fee_bps, buyers pay notional + fee and sellers receive
+/// notional − fee, each fee rounded down per participant (see `settle`).
+/// Any difference between coin paid by buyers and coin received by sellers
+/// (spread plus fees) is settled against config.operator_id, so conservation stays exact.
+/// Prices are offset-encoded: below config.price_offset the real price is
+/// negative and buyers receive coin while sellers pay (fees on |notional|).
+/// With config.loss_bps, buyers pay for their whole allocation but receive
+/// it net of delivery losses; the lost energy goes to config.loss_sink_id.
+///
+/// Returns: Ok(Some((Vec<(participant_id, out_coin, out_energy)>, fees))),
+/// fees being Σ config.fee_bps fee over every side that traded, Ok(None)
+/// when the imbalance cannot be settled (see `settle_imbalance`),
+/// Err(Overflow) when a payment or balance leaves its integer range, or
+/// Err(InsufficientCoin / InsufficientEnergy) naming the first participant
+/// whose allocation exceeds its balance
+fn compute_outputs(
+    participants: &[Participant],
+    allocations: &[(OrderKey, u64)],
+    (buyer_price, seller_price): (u64, u64),
+    config: &AuctionConfig,
+) -> Result<Option<(Outputs, u64)>, AuctionError> {
+    let mut outputs = Vec::new();
+    let mut coin_paid = 0i128;
+    let mut coin_received = 0i128;
+    let mut energy_lost = 0u64;
+    let mut fees = 0i128;
+
+    for p in participants {
+        let allocated = allocation_of(allocations, p);
+        let side_price = if p.role == 0 {
+            buyer_price
+        } else {
+            seller_price
+        };
+        let price = real_price(unit_price(p, side_price, config), config);
+
+        // Negative prices reverse the coin flow (buyer is paid, seller pays)
+        let (buyer_pays, seller_gets, dust) = checked(settle(price, allocated, config.fee_bps))?;
+        fees = checked(fees.checked_add(dust / 2))?; // Dust is this side's fee twice
+
+        let (out_coin, out_energy) = if p.role == 0 {
+            // BUY: spend coins (plus fee), receive energy net of delivery losses
+            if allocated > 0 {
+                coin_paid = checked(coin_paid.checked_add(buyer_pays))?;
+                let delivered = delivered_energy(allocated, config);
+                energy_lost = checked(energy_lost.checked_add(allocated - delivered))?;
+                (
+                    settled_coin(p, checked(buyer_pays.checked_neg())?, allocated)?,
+                    checked(p.in_energy.checked_add(delivered))?,
+                )
+            } else {
+                (p.in_coin, p.in_energy)
+            }
+        } else {
+            // SELL: receive coins (minus fee), spend energy
+            if allocated > 0 {
+                coin_received = checked(coin_received.checked_add(seller_gets))?;
+                (
+                    settled_coin(p, seller_gets, allocated)?,
+                    settled_energy(p, allocated)?,
+                )
+            } else {
+                (p.in_coin, p.in_energy)
+            }
+        };
+
+        checked(merge_output(&mut outputs, p, (out_coin, out_energy)))?;
+    }
+
+    // Settle the spread and fees so that Σ in_coin == Σ out_coin
+    let imbalance = checked(coin_paid.checked_sub(coin_received))?;
+    if !settle_imbalance(&mut outputs, imbalance, config)? {
+        return Ok(None);
+    }
+
+    // Credit delivery losses so that Σ in_energy == Σ out_energy
+    if energy_lost > 0 {
+        if let Some(sink) = outputs
+            .iter_mut()
+            .find(|(id, _, _)| Some(*id) == config.loss_sink_id)
+        {
+            sink.2 = checked(sink.2.checked_add(energy_lost))?;
+        }
+    }
+
+    Ok(Some((outputs, checked(to_balance(fees))?)))
+}
+
+/// Settle a coin imbalance against the settlement account (config.operator_id)
+///
+/// A surplus (buyers paid more than sellers received) is credited to the
+/// account and a deficit debited from it. Returns Ok(false), i.e. no trade,
+/// when a non-zero imbalance has no account to land on or a deficit exceeds
+/// the account's coin, so the account never goes negative; a surplus that
+/// would push it past u64::MAX is Err(Overflow).
+fn settle_imbalance(
+    outputs: &mut [(u32, u64, u64)],
+    imbalance: i128,
+    config: &AuctionConfig,
+) -> Result<bool, AuctionError> {
+    if imbalance == 0 {
+        return Ok(true);
+    }
+    let Some(account) = outputs
+        .iter_mut()
+        .find(|(id, _, _)| Some(*id) == config.operator_id)
+    else {
+        return Ok(false);
+    };
+    let balance = checked((account.1 as i128).checked_add(imbalance))?;
+    if balance < 0 {
+        return Ok(false);
+    }
+    account.1 = checked(to_balance(balance))?;
+    Ok(true)
+}
+
+/// Balance after a settlement step, or None outside the u64 range
+///
+/// Settlement runs on signed 128-bit intermediates; a result below 0 or
+/// above u64::MAX cannot be committed, so the caller reports an overflow
+/// instead of wrapping.
+fn to_balance(value: i128) -> Option<u64> {
+    u64::try_from(value).ok()
+}
+
+/// Coin balance after moving `delta` for an allocation (negative: paid out)
+///
+/// Below 0 means the allocation was not capped by the participant's coin,
+/// e.g. by a replaced clearing algorithm; above u64::MAX is an overflow.
+fn settled_coin(p: &Participant, delta: i128, allocated: u64) -> Result<u64, AuctionError> {
+    let coin = checked((p.in_coin as i128).checked_add(delta))?;
+    if coin < 0 {
+        return Err(AuctionError::InsufficientCoin(p.id, allocated));
+    }
+    checked(to_balance(coin))
+}
+
+/// Energy balance after a seller delivers its allocation
+///
+/// An allocation above in_energy means it was not capped by the seller's
+/// energy (see `effective_cap`).
+fn settled_energy(p: &Participant, allocated: u64) -> Result<u64, AuctionError> {
+    p.in_energy
+        .checked_sub(allocated)
+        .ok_or(AuctionError::InsufficientEnergy(p.id, allocated))
+}
+
+/// Curtailment compensation for priority sellers, funded by a fee on matched sales
+///
+/// Every seller that trades pays floor(notional × funding_fee_bps / 10_000)
+/// into a pool. Every priority seller whose ask is within the seller price
+/// is owed curtailment_comp_per_unit for each unit of its effective cap left
+/// unallocated. If the pool covers the total owed, each is paid in full;
+/// otherwise each receives floor(pool × owed / total owed), rounded down per
+/// seller. The operator receives what the pool has left (surplus or dust),
+/// so coin is conserved exactly. A stepped seller pays and is owed once, on
+/// its whole allocation and from its lowest step.
+///
+/// Returns: None if a credit or the pool leaves its integer range
+fn settle_curtailment(
+    outputs: &mut [(u32, u64, u64)],
+    sellers: &[&Participant],
+    allocations: &[(OrderKey, u64)],
+    seller_price: u64,
+    config: &AuctionConfig,
+) -> Option<()> {
+    if config.funding_fee_bps == 0 && config.curtailment_comp_per_unit == 0 {
+        return Some(());
+    }
+    let mut credit = |id: Option<u32>, amount: i128| -> Option<()> {
+        if let Some(row) = outputs
+            .iter_mut()
+            .find(|(row_id, _, _)| Some(*row_id) == id)
+        {
+            row.1 = to_balance((row.1 as i128).checked_add(amount)?)?;
+        }
+        Some(())
+    };
+
+    let mut pool = 0u128;
+    let mut owed: Vec<(u32, u128)> = Vec::new();
+    let mut settled: Vec<OrderKey> = Vec::new();
+    for seller in sellers {
+        // A stepped seller's orders share one key and its summed allocation
+        if settled.contains(&order_key(seller)) {
+            continue;
+        }
+        settled.push(order_key(seller));
+        let allocated = allocation_of(allocations, seller);
+        let notional = unit_price(seller, seller_price, config) as u128 * allocated as u128;
+        let fee = trade_fee(notional, config.funding_fee_bps)?;
+        pool = pool.checked_add(fee)?;
+        credit(Some(seller.id), -i128::try_from(fee).ok()?)?;
+
+        if seller.priority && seller.price <= seller_price {
+            let curtailed = effective_cap(seller, seller_price, config).saturating_sub(allocated);
+            let due = curtailed as u128 * config.curtailment_comp_per_unit as u128; // Fits: u64 × u64
+            if due > 0 {
+                owed.push((seller.id, due));
+            }
+        }
+    }
+
+    let total_owed = owed
+        .iter()
+        .try_fold(0u128, |total, (_, due)| total.checked_add(*due))?;
+    let mut paid = 0u128;
+    for (id, due) in owed {
+        let payment = if total_owed <= pool {
+            due
+        } else {
+            pool.checked_mul(due)? / total_owed
+        };
+        paid += payment; // Σ payments ≤ pool
+        credit(Some(id), i128::try_from(payment).ok()?)?;
+    }
+    credit(config.operator_id, i128::try_from(pool - paid).ok()?)
+}
+
+/// Energy a buyer receives for an allocation after delivery losses
+///
+/// Rounded down per allocation: floor(allocated × (10000 − loss_bps) / 10000),
+/// so the sink absorbs all rounding dust (a 1-unit fill can deliver 0).
+fn delivered_energy(allocated: u64, config: &AuctionConfig) -> u64 {
+    let kept = BPS_DENOMINATOR - config.loss_bps.min(10_000) as u128;
+    (allocated as u128 * kept / BPS_DENOMINATOR) as u64
+}
+
+/// Record a settlement row, netting a prosumer's buy and sell sides
+///
+/// The first side of a participant pushes its row; a later side with the same
+/// id adds its coin / energy deltas to that row.
+fn merge_output(
+    outputs: &mut Vec<(u32, u64, u64)>,
+    p: &Participant,
+    (out_coin, out_energy): (u64, u64),
+) -> Option<()> {
+    match outputs.iter_mut().find(|(id, _, _)| *id == p.id) {
+        Some(row) => {
+            row.1 = to_balance(row.1 as i128 + out_coin as i128 - p.in_coin as i128)?;
+            row.2 = to_balance(row.2 as i128 + out_energy as i128 - p.in_energy as i128)?;
+        }
+        None => outputs.push((p.id, out_coin, out_energy)),
+    }
+    Some(())
+}
+
+/// Apply allocations with VCG transfers (Settlement::Vcg)
+///
+/// Each winner's transfer is the externality it imposes on everyone else:
+///   t_i = W(-i) - (W - v_i),  v_i = bid × qty (buyer) or -ask × qty (seller)
+/// where W is the realized surplus and W(-i) the surplus of re-running the
+/// allocation without i (N+1 allocation runs in total). Buyers pay t_i and
+/// sellers receive -t_i, clamped to individual rationality because the
+/// priority allocation is not guaranteed welfare-optimal.
+///
+/// VCG is not budget balanced: the operator absorbs Σ paid − Σ received.
+/// Returns Ok(None) (no trade) if the operator's coin cannot cover a deficit,
+/// Err(Overflow) if a transfer leaves its integer range, and an
+/// InsufficientCoin / InsufficientEnergy error if an allocation exceeds a
+/// balance.
+fn compute_vcg_outputs(
+    participants: &[Participant],
+    (buyers, sellers): (&[&Participant], &[&Participant]),
+    allocations: &[(OrderKey, u64)],
+    config: &AuctionConfig,
+) -> Result<Option<Outputs>, AuctionError> {
+    let welfare = checked(realized_surplus(buyers, sellers, allocations))?;
+    let mut outputs = Vec::new();
+    let mut imbalance = 0i128;
+
+    for p in participants {
+        let allocated = allocation_of(allocations, p);
+        if allocated == 0 {
+            outputs.push((p.id, p.in_coin, p.in_energy));
+            continue;
+        }
+
+        // Re-run the allocation without this participant
+        let others_buyers: Vec<&Participant> =
+            buyers.iter().copied().filter(|b| b.id != p.id).collect();
+        let others_sellers: Vec<&Participant> =
+            sellers.iter().copied().filter(|s| s.id != p.id).collect();
+        let welfare_without = match find_clearing_price(&others_buyers, &others_sellers, config) {
+            Some((_, alloc)) => checked(realized_surplus(&others_buyers, &others_sellers, &alloc))?,
+            None => 0,
+        };
+
+        let amount = checked((p.price as i128).checked_mul(allocated as i128))?;
+        let (coin, energy) = if p.role == 0 {
+            // BUY: pay externality, at most the bid
+            let externality = checked(
+                welfare
+                    .checked_sub(amount)
+                    .and_then(|rest| welfare_without.checked_sub(rest)),
+            )?;
+            let payment = externality.clamp(0, amount);
+            imbalance = checked(imbalance.checked_add(payment))?;
+            (
+                settled_coin(p, -payment, allocated)?, // 0 ≤ payment ≤ amount
+                checked(p.in_energy.checked_add(allocated))?,
+            )
+        } else {
+            // SELL: receive externality, at least the ask
+            let externality = checked(
+                welfare
+                    .checked_sub(welfare_without)
+                    .and_then(|gain| gain.checked_add(amount)),
+            )?;
+            let receipt = externality.max(amount);
+            imbalance = checked(imbalance.checked_sub(receipt))?;
+            (
+                settled_coin(p, receipt, allocated)?,
+                settled_energy(p, allocated)?,
+            )
+        };
+        outputs.push((p.id, coin, energy));
+    }
+
+    // Settle the budget imbalance against the operator
+    if !settle_imbalance(&mut outputs, imbalance, config)? {
+        return Ok(None);
+    }
+    Ok(Some(outputs))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// CLOCK AUCTIONS: Single Seller, Many Buyers
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Deterministic simulations of price clocks for books with exactly one
+// seller. Buyers pay the clock price at which they accept, so settlement is
+// expressed as per-participant fills rather than a single clearing price.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Settlement of one order: energy and coin moved (direction by the key's side)
+struct Fill {
+    key: OrderKey,
+    energy: u64,
+    coin: u64,
+}
+
+/// Descending-price (Dutch) clock
+///
+/// The clock starts at ask + clock_increment and falls by clock_tick down to
+/// the ask (never below 1). At each tick, pending buyers whose bid meets the
+/// price accept in config.tie_break order (id by default), take
+/// min(quantity, affordable, remaining) at that price and leave the clock,
+/// until the seller's deliverable quantity is exhausted. Ticks at which
+/// nobody can accept are skipped in one step.
+///
+/// Returns: Ok(Some((fills, clearing))) with the last price that sold,
+/// Ok(None) unless the book has exactly one seller and at least one buyer,
+/// Err(Overflow) if the seller's proceeds or a jump of the clock overflow u64
+fn run_dutch_clock(
+    (buyers, sellers): (&[&Participant], &[&Participant]),
+    config: &AuctionConfig,
+) -> Result<Option<(Vec<Fill>, Clearing)>, AuctionError> {
+    let [seller] = sellers else {
+        return Ok(None);
+    };
+    if buyers.is_empty() || config.clock_tick == 0 {
+        return Ok(None);
+    }
+
+    let floor = seller.price.max(1);
+    let mut price = seller
+        .price
+        .saturating_add(config.clock_increment)
+        .max(floor);
+    let mut remaining = seller.quantity.min(seller.in_energy);
+    let mut pending: Vec<&Participant> = buyers
+        .iter()
+        .copied()
+        .filter(|b| !same_owner(b, seller)) // Self-trade prevention
+        .collect();
+    pending.sort_by_cached_key(|p| tie_key(p, config)); // Same-tick acceptance order
+    let mut fills = Vec::new();
+    let mut sold_energy = 0u64;
+    let mut sold_coin = 0u64;
+    let mut sold_price = 0u64;
+
+    while remaining > 0 && !pending.is_empty() {
+        // Jump to the first tick at or below the best pending bid
+        let Some(best_bid) = pending.iter().map(|b| b.price).max() else {
+            break;
+        };
+        if best_bid < floor {
+            break;
+        }
+        if best_bid < price {
+            let steps = (price - best_bid).div_ceil(config.clock_tick);
+            let jump = checked(steps.checked_mul(config.clock_tick))?;
+            price = price.saturating_sub(jump).max(floor);
+        }
+
+        // Accept every pending buyer whose bid meets the current price
+        let (accepting, rest): (Vec<&Participant>, Vec<&Participant>) =
+            pending.into_iter().partition(|b| b.price >= price);
+        pending = rest;
+        for buyer in accepting {
+            let take = buyer.quantity.min(buyer.in_coin / price).min(remaining);
+            if take > 0 {
+                // Fee-free, so the buyer pays exactly what the seller gets (no dust)
+                let (coin, _, _) = checked(settle(price as i128, take, 0))?;
+                let coin = checked(to_balance(coin))?; // take × price ≤ in_coin
+                fills.push(Fill {
+                    key: order_key(buyer),
+                    energy: take,
+                    coin,
+                });
+                remaining -= take;
+                sold_energy += take;
+                sold_price = price;
+                sold_coin = checked(sold_coin.checked_add(coin))?;
+            }
+        }
+    }
+
+    fills.push(Fill {
+        key: order_key(seller),
+        energy: sold_energy,
+        coin: sold_coin,
+    });
+    Ok(Some((fills, (sold_price, sold_energy))))
+}
+
+/// Ascending-price (English) clock, alongside find_clearing_price
+///
+/// The clock starts at the seller's ask (never below 1) and rises by
+/// clock_tick while demand (Σ quantity of buyers bidding at least the price)
+/// exceeds the seller's deliverable quantity. It stops at the first price
+/// where demand no longer exceeds supply; if no bid reaches that price there
+/// is no trade. Qualified buyers are then allocated at the final price with
+/// the usual effective caps and priority rationing. Prices at which demand
+/// cannot change are skipped in one step.
+///
+/// Returns: Ok(Some((clearing_price, Vec<(order_key, allocation)>))),
+/// Ok(None) on no trade, Err(Overflow) if a jump of the clock overflows u64
+fn find_english_clock_price(
+    buyers: &[&Participant],
+    sellers: &[&Participant],
+    config: &AuctionConfig,
+) -> Result<Option<PriceClearing>, AuctionError> {
+    let [seller] = sellers else {
+        return Ok(None);
+    };
+    if buyers.is_empty() || config.clock_tick == 0 {
+        return Ok(None);
+    }
+
+    let supply = seller.quantity.min(seller.in_energy);
+    let mut price = seller.price.max(1);
+    loop {
+        let (demand, _) = demand_supply_at(buyers, &[], price, config);
+        if demand <= supply as u128 {
+            break;
+        }
+        // Demand only drops once the price passes the lowest active bid
+        let Some(lowest_active) = buyers
+            .iter()
+            .filter(|b| b.price >= price)
+            .map(|b| b.price)
+            .min()
+        else {
+            return Ok(None);
+        };
+        let steps = (lowest_active - price) / config.clock_tick + 1;
+        let jump = checked(steps.checked_mul(config.clock_tick))?;
+        let Some(raised) = price.checked_add(jump) else {
+            return Ok(None); // Past every bid
+        };
+        price = raised;
+    }
+
+    let qualified: Vec<&Participant> = buyers
+        .iter()
+        .copied()
+        .filter(|b| b.price >= price)
+        .collect();
+    if qualified.is_empty() {
+        return Ok(None); // Price rose above every bid
+    }
+
+    let allocations = allocate_at_price(&qualified, sellers, price, config);
+    Ok(Some((price, allocations)))
+}
+
+/// Apply per-order fills to compute final balances
+///
+/// Fills are looked up by order key, so a prosumer's buy and sell fills land
+/// on their own sides and are netted into one row (see `merge_output`).
+///
+/// Returns: Ok(Vec<(participant_id, out_coin, out_energy)>), Err(Overflow) if
+/// a balance would leave the u64 range, or Err(InsufficientCoin /
+/// InsufficientEnergy) naming the first participant whose fill exceeds its
+/// balance
+fn compute_fill_outputs(
+    participants: &[Participant],
+    fills: &[Fill],
+) -> Result<Outputs, AuctionError> {
+    let mut outputs = Vec::new();
+    for p in participants {
+        let (energy, coin) = fills
+            .iter()
+            .find(|f| f.key == order_key(p))
+            .map_or((0, 0), |f| (f.energy, f.coin));
+        let settled = if p.role == 0 {
+            // BUY: spend coins, receive energy
+            (
+                settled_coin(p, -(coin as i128), energy)?,
+                checked(p.in_energy.checked_add(energy))?,
+            )
+        } else {
+            // SELL: receive coins, spend energy
+            (
+                settled_coin(p, coin as i128, energy)?,
+                settled_energy(p, energy)?,
+            )
+        };
+        checked(merge_output(&mut outputs, p, settled))?;
+    }
+    Ok(outputs)
+}
+
+/// Allocations of per-order fills, each key's energy summed
+///
+/// Returns: None if a key's energy overflows u64
+fn fill_allocations(fills: &[Fill]) -> Option<Vec<(OrderKey, u64)>> {
+    let mut allocations: Vec<(OrderKey, u64)> = Vec::new();
+    for fill in fills {
+        match allocations.iter_mut().find(|(key, _)| *key == fill.key) {
+            Some((_, energy)) => *energy = energy.checked_add(fill.energy)?,
+            None => allocations.push((fill.key, fill.energy)),
+        }
+    }
+    Some(allocations)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// BILATERAL MATCHING: Explicit Trade List
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Algorithms that pair individual buyers with individual sellers. Each pair
+// trades at its own price, recorded as a Trade; balances are derived from the
+// trade list, so coin and energy are conserved trade by trade.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// A bilateral trade between one buyer and one seller
+struct Trade {
+    buyer_id: u32,
+    seller_id: u32,
+    qty: u64,
+    price: u64, // Per unit
+}
+
+/// Greedy bilateral matching
+///
+/// Repeatedly pairs the highest remaining bid with the lowest remaining ask
+/// (book order, ties by id) while bid >= ask and trades min(remaining buyer
+/// quantity, affordable units, remaining seller quantity) at the mid-point
+/// of the pair. A buyer that cannot afford a unit at the pair price, or a
+/// seller with nothing left to deliver, leaves the book. As in the uniform
+/// mechanism, a pair priced at 0 trades for free. Self-trade prevention: a buyer
+/// skips the asks of its own owner and pairs with the lowest remaining ask
+/// of another; a buyer whose best such ask is above its bid leaves the book,
+/// while the asks it skipped stay open for the buyers behind it.
+fn run_greedy_matching((buyers, sellers): (&[&Participant], &[&Participant])) -> Vec<Trade> {
+    let mut buyer_left: Vec<(u64, u64)> = buyers.iter().map(|b| (b.quantity, b.in_coin)).collect();
+    let mut seller_left: Vec<u64> = sellers
+        .iter()
+        .map(|s| s.quantity.min(s.in_energy))
+        .collect();
+    let mut trades = Vec::new();
+    let mut bi = 0;
+    let mut open = 0; // Sellers before this index are exhausted
+
+    while bi < buyers.len() {
+        while open < sellers.len() && seller_left[open] == 0 {
+            open += 1;
+        }
+        let buyer = buyers[bi];
+        let Some(si) =
+            (open..sellers.len()).find(|&j| seller_left[j] > 0 && !same_owner(buyer, sellers[j]))
+        else {
+            if open == sellers.len() {
+                break;
+            }
+            bi += 1;
+            continue;
+        };
+        let seller = sellers[si];
+        if buyer.price < seller.price {
+            bi += 1;
+            continue;
+        }
+        let price = buyer.price.midpoint(seller.price);
+
+        let (qty_left, coin_left) = &mut buyer_left[bi];
+        let affordable = coin_left.checked_div(price).unwrap_or(u64::MAX);
+        let qty = (*qty_left).min(affordable).min(seller_left[si]);
+        if qty > 0 {
+            trades.push(Trade {
+                buyer_id: buyer.id,
+                seller_id: seller.id,
+                qty,
+                price,
+            });
+            *qty_left -= qty;
+            *coin_left -= qty * price;
+            seller_left[si] -= qty;
+        }
+
+        if *qty_left == 0 || *coin_left < price {
+            bi += 1;
+        }
+    }
+
+    trades
+}
+
+/// Aggregate a trade list into per-order fills (buyer and seller side keys)
+///
+/// Returns: None if a participant's total coin or energy overflows u64
+fn fills_from_trades(trades: &[Trade]) -> Option<Vec<Fill>> {
+    let mut fills: Vec<Fill> = Vec::new();
+    for trade in trades {
+        // Fee-free, so the buyer pays exactly what the seller gets (no dust)
+        let (coin, _, _) = settle(trade.price as i128, trade.qty, 0)?;
+        let coin = to_balance(coin)?; // qty × price ≤ the buyer's coin
+        for key in [(trade.buyer_id, 0), (trade.seller_id, 1)] {
+            match fills.iter_mut().find(|f| f.key == key) {
+                Some(fill) => {
+                    fill.energy = fill.energy.checked_add(trade.qty)?;
+                    fill.coin = fill.coin.checked_add(coin)?;
+                }
+                None => fills.push(Fill {
+                    key,
+                    energy: trade.qty,
+                    coin,
+                }),
+            }
+        }
+    }
+    Some(fills)
+}
+
+/// Clearing of a trade list: (price of the last trade, Σ qty)
+///
+/// Returns: None if the volume overflows u64
+fn trades_clearing(trades: &[Trade]) -> Option<Clearing> {
+    let volume = trades
+        .iter()
+        .try_fold(0u64, |total, trade| total.checked_add(trade.qty))?;
+    Some((trades.last().map_or(0, |trade| trade.price), volume))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// COMBINATORIAL AUCTION: Energy + Capacity Bundles
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled by config.bundles. Buyers bid for an all-or-nothing bundle of
+// `quantity` energy plus `capacity` capacity at `price` per bundle unit;
+// sellers offer up to `quantity` energy and `capacity` capacity at `price`
+// per unit of either good. The journal is PublicJournal (coin and energy)
+// followed by CapacityJournal, both in protocol order.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Run the bundle auction and build both journal sections
+fn run_bundle_auction(
+    input: &AuctionInput,
+) -> Result<(PublicJournal, CapacityJournal), AuctionError> {
+    let participants = &input.participants;
+    let (buyers, sellers) = separate_and_sort(participants, &input.config);
+    let operator_id = input.config.operator_id;
+    let book_buyers: Vec<&Participant> = buyers
+        .iter()
+        .copied()
+        .filter(|b| Some(b.id) != operator_id)
+        .collect();
+    let book_sellers: Vec<&Participant> = sellers
+        .iter()
+        .copied()
+        .filter(|s| Some(s.id) != operator_id)
+        .collect();
+
+    // Without an accepted bundle the fills are empty and every balance passes through
+    let fills = checked(run_bundle_matching(&book_buyers, &book_sellers))?;
+    let (mut outputs, capacity_outputs) = settle_bundles(participants, &fills)?;
+    let clearing = checked(bundle_clearing(&book_buyers, &fills))?;
+    let allocations = checked(bundle_allocations(&book_buyers, &fills))?;
+    let surplus = checked(matched_surplus(&book_buyers, &book_sellers, &allocations))?;
+    let wanted = |p: &Participant| p.quantity.saturating_add(p.capacity);
+    // The allocation column counts energy: capacity has a journal of its own
+    let energy: Vec<(OrderKey, u64)> = allocations
+        .iter()
+        .zip(&fills)
+        .map(|((key, _), (_, (_, energy, _)))| (*key, *energy))
+        .collect();
+
+    let journal = PublicJournal {
+        surplus,
+        fill_counts: fill_counts((&buyers, &sellers), participants, &allocations, wanted),
+        allocation: allocation_column(participants, (&buyers, &sellers), &energy),
+        ..build_journal_with_outputs(participants, &buyers, &sellers, &mut outputs, clearing)
+    };
+    let capacity = build_capacity_journal(&buyers, &sellers, &capacity_outputs);
+    Ok((journal, capacity))
+}
+
+/// Coin, energy and capacity moved for one participant: (id, (coin, energy, capacity))
+type BundleFill = (u32, (u64, u64, u64));
+
+/// Clearing of the bundle fills: (lowest accepted bid, Σ energy bought)
+///
+/// Buyers are in book order (descending bid), so the last accepted bid is
+/// the lowest; capacity-only bundles set neither. Returns: None if the
+/// volume overflows u64
+fn bundle_clearing(buyers: &[&Participant], fills: &[BundleFill]) -> Option<Clearing> {
+    buyers
+        .iter()
+        .filter_map(|b| {
+            let (_, (_, energy, _)) = fills.iter().find(|(id, _)| *id == b.id)?;
+            (*energy > 0).then_some((b.price, *energy))
+        })
+        .try_fold(NO_TRADE, |(_, volume), (price, energy)| {
+            Some((price, volume.checked_add(energy)?))
+        })
+}
+
+/// Allocations of the bundle fills, every unit of energy or capacity being a matched unit
+///
+/// Returns: None if a participant's units overflow u64
+fn bundle_allocations(
+    buyers: &[&Participant],
+    fills: &[BundleFill],
+) -> Option<Vec<(OrderKey, u64)>> {
+    fills
+        .iter()
+        .map(|(id, (_, energy, capacity))| {
+            let role = if buyers.iter().any(|b| b.id == *id) {
+                0
+            } else {
+                1
+            };
+            Some(((*id, role), energy.checked_add(*capacity)?))
+        })
+        .collect()
+}
+
+/// Settled bundle balances: (coin / energy outputs, capacity outputs)
+type BundleOutputs = (Vec<(u32, u64, u64)>, Vec<(u32, u64)>);
+
+/// Apply bundle fills to the coin, energy and capacity balances
+///
+/// Every subtraction is checked, as in compute_outputs: a fill that takes
+/// more than the participant holds names it with its allocation (energy
+/// plus capacity), a seller short of capacity as InsufficientEnergy.
+///
+/// Returns: Ok((coin / energy outputs, capacity outputs)), Err(Overflow) if
+/// a credit would leave the u64 range, or Err(InsufficientCoin /
+/// InsufficientEnergy) naming the first participant whose fill exceeds its
+/// balance
+fn settle_bundles(
+    participants: &[Participant],
+    fills: &[BundleFill],
+) -> Result<BundleOutputs, AuctionError> {
+    let mut outputs: Vec<(u32, u64, u64)> = Vec::new();
+    let mut capacity_outputs: Vec<(u32, u64)> = Vec::new();
+    for p in participants {
+        let (coin, energy, capacity) = fills
+            .iter()
+            .find(|(id, _)| *id == p.id)
+            .map_or((0, 0, 0), |(_, fill)| *fill);
+        let allocated = checked(energy.checked_add(capacity))?;
+        if p.role == 0 {
+            let out_coin = p
+                .in_coin
+                .checked_sub(coin)
+                .ok_or(AuctionError::InsufficientCoin(p.id, allocated))?;
+            outputs.push((p.id, out_coin, checked(p.in_energy.checked_add(energy))?));
+            capacity_outputs.push((p.id, checked(p.in_capacity.checked_add(capacity))?));
+        } else {
+            let out_energy = p
+                .in_energy
+                .checked_sub(energy)
+                .ok_or(AuctionError::InsufficientEnergy(p.id, allocated))?;
+            let out_capacity = p
+                .in_capacity
+                .checked_sub(capacity)
+                .ok_or(AuctionError::InsufficientEnergy(p.id, allocated))?;
+            outputs.push((p.id, checked(p.in_coin.checked_add(coin))?, out_energy));
+            capacity_outputs.push((p.id, out_capacity));
+        }
+    }
+    Ok((outputs, capacity_outputs))
+}
+
+/// Greedy bundle winner determination
+///
+/// Bundles are considered in descending price-per-unit order (book order,
+/// ties by id). A bundle is accepted if the buyer can pay price × (energy +
+/// capacity) and sellers asking at most the buyer's price still hold enough
+/// of both goods; energy and capacity are then drawn from those sellers in
+/// ascending ask order, each seller receiving the buyer's price per unit.
+/// Rejected bundles take nothing.
+///
+/// Returns: Vec<(participant_id, (coin, energy, capacity))> moved per
+/// participant, or None if a participant's totals overflow u64
+fn run_bundle_matching(
+    buyers: &[&Participant],
+    sellers: &[&Participant],
+) -> Option<Vec<BundleFill>> {
+    let mut energy_left: Vec<u64> = sellers
+        .iter()
+        .map(|s| s.quantity.min(s.in_energy))
+        .collect();
+    let mut capacity_left: Vec<u64> = sellers
+        .iter()
+        .map(|s| s.capacity.min(s.in_capacity))
+        .collect();
+    let mut fills: Vec<BundleFill> = Vec::new();
+
+    for buyer in buyers {
+        // Checked so a huge bundle is rejected rather than wrapping to a cheap one
+        let Some(units) = buyer.quantity.checked_add(buyer.capacity) else {
+            continue;
+        };
+        let Some(cost) = buyer.price.checked_mul(units) else {
+            continue;
+        };
+        if units == 0 || cost > buyer.in_coin {
+            continue;
+        }
+
+        // Sellers eligible at this bid form a prefix of the ascending book
+        let eligible = sellers
+            .iter()
+            .take_while(|s| s.price <= buyer.price)
+            .count();
+        let energy_available: u128 = energy_left[..eligible].iter().map(|&e| e as u128).sum();
+        let capacity_available: u128 = capacity_left[..eligible].iter().map(|&c| c as u128).sum();
+        if energy_available < buyer.quantity as u128 || capacity_available < buyer.capacity as u128
+        {
+            continue;
+        }
+
+        let (mut energy_need, mut capacity_need) = (buyer.quantity, buyer.capacity);
+        for (si, seller) in sellers[..eligible].iter().enumerate() {
+            let energy = energy_need.min(energy_left[si]);
+            let capacity = capacity_need.min(capacity_left[si]);
+            if energy + capacity == 0 {
+                continue;
+            }
+            energy_left[si] -= energy;
+            capacity_left[si] -= capacity;
+            energy_need -= energy;
+            capacity_need -= capacity;
+            // Fee-free, so the buyer pays exactly what the seller gets (no dust)
+            let (coin, _, _) = settle(buyer.price as i128, energy + capacity, 0)?;
+            let coin = to_balance(coin)?; // ≤ cost
+            add_bundle_fill(&mut fills, seller.id, (coin, energy, capacity))?;
+        }
+        add_bundle_fill(&mut fills, buyer.id, (cost, buyer.quantity, buyer.capacity))?;
+    }
+
+    Some(fills)
+}
+
+/// Accumulate a (coin, energy, capacity) movement for one participant
+fn add_bundle_fill(
+    fills: &mut Vec<BundleFill>,
+    id: u32,
+    (coin, energy, capacity): (u64, u64, u64),
+) -> Option<()> {
+    match fills.iter_mut().find(|(fill_id, _)| *fill_id == id) {
+        Some((_, fill)) => {
+            fill.0 = fill.0.checked_add(coin)?;
+            fill.1 = fill.1.checked_add(energy)?;
+            fill.2 = fill.2.checked_add(capacity)?;
+        }
+        None => fills.push((id, (coin, energy, capacity))),
+    }
+    Some(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// CARBON CO-CLEARING: Embedded Carbon Budgets
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled when a seller declares carbon_per_unit or a buyer a carbon_budget.
+// The book clears at a uniform price as usual; a second pass then sources
+// each buyer's allocation from the cleanest sellers and trims it to what
+// the buyer's carbon budget covers. The journal is PublicJournal followed
+// by CarbonJournal, both in protocol order.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Run the uniform-price auction under carbon budgets and build both sections
+///
+/// The PublicJournal passes the checks clear_book makes (see
+/// `check_clearing`); a no-trade journal standing in for a failure
+/// commits no carbon. validate_input has refused every combination the
+/// co-clearing does not settle (see `unsupported_mode`), so the book has
+/// no losses, grid, steps or prosumers to expand.
+fn run_carbon_auction(
+    input: &AuctionInput,
+) -> Result<(PublicJournal, CarbonJournal), AuctionError> {
+    let (buyers, sellers) = separate_and_sort(&input.participants, &input.config);
+    let mut carbon = Vec::new();
+    let cleared = clear_carbon_book(input).map(|(journal, trimmed)| {
+        carbon = trimmed;
+        journal
+    });
+    let journal = check_clearing(input, cleared)?;
+    if journal.status == STATUS_SETTLED_AS_NO_TRADE {
+        carbon.clear();
+    }
+    let carbon = build_carbon_journal(&buyers, &sellers, &carbon);
+    Ok((journal, carbon))
+}
+
+/// Clear the book at a uniform price, then trim it to the carbon budgets
+///
+/// Returns: (PublicJournal, carbon per order key: see `apply_carbon_budgets`)
+fn clear_carbon_book(
+    input: &AuctionInput,
+) -> Result<(PublicJournal, Vec<(OrderKey, u64)>), AuctionError> {
+    let participants = &input.participants;
+    let config = &input.config;
+    let (buyers, sellers) = separate_and_sort(participants, config);
+    let no_trade = || Ok((build_journal(participants, &buyers, &sellers), Vec::new()));
+
+    if needs_operator(config) && !has_participant(participants, config.operator_id) {
+        return no_trade();
+    }
+
+    let operator_id = config.operator_id;
+    let book_buyers: Vec<&Participant> = buyers
+        .iter()
+        .copied()
+        .filter(|b| Some(b.id) != operator_id && is_live(b, config) && is_tradable(b))
+        .collect();
+    let book_sellers: Vec<&Participant> = sellers
+        .iter()
+        .copied()
+        .filter(|s| Some(s.id) != operator_id && is_live(s, config) && is_tradable(s))
+        .collect();
+
+    let Some((price, allocations)) = find_clearing_price(&book_buyers, &book_sellers, config)
+    else {
+        return no_trade();
+    };
+    let (allocations, carbon) = checked(apply_carbon_budgets(
+        &book_buyers,
+        &book_sellers,
+        &allocations,
+    ))?;
+
+    let Some((mut outputs, fees_collected)) =
+        compute_outputs(participants, &allocations, (price, price), config)?
+    else {
+        return no_trade();
+    };
+    let clearing = match allocated_volume(&allocations) {
+        0 => NO_TRADE,
+        volume => (price, volume),
+    };
+    let surplus = checked(matched_surplus(&book_buyers, &book_sellers, &allocations))?;
+    let wanted = |p: &Participant| order_quantity(p, price, config);
+    let journal = PublicJournal {
+        fees_collected,
+        surplus,
+        fill_counts: fill_counts((&buyers, &sellers), participants, &allocations, wanted),
+        allocation: allocation_column(participants, (&buyers, &sellers), &allocations),
+        ..build_journal_with_outputs(participants, &buyers, &sellers, &mut outputs, clearing)
+    };
+    Ok((journal, carbon))
+}
+
+/// Carbon-trimmed clearing: (allocations, carbon per order key)
+type CarbonClearing = (Vec<(OrderKey, u64)>, Vec<(OrderKey, u64)>);
+
+/// Trim buyer allocations to their carbon budgets
+///
+/// Allocated seller volume is taken cleanest first (ascending
+/// carbon_per_unit, ties in book order). Buyers in priority order draw on
+/// it, each taking only the units its remaining budget can cover; the rest
+/// of its allocation is dropped. Seller volume left unmatched is dropped
+/// too, which removes it from the dirtiest sellers first since those are
+/// drawn on last. Both sides shrink by the same amount, so the uniform
+/// price stays valid.
+///
+/// Returns: (allocations, carbon per order key: embedded for buyers, emitted
+/// for sellers), or None if the total carbon overflows u64
+fn apply_carbon_budgets(
+    buyers: &[&Participant],
+    sellers: &[&Participant],
+    allocations: &[(OrderKey, u64)],
+) -> Option<CarbonClearing> {
+    let mut supply: Vec<(&Participant, u64)> = sellers
+        .iter()
+        .map(|s| (*s, allocation_of(allocations, s)))
+        .filter(|(_, allocated)| *allocated > 0)
+        .collect();
+    supply.sort_by_key(|(s, _)| s.carbon_per_unit);
+
+    let mut trimmed = Vec::new();
+    let mut carbon = Vec::new();
+    let mut total_carbon = 0u64; // Bounds every per-order tally
+    for buyer in buyers {
+        let mut wanted = allocation_of(allocations, buyer);
+        let mut budget = buyer.carbon_budget.unwrap_or(u64::MAX);
+        for (seller, left) in supply.iter_mut() {
+            if wanted == 0 {
+                break;
+            }
+            let intensity = seller.carbon_per_unit;
+            let covered = budget.checked_div(intensity).unwrap_or(*left);
+            let take = wanted.min(*left).min(covered);
+            if take == 0 {
+                continue;
+            }
+            *left -= take;
+            wanted -= take;
+            budget -= take * intensity;
+            total_carbon = total_carbon.checked_add(take * intensity)?;
+            add_allocation(&mut trimmed, buyer, take);
+            add_allocation(&mut trimmed, seller, take);
+            add_allocation(&mut carbon, buyer, take * intensity);
+            add_allocation(&mut carbon, seller, take * intensity);
+        }
+    }
+    Some((trimmed, carbon))
+}
+
+/// Build the carbon section in protocol order
+fn build_carbon_journal(
+    buyers_sorted: &[&Participant],
+    sellers_sorted: &[&Participant],
+    carbon: &[(OrderKey, u64)],
+) -> CarbonJournal {
+    let rows: Vec<u64> = buyers_sorted
+        .iter()
+        .chain(sellers_sorted.iter())
+        .map(|p| allocation_of(carbon, p))
+        .collect();
+    let total_carbon = sellers_sorted
+        .iter()
+        .map(|s| allocation_of(carbon, s))
+        .sum();
+    CarbonJournal {
+        carbon: rows,
+        total_carbon,
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// MULTI-ROUND AUCTION: Balances Carried Between Rounds
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled by a non-empty input.rounds. input.participants fixes the initial
+// balances; each round supplies its own bids, runs run_double_auction with
+// the carried balances, and its outputs become the next round's inputs.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Run every round in order, carrying coin and energy balances by id
+///
+/// Round bids take their balances from the carried state (the balance
+/// fields of round participants are ignored); ids without an initial
+/// balance do not take part. A participant absent from a round keeps its
+/// balances unchanged. Round i (from 0) clears as config.current_round + i,
+/// so orders expire across the sequence.
+///
+/// Returns: (RoundsJournal, cycles spent per round)
+fn run_rounds(input: &AuctionInput) -> Result<(RoundsJournal, Vec<u64>), AuctionError> {
+    let mut state: Vec<(u32, u64, u64)> = input
+        .participants
+        .iter()
+        .map(|p| (p.id, p.in_coin, p.in_energy))
+        .collect();
+    state.sort_by_key(|(id, _, _)| *id);
+
+    let mut rounds = Vec::new();
+    let mut round_cycles = Vec::new();
+    for (index, round) in input.rounds.iter().enumerate() {
+        let start = env::cycle_count();
+
+        let participants: Vec<Participant> = round
+            .participants
+            .iter()
+            .filter_map(|bid| {
+                let (_, coin, energy) = state.iter().find(|(id, _, _)| *id == bid.id)?;
+                Some(Participant {
+                    in_coin: *coin,
+                    in_energy: *energy,
+                    ..bid.clone()
+                })
+            })
+            .collect();
+        let round_input = AuctionInput {
+            participants,
+            config: AuctionConfig {
+                current_round: input.config.current_round + index as u32,
+                ..input.config.clone()
+            },
+            rounds: Vec::new(),
+            commitments: Vec::new(),
+            output_salt: [0; 32],
+        };
+        let journal = clear_book(&round_input)?;
+
+        // The round journal is in protocol order; carry its outputs forward
+        for (p, (coin, energy)) in protocol_order(&round_input.participants, &input.config)
+            .iter()
+            .zip(journal.out_coin.iter().zip(journal.out_energy.iter()))
+        {
+            if let Some(entry) = state.iter_mut().find(|(id, _, _)| *id == p.id) {
+                *entry = (p.id, *coin, *energy);
+            }
+        }
+
+        rounds.push(journal);
+        round_cycles.push(env::cycle_count() - start);
+    }
+
+    let journal = RoundsJournal {
+        version: ROUNDS_JOURNAL_VERSION,
+        rounds,
+        ids: state.iter().map(|(id, _, _)| *id).collect(),
+        final_coin: state.iter().map(|(_, coin, _)| *coin).collect(),
+        final_energy: state.iter().map(|(_, _, energy)| *energy).collect(),
+    };
+    Ok((journal, round_cycles))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// MULTI-PERIOD AUCTION: Hourly Products Sharing One Coin Balance
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled when any participant lists periods. Period i clears every
+// participant with its i-th (price, quantity) bid, or quantity 0 when the
+// list is shorter, through run_double_auction. Periods clear in order and
+// each one spends the coin left by the previous one, so a buyer's budget is
+// shared across the day. Energy is a separate product per period: every
+// period starts from in_energy (e.g. a generator's hourly capacity).
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Clear the first MAX_PERIODS periods in order, carrying coin by id
+///
+/// Returns: (PeriodsJournal, cycles spent per period)
+fn run_periods(input: &AuctionInput) -> Result<(PeriodsJournal, Vec<u64>), AuctionError> {
+    let periods = period_count(&input.participants);
+
+    let mut state: Vec<(u32, u64)> = input
+        .participants
+        .iter()
+        .map(|p| (p.id, p.in_coin))
+        .collect();
+    state.sort_by_key(|(id, _)| *id);
+
+    let mut counts = Vec::new();
+    let mut out_energy = Vec::new();
+    let mut period_cycles = Vec::new();
+    for period in 0..periods {
+        let start = env::cycle_count();
+
+        let participants: Vec<Participant> = period_participants(&input.participants, period)
+            .into_iter()
+            .map(|p| {
+                let (_, coin) = state
+                    .iter()
+                    .find(|(id, _)| *id == p.id)
+                    .ok_or(AuctionError::Internal(p.id))?;
+                Ok(Participant { in_coin: *coin, ..p })
+            })
+            .collect::<Result<_, AuctionError>>()?;
+        let period_input = AuctionInput {
+            participants,
+            config: input.config.clone(),
+            rounds: Vec::new(),
+            commitments: Vec::new(),
+            output_salt: [0; 32],
+        };
+        let journal = clear_book(&period_input)?;
+
+        // The period journal is in protocol order; report energy and carry coin by id
+        let mut energy: Vec<(u32, u64)> = Vec::new();
+        for (p, (coin, out)) in protocol_order(&period_input.participants, &input.config)
+            .iter()
+            .zip(journal.out_coin.iter().zip(journal.out_energy.iter()))
+        {
+            if let Some(entry) = state.iter_mut().find(|(id, _)| *id == p.id) {
+                entry.1 = *coin;
+            }
+            energy.push((p.id, *out));
+        }
+        energy.sort_by_key(|(id, _)| *id);
+
+        counts.push(energy.len() as u32);
+        out_energy.push(energy.iter().map(|(_, out)| *out).collect());
+        period_cycles.push(env::cycle_count() - start);
+    }
+
+    let journal = PeriodsJournal {
+        version: PERIODS_JOURNAL_VERSION,
+        input_digest: Digest::ZERO, // Bound by run before commit
+        algorithm_id: ALGORITHM_ID,
+        config_digest: Digest::ZERO, // Bound by run before commit
+        counts,
+        ids: state.iter().map(|(id, _)| *id).collect(),
+        out_energy,
+        final_coin: state.iter().map(|(_, coin)| *coin).collect(),
+        round_id: 0, // Bound by run before commit
+    };
+    Ok((journal, period_cycles))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// SECONDARY ROUND: Leftover Quantity at a Price Concession
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled by config.secondary_concession. The book first clears as usual;
+// every order then re-enters with its unmatched quantity, the balances left
+// by the primary clearing and its limit moved by the concession (bids up,
+// asks down), and run_double_auction clears those remainders once more.
+// Each pass is settled at its own uniform price and the journal holds the
+// combined balances, so conservation carries over from both passes.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Clear the book, then clear the unmatched remainders with the concession
+///
+/// A participant's remainder is its quantity less the energy it traded in
+/// the primary clearing; with the concession X a buyer bids price + X and a
+/// seller asks price − X (saturating), so an order may trade up to X past
+/// its own limit in the second pass. validate_input has refused fees,
+/// losses, prosumers, steps and budget bids (see `unsupported_mode`), so
+/// each row's energy change is the quantity it traded.
+///
+/// Returns: (PublicJournal over both passes, SecondaryJournal)
+fn run_secondary_round(
+    input: &AuctionInput,
+) -> Result<(PublicJournal, SecondaryJournal), AuctionError> {
+    let config = &input.config;
+    let concession = config.secondary_concession.unwrap_or(0);
+    let primary = run_double_auction(input)?;
+
+    // The primary journal is in protocol order; carry its outputs into the remainders
+    let remainders: Vec<Participant> = protocol_order(&input.participants, config)
+        .iter()
+        .zip(primary.out_coin.iter().zip(primary.out_energy.iter()))
+        .map(|(p, (&coin, &energy))| Participant {
+            price: if p.role == 0 {
+                p.price.saturating_add(concession)
+            } else {
+                p.price.saturating_sub(concession)
+            },
+            quantity: p.quantity.saturating_sub(p.in_energy.abs_diff(energy)),
+            in_coin: coin,
+            in_energy: energy,
+            ..(*p).clone()
+        })
+        .collect();
+    let secondary_input = AuctionInput {
+        participants: remainders,
+        config: config.clone(),
+        rounds: Vec::new(),
+        commitments: Vec::new(),
+        output_salt: [0; 32],
+    };
+    let secondary = run_double_auction(&secondary_input)?;
+
+    let mut outputs: Vec<(u32, u64, u64)> = protocol_order(&secondary_input.participants, config)
+        .iter()
+        .zip(secondary.out_coin.iter().zip(secondary.out_energy.iter()))
+        .map(|(p, (&coin, &energy))| (p.id, coin, energy))
+        .collect();
+    let summary = SecondaryJournal {
+        primary_price: primary.clearing_price,
+        secondary_price: secondary.clearing_price,
+        secondary_volume: secondary.traded_volume,
+    };
+
+    // The combined journal keeps the primary price and counts both passes' volume
+    let volume = checked(primary.traded_volume.checked_add(secondary.traded_volume))?;
+    let (buyers, sellers) = separate_and_sort(&input.participants, config);
+
+    // Both passes' allocations, as energy moved (no losses or prosumers here)
+    let allocations: Vec<(OrderKey, u64)> = input
+        .participants
+        .iter()
+        .filter_map(|p| {
+            let (_, _, energy) = outputs.iter().find(|(id, _, _)| *id == p.id)?;
+            Some((order_key(p), p.in_energy.abs_diff(*energy)))
+        })
+        .collect();
+    let counts = fill_counts(
+        (&buyers, &sellers),
+        &input.participants,
+        &allocations,
+        |p| p.quantity,
+    );
+    let journal = PublicJournal {
+        fees_collected: checked(primary.fees_collected.checked_add(secondary.fees_collected))?,
+        surplus: checked(primary.surplus.checked_add(secondary.surplus))?,
+        fill_counts: counts,
+        allocation: allocation_column(&input.participants, (&buyers, &sellers), &allocations),
+        ..build_journal_with_outputs(
+            &input.participants,
+            &buyers,
+            &sellers,
+            &mut outputs,
+            (primary.clearing_price, volume),
+        )
+    };
+    Ok((journal, summary))
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// MULTI-COMMODITY AUCTION: Shared Coin, Independent Markets
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled when participants carry more than one commodity tag. One id may
+// appear once per commodity: its rows share one coin account (opened with
+// the first row's in_coin) while in_energy is that commodity's balance.
+// Markets clear one after another in ascending commodity id, each with the
+// coin left by the previous ones, so a budget constrains total spend.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Clear each commodity in ascending id order with a shared coin account
+///
+/// Returns: CommodityJournal with one PublicJournal per commodity
+fn run_commodities(input: &AuctionInput) -> Result<CommodityJournal, AuctionError> {
+    let mut commodities: Vec<u32> = input.participants.iter().map(|p| p.commodity).collect();
+    commodities.sort();
+    commodities.dedup();
+
+    let mut coin: Vec<(u32, u64)> = Vec::new();
+    for p in &input.participants {
+        if !coin.iter().any(|(id, _)| *id == p.id) {
+            coin.push((p.id, p.in_coin));
+        }
+    }
+
+    let mut counts = Vec::new();
+    let mut markets = Vec::new();
+    for &commodity in &commodities {
+        let participants: Vec<Participant> = input
+            .participants
+            .iter()
+            .filter(|p| p.commodity == commodity)
+            .map(|p| {
+                let (_, account) = coin
+                    .iter()
+                    .find(|(id, _)| *id == p.id)
+                    .ok_or(AuctionError::Internal(p.id))?;
+                Ok(Participant {
+                    in_coin: *account,
+                    ..p.clone()
+                })
+            })
+            .collect::<Result<_, AuctionError>>()?;
+        let market_input = AuctionInput {
+            participants,
+            config: input.config.clone(),
+            rounds: Vec::new(),
+            commitments: Vec::new(),
+            output_salt: [0; 32],
+        };
+        let journal = clear_book(&market_input)?;
+
+        // The market journal is in protocol order; carry coin to later markets
+        for (p, out_coin) in protocol_order(&market_input.participants, &input.config)
+            .iter()
+            .zip(&journal.out_coin)
+        {
+            if let Some(entry) = coin.iter_mut().find(|(id, _)| *id == p.id) {
+                entry.1 = *out_coin;
+            }
+        }
+
+        counts.push(journal.in_coin.len() as u32);
+        markets.push(journal);
+    }
+
+    Ok(CommodityJournal {
+        version: COMMODITY_JOURNAL_VERSION,
+        commodities,
+        counts,
+        markets,
+    })
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// ZONAL AUCTION: Inter-Zone Transfer Capacity
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled when participants carry more than one zone tag. Every zone first
+// clears on its own (UniformPrice, Uniform settlement). Each transfer link
+// (from, to, capacity) is then applied in config order: the supply the
+// `from` zone left unsold is offered to the `to` zone's book, cheapest ask
+// first and at most `capacity` units, and the `to` zone re-clears with the
+// imported supply. Imports that clear are sold at the importing zone's
+// price, so prices can differ per zone; an exporter keeps only its current
+// allocation in its home zone. Each zone is budget balanced and the energy
+// moved between zones nets to zero globally.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Order book of one zone and its latest clearing
+struct ZoneMarket {
+    zone: u32,
+    orders: Vec<Participant>, // Home orders followed by imports (tagged with their home zone)
+    clearing: Option<(u64, Vec<(OrderKey, u64)>)>,
+}
+
+/// Clear a zone's book at a uniform price
+fn clear_zone(
+    orders: &[Participant],
+    config: &AuctionConfig,
+) -> Option<(u64, Vec<(OrderKey, u64)>)> {
+    let (buyers, sellers) = separate_and_sort(orders, config);
+    find_clearing_price(&buyers, &sellers, config)
+}
+
+/// Clear each zone, couple them along the transfer links and settle globally
+///
+/// Returns: ZonalJournal with per-zone prices, per-link flows and one journal
+fn run_zones(input: &AuctionInput) -> Result<ZonalJournal, AuctionError> {
+    let config = &input.config;
+    let mut zones: Vec<u32> = input.participants.iter().map(|p| p.zone).collect();
+    zones.sort();
+    zones.dedup();
+
+    // Independent clearing (the operator is a settlement account, not an order)
+    let orders = conform_to_grid(&expand_orders(&input.participants, config), config);
+    let mut markets: Vec<ZoneMarket> = zones
+        .iter()
+        .map(|&zone| {
+            let orders: Vec<Participant> = orders
+                .iter()
+                .filter(|p| p.zone == zone && Some(p.id) != config.operator_id)
+                .cloned()
+                .collect();
+            let clearing = clear_zone(&orders, config);
+            ZoneMarket {
+                zone,
+                orders,
+                clearing,
+            }
+        })
+        .collect();
+
+    for &(from, to, capacity) in &config.transfer_capacities {
+        let (Some(src), Some(dst)) = (
+            markets.iter().position(|m| m.zone == from),
+            markets.iter().position(|m| m.zone == to),
+        ) else {
+            continue;
+        };
+
+        // Unsold home supply, cheapest ask first, up to the link capacity
+        let exports: Vec<(OrderKey, u64, u64)> = {
+            let market = &markets[src];
+            let (_, sellers) = separate_and_sort(&market.orders, config);
+            let mut remaining = capacity;
+            sellers
+                .iter()
+                .filter(|s| s.zone == from)
+                .filter_map(|s| {
+                    let (price, allocated) = match &market.clearing {
+                        Some((price, allocations)) => (*price, allocation_of(allocations, s)),
+                        None => (s.price, 0),
+                    };
+                    let unsold = effective_cap(s, price, config).saturating_sub(allocated);
+                    let amount = unsold.min(remaining);
+                    remaining -= amount;
+                    (amount > 0).then_some((order_key(s), amount, allocated))
+                })
+                .collect()
+        };
+        if exports.is_empty() {
+            continue;
+        }
+
+        // Exporters keep their home allocation; the rest is bid into `to`
+        let mut imports = Vec::new();
+        for (key, amount, allocated) in exports {
+            if let Some(order) = markets[src].orders.iter_mut().find(|o| order_key(o) == key) {
+                imports.push(Participant {
+                    quantity: amount,
+                    ..order.clone()
+                });
+                order.quantity = allocated;
+            }
+        }
+        let market = &mut markets[dst];
+        market.orders.extend(imports);
+        market.clearing = clear_zone(&market.orders, config);
+    }
+
+    // Net every zone's settlement per participant
+    let mut outputs: Vec<(u32, u64, u64)> = input
+        .participants
+        .iter()
+        .map(|p| (p.id, p.in_coin, p.in_energy))
+        .collect();
+    let mut fees_collected = 0u64;
+    let mut surplus = 0u64;
+    let mut settled: Vec<(OrderKey, u64)> = Vec::new(); // Every settled zone's allocations
+    for market in &markets {
+        let Some((price, allocations)) = &market.clearing else {
+            continue;
+        };
+        let accounts = settlement_accounts(&market.orders);
+        let Some((rows, fees)) = compute_outputs(&accounts, allocations, (*price, *price), config)?
+        else {
+            continue;
+        };
+        let (buyers, sellers) = separate_and_sort(&market.orders, config);
+        let zone_surplus = checked(matched_surplus(&buyers, &sellers, allocations))?;
+        fees_collected = checked(fees_collected.checked_add(fees))?;
+        surplus = checked(surplus.checked_add(zone_surplus))?;
+        for (key, amount) in allocations {
+            match settled
+                .iter_mut()
+                .find(|(settled_key, _)| settled_key == key)
+            {
+                Some((_, total)) => *total = checked(total.checked_add(*amount))?,
+                None => settled.push((*key, *amount)),
+            }
+        }
+        for (id, coin, energy) in rows {
+            if let Some(account) = accounts.iter().find(|a| a.id == id) {
+                checked(merge_output(&mut outputs, account, (coin, energy)))?;
+            }
+        }
+    }
+
+    let flows = config
+        .transfer_capacities
+        .iter()
+        .map(|&(from, to, _)| {
+            markets
+                .iter()
+                .filter(|m| m.zone == to)
+                .filter_map(|m| m.clearing.as_ref())
+                .flat_map(|(_, allocations)| allocations)
+                .filter(|(key, _)| {
+                    input
+                        .participants
+                        .iter()
+                        .any(|p| p.id == key.0 && p.zone == from)
+                })
+                .map(|(_, amount)| amount)
+                .sum()
+        })
+        .collect();
+
+    // Prices differ per zone; the journal commits the volume over every zone
+    let volume = markets
+        .iter()
+        .filter_map(|m| m.clearing.as_ref())
+        .try_fold(0u64, |total, (_, allocations)| {
+            total.checked_add(allocated_volume(allocations))
+        });
+    let clearing = (0, checked(volume)?);
+
+    // An exporter's allocations in both zones count against its home order
+    let home_price = |zone: u32| {
+        markets
+            .iter()
+            .find(|m| m.zone == zone)
+            .and_then(|m| m.clearing.as_ref())
+            .map_or(0, |(price, _)| *price)
+    };
+    let wanted = |p: &Participant| order_quantity(p, home_price(p.zone), config);
+    let (buyers, sellers) = separate_and_sort(&input.participants, config);
+    let counts = fill_counts((&buyers, &sellers), &orders, &settled, wanted);
+    Ok(ZonalJournal {
+        version: ZONAL_JOURNAL_VERSION,
+        zones,
+        prices: markets
+            .iter()
+            .map(|m| m.clearing.as_ref().map_or(0, |(price, _)| *price))
+            .collect(),
+        flows,
+        journal: PublicJournal {
+            fees_collected,
+            surplus,
+            fill_counts: counts,
+            allocation: allocation_column(&input.participants, (&buyers, &sellers), &settled),
+            ..build_journal_with_outputs(
+                &input.participants,
+                &buyers,
+                &sellers,
+                &mut outputs,
+                clearing,
+            )
+        },
+    })
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// COMPACT JOURNAL: Merkle Root of the Output Rows
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled by JournalVariant::Compact (single book only). The book clears
+// and passes check_journal as usual; the guest then commits a CompactJournal,
+// tagged COMPACT_JOURNAL_VERSION, whose outputs_root is a binary Merkle tree
+// over the rows, and writes the full PublicJournal to stdout, outside the
+// proof. The host rebuilds the
+// tree from those rows, refuses them unless they hash to the committed
+// root, and saves every leaf with its authentication path to outputs.json.
+//   • Leaf: SHA-256(0x00 || id || out_coin || out_energy), little-endian
+//   • Inner node: SHA-256(0x01 || left || right)
+//   • A node without a sibling moves up a level unchanged
+// The 0x00 / 0x01 prefixes keep a leaf from passing for an inner node.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Commit a checked journal's rows as one Merkle root, with counts, totals and the clearing
+fn compact_journal(journal: &PublicJournal) -> CompactJournal {
+    CompactJournal {
+        version: COMPACT_JOURNAL_VERSION,
+        input_digest: journal.input_digest,
+        algorithm_id: journal.algorithm_id,
+        config_digest: journal.config_digest,
+        outputs_root: outputs_root(journal),
+        row_count: journal.ids.len() as u32, // At most MAX_PARTICIPANTS
+        buyer_count: journal.buyer_count,
+        seller_count: journal.seller_count,
+        total_coin: journal.out_coin.iter().map(|&coin| coin as u128).sum(),
+        total_energy: journal
+            .out_energy
+            .iter()
+            .map(|&energy| energy as u128)
+            .sum(),
+        status: journal.status,
+        clearing_price: journal.clearing_price,
+        traded_volume: journal.traded_volume,
+        fees_collected: journal.fees_collected,
+        surplus: journal.surplus,
+        fill_counts: journal.fill_counts,
+        round_id: journal.round_id,
+    }
+}
+
+/// Merkle root over the journal's rows, one leaf per row in protocol order
+///
+/// The leaves and nodes are hashed by the types crate (output_leaf and
+/// merkle_node), whose merkle_levels builds the host's paths from the same tree.
+fn outputs_root(journal: &PublicJournal) -> Digest {
+    let mut level: Vec<Digest> = (0..journal.ids.len())
+        .map(|row| {
+            output_leaf(
+                journal.ids[row],
+                journal.out_coin[row],
+                journal.out_energy[row],
+            )
+        })
+        .collect();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => merkle_node(left, right),
+                _ => pair[0],
+            })
+            .collect();
+    }
+    level.first().copied().unwrap_or(Digest::ZERO) // No rows: EmptyMarket exits first
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// TRADE LIST: The Bilateral Trades Behind the Rows
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled by config.max_trades (single book, Greedy only). The book clears
+// and passes its checks as usual; a second matching pass then lists every
+// trade, which must reproduce each row's change exactly before the guest
+// commits the list after the journal (see check_trades). The cap bounds
+// the journal: a longer list ends with TooManyTrades.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Clear the book and list its trades, checked against the rows
+///
+/// As in clear_book, a failure that passes through settles the book as no
+/// trade without config.strict, with an empty list.
+fn clear_with_trades(
+    input: &AuctionInput,
+    max_trades: u32,
+) -> Result<(PublicJournal, TradesJournal), AuctionError> {
+    let (participants, config) = (&input.participants, &input.config);
+    let journal = clear_book(input)?;
+    let trades = trade_list(input, &journal);
+    let listed = if trades.len() > max_trades as usize {
+        Err(AuctionError::TooManyTrades(trades.len()))
+    } else {
+        check_trades(&journal, &trades).map(|()| TradesJournal { trades })
+    };
+    match listed {
+        Err(error) if !config.strict && error.passes_through() => {
+            let (buyers, sellers) = separate_and_sort(participants, config);
+            let trades = TradesJournal { trades: Vec::new() };
+            let journal = PublicJournal {
+                status: STATUS_SETTLED_AS_NO_TRADE,
+                ..build_journal(participants, &buyers, &sellers)
+            };
+            Ok((journal, trades))
+        }
+        listed => Ok((journal, listed?)),
+    }
+}
+
+/// Greedy's trades behind a journal, from a second matching pass
+///
+/// Re-derives the book run_double_auction matched (expanded, on the grid,
+/// without the operator and untradable orders). A journal in which no
+/// energy moved has no trades, so the list also agrees with the no-trade
+/// fallbacks; the other algorithms record none.
+fn trade_list(input: &AuctionInput, journal: &PublicJournal) -> Vec<TradeRecord> {
+    let config = &input.config;
+    if config.algorithm != Algorithm::Greedy || journal.out_energy == journal.in_energy {
+        return Vec::new();
+    }
+
+    let orders = conform_to_grid(&expand_orders(&input.participants, config), config);
+    let (mut buyers, mut sellers) = separate_and_sort(&orders, config);
+    buyers.retain(|b| Some(b.id) != config.operator_id && is_tradable(b));
+    sellers.retain(|s| Some(s.id) != config.operator_id && is_tradable(s));
+    run_greedy_matching((&buyers, &sellers))
+        .iter()
+        .map(|trade| TradeRecord {
+            buyer_id: trade.buyer_id,
+            seller_id: trade.seller_id,
+            quantity: trade.qty,
+            price: trade.price,
+        })
+        .collect()
+}
+
+/// The trade list must reproduce every row's change and the traded volume
+///
+/// Each trade moves its quantity from the seller to the buyer and its
+/// settled coin (fee-free, as in fills_from_trades) the other way, so a
+/// row's trades add up to out − in in both columns. MalformedJournal names
+/// the first row that differs, or the row count when every row agrees but
+/// Σ quantity is not traded_volume (a trade between ids the journal does
+/// not list).
+fn check_trades(journal: &PublicJournal, trades: &[TradeRecord]) -> Result<(), AuctionError> {
+    let coin = journal.in_coin.iter().zip(&journal.out_coin);
+    let energy = journal.in_energy.iter().zip(&journal.out_energy);
+    for (row, ((&id, (&in_coin, &out_coin)), (&in_energy, &out_energy))) in
+        journal.ids.iter().zip(coin).zip(energy).enumerate()
+    {
+        let (mut coin_change, mut energy_change) = (0i128, 0i128);
+        for trade in trades {
+            let (paid, _, _) = checked(settle(trade.price as i128, trade.quantity, 0))?;
+            let quantity = trade.quantity as i128;
+            if trade.buyer_id == id {
+                coin_change = checked(coin_change.checked_sub(paid))?;
+                energy_change += quantity;
+            }
+            if trade.seller_id == id {
+                coin_change = checked(coin_change.checked_add(paid))?;
+                energy_change -= quantity;
+            }
+        }
+        if coin_change != out_coin as i128 - in_coin as i128
+            || energy_change != out_energy as i128 - in_energy as i128
+        {
+            return Err(AuctionError::MalformedJournal(row));
+        }
+    }
+    let volume = trades
+        .iter()
+        .try_fold(0u64, |total, trade| total.checked_add(trade.quantity));
+    if volume != Some(journal.traded_volume) {
+        return Err(AuctionError::MalformedJournal(journal.ids.len()));
+    }
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// AUDIT JOURNAL: The Ranked Order Book
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled by config.audit (plain single book only). The book clears and
+// passes its checks as usual; the guest then commits the stated quote of
+// every buyer and seller row in the journal's own order, so an auditor
+// reads the sorted book that was cleared from the receipt:
+//   bid_prices[0] >= bid_prices[1] >= ...   ask_prices[0] <= ask_prices[1] <= ...
+// and rebuilds demand and supply at any price from the proof alone (see
+// check_audit).
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// The quotes of the journal's buyer and seller rows, in protocol order
+fn audit_journal(input: &AuctionInput) -> AuditJournal {
+    let (buyers, sellers) = separate_and_sort(&input.participants, &input.config);
+    let quotes = |side: &[&Participant]| -> (Vec<u64>, Vec<u64>) {
+        side.iter().map(|p| (p.price, p.quantity)).unzip()
+    };
+    let (bid_prices, bid_quantities) = quotes(&buyers);
+    let (ask_prices, ask_quantities) = quotes(&sellers);
+    AuditJournal {
+        bid_prices,
+        bid_quantities,
+        ask_prices,
+        ask_quantities,
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// DELTA JOURNAL: Net Balance Changes
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled by JournalVariant::Delta (single book only). The book clears and
+// passes check_journal as usual; the guest then commits each row's net
+// change out − in for coin and energy instead of the input and output
+// balances. Its version word is DELTA_JOURNAL_VERSION, so the host picks
+// the decoder from the first word, and it rebuilds the absolute outputs
+// from the scenario's input balances.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// The checked journal's rows as net changes, in the same protocol order
+///
+/// Returns Overflow when a change does not fit i64 (a balance moving by
+/// more than i64::MAX, e.g. at a large config.quantity_scale).
+fn delta_journal(journal: &PublicJournal) -> Result<DeltaJournal, AuctionError> {
+    let delta = |ins: &[u64], outs: &[u64]| -> Result<Vec<i64>, AuctionError> {
+        ins.iter()
+            .zip(outs)
+            .map(|(&before, &after)| {
+                i64::try_from(after as i128 - before as i128).map_err(|_| AuctionError::Overflow)
+            })
+            .collect()
+    };
+    Ok(DeltaJournal {
+        version: DELTA_JOURNAL_VERSION,
+        input_digest: journal.input_digest,
+        algorithm_id: journal.algorithm_id,
+        config_digest: journal.config_digest,
+        ids: journal.ids.clone(),
+        buyer_count: journal.buyer_count,
+        seller_count: journal.seller_count,
+        coin_delta: delta(&journal.in_coin, &journal.out_coin)?,
+        energy_delta: delta(&journal.in_energy, &journal.out_energy)?,
+        allocation: journal.allocation.clone(),
+        status: journal.status,
+        clearing_price: journal.clearing_price,
+        traded_volume: journal.traded_volume,
+        fees_collected: journal.fees_collected,
+        surplus: journal.surplus,
+        fill_counts: journal.fill_counts,
+        round_id: journal.round_id,
+    })
+}
+
+/// Both delta columns must sum to zero, one row per id, before commit
+///
+/// Conservation seen from the deltas alone, which is all a verifier of
+/// this variant reads: MalformedJournal names the row count otherwise, as
+/// check_journal does when only the totals differ.
+fn check_deltas(delta: &DeltaJournal) -> Result<(), AuctionError> {
+    let rows = delta.ids.len();
+    if delta.coin_delta.len() != rows || delta.energy_delta.len() != rows {
+        return Err(AuctionError::MalformedJournal(
+            rows.min(delta.coin_delta.len())
+                .min(delta.energy_delta.len()),
+        ));
+    }
+    let total = |column: &[i64]| column.iter().map(|&change| change as i128).sum::<i128>();
+    if total(&delta.coin_delta) != 0 || total(&delta.energy_delta) != 0 {
+        return Err(AuctionError::MalformedJournal(rows));
+    }
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// QUANTIZED JOURNAL: Balances in Units of a Scale
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled by JournalVariant::Quantized (single book only). The book clears
+// exactly and passes check_journal as usual; the guest then commits every
+// balance rounded down to a multiple of the scale, as u32 units:
+//   units = ⌊balance / scale⌋,  units × scale <= balance < (units + 1) × scale
+// so each committed balance is short of the exact one by less than scale.
+// The clearing price, volume, fees and surplus stay exact. Conservation
+// survives only within the rounding: Σ in == Σ out exactly, so per column
+//   |Σ out_units − Σ in_units| < rows
+// (each row's difference of floors is off by less than one unit). The
+// version word is QUANTIZED_JOURNAL_VERSION, which the host decodes by.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// The checked journal's balances in units of scale, rounded down
+///
+/// Returns Overflow when scale is 0 or a balance holds u32::MAX + 1 units or
+/// more: the scale must exceed the largest balance / u32::MAX.
+fn quantized_journal(
+    journal: &PublicJournal,
+    scale: u64,
+) -> Result<QuantizedJournal, AuctionError> {
+    let quantize = |column: &[u64]| -> Result<Vec<u32>, AuctionError> {
+        column
+            .iter()
+            .map(|&balance| {
+                let units = checked(balance.checked_div(scale))?;
+                u32::try_from(units).map_err(|_| AuctionError::Overflow)
+            })
+            .collect()
+    };
+    Ok(QuantizedJournal {
+        version: QUANTIZED_JOURNAL_VERSION,
+        input_digest: journal.input_digest,
+        algorithm_id: journal.algorithm_id,
+        config_digest: journal.config_digest,
+        scale,
+        ids: journal.ids.clone(),
+        buyer_count: journal.buyer_count,
+        seller_count: journal.seller_count,
+        in_coin: quantize(&journal.in_coin)?,
+        in_energy: quantize(&journal.in_energy)?,
+        out_coin: quantize(&journal.out_coin)?,
+        out_energy: quantize(&journal.out_energy)?,
+        status: journal.status,
+        clearing_price: journal.clearing_price,
+        traded_volume: journal.traded_volume,
+        fees_collected: journal.fees_collected,
+        surplus: journal.surplus,
+        fill_counts: journal.fill_counts,
+        round_id: journal.round_id,
+    })
+}
+
+/// Every committed balance must fall short of the exact one by less than the scale
+///
+/// The rounding bound a verifier of this variant relies on, checked against
+/// the exact journal before commit: MalformedJournal names the first row
+/// whose units are missing or out of [balance − scale + 1, balance] / scale.
+fn check_quantized(
+    journal: &PublicJournal,
+    quantized: &QuantizedJournal,
+) -> Result<(), AuctionError> {
+    let scale = quantized.scale as u128;
+    let columns = [
+        (&journal.in_coin, &quantized.in_coin),
+        (&journal.in_energy, &quantized.in_energy),
+        (&journal.out_coin, &quantized.out_coin),
+        (&journal.out_energy, &quantized.out_energy),
+    ];
+    for row in 0..journal.ids.len() {
+        let within =
+            |(exact, units): &(&Vec<u64>, &Vec<u32>)| match (exact.get(row), units.get(row)) {
+                (Some(&balance), Some(&units)) => {
+                    let floor = units as u128 * scale;
+                    floor <= balance as u128 && (balance as u128) < floor + scale
+                }
+                _ => false,
+            };
+        if quantized.ids.get(row) != journal.ids.get(row) || !columns.iter().all(within) {
+            return Err(AuctionError::MalformedJournal(row));
+        }
+    }
+    if quantized.ids.len() != journal.ids.len()
+        || columns
+            .iter()
+            .any(|(_, units)| units.len() != journal.ids.len())
+    {
+        return Err(AuctionError::MalformedJournal(journal.ids.len()));
+    }
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// SLIM JOURNAL: The Rows Without Their Inputs
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled by JournalVariant::Slim (single book only). The book clears and
+// passes check_journal as usual; the guest then commits the journal
+// without in_coin and in_energy, four words per row fewer. A verifier that
+// wrote the input holds those balances already, and input_digest binds
+// them: it re-derives each row's inputs from its own input by id, after
+// checking that the input hashes to the committed digest. The version word
+// is SLIM_JOURNAL_VERSION, which the host decodes by.
+//
+// ═══════════════════════════════════════════════════════════════════════════
 
-/// Participant in the auction (received from host)
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Participant {
-    pub id: u32,        // Unique ID (0..N-1)
-    pub role: u32,      // 0=BUY, 1=SELL
-    pub price: u64,     // Bid (buyers) or Ask (sellers)
-    pub quantity: u64,  // Desired trade amount
-    pub in_coin: u64,   // Initial coin balance
-    pub in_energy: u64, // Initial energy balance
+/// The checked journal without its input balance columns
+fn slim_journal(journal: &PublicJournal) -> SlimJournal {
+    SlimJournal {
+        version: SLIM_JOURNAL_VERSION,
+        input_digest: journal.input_digest,
+        algorithm_id: journal.algorithm_id,
+        config_digest: journal.config_digest,
+        ids: journal.ids.clone(),
+        buyer_count: journal.buyer_count,
+        seller_count: journal.seller_count,
+        out_coin: journal.out_coin.clone(),
+        out_energy: journal.out_energy.clone(),
+        allocation: journal.allocation.clone(),
+        status: journal.status,
+        clearing_price: journal.clearing_price,
+        traded_volume: journal.traded_volume,
+        fees_collected: journal.fees_collected,
+        surplus: journal.surplus,
+        fill_counts: journal.fill_counts,
+        round_id: journal.round_id,
+    }
 }
 
-/// Input to the auction algorithm
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct AuctionInput {
-    pub participants: Vec<Participant>,
-}
+// ═══════════════════════════════════════════════════════════════════════════
+// PRIVATE OUTPUTS: Aggregates and a Salted Digest of the Rows
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled by JournalVariant::Private (single book only). The book clears and
+// passes check_journal as usual, conservation included; the guest then
+// commits a PrivateJournal with the clearing, the block counts, the coin
+// turnover and outputs_digest, and writes the full PublicJournal to stdout,
+// outside the proof. The digest covers every row in protocol order:
+//   SHA-256(output_salt || id || in_coin || in_energy || out_coin || out_energy ...)
+// little-endian, 36 bytes per row. The secret 32-byte input.output_salt
+// keeps small balances from being recovered by trying every candidate row
+// set against the public digest; input_digest covers it only as a hash.
+//
+// ═══════════════════════════════════════════════════════════════════════════
 
-/// Output journal committed to zkVM receipt
-/// CRITICAL: Arrays must be sorted [buyers DESC by price, sellers ASC by price]
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct PublicJournal {
-    pub in_coin: Vec<u64>,      // Input balances (protocol order)
-    pub in_energy: Vec<u64>,    // Input balances (protocol order)
-    pub out_coin: Vec<u64>,     // Output balances (YOUR ALGORITHM)
-    pub out_energy: Vec<u64>,   // Output balances (YOUR ALGORITHM)
+/// Commit a checked journal's aggregates, its rows only as a salted digest
+fn private_journal(journal: &PublicJournal, salt: &[u8; 32]) -> PrivateJournal {
+    let turnover = journal
+        .in_coin
+        .iter()
+        .zip(&journal.out_coin)
+        .map(|(&before, &after)| before.saturating_sub(after) as u128)
+        .sum();
+    PrivateJournal {
+        version: PRIVATE_JOURNAL_VERSION,
+        input_digest: journal.input_digest,
+        algorithm_id: journal.algorithm_id,
+        config_digest: journal.config_digest,
+        outputs_digest: private_outputs_digest(salt, &private_rows(journal)),
+        row_count: journal.ids.len() as u32, // At most MAX_PARTICIPANTS
+        buyer_count: journal.buyer_count,
+        seller_count: journal.seller_count,
+        turnover,
+        status: journal.status,
+        clearing_price: journal.clearing_price,
+        traded_volume: journal.traded_volume,
+        fees_collected: journal.fees_collected,
+        surplus: journal.surplus,
+        fill_counts: journal.fill_counts,
+        round_id: journal.round_id,
+    }
 }
 
 // ═══════════════════════════════════════════════════════════════════════════
-// ENTRY POINT (DO NOT MODIFY)
+// SEALED BIDS: Commitments Opened Inside the Guest
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// Enabled by a non-empty input.commitments (single book only). Before the
+// auction each participant publishes SHA-256(salt || id || role || price ||
+// quantity), little-endian, with a secret 32-byte salt; the input then
+// carries every bid with its salt and the published list in ascending id.
+// validate_input opens each commitment with the SHA accelerator before any
+// clearing, and the guest commits the list (not the bids) after the
+// journal, so the receipt binds the result to the sealed bids. Only the
+// price and quantity are sealed; the other order fields are bound by
+// input_digest alone.
+//
 // ═══════════════════════════════════════════════════════════════════════════
 
-fn main() {
-    let auction_input: AuctionInput = env::read();
-    let journal = run_double_auction(&auction_input);
-    env::commit(&journal);
+/// Every participant's bid must open its commitment, listed in ascending id
+///
+/// CommitmentMismatch names the first position that fails, or the shorter
+/// length when the list and the participants differ in count, so the error
+/// does not depend on the order of the rows.
+fn check_commitments(input: &AuctionInput) -> Result<(), AuctionError> {
+    if input.commitments.is_empty() {
+        return Ok(());
+    }
+    let mut participants: Vec<&Participant> = input.participants.iter().collect();
+    participants.sort_by_key(|p| (p.id, p.commodity));
+    if let Some(position) = participants
+        .iter()
+        .zip(&input.commitments)
+        .position(|(p, commitment)| bid_commitment(p) != *commitment)
+    {
+        return Err(AuctionError::CommitmentMismatch(position));
+    }
+    if participants.len() != input.commitments.len() {
+        return Err(AuctionError::CommitmentMismatch(
+            participants.len().min(input.commitments.len()),
+        ));
+    }
+    Ok(())
 }
 
 // ═══════════════════════════════════════════════════════════════════════════
-// MODULAR ALGORITHM INTERFACE
+// ABI JOURNAL: Fixed-Width Bytes for Solidity Verifiers
 // ═══════════════════════════════════════════════════════════════════════════
 //
-// DEVELOPER CUSTOMIZATION POINT:
-//   Replace the function body below with your custom auction algorithm.
-//   Keep the function signature unchanged.
-//
-// INPUT:
-//   • participants: Vec<Participant> with id, role, price, quantity, balances
+// Enabled by JournalVariant::Abi (single book only). The book clears and
+// passes check_journal as usual; the guest then commits the journal as raw
+// bytes, exactly abi.encodePacked of the fields in PublicJournal order, so
+// a settlement contract reads them at fixed offsets with no serde decoder:
+//   • uint32, big-endian, 4 bytes: version (ABI_JOURNAL_VERSION)
+//   • bytes32: input_digest, then uint32 algorithm_id, bytes32 config_digest
+//   • uint32 each: row_count, buyer_count, seller_count
+//   • Arrays of row_count elements, 32 bytes each (encodePacked pads array
+//     elements), big-endian: uint32[] ids, then uint256[] in_coin,
+//     in_energy, out_coin, out_energy and allocation
+//   • uint32 status, then uint256 clearing_price, traded_volume,
+//     fees_collected and surplus
+//   • uint32 each: the six fill_counts, buyers then sellers, each side
+//     filled, partial, unfilled
+//   • uint256 round_id
+// Packed arrays carry no length word, hence row_count before them. A book
+// of n rows commits 272 + 192·n bytes. The types crate packs them
+// (abi_journal_bytes), so the host repacks a decoded journal identically.
 //
-// OUTPUT:
-//   • PublicJournal with computed allocations
-//   • MUST preserve protocol ordering (buyers DESC, sellers ASC by price)
-//   • MUST satisfy conservation: Σ in == Σ out (both coin and energy)
+// ═══════════════════════════════════════════════════════════════════════════
+
+// ═══════════════════════════════════════════════════════════════════════════
+// KECCAK JOURNAL DIGEST: One Word for EVM Verifiers
+// ═══════════════════════════════════════════════════════════════════════════
 //
-// CURRENT IMPLEMENTATION:
-//   Uniform-price double auction with marginal pricing
+// Enabled by config.keccak_digest (the plain single-book journal, or with
+// JournalVariant::Abi). The receipt's own journal digest is SHA-256, which costs an
+// EVM contract far more than keccak256, so the guest also commits
+// keccak256 of the checked journal's abi.encodePacked bytes (the layout
+// above, whether or not those bytes are committed) as the journal's last
+// 32 bytes: a contract that holds the fields packs them, hashes them with
+// the keccak256 opcode and compares one word. With the ABI journal the digest
+// follows the packed bytes raw; otherwise it is a Digest after the market
+// status. The permutation is keccak-f[1600] in software (the keccak
+// crate): the zkVM's keccak accelerator is reachable only through its
+// unstable keccak proving path, which this guest does not enable.
 //
 // ═══════════════════════════════════════════════════════════════════════════
 
-fn run_double_auction(input: &AuctionInput) -> PublicJournal {
-    let participants = &input.participants;
+/// keccak256 of a checked journal's abi.encodePacked bytes, as a Digest
+fn journal_keccak(journal: &PublicJournal) -> Digest {
+    Digest::from_bytes(keccak256(&abi_journal_bytes(journal)))
+}
 
-    // Separate and sort participants (protocol requirement)
+// ═══════════════════════════════════════════════════════════════════════════
+// PROTOCOL INFRASTRUCTURE (DO NOT MODIFY)
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Build the capacity section in protocol order (bundle mode)
+fn build_capacity_journal(
+    buyers_sorted: &[&Participant],
+    sellers_sorted: &[&Participant],
+    capacity_outputs: &[(u32, u64)],
+) -> CapacityJournal {
+    let mut in_capacity = Vec::new();
+    let mut out_capacity = Vec::new();
+
+    for p in buyers_sorted.iter().chain(sellers_sorted.iter()) {
+        in_capacity.push(p.in_capacity);
+        let out = capacity_outputs
+            .iter()
+            .find(|(id, _)| *id == p.id)
+            .map_or(p.in_capacity, |(_, capacity)| *capacity);
+        out_capacity.push(out);
+    }
+
+    CapacityJournal {
+        in_capacity,
+        out_capacity,
+    }
+}
+
+/// Build the clearing summary of a single uniform-price auction
+///
+/// Re-derives the book and crossing of run_double_auction (a second
+/// clearing pass). A journal in which no energy moved reports no trade, so
+/// the summary also agrees with the no-trade fallbacks.
+fn build_objective_journal(input: &AuctionInput, journal: &PublicJournal) -> ObjectiveJournal {
+    let config = &input.config;
+    let mut summary = ObjectiveJournal {
+        objective: config.objective,
+        clearing_price: 0,
+        volume: 0,
+        surplus: 0,
+    };
+    if journal.out_energy == journal.in_energy {
+        return summary;
+    }
+
+    let orders = conform_to_grid(&expand_orders(&input.participants, config), config);
+    let (mut buyers, mut sellers) = separate_and_sort(&orders, config);
+    buyers.retain(|b| Some(b.id) != config.operator_id && is_tradable(b));
+    sellers.retain(|s| Some(s.id) != config.operator_id && is_tradable(s));
+    if let Some((price, mut allocations)) = find_clearing_price(&buyers, &sellers, config) {
+        net_owner_trades(&buyers, &sellers, &mut allocations);
+        summary.clearing_price = price;
+        summary.volume = traded_volume(&buyers, &allocations);
+        summary.surplus = realized_surplus(&buyers, &sellers, &allocations)
+            .map_or(u128::MAX, |surplus| surplus as u128);
+    }
+    summary
+}
+
+/// Separate buyers and sellers in protocol order
+///
+/// Sort: buyers DESC by price, sellers ASC by price (ties per tie_break)
+fn separate_and_sort<'a>(
+    participants: &'a [Participant],
+    config: &AuctionConfig,
+) -> (Vec<&'a Participant>, Vec<&'a Participant>) {
     let mut buyers: Vec<&Participant> = participants
         .iter()
         .filter(|p| p.role == 0)
@@ -102,269 +4292,526 @@ fn run_double_auction(input: &AuctionInput) -> PublicJournal {
         .filter(|p| p.role == 1)
         .collect();
 
-    // Sort: buyers DESC by price, sellers ASC by price (ties broken by ID)
-    buyers.sort_by(|a, b| match b.price.cmp(&a.price) {
-        core::cmp::Ordering::Equal => a.id.cmp(&b.id),
-        other => other,
-    });
-    sellers.sort_by(|a, b| match a.price.cmp(&b.price) {
-        core::cmp::Ordering::Equal => a.id.cmp(&b.id),
-        other => other,
-    });
-
-    // ─────────────────────────────────────────────────────────────────────────
-    // AUCTION ALGORITHM (CUSTOMIZE THIS SECTION)
-    // ─────────────────────────────────────────────────────────────────────────
-
-    let clearing_result = find_clearing_price(&buyers, &sellers);
-
-    let (clearing_price, allocations) = match clearing_result {
-        Some(result) => result,
-        None => return build_journal(participants, &buyers, &sellers), // No trade
-    };
+    // Cached keys: a seeded tie-break hashes each order once, not per comparison
+    buyers.sort_by_cached_key(|p| (core::cmp::Reverse(p.price), tie_key(p, config)));
+    sellers.sort_by_cached_key(|p| (p.price, tie_key(p, config)));
 
-    let mut outputs = compute_outputs(participants, &allocations, clearing_price);
+    (buyers, sellers)
+}
 
-    // ─────────────────────────────────────────────────────────────────────────
-    // END CUSTOMIZABLE SECTION
-    // ─────────────────────────────────────────────────────────────────────────
+/// Same-price rank of an order: (quantity rank, seeded rank, id), ascending
+type TieKey = (u64, [u8; 32], u32);
 
-    // Format journal in protocol order (DO NOT MODIFY)
-    build_journal_with_outputs(participants, &buyers, &sellers, &mut outputs)
+/// Rank key of an order among orders at the same price
+fn tie_key(p: &Participant, config: &AuctionConfig) -> TieKey {
+    match config.tie_break {
+        TieBreak::ById => (0, [0; 32], p.id),
+        TieBreak::ByQuantityDesc => (u64::MAX - p.quantity, [0; 32], p.id),
+        TieBreak::ByQuantityAsc => (p.quantity, [0; 32], p.id),
+        TieBreak::Seeded => (0, seeded_rank(&config.seed, p.id), p.id),
+    }
 }
 
-// ═══════════════════════════════════════════════════════════════════════════
-// REFERENCE IMPLEMENTATION: Uniform-Price Double Auction
-// ═══════════════════════════════════════════════════════════════════════════
-//
-// The functions below implement the default auction mechanism.
-// You may DELETE or REPLACE these when implementing a custom algorithm.
-//
-// ALGORITHM:
-//   1. Find clearing price p* where supply(p) >= demand(p)
-//   2. Set price = (marginal_bid + marginal_ask) / 2
-//   3. Allocate based on effective caps (min of quantity, balance constraint)
-//   4. Pro-rata allocation on binding side
-//
-// ═══════════════════════════════════════════════════════════════════════════
+/// SHA-256(seed || id as little-endian u32), via the zkVM SHA accelerator
+fn seeded_rank(seed: &[u8; 32], id: u32) -> [u8; 32] {
+    let mut bytes = [0u8; 36];
+    bytes[..32].copy_from_slice(seed);
+    bytes[32..].copy_from_slice(&id.to_le_bytes());
+    let mut rank = [0u8; 32];
+    rank.copy_from_slice(Impl::hash_bytes(&bytes).as_bytes());
+    rank
+}
 
-/// Find uniform clearing price using supply-demand crossing
-///
-/// Returns: Option<(clearing_price, Vec<(participant_id, allocation)>)>
-fn find_clearing_price(
+/// Build journal with no trades (fallback for no market clearing)
+fn build_journal(
+    participants: &[Participant],
     buyers: &[&Participant],
     sellers: &[&Participant],
-) -> Option<(u64, Vec<(u32, u64)>)> {
-    if buyers.is_empty() || sellers.is_empty() {
-        return None;
-    }
-
-    // Build price grid from all bids and asks
-    let mut prices: Vec<u64> = buyers.iter().map(|b| b.price).collect();
-    prices.extend(sellers.iter().map(|s| s.price));
-    prices.sort();
-    prices.dedup();
-
-    // Find p* where supply >= demand
-    let mut p_star = None;
-    for &p in &prices {
-        let (demand, supply) = demand_supply_at(buyers, sellers, p);
-        if supply >= demand {
-            p_star = Some(p);
-            break;
-        }
-    }
-    let p_star = p_star?;
-
-    // Identify qualified participants at p*
-    let qualified_buyers: Vec<&Participant> = buyers
-        .iter()
-        .copied()
-        .filter(|b| b.price >= p_star)
-        .collect();
-    let qualified_sellers: Vec<&Participant> = sellers
+) -> PublicJournal {
+    let mut outputs_vec: Vec<(u32, u64, u64)> = participants
         .iter()
-        .copied()
-        .filter(|s| s.price <= p_star)
+        .map(|p| (p.id, p.in_coin, p.in_energy))
         .collect();
 
-    if qualified_buyers.is_empty() || qualified_sellers.is_empty() {
-        return None;
-    }
-
-    // Marginal pricing: average of lowest buyer and highest seller
-    let b_marg = qualified_buyers.last().unwrap().price;
-    let a_marg = qualified_sellers.last().unwrap().price;
-    let clearing_price = (b_marg + a_marg) / 2;
+    build_journal_with_outputs(participants, buyers, sellers, &mut outputs_vec, NO_TRADE)
+}
 
-    if clearing_price == 0 {
-        return None;
-    }
+/// Prosumers (role 2) in protocol order: ascending by ID, after the sellers
+fn sorted_prosumers(participants: &[Participant]) -> Vec<&Participant> {
+    let mut prosumers: Vec<&Participant> = participants.iter().filter(|p| p.role == 2).collect();
+    prosumers.sort_by_key(|p| p.id);
+    prosumers
+}
 
-    // Compute effective caps (budget and inventory constraints)
-    let mut buyer_caps: Vec<(u32, u64)> = Vec::new();
-    let mut seller_caps: Vec<(u32, u64)> = Vec::new();
+/// All journal rows in protocol order: buyers, sellers, then prosumers
+fn protocol_order<'a>(
+    participants: &'a [Participant],
+    config: &AuctionConfig,
+) -> Vec<&'a Participant> {
+    let (buyers, sellers) = separate_and_sort(participants, config);
+    let mut rows = buyers;
+    rows.extend(sellers);
+    rows.extend(sorted_prosumers(participants));
+    rows
+}
 
-    let mut eff_demand = 0u64;
-    for buyer in &qualified_buyers {
-        let afford = buyer.in_coin / clearing_price;
-        let cap = buyer.quantity.min(afford);
-        buyer_caps.push((buyer.id, cap));
-        eff_demand += cap;
+/// Structural checks on a journal before it is committed
+///
+/// Circuits and verifiers index the five vectors positionally, so each must
+/// hold exactly one row per buyer, seller and prosumer of the book, every
+/// row must carry its participant's id and input balances in protocol
+/// order, and buyer prices must not rise (seller prices not fall) along the
+/// rows, whose buyer and seller blocks the two counts must delimit. The
+/// columns must also conserve coin and energy (Σ in == Σ out), which every
+/// settlement path guarantees through `settle` and the settlement account.
+/// A journal that fails is never committed: the guest exits with
+/// MalformedJournal naming the first bad row, or the row count when only
+/// the totals differ. A journal of another layout version fails at row 0.
+/// Its fees_collected must then pass `check_fees` and its allocation
+/// `check_allocation`.
+fn check_journal(
+    journal: &PublicJournal,
+    participants: &[Participant],
+    config: &AuctionConfig,
+) -> Result<(), AuctionError> {
+    if journal.version != JOURNAL_VERSION {
+        return Err(AuctionError::MalformedJournal(0));
     }
-
-    let mut eff_supply = 0u64;
-    for seller in &qualified_sellers {
-        let cap = seller.quantity.min(seller.in_energy);
-        seller_caps.push((seller.id, cap));
-        eff_supply += cap;
+    let rows = protocol_order(participants, config);
+    let lengths = [
+        journal.ids.len(),
+        journal.in_coin.len(),
+        journal.in_energy.len(),
+        journal.out_coin.len(),
+        journal.out_energy.len(),
+        journal.allocation.len(),
+    ];
+    if let Some(length) = lengths.into_iter().find(|&length| length != rows.len()) {
+        return Err(AuctionError::MalformedJournal(length.min(rows.len())));
     }
-
-    let traded_total = eff_demand.min(eff_supply);
-    if traded_total == 0 {
-        return Some((clearing_price, Vec::new()));
+    let misplaced = rows.iter().enumerate().position(|(row, p)| {
+        journal.ids[row] != p.id
+            || journal.in_coin[row] != p.in_coin
+            || journal.in_energy[row] != p.in_energy
+    });
+    let buyers = rows.iter().take_while(|p| p.role == 0).count();
+    let sellers = rows[buyers..].iter().take_while(|p| p.role == 1).count();
+    let unordered = rows[..buyers]
+        .windows(2)
+        .position(|pair| pair[0].price < pair[1].price)
+        .or_else(|| {
+            rows[buyers..buyers + sellers]
+                .windows(2)
+                .position(|pair| pair[0].price > pair[1].price)
+                .map(|row| buyers + row)
+        })
+        .map(|row| row + 1);
+    if let Some(row) = misplaced.into_iter().chain(unordered).min() {
+        return Err(AuctionError::MalformedJournal(row));
     }
+    if journal.buyer_count as usize != buyers {
+        return Err(AuctionError::MalformedJournal(
+            buyers.min(journal.buyer_count as usize),
+        ));
+    }
+    if journal.seller_count as usize != sellers {
+        return Err(AuctionError::MalformedJournal(
+            buyers + sellers.min(journal.seller_count as usize),
+        ));
+    }
+    let total = |column: &[u64]| column.iter().map(|&v| v as u128).sum::<u128>();
+    let fills = &journal.fill_counts;
+    let classified = [
+        [
+            fills.buyers_filled,
+            fills.buyers_partial,
+            fills.buyers_unfilled,
+        ],
+        [
+            fills.sellers_filled,
+            fills.sellers_partial,
+            fills.sellers_unfilled,
+        ],
+    ]
+    .map(|side| side.iter().map(|&count| count as usize).sum::<usize>());
+    let nothing_filled =
+        fills.buyers_unfilled as usize == buyers && fills.sellers_unfilled as usize == sellers;
+    if total(&journal.in_coin) != total(&journal.out_coin)
+        || total(&journal.in_energy) != total(&journal.out_energy)
+        || journal.status > STATUS_SETTLED_AS_NO_TRADE
+        || (journal.status == STATUS_CLEARED) != (journal.traded_volume > 0)
+        || classified != [buyers, sellers]
+        || (journal.traded_volume == 0 && !nothing_filled)
+    {
+        return Err(AuctionError::MalformedJournal(rows.len()));
+    }
+    check_fees(journal, config)?;
+    check_allocation(journal, &rows, participants, config)
+}
 
-    // Allocate based on binding constraint
-    let mut allocations: Vec<(u32, u64)> = Vec::new();
-
-    if eff_demand >= eff_supply {
-        // Supply-constrained: fill all sellers, allocate buyers by priority
-        for (id, cap) in seller_caps {
-            allocations.push((id, cap));
-        }
+/// The committed fees against the operator's coin, after check_journal's row checks
+///
+/// The operator is kept out of the book, so it never trades on its own
+/// behalf and its whole coin gain is settlement flow. When config.fee_bps
+/// is the only flow (uniform settlement, no McAfee trade reduction,
+/// congestion rent or curtailment pool) the gain must equal fees_collected
+/// exactly, floors and all; otherwise the spread lands on the operator too
+/// and the gain must cover it. Without fees, or under Vcg, which charges
+/// none, fees_collected must be 0. MalformedJournal names the operator's
+/// row, or the row count when the journal has none.
+fn check_fees(journal: &PublicJournal, config: &AuctionConfig) -> Result<(), AuctionError> {
+    let row = journal
+        .ids
+        .iter()
+        .position(|&id| Some(id) == config.operator_id);
+    let malformed = Err(AuctionError::MalformedJournal(
+        row.unwrap_or(journal.ids.len()),
+    ));
+    let fees = journal.fees_collected as i128;
+    if config.fee_bps == 0 || config.settlement == Settlement::Vcg {
+        return if fees == 0 { Ok(()) } else { malformed };
+    }
+    let gain = row.map_or(0, |row| {
+        journal.out_coin[row] as i128 - journal.in_coin[row] as i128
+    });
+    let fees_only = config.settlement == Settlement::Uniform
+        && config.algorithm != Algorithm::McAfee
+        && !config.congestion_rent
+        && config.funding_fee_bps == 0
+        && config.curtailment_comp_per_unit == 0;
+    if gain < fees || (fees_only && gain != fees) {
+        return malformed;
+    }
+    Ok(())
+}
 
-        let mut remaining = traded_total;
-        for buyer in &qualified_buyers {
-            if remaining == 0 {
-                break;
-            }
-            let cap = buyer_caps
-                .iter()
-                .find(|(id, _)| *id == buyer.id)
-                .map(|(_, cap)| *cap)
-                .unwrap_or(0);
-            let take = cap.min(remaining);
-            if take > 0 {
-                allocations.push((buyer.id, take));
-                remaining -= take;
-            }
-        }
-    } else {
-        // Demand-constrained: fill all buyers, allocate sellers by priority
-        for (id, cap) in buyer_caps {
-            allocations.push((id, cap));
+/// The committed allocation against the balance deltas, after check_journal's row checks
+///
+/// Every seller row must deliver exactly its allocation and every buyer row
+/// receive it net of config.loss_bps losses (see `delivered_energy`); a
+/// prosumer's net change stays within its two sides. Where the pricing rule
+/// fixes the coin of every unit (see `coin_follows_allocation`) each row's
+/// coin must also move by its allocation settled at its price: the clearing
+/// price under uniform settlement, the row's own quote under pay-as-bid,
+/// fees included. The operator and the loss sink are settlement accounts
+/// and allocated nothing. MalformedJournal names the first row that
+/// disagrees.
+fn check_allocation(
+    journal: &PublicJournal,
+    rows: &[&Participant],
+    participants: &[Participant],
+    config: &AuctionConfig,
+) -> Result<(), AuctionError> {
+    let coin_rule = coin_follows_allocation(participants, config);
+    let disagrees = |(row, p): (usize, &&Participant)| {
+        let allocated = journal.allocation[row];
+        if Some(p.id) == config.operator_id || Some(p.id) == config.loss_sink_id {
+            return allocated != 0;
         }
-
-        let mut remaining = traded_total;
-        for seller in &qualified_sellers {
-            if remaining == 0 {
-                break;
+        let coin = journal.out_coin[row] as i128 - journal.in_coin[row] as i128;
+        let energy = journal.out_energy[row] as i128 - journal.in_energy[row] as i128;
+        let quote = match config.settlement {
+            Settlement::Uniform => Some(journal.clearing_price),
+            _ if p.steps.is_empty() => Some(p.price),
+            _ => None, // Each step settles at its own bid
+        };
+        let settled = quote
+            .filter(|_| coin_rule)
+            .map(|quote| settle(real_price(quote, config), allocated, config.fee_bps));
+        match p.role {
+            0 => {
+                energy != delivered_energy(allocated, config) as i128
+                    || settled.is_some_and(|coins| coins.is_none_or(|(pays, _, _)| coin != -pays))
             }
-            let cap = seller_caps
-                .iter()
-                .find(|(id, _)| *id == seller.id)
-                .map(|(_, cap)| *cap)
-                .unwrap_or(0);
-            let take = cap.min(remaining);
-            if take > 0 {
-                allocations.push((seller.id, take));
-                remaining -= take;
+            1 => {
+                -energy != allocated as i128
+                    || settled.is_some_and(|coins| coins.is_none_or(|(_, gets, _)| coin != gets))
             }
+            _ => energy.unsigned_abs() > allocated as u128,
         }
+    };
+    match rows.iter().enumerate().position(disagrees) {
+        Some(row) => Err(AuctionError::MalformedJournal(row)),
+        None => Ok(()),
     }
-
-    Some((clearing_price, allocations))
 }
 
-/// Compute aggregate demand and supply at given price
-fn demand_supply_at(buyers: &[&Participant], sellers: &[&Participant], price: u64) -> (u64, u64) {
-    let demand: u64 = buyers
-        .iter()
-        .filter(|b| b.price >= price)
-        .map(|b| b.quantity)
-        .sum();
+/// Whether a single book's coin moves by its allocations alone at one price per order
+///
+/// Uniform settlement or pay-as-bid under a uniform-price algorithm, with
+/// no flow besides the trades and their fees: not the clocks' or greedy
+/// matching's per-fill prices, McAfee's two prices, congestion rent, the
+/// curtailment pool, a second pass, net-owner groups, bundles, zones or
+/// carbon prices, and under pay-as-bid no order rounded onto the grid.
+fn coin_follows_allocation(participants: &[Participant], config: &AuctionConfig) -> bool {
+    let settlement = match config.settlement {
+        Settlement::Uniform => true,
+        Settlement::PayAsBid => !config.round_to_grid,
+        Settlement::Vcg => false,
+    };
+    settlement
+        && matches!(
+            config.algorithm,
+            Algorithm::UniformPrice | Algorithm::EnglishClock | Algorithm::Merge
+        )
+        && !config.congestion_rent
+        && config.funding_fee_bps == 0
+        && config.curtailment_comp_per_unit == 0
+        && config.secondary_concession.is_none()
+        && !config.net_owners
+        && !config.bundles
+        && !is_zonal(participants)
+        && !is_carbon_priced(participants)
+}
 
-    let supply: u64 = sellers
-        .iter()
-        .filter(|s| s.price <= price)
-        .map(|s| s.quantity)
-        .sum();
+/// Clear one book and check its journal before it is committed (see `check_clearing`)
+///
+/// Used for the single book and for each round, period and market.
+fn clear_book(input: &AuctionInput) -> Result<PublicJournal, AuctionError> {
+    check_clearing(input, run_double_auction(input))
+}
 
-    (demand, supply)
+/// Check a book's cleared journal before it is committed
+///
+/// The journal must pass `check_journal`, `check_rationality` and
+/// `check_feasibility`. With config.strict (the default) any failure ends
+/// the session with its exit code; without it, a failure a no-trade journal
+/// may stand in for (see `AuctionError::passes_through`) commits that
+/// journal instead, every row keeping its input balances. Used by
+/// clear_book and the carbon co-clearing.
+fn check_clearing(
+    input: &AuctionInput,
+    cleared: Result<PublicJournal, AuctionError>,
+) -> Result<PublicJournal, AuctionError> {
+    let (participants, config) = (&input.participants, &input.config);
+    let cleared = cleared.and_then(|journal| {
+        check_journal(&journal, participants, config)?;
+        check_rationality(&journal, participants, config)?;
+        check_feasibility(&journal, participants, config)?;
+        Ok(journal)
+    });
+    match cleared {
+        Err(error) if !config.strict && error.passes_through() => {
+            let (buyers, sellers) = separate_and_sort(participants, config);
+            Ok(PublicJournal {
+                status: STATUS_SETTLED_AS_NO_TRADE,
+                ..build_journal(participants, &buyers, &sellers)
+            })
+        }
+        cleared => cleared,
+    }
 }
 
-/// Apply allocations to compute final balances
+/// Individual rationality of a checked journal, enabled by config.check_rationality
 ///
-/// Returns: Vec<(participant_id, out_coin, out_energy)>
-fn compute_outputs(
+/// Every buyer row must pay at most what its bid would charge for the units
+/// it was allocated (received, plus any config.loss_bps losses), and every
+/// seller row receive at least what its ask would pay for the units it
+/// delivered, both including config.fee_bps (sellers also
+/// config.funding_fee_bps) and in real prices (see `settle`); a stepped
+/// order is held to its best step and an owner-netted order to its group's
+/// best quote (see `best_quote`). The operator, the loss sink and prosumers (one netted row for two quotes) are
+/// not checked. Clearing below a bid or above an ask is what the reference
+/// algorithms promise, so a violation is a pricing bug, never an input
+/// error; a replacement that settles past quotes on purpose must turn the
+/// check off.
+fn check_rationality(
+    journal: &PublicJournal,
     participants: &[Participant],
-    allocations: &[(u32, u64)],
-    clearing_price: u64,
-) -> Vec<(u32, u64, u64)> {
-    let mut outputs = Vec::new();
-
-    for p in participants {
-        let allocated = allocations
-            .iter()
-            .find(|(id, _)| *id == p.id)
-            .map(|(_, amount)| *amount)
-            .unwrap_or(0);
+    config: &AuctionConfig,
+) -> Result<(), AuctionError> {
+    if !config.check_rationality {
+        return Ok(());
+    }
+    match rationality_violation(journal, participants, config) {
+        Some(id) => Err(AuctionError::Irrational(id)),
+        None => Ok(()),
+    }
+}
 
-        let (out_coin, out_energy) = if p.role == 0 {
-            // BUY: spend coins, receive energy
-            if allocated > 0 {
-                (
-                    p.in_coin - (clearing_price * allocated),
-                    p.in_energy + allocated,
-                )
-            } else {
-                (p.in_coin, p.in_energy)
-            }
-        } else {
-            // SELL: receive coins, spend energy
-            if allocated > 0 {
-                (
-                    p.in_coin + (clearing_price * allocated),
-                    p.in_energy - allocated,
-                )
+/// Smallest id of a buyer or seller row settled past its own quote
+fn rationality_violation(
+    journal: &PublicJournal,
+    participants: &[Participant],
+    config: &AuctionConfig,
+) -> Option<u32> {
+    protocol_order(participants, config)
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| {
+            p.role <= 1 && Some(p.id) != config.operator_id && Some(p.id) != config.loss_sink_id
+        })
+        .filter(|&(row, p)| {
+            let coin = journal.out_coin[row] as i128 - journal.in_coin[row] as i128;
+            let quote = real_price(best_quote(p, participants, config), config);
+            if p.role == 0 {
+                let Some(bought) = journal.out_energy[row].checked_sub(journal.in_energy[row])
+                else {
+                    return true; // A buyer never delivers
+                };
+                // The allocation that would let the bid charge the most
+                let (least, most) = allocations_delivering(bought, config);
+                let Some(allocated) = (if quote < 0 { Some(least) } else { most }) else {
+                    return false; // Every unit is lost: any allocation delivers nothing
+                };
+                settle(quote, allocated, config.fee_bps).is_some_and(|(limit, _, _)| -coin > limit)
             } else {
-                (p.in_coin, p.in_energy)
+                let Some(sold) = journal.in_energy[row].checked_sub(journal.out_energy[row]) else {
+                    return true; // A seller never receives energy
+                };
+                // Sellers also fund curtailment: n − fee(n) − funding(n) >= ask − (both fees)(ask)
+                let fee_bps = config.fee_bps + config.funding_fee_bps;
+                if fee_bps > BPS_DENOMINATOR as u64 {
+                    return false; // Fees above the notional leave no floor
+                }
+                settle(quote, sold, fee_bps).is_some_and(|(_, floor, _)| coin < floor)
             }
-        };
+        })
+        .map(|(_, p)| p.id)
+        .min()
+}
 
-        outputs.push((p.id, out_coin, out_energy));
+/// Allocations that deliver `delivered` units (inverse of `delivered_energy`)
+///
+/// Returns: (smallest, largest), the largest None when config.loss_bps is
+/// 10000 or more (every allocation delivers 0)
+fn allocations_delivering(delivered: u64, config: &AuctionConfig) -> (u64, Option<u64>) {
+    let kept = BPS_DENOMINATOR - config.loss_bps.min(10_000) as u128;
+    if kept == 0 {
+        return (0, None);
     }
+    let bound = |allocated: u128| allocated.min(u64::MAX as u128) as u64;
+    let least = (delivered as u128 * BPS_DENOMINATOR).div_ceil(kept);
+    let most = ((delivered as u128 + 1) * BPS_DENOMINATOR - 1) / kept;
+    (bound(least), Some(bound(most)))
+}
 
-    outputs
+/// Most favourable price an order quotes: a buyer's highest bid, a seller's lowest ask
+///
+/// Steps replace the order's price; with config.net_owners an owner's orders
+/// on one side trade as one synthetic order at the group's best price.
+fn best_quote(p: &Participant, participants: &[Participant], config: &AuctionConfig) -> u64 {
+    let group = participants.iter().filter(|q| {
+        q.id == p.id
+            || (config.net_owners && p.owner.is_some() && q.owner == p.owner && q.role == p.role)
+    });
+    let quotes = group.flat_map(|q| {
+        let price = q.steps.is_empty().then_some(q.price);
+        price
+            .into_iter()
+            .chain(q.steps.iter().take(MAX_STEPS).map(|&(price, _)| price))
+    });
+    let best = if p.role == 0 {
+        quotes.max()
+    } else {
+        quotes.min()
+    };
+    best.unwrap_or(p.price)
 }
 
-// ═══════════════════════════════════════════════════════════════════════════
-// PROTOCOL INFRASTRUCTURE (DO NOT MODIFY)
-// ═══════════════════════════════════════════════════════════════════════════
+/// Budget feasibility of a checked journal: every row reachable from its inputs
+///
+/// Each row's allocation is read back from its energy column (a seller's
+/// delivered units, the smallest allocation that delivers what a buyer
+/// received) and the row is re-derived from it, independently of how the
+/// outputs were computed: a buyer gains and a seller loses energy, neither
+/// beyond the quantity it quotes (steps summed, a prosumer within its buy
+/// and sell quantities), and at most the lowest price any order can settle
+/// at (`real_price(0)`, fees included) moves coin the wrong way, so a buyer
+/// never gains coin and a seller never loses it without config.price_offset.
+/// An output computed with wrapping arithmetic ends far outside these bounds.
+/// The operator and the loss sink are settlement accounts and not checked,
+/// a prosumer's coin (two quotes netted) neither. A violation is a guest
+/// bug, never an input error: the guest exits with Infeasible.
+fn check_feasibility(
+    journal: &PublicJournal,
+    participants: &[Participant],
+    config: &AuctionConfig,
+) -> Result<(), AuctionError> {
+    match infeasible_row(journal, participants, config) {
+        Some(id) => Err(AuctionError::Infeasible(id)),
+        None => Ok(()),
+    }
+}
 
-/// Build journal with no trades (fallback for no market clearing)
-fn build_journal(
+/// Smallest id of a row its allocation cannot reach from its input balances
+fn infeasible_row(
+    journal: &PublicJournal,
     participants: &[Participant],
-    buyers: &[&Participant],
-    sellers: &[&Participant],
-) -> PublicJournal {
-    let mut outputs_vec: Vec<(u32, u64, u64)> = participants
+    config: &AuctionConfig,
+) -> Option<u32> {
+    let lowest = real_price(0, config);
+    protocol_order(participants, config)
         .iter()
-        .map(|p| (p.id, p.in_coin, p.in_energy))
-        .collect();
+        .enumerate()
+        .filter(|(_, p)| Some(p.id) != config.operator_id && Some(p.id) != config.loss_sink_id)
+        .filter(|&(row, p)| {
+            let coin = journal.out_coin[row] as i128 - journal.in_coin[row] as i128;
+            let energy = journal.out_energy[row] as i128 - journal.in_energy[row] as i128;
+            match p.role {
+                0 => {
+                    let Ok(received) = u64::try_from(energy) else {
+                        return true; // A buyer never delivers
+                    };
+                    let (least, most) = allocations_delivering(received, config);
+                    if ordered_quantity(p).is_some_and(|quantity| least > quantity) {
+                        return true;
+                    }
+                    // The most a buyer can be paid: its largest allocation at the lowest price
+                    match most {
+                        Some(allocated) => settle(lowest, allocated, config.fee_bps)
+                            .is_some_and(|(pays, _, _)| coin > -pays),
+                        None => received > 0 || (lowest >= 0 && coin > 0),
+                    }
+                }
+                1 => {
+                    let Ok(sold) = u64::try_from(-energy) else {
+                        return true; // A seller never receives energy
+                    };
+                    if ordered_quantity(p).is_some_and(|quantity| sold > quantity) {
+                        return true;
+                    }
+                    // The most a seller can pay: its sale at the lowest price, both fees
+                    let fee_bps = config.fee_bps + config.funding_fee_bps;
+                    fee_bps <= BPS_DENOMINATOR as u64
+                        && settle(lowest, sold, fee_bps).is_some_and(|(_, gets, _)| coin < gets)
+                }
+                _ => energy > p.quantity as i128 || -energy > p.sell_quantity as i128,
+            }
+        })
+        .map(|(_, p)| p.id)
+        .min()
+}
 
-    build_journal_with_outputs(participants, buyers, sellers, &mut outputs_vec)
+/// Units an order quotes: its quantity, or its first MAX_STEPS steps summed
+///
+/// Returns: None for a budget bid (its quantity follows the price)
+fn ordered_quantity(p: &Participant) -> Option<u64> {
+    if is_budget_bid(p) {
+        None
+    } else if p.steps.is_empty() {
+        Some(p.quantity)
+    } else {
+        p.steps
+            .iter()
+            .take(MAX_STEPS)
+            .try_fold(0u64, |total, &(_, quantity)| total.checked_add(quantity))
+            .or(Some(u64::MAX))
+    }
 }
 
 /// Build journal in protocol order: buyers (DESC price) then sellers (ASC price)
 ///
+/// Prosumers follow the sellers (ascending ID) with one netted row each.
+/// The clearing the algorithm reports is committed after the rows.
+///
 /// CRITICAL: This ordering is required for circuit verification. Do not modify.
 fn build_journal_with_outputs(
-    _participants: &[Participant],
+    participants: &[Participant],
     buyers_sorted: &[&Participant],
     sellers_sorted: &[&Participant],
     outputs: &mut [(u32, u64, u64)],
+    (clearing_price, traded_volume): Clearing,
 ) -> PublicJournal {
     use std::collections::BTreeMap;
 
@@ -374,6 +4821,7 @@ fn build_journal_with_outputs(
         output_map.insert(*id, (*coin, *energy));
     }
 
+    let mut ids = Vec::new();
     let mut in_coin = Vec::new();
     let mut in_energy = Vec::new();
     let mut out_coin = Vec::new();
@@ -381,6 +4829,7 @@ fn build_journal_with_outputs(
 
     // Buyers first (descending by price)
     for buyer in buyers_sorted {
+        ids.push(buyer.id);
         in_coin.push(buyer.in_coin);
         in_energy.push(buyer.in_energy);
 
@@ -392,6 +4841,7 @@ fn build_journal_with_outputs(
 
     // Sellers second (ascending by price)
     for seller in sellers_sorted {
+        ids.push(seller.id);
         in_coin.push(seller.in_coin);
         in_energy.push(seller.in_energy);
 
@@ -401,10 +4851,44 @@ fn build_journal_with_outputs(
         out_energy.push(*oe);
     }
 
+    // Prosumers last (ascending by ID)
+    for prosumer in sorted_prosumers(participants) {
+        ids.push(prosumer.id);
+        in_coin.push(prosumer.in_coin);
+        in_energy.push(prosumer.in_energy);
+
+        let default = (prosumer.in_coin, prosumer.in_energy);
+        let (oc, oe) = output_map.get(&prosumer.id).unwrap_or(&default);
+        out_coin.push(*oc);
+        out_energy.push(*oe);
+    }
+
     PublicJournal {
+        version: JOURNAL_VERSION,
+        input_digest: Digest::ZERO, // Bound by run before commit
+        algorithm_id: ALGORITHM_ID,
+        config_digest: Digest::ZERO, // Bound by run before commit
+        ids,
+        buyer_count: buyers_sorted.len() as u32, // At most MAX_PARTICIPANTS
+        seller_count: sellers_sorted.len() as u32,
         in_coin,
         in_energy,
         out_coin,
+        allocation: vec![0; out_energy.len()], // Callers that match orders set it
         out_energy,
+        status: match traded_volume {
+            0 => STATUS_NO_CROSSING, // Callers that know more refine it
+            _ => STATUS_CLEARED,
+        },
+        clearing_price,
+        traded_volume,
+        fees_collected: 0, // Callers that charge fees set it
+        surplus: 0,        // Callers that match orders set it
+        fill_counts: FillCounts {
+            buyers_unfilled: buyers_sorted.len() as u32, // Until callers that match orders set it
+            sellers_unfilled: sellers_sorted.len() as u32,
+            ..FillCounts::default()
+        },
+        round_id: 0, // Bound by run before commit
     }
 }
\ No newline at end of file
@@ -0,0 +1,4858 @@
+// ═══════════════════════════════════════════════════════════════════════════
+// Property Suite - run_double_auction on Generated Books
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// The guest source is compiled natively (include!), as in fuzz/, and cleared
+// on books proptest builds from bounded row counts, quotes and balances; a
+// failing book is shrunk to a minimal counterexample. Small books whose
+// outputs each test works out by hand in its doc comment follow the
+// properties.
+//
+// Run with `cargo test -p properties`; PROPTEST_CASES=<n> runs more books.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+#![allow(dead_code)]
+
+use proptest::prelude::*;
+
+include!("../../methods/guest/src/main.rs");
+
+/// Rows per generated book
+const MAX_ROWS: usize = 12;
+
+/// Highest generated bid or ask
+const MAX_QUOTE: u64 = 1_000;
+
+/// Largest generated order quantity
+const MAX_QUANTITY: u64 = 100;
+
+/// Largest generated coin balance (a buyer may afford none to all of its order)
+const MAX_COIN: u64 = MAX_QUOTE * MAX_QUANTITY;
+
+/// Largest generated energy balance
+const MAX_ENERGY: u64 = 2 * MAX_QUANTITY;
+
+/// A plain buyer (role 0) or seller (role 1): every optional order feature off
+fn order(
+    id: u32,
+    role: u32,
+    price: u64,
+    quantity: u64,
+    in_coin: u64,
+    in_energy: u64,
+) -> Participant {
+    Participant {
+        id,
+        role,
+        price,
+        quantity,
+        in_coin,
+        in_energy,
+        capacity: 0,
+        in_capacity: 0,
+        reserve_price: None,
+        all_or_nothing: false,
+        min_fill: 0,
+        steps: Vec::new(),
+        commodity: 0,
+        sell_price: 0,
+        sell_quantity: 0,
+        zone: 0,
+        priority: false,
+        flexible: false,
+        carbon_per_unit: 0,
+        carbon_budget: None,
+        valid_until_round: u32::MAX,
+        display_quantity: None,
+        owner: None,
+        fixed_cost: 0,
+        budget: 0,
+        max_position_energy: None,
+        periods: Vec::new(),
+        salt: [0; 32],
+    }
+}
+
+fn auction(participants: Vec<Participant>, config: AuctionConfig) -> AuctionInput {
+    AuctionInput {
+        participants,
+        config,
+        rounds: Vec::new(),
+        commitments: Vec::new(),
+        output_salt: [0; 32],
+    }
+}
+
+/// SHA-256 of the concatenated parts, computed apart from the guest (sha2, as the host)
+fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    use sha2::Digest as _;
+    let mut hasher = sha2::Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// Algorithms run_double_auction clears on a single book
+fn algorithm() -> impl Strategy<Value = Algorithm> {
+    prop_oneof![
+        Just(Algorithm::UniformPrice),
+        Just(Algorithm::McAfee),
+        Just(Algorithm::DutchClock),
+        Just(Algorithm::EnglishClock),
+        Just(Algorithm::Greedy),
+        Just(Algorithm::Merge),
+    ]
+}
+
+fn pricing_rule() -> impl Strategy<Value = PricingRule> {
+    prop_oneof![
+        Just(PricingRule::MidPoint),
+        Just(PricingRule::BuyerBid),
+        Just(PricingRule::SellerAsk),
+        Just(PricingRule::KDouble),
+        Just(PricingRule::SecondPrice),
+    ]
+}
+
+prop_compose! {
+    /// Buyers and sellers with random quotes and balances, ids 0..N
+    fn book()(orders in prop::collection::vec(
+        (0u32..=1, 1..=MAX_QUOTE, 1..=MAX_QUANTITY, 0..=MAX_COIN, 0..=MAX_ENERGY),
+        2..=MAX_ROWS,
+    )) -> Vec<Participant> {
+        orders
+            .into_iter()
+            .enumerate()
+            .map(|(id, (role, price, quantity, in_coin, in_energy))| {
+                order(id as u32, role, price, quantity, in_coin, in_energy)
+            })
+            .collect()
+    }
+}
+
+prop_compose! {
+    /// Books whose buyers afford their whole order at any quote up to 2 × MAX_QUOTE
+    /// and whose sellers hold their whole quantity
+    fn funded_book()(orders in prop::collection::vec(
+        (0u32..=1, 1..=MAX_QUOTE, 1..=MAX_QUANTITY, 0..=MAX_ENERGY),
+        2..=MAX_ROWS,
+    )) -> Vec<Participant> {
+        orders
+            .into_iter()
+            .enumerate()
+            .map(|(id, (role, price, quantity, other))| match role {
+                0 => order(id as u32, 0, price, quantity, 2 * MAX_QUOTE * quantity, other),
+                _ => order(id as u32, 1, price, quantity, other, quantity + other),
+            })
+            .collect()
+    }
+}
+
+/// Clear a book the guest accepts, failing the case on any error
+fn clear(input: &AuctionInput) -> Result<PublicJournal, TestCaseError> {
+    validate_input(input).map_err(|error| TestCaseError::fail(format!("rejected: {:?}", error)))?;
+    run_double_auction(input)
+        .map_err(|error| TestCaseError::fail(format!("clearing failed: {:?}", error)))
+}
+
+/// Units the buyers received (Σ out_energy − in_energy over buyer rows)
+fn bought_volume(journal: &PublicJournal, input: &AuctionInput) -> u64 {
+    protocol_order(&input.participants, &input.config)
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.role == 0)
+        .map(|(row, _)| journal.out_energy[row] - journal.in_energy[row])
+        .sum()
+}
+
+proptest! {
+    #[test]
+    fn conserves_coin_and_energy(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+    ) {
+        let config = AuctionConfig { algorithm, pricing_rule, ..AuctionConfig::default() };
+        let journal = clear(&auction(participants, config))?;
+        let total = |column: &[u64]| column.iter().map(|&value| value as u128).sum::<u128>();
+        prop_assert_eq!(total(&journal.in_coin), total(&journal.out_coin));
+        prop_assert_eq!(total(&journal.in_energy), total(&journal.out_energy));
+    }
+
+    #[test]
+    fn individually_rational(participants in book()) {
+        let input = auction(participants, AuctionConfig::default());
+        let journal = clear(&input)?;
+        for (row, p) in protocol_order(&input.participants, &input.config).iter().enumerate() {
+            let coin = journal.out_coin[row] as i128 - journal.in_coin[row] as i128;
+            let energy = journal.out_energy[row] as i128 - journal.in_energy[row] as i128;
+            let quote = p.price as i128;
+            if p.role == 0 {
+                prop_assert!(
+                    energy >= 0 && -coin <= quote * energy,
+                    "buyer {} paid {} for {} units at bid {}", p.id, -coin, energy, p.price
+                );
+            } else {
+                prop_assert!(
+                    energy <= 0 && coin >= quote * -energy,
+                    "seller {} got {} for {} units at ask {}", p.id, coin, -energy, p.price
+                );
+            }
+        }
+    }
+
+    /// Raising one buyer's bid never lowers the traded volume under MaxVolume
+    /// while every buyer can afford its whole order. FirstCrossing is not
+    /// monotone: bids 1 for 26 and 1 for 2 against an ask of 1 for 27 trade
+    /// 27 at 1, and raising the first bid to 2 moves the crossing to 2, where
+    /// only 26 are demanded
+    #[test]
+    fn volume_monotone_in_bid(
+        participants in funded_book(),
+        buyer in any::<prop::sample::Index>(),
+        raise in 1..=MAX_QUOTE,
+    ) {
+        let buyers: Vec<usize> = (0..participants.len())
+            .filter(|&index| participants[index].role == 0)
+            .collect();
+        prop_assume!(!buyers.is_empty());
+        let mut raised = participants.clone();
+        raised[*buyer.get(&buyers)].price += raise;
+
+        let config = AuctionConfig { objective: Objective::MaxVolume, ..AuctionConfig::default() };
+        let before = auction(participants, config.clone());
+        let after = auction(raised, config);
+        prop_assert!(bought_volume(&clear(&after)?, &after) >= bought_volume(&clear(&before)?, &before));
+    }
+
+    #[test]
+    fn passes_rationality_check(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+    ) {
+        let config = AuctionConfig { algorithm, pricing_rule, ..AuctionConfig::default() };
+        let input = auction(participants, config);
+        let journal = clear(&input)?;
+        prop_assert_eq!(check_rationality(&journal, &input.participants, &input.config), Ok(()));
+    }
+
+    #[test]
+    fn rejects_overcharged_buyer(participants in funded_book(), buyer in any::<prop::sample::Index>()) {
+        // A bid at MAX_QUOTE and an ask of 1 guarantee a trade
+        let mut participants = participants;
+        let next = participants.len() as u32;
+        participants.push(order(next, 0, MAX_QUOTE, 1, 2 * MAX_QUOTE, 0));
+        participants.push(order(next + 1, 1, 1, 1, 0, 1));
+        let input = auction(participants, AuctionConfig::default());
+        let mut journal = clear(&input)?;
+        let rows = protocol_order(&input.participants, &input.config);
+        let traded: Vec<usize> = (0..rows.len())
+            .filter(|&row| rows[row].role == 0 && journal.out_energy[row] > journal.in_energy[row])
+            .collect();
+
+        // Move coin from one buyer to the first seller row until it pays its bid plus one
+        let row = *buyer.get(&traded);
+        let bought = journal.out_energy[row] - journal.in_energy[row];
+        let paid = journal.in_coin[row] - journal.out_coin[row];
+        let excess = rows[row].price * bought - paid + 1; // Funded: the buyer holds it
+        let seller = rows.iter().position(|p| p.role == 1).unwrap();
+        journal.out_coin[row] -= excess;
+        journal.out_coin[seller] += excess;
+
+        prop_assert_eq!(
+            check_rationality(&journal, &input.participants, &input.config),
+            Err(AuctionError::Irrational(rows[row].id))
+        );
+        let unchecked = AuctionConfig { check_rationality: false, ..input.config.clone() };
+        prop_assert_eq!(check_rationality(&journal, &input.participants, &unchecked), Ok(()));
+    }
+
+    #[test]
+    fn passes_feasibility_check(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+    ) {
+        let config = AuctionConfig { algorithm, pricing_rule, ..AuctionConfig::default() };
+        let input = auction(participants, config);
+        let journal = clear(&input)?;
+        prop_assert_eq!(check_feasibility(&journal, &input.participants, &input.config), Ok(()));
+    }
+
+    #[test]
+    fn rejects_wrapped_row(participants in book(), row in any::<prop::sample::Index>()) {
+        let input = auction(participants, AuctionConfig::default());
+        let mut journal = clear(&input)?;
+        let rows = protocol_order(&input.participants, &input.config);
+
+        // Debit one more than the row holds, as an unchecked subtraction would
+        let row = row.index(rows.len());
+        if rows[row].role == 0 {
+            journal.out_coin[row] = journal.in_coin[row].wrapping_sub(journal.in_coin[row] + 1);
+        } else {
+            journal.out_energy[row] =
+                journal.in_energy[row].wrapping_sub(journal.in_energy[row] + 1);
+        }
+
+        prop_assert_eq!(
+            check_feasibility(&journal, &input.participants, &input.config),
+            Err(AuctionError::Infeasible(rows[row].id))
+        );
+    }
+
+    #[test]
+    fn rows_in_protocol_order(participants in book()) {
+        let journal = clear(&auction(participants.clone(), AuctionConfig::default()))?;
+        let mut buyers: Vec<&Participant> = participants.iter().filter(|p| p.role == 0).collect();
+        let mut sellers: Vec<&Participant> = participants.iter().filter(|p| p.role == 1).collect();
+        buyers.sort_by_key(|p| (core::cmp::Reverse(p.price), p.id));
+        sellers.sort_by_key(|p| (p.price, p.id));
+
+        let ids: Vec<u32> = buyers.iter().chain(&sellers).map(|p| p.id).collect();
+        prop_assert_eq!(&journal.ids, &ids);
+        prop_assert_eq!(journal.buyer_count as usize, buyers.len());
+        prop_assert_eq!(journal.seller_count as usize, sellers.len());
+        let expected: Vec<(u64, u64)> = buyers
+            .iter()
+            .chain(&sellers)
+            .map(|p| (p.in_coin, p.in_energy))
+            .collect();
+        let rows: Vec<(u64, u64)> = journal
+            .in_coin
+            .iter()
+            .copied()
+            .zip(journal.in_energy.iter().copied())
+            .collect();
+        prop_assert_eq!(rows, expected);
+        prop_assert_eq!(journal.out_coin.len(), participants.len());
+        prop_assert_eq!(journal.out_energy.len(), participants.len());
+    }
+
+    #[test]
+    fn commits_its_clearing(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+    ) {
+        let config = AuctionConfig { algorithm, pricing_rule, ..AuctionConfig::default() };
+        let input = auction(participants, config);
+        let journal = clear(&input)?;
+        prop_assert_eq!(journal.traded_volume, bought_volume(&journal, &input));
+        if journal.traded_volume == 0 {
+            prop_assert_eq!(journal.clearing_price, 0);
+        }
+        // The clocks and greedy matching price each buyer on its own
+        if matches!(algorithm, Algorithm::DutchClock | Algorithm::Greedy) {
+            return Ok(());
+        }
+        for (row, p) in protocol_order(&input.participants, &input.config).iter().enumerate() {
+            let bought = journal.out_energy[row].saturating_sub(journal.in_energy[row]);
+            if p.role == 0 {
+                prop_assert_eq!(
+                    journal.in_coin[row] - journal.out_coin[row],
+                    journal.clearing_price * bought,
+                    "buyer {}", p.id
+                );
+            }
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn compact_root_proves_every_row(participants in book(), row in any::<prop::sample::Index>()) {
+        let config = AuctionConfig { journal_variant: JournalVariant::Compact, ..AuctionConfig::default() };
+        let journal = clear(&auction(participants, config))?;
+        let compact = compact_journal(&journal);
+        let outputs = outputs_file_of(&compact, &journal).unwrap();
+        prop_assert_eq!(&outputs.outputs_root, &compact.outputs_root.to_string());
+        prop_assert_eq!(outputs.leaves.len(), journal.ids.len());
+        prop_assert_eq!(compact.row_count as usize, journal.ids.len());
+        let total = |column: &[u64]| column.iter().map(|&value| value as u128).sum::<u128>();
+        prop_assert_eq!(compact.total_coin, total(&journal.in_coin));
+        prop_assert_eq!(compact.total_energy, total(&journal.in_energy));
+        for (row, leaf) in outputs.leaves.iter().enumerate() {
+            let id = journal.ids[row].to_le_bytes();
+            let coin = journal.out_coin[row].to_le_bytes();
+            let energy = journal.out_energy[row].to_le_bytes();
+            let expected = Digest::from(sha256(&[&[0], &id, &coin, &energy]));
+            prop_assert_eq!(&leaf.leaf, &expected.to_string());
+            prop_assert!(verify_merkle_path(&leaf.leaf, &leaf.path, &compact.outputs_root));
+        }
+
+        let row = row.index(journal.ids.len());
+        let leaf = &outputs.leaves[row];
+        let tampered = output_leaf(leaf.id, leaf.out_coin ^ 1, leaf.out_energy).to_string();
+        prop_assert!(!verify_merkle_path(&tampered, &leaf.path, &compact.outputs_root));
+        prop_assert!(!verify_merkle_path("not hex", &leaf.path, &compact.outputs_root));
+        let mut rows = journal.clone();
+        rows.out_coin[row] ^= 1;
+        prop_assert!(outputs_file_of(&compact, &rows).unwrap_err().starts_with("hash to "));
+    }
+}
+
+/// compact_journal_N5.json: five rows, so the last leaf has no sibling on the
+/// first two levels and moves up to the root's right child unchanged; in
+/// the host's outputs file its path is the root's left child alone, and rows
+/// that do not match the committed row count are refused
+#[test]
+fn compact_root_of_five_rows() {
+    let participants = vec![
+        order(0, 0, 60, 10, 1000, 0),
+        order(1, 0, 50, 10, 1000, 0),
+        order(2, 1, 20, 10, 0, 10),
+        order(3, 1, 40, 10, 0, 10),
+        order(4, 0, 30, 5, 500, 0),
+    ];
+    let config = AuctionConfig {
+        journal_variant: JournalVariant::Compact,
+        ..AuctionConfig::default()
+    };
+    let rows = clear_book(&auction(participants, config)).unwrap();
+    let compact = compact_journal(&rows);
+    assert_eq!(
+        compact.outputs_root.to_string(),
+        "5431968b0b2dc60330466abb6c50f80e1d62508c0abaf6afb4aca545822318d4"
+    );
+    assert_eq!(
+        (compact.row_count, compact.buyer_count, compact.seller_count),
+        (5, 3, 2)
+    );
+    assert_eq!((compact.total_coin, compact.total_energy), (2500, 20));
+    assert_eq!((compact.clearing_price, compact.traded_volume), (45, 20));
+
+    let outputs = outputs_file_of(&compact, &rows).unwrap();
+    let sides =
+        |leaf: &OutputLeaf| -> Vec<bool> { leaf.path.iter().map(|step| step.left).collect() };
+    assert_eq!(sides(&outputs.leaves[0]), [false, false, false]);
+    assert_eq!(sides(&outputs.leaves[3]), [true, true, false]);
+    assert_eq!(sides(&outputs.leaves[4]), [true]);
+    let first_four = (0..4)
+        .map(|row| output_leaf(rows.ids[row], rows.out_coin[row], rows.out_energy[row]))
+        .collect();
+    let levels = merkle_levels(first_four);
+    assert_eq!(outputs.leaves[4].path[0].sibling, levels[2][0].to_string());
+    let mut short = compact.clone();
+    short.row_count = 4;
+    assert_eq!(
+        outputs_file_of(&short, &rows).unwrap_err(),
+        "do not match the committed counts and totals"
+    );
+}
+
+/// SHA-256(salt || rows) over (id, in_coin, in_energy, out_coin, out_energy)
+/// rows, as the holder of an outputs file recomputes it
+fn rows_digest(salt: &[u8], rows: &[[u64; 5]]) -> [u8; 32] {
+    let words: Vec<[u8; 8]> = rows
+        .iter()
+        .flatten()
+        .map(|value| value.to_le_bytes())
+        .collect();
+    let mut parts: Vec<&[u8]> = vec![salt];
+    for row in words.chunks(5) {
+        parts.push(&row[0][..4]); // The id is a u32
+        parts.extend(row[1..].iter().map(|word| &word[..]));
+    }
+    sha256(&parts)
+}
+
+/// The rows of a checked journal as an outputs file lists them
+fn journal_rows(journal: &PublicJournal) -> Vec<[u64; 5]> {
+    (0..journal.ids.len())
+        .map(|row| {
+            [
+                journal.ids[row] as u64,
+                journal.in_coin[row],
+                journal.in_energy[row],
+                journal.out_coin[row],
+                journal.out_energy[row],
+            ]
+        })
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn private_journal_hides_the_rows(
+        participants in book(),
+        algorithm in algorithm(),
+        salt in any::<[u8; 32]>(),
+        row in any::<prop::sample::Index>(),
+    ) {
+        let config = AuctionConfig { algorithm, journal_variant: JournalVariant::Private, ..AuctionConfig::default() };
+        let journal = clear(&auction(participants, config))?;
+        let private = private_journal(&journal, &salt);
+        let words = risc0_zkvm::serde::to_vec(&private).unwrap();
+        prop_assert_eq!(words[0], PRIVATE_JOURNAL_VERSION);
+        let rows = journal_rows(&journal);
+        prop_assert_eq!(private.outputs_digest.as_bytes(), rows_digest(&salt, &rows));
+        prop_assert_eq!(private.row_count as usize, rows.len());
+        prop_assert_eq!(
+            (private.buyer_count, private.seller_count),
+            (journal.buyer_count, journal.seller_count)
+        );
+        prop_assert_eq!(
+            (private.clearing_price, private.traded_volume),
+            (journal.clearing_price, journal.traded_volume)
+        );
+        // By conservation the coin paid equals the coin received
+        let received: u128 = rows.iter().map(|r| r[3].saturating_sub(r[1]) as u128).sum();
+        prop_assert_eq!(private.turnover, received);
+
+        let row = row.index(rows.len());
+        let mut tampered = rows.clone();
+        tampered[row][3] ^= 1;
+        prop_assert_ne!(private.outputs_digest.as_bytes(), rows_digest(&salt, &tampered));
+        let mut other_salt = salt;
+        other_salt[0] ^= 1;
+        prop_assert_ne!(private.outputs_digest, private_outputs_digest(&other_salt, &private_rows(&journal)));
+    }
+}
+
+/// The rows an outputs file lists, with its salt and claimed digest
+fn outputs_file(text: &str) -> (Vec<u8>, String, Vec<[u64; 5]>) {
+    let file: serde_json::Value = serde_json::from_str(text).unwrap();
+    let field = |row: &serde_json::Value, key: &str| row[key].as_u64().unwrap();
+    let rows = file["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| {
+            ["id", "in_coin", "in_energy", "out_coin", "out_energy"].map(|key| field(row, key))
+        })
+        .collect();
+    let salt = fixture_bytes(file["salt"].as_str().unwrap());
+    let digest = file["outputs_digest"].as_str().unwrap().to_string();
+    (salt, digest, rows)
+}
+
+/// fixtures/private_outputs_N4.json: private_outputs.json of
+/// private_outputs_N4.json (marginal_inside_ask_N4 under salt 0x5a...), whose
+/// rows hash to the committed outputs_digest. The tampered copy credits
+/// buyer 0's trade to buyer 1: the totals, the counts and the turnover still
+/// match the journal, only the digest refuses it
+#[test]
+fn tampered_outputs_file_is_refused() {
+    let input = AuctionInput {
+        config: AuctionConfig {
+            journal_variant: JournalVariant::Private,
+            ..AuctionConfig::default()
+        },
+        output_salt: [0x5a; 32],
+        ..marginal_inside_ask()
+    };
+    let journal = clear_book(&input).unwrap();
+    let private = private_journal(&journal, &input.output_salt);
+    assert_eq!(
+        (private.row_count, private.buyer_count, private.seller_count),
+        (4, 2, 2)
+    );
+    assert_eq!(private.turnover, 400);
+    assert_eq!((private.clearing_price, private.traded_volume), (40, 10));
+
+    let (salt, digest, rows) = outputs_file(include_str!("fixtures/private_outputs_N4.json"));
+    assert_eq!(salt, input.output_salt);
+    assert_eq!(digest, private.outputs_digest.to_string());
+    assert_eq!(rows, journal_rows(&journal));
+    assert_eq!(rows_digest(&salt, &rows), private.outputs_digest.as_bytes());
+
+    let tampered = include_str!("fixtures/private_outputs_N4_tampered.json");
+    let (salt, digest, rows) = outputs_file(tampered);
+    assert_eq!(digest, private.outputs_digest.to_string());
+    let total = |column: usize| rows.iter().map(|row| row[column]).sum::<u64>();
+    assert_eq!((total(1), total(2)), (total(3), total(4))); // Still conserved
+    assert_ne!(rows_digest(&salt, &rows), private.outputs_digest.as_bytes());
+}
+
+/// Outputs rebuilt from the input balances and a delta column, as the host does
+fn rebuild(inputs: &[u64], deltas: &[i64]) -> Vec<u64> {
+    inputs
+        .iter()
+        .zip(deltas)
+        .map(|(&before, &change)| (before as i128 + change as i128) as u64)
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn delta_journal_rebuilds_the_rows(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+    ) {
+        let config = AuctionConfig { algorithm, pricing_rule, ..AuctionConfig::default() };
+        let journal = clear(&auction(participants, config))?;
+        let delta = delta_journal(&journal).unwrap();
+        prop_assert_eq!(check_deltas(&delta), Ok(()));
+        let words = risc0_zkvm::serde::to_vec(&delta).unwrap();
+        prop_assert_eq!(words[0], DELTA_JOURNAL_VERSION);
+        prop_assert_eq!(&delta.ids, &journal.ids);
+        prop_assert_eq!(rebuild(&journal.in_coin, &delta.coin_delta), journal.out_coin);
+        prop_assert_eq!(rebuild(&journal.in_energy, &delta.energy_delta), journal.out_energy);
+    }
+}
+
+/// delta_journal_N4.json: the deltas of marginal_inside_ask_N4, and the
+/// sum-to-zero check refusing a coin or energy column that does not
+/// balance (tampered by one unit) or does not cover every row
+#[test]
+fn check_deltas_refuses_unbalanced_columns() {
+    let journal = clear_book(&marginal_inside_ask()).unwrap();
+    let delta = delta_journal(&journal).unwrap();
+    assert_eq!(delta.coin_delta, [-400, 0, 400, 0]);
+    assert_eq!(delta.energy_delta, [10, 0, -10, 0]);
+    assert_eq!(check_deltas(&delta), Ok(()));
+
+    let mut coin = delta.clone();
+    coin.coin_delta[1] += 1;
+    assert_eq!(check_deltas(&coin), Err(AuctionError::MalformedJournal(4)));
+    let mut energy = delta.clone();
+    energy.energy_delta[3] -= 1;
+    assert_eq!(
+        check_deltas(&energy),
+        Err(AuctionError::MalformedJournal(4))
+    );
+    let mut short = delta;
+    short.energy_delta.pop();
+    assert_eq!(check_deltas(&short), Err(AuctionError::MalformedJournal(3)));
+}
+
+/// delta_overflow_N2.json: buyer 0 pays 1.5e19 coin, beyond i64
+#[test]
+fn delta_beyond_i64_overflows() {
+    let participants = vec![
+        order(
+            0,
+            0,
+            6,
+            3_000_000_000_000_000_000,
+            18_000_000_000_000_000_000,
+            0,
+        ),
+        order(
+            1,
+            1,
+            4,
+            3_000_000_000_000_000_000,
+            0,
+            3_000_000_000_000_000_000,
+        ),
+    ];
+    let config = AuctionConfig {
+        quantity_scale: 1000,
+        journal_variant: JournalVariant::Delta,
+        ..AuctionConfig::default()
+    };
+    let journal = clear_book(&auction(participants, config)).unwrap();
+    assert_eq!(
+        journal.out_coin,
+        [3_000_000_000_000_000_000, 15_000_000_000_000_000_000]
+    );
+    assert_eq!(delta_journal(&journal).unwrap_err(), AuctionError::Overflow);
+}
+
+proptest! {
+    #[test]
+    fn quantized_deltas_stay_within_the_bound(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+        scale in 1u64..=2_000,
+    ) {
+        let config = AuctionConfig {
+            algorithm,
+            pricing_rule,
+            journal_variant: JournalVariant::Quantized(scale),
+            ..AuctionConfig::default()
+        };
+        let journal = clear(&auction(participants, config))?;
+        let quantized = quantized_journal(&journal, scale).unwrap();
+        prop_assert_eq!(check_quantized(&journal, &quantized), Ok(()));
+        let words = risc0_zkvm::serde::to_vec(&quantized).unwrap();
+        prop_assert_eq!(words[0], QUANTIZED_JOURNAL_VERSION);
+        let rows = journal.ids.len();
+        for (exact, units) in [
+            (&journal.in_coin, &quantized.in_coin),
+            (&journal.in_energy, &quantized.in_energy),
+            (&journal.out_coin, &quantized.out_coin),
+            (&journal.out_energy, &quantized.out_energy),
+        ] {
+            for (&balance, &units) in exact.iter().zip(units.iter()) {
+                prop_assert!(balance - units as u64 * scale < scale);
+            }
+        }
+        for (ins, outs) in [
+            (&quantized.in_coin, &quantized.out_coin),
+            (&quantized.in_energy, &quantized.out_energy),
+        ] {
+            let drift = ins
+                .iter()
+                .zip(outs)
+                .map(|(&before, &after)| after as i64 - before as i64)
+                .sum::<i64>();
+            prop_assert!(drift.unsigned_abs() < rows.max(1) as u64, "drift {} over {} rows", drift, rows);
+        }
+    }
+}
+
+/// quantized_journal_N4.json: marginal_inside_ask_N4 in units of 300, which
+/// rounds every energy balance to 0 and the coin 600 / 1000 / 400 down; a
+/// count moved by one unit, a missing row, a zero scale and a balance of
+/// u32::MAX + 1 units are refused
+#[test]
+fn quantized_journal_of_marginal_inside_ask() {
+    let journal = clear_book(&marginal_inside_ask()).unwrap();
+    let quantized = quantized_journal(&journal, 300).unwrap();
+    assert_eq!(quantized.in_coin, [3, 3, 0, 0]);
+    assert_eq!(quantized.out_coin, [2, 3, 1, 0]);
+    assert_eq!(quantized.in_energy, [0; 4]);
+    assert_eq!(quantized.out_energy, [0; 4]);
+    assert_eq!(
+        (quantized.clearing_price, quantized.traded_volume),
+        (40, 10)
+    );
+    assert_eq!(check_quantized(&journal, &quantized), Ok(()));
+
+    let mut raised = quantized.clone();
+    raised.out_coin[2] += 1;
+    assert_eq!(
+        check_quantized(&journal, &raised),
+        Err(AuctionError::MalformedJournal(2))
+    );
+    let mut lowered = quantized.clone();
+    lowered.out_coin[1] -= 1;
+    assert_eq!(
+        check_quantized(&journal, &lowered),
+        Err(AuctionError::MalformedJournal(1))
+    );
+    let mut short = quantized;
+    short.in_energy.pop();
+    assert_eq!(
+        check_quantized(&journal, &short),
+        Err(AuctionError::MalformedJournal(3))
+    );
+
+    assert_eq!(
+        quantized_journal(&journal, 0).unwrap_err(),
+        AuctionError::Overflow
+    );
+    let mut large = journal.clone();
+    large.in_coin[0] = (u32::MAX as u64 + 1) * 2;
+    assert_eq!(
+        quantized_journal(&large, 2).unwrap_err(),
+        AuctionError::Overflow
+    );
+    assert_eq!(
+        quantized_journal(&large, 3).unwrap().in_coin[0],
+        2_863_311_530
+    );
+}
+
+/// The exact rows behind a decoded journal, as the host rebuilds them: the
+/// full and ABI journals as they stand, delta and slim ones from the book
+/// the input digest binds (absolute_outputs, full_journal). None for the
+/// compact, private and quantized journals, which carry no exact rows
+fn rebuilt_rows(
+    decoded: &DecodedJournal,
+    participants: &[Participant],
+) -> Option<Result<PublicJournal, String>> {
+    match &decoded.committed {
+        CommittedJournal::Full(journal) | CommittedJournal::Abi(journal) => {
+            Some(Ok(journal.clone()))
+        }
+        CommittedJournal::Delta(delta) => Some(absolute_outputs(delta, participants)),
+        CommittedJournal::Slim(slim) => Some(full_journal(slim, participants)),
+        _ => None,
+    }
+}
+
+/// The serde words of a committed journal as the bytes a receipt carries
+fn journal_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+proptest! {
+    #[test]
+    fn slim_and_full_journals_decode_by_version(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+    ) {
+        let config = AuctionConfig { algorithm, pricing_rule, ..AuctionConfig::default() };
+        let input = auction(participants, config);
+        let journal = bound_journal(&input)?;
+        let status = market_status(&input);
+        let full = risc0_zkvm::serde::to_vec(&(&journal, status)).unwrap();
+        let slim = risc0_zkvm::serde::to_vec(&(slim_journal(&journal), status)).unwrap();
+        prop_assert_eq!(full[0], JOURNAL_VERSION);
+        prop_assert_eq!(slim[0], SLIM_JOURNAL_VERSION);
+        prop_assert_eq!(slim.len() + 4 * journal.ids.len() + 2, full.len());
+
+        let words = |journal: &PublicJournal| risc0_zkvm::serde::to_vec(journal).unwrap();
+        for (bytes, variant) in [(&full, JournalVariant::Full), (&slim, JournalVariant::Slim)] {
+            let decoded = decode_any_journal(&journal_bytes(bytes)).unwrap();
+            prop_assert_eq!(decoded.variant, variant);
+            prop_assert_eq!(decoded.market_status, Some(status));
+            let rows = rebuilt_rows(&decoded, &input.participants).unwrap().unwrap();
+            prop_assert_eq!(words(&rows), words(&journal));
+        }
+    }
+}
+
+/// slim_journal_N4.json: marginal_inside_ask_N4 without its input columns,
+/// 18 words shorter than the full journal, rebuilt by the host's
+/// full_journal from the book. The inputs do not come from the journal:
+/// another book's balances under the same ids are refused when they do not
+/// sum to the committed outputs, and rebuild another journal when they
+/// do, which is why the host checks the input digest first. A version tag
+/// no journal commits and a slim journal naming an id the book lacks are
+/// refused
+#[test]
+fn slim_journal_of_marginal_inside_ask() {
+    let input = marginal_inside_ask();
+    let journal = clear_book(&input).unwrap();
+    let slim = slim_journal(&journal);
+    assert_eq!(slim.version, 0x0010_0009);
+    assert_eq!(slim.out_coin, [600, 1000, 400, 0]);
+    assert_eq!(slim.out_energy, [10, 0, 5, 10]);
+    assert_eq!(slim.allocation, [10, 0, 10, 0]);
+    let full = risc0_zkvm::serde::to_vec(&journal).unwrap();
+    let words = risc0_zkvm::serde::to_vec(&slim).unwrap();
+    assert_eq!(words.len() + 18, full.len());
+    let committed = risc0_zkvm::serde::to_vec(&(&slim, market_status(&input))).unwrap();
+    let decoded = decode_any_journal(&journal_bytes(&committed)).unwrap();
+    assert_eq!(decoded.variant, JournalVariant::Slim);
+    let rebuilt = rebuilt_rows(&decoded, &input.participants)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        (&rebuilt.in_coin, &rebuilt.in_energy),
+        (&journal.in_coin, &journal.in_energy)
+    );
+
+    let mut richer = input.participants.clone();
+    richer[1].in_coin = 2000;
+    assert_eq!(
+        full_journal(&slim, &richer).unwrap_err(),
+        "coin: the scenario's inputs sum to 3000, the committed outputs to 2000"
+    );
+    let mut swapped = input.participants.clone();
+    swapped[0].in_coin = 0;
+    swapped[2].in_coin = 1000;
+    let other = full_journal(&slim, &swapped).unwrap();
+    assert_eq!(other.in_coin, [0, 1000, 1000, 0]);
+    assert_ne!(
+        input_digest(&auction(swapped, input.config.clone())),
+        input_digest(&input)
+    );
+
+    let mut unknown = committed;
+    unknown[0] = JOURNAL_VERSION | 1 << 26;
+    assert_eq!(
+        decode_any_journal(&journal_bytes(&unknown)).unwrap_err(),
+        format!("unknown version tag Some({})", JOURNAL_VERSION | 1 << 26)
+    );
+    let mut stranger = slim;
+    stranger.ids[3] = 9;
+    assert_eq!(
+        full_journal(&stranger, &input.participants).unwrap_err(),
+        "row 3: participant 9 is not in the scenario"
+    );
+}
+
+/// Every JournalVariant of marginal_inside_ask_N4.json, committed as run
+/// commits it, decodes by its tag alone back to that variant and to the
+/// common fields of the journal that was cleared, with its exact rows
+/// wherever the variant carries or rebuilds them. Each tag is
+/// JOURNAL_VERSION with a bit of its own, none shared by two variants
+#[test]
+fn every_journal_variant_round_trips() {
+    let input = marginal_inside_ask();
+    let journal = bound_journal(&input).unwrap();
+    let status = market_status(&input);
+    let committed = |variant: JournalVariant| -> Vec<u8> {
+        let words = match variant {
+            JournalVariant::Full => risc0_zkvm::serde::to_vec(&(&journal, status)),
+            JournalVariant::Compact => {
+                risc0_zkvm::serde::to_vec(&(compact_journal(&journal), status))
+            }
+            JournalVariant::Delta => {
+                risc0_zkvm::serde::to_vec(&(delta_journal(&journal).unwrap(), status))
+            }
+            JournalVariant::Abi => return abi_journal_bytes(&journal),
+            JournalVariant::Private => {
+                risc0_zkvm::serde::to_vec(&(private_journal(&journal, &[0x5a; 32]), status))
+            }
+            JournalVariant::Quantized(scale) => {
+                let quantized = quantized_journal(&journal, scale).unwrap();
+                risc0_zkvm::serde::to_vec(&(quantized, status))
+            }
+            JournalVariant::Slim => risc0_zkvm::serde::to_vec(&(slim_journal(&journal), status)),
+        };
+        journal_bytes(&words.unwrap())
+    };
+    let words = |journal: &PublicJournal| risc0_zkvm::serde::to_vec(journal).unwrap();
+    let mut tags = Vec::new();
+    for variant in [
+        JournalVariant::Full,
+        JournalVariant::Compact,
+        JournalVariant::Delta,
+        JournalVariant::Abi,
+        JournalVariant::Private,
+        JournalVariant::Quantized(10),
+        JournalVariant::Slim,
+    ] {
+        let bytes = committed(variant);
+        let tag: [u8; 4] = bytes[..4].try_into().unwrap();
+        let tag = match variant {
+            JournalVariant::Abi => u32::from_be_bytes(tag),
+            _ => u32::from_le_bytes(tag),
+        };
+        assert_eq!(tag & 0xffff, JOURNAL_VERSION, "{:?}", variant);
+        tags.push(tag);
+
+        let decoded = decode_any_journal(&bytes).unwrap();
+        assert_eq!(decoded.variant, variant);
+        assert_eq!(
+            (
+                decoded.input_digest,
+                decoded.algorithm_id,
+                decoded.config_digest
+            ),
+            (
+                journal.input_digest,
+                journal.algorithm_id,
+                journal.config_digest
+            ),
+            "{:?}",
+            variant
+        );
+        assert_eq!(
+            (decoded.row_count, decoded.buyer_count, decoded.seller_count),
+            (4, journal.buyer_count, journal.seller_count),
+            "{:?}",
+            variant
+        );
+        assert_eq!(
+            (
+                decoded.status,
+                decoded.clearing_price,
+                decoded.traded_volume,
+                decoded.fees_collected,
+                decoded.surplus,
+                decoded.fill_counts,
+                decoded.round_id
+            ),
+            (
+                journal.status,
+                journal.clearing_price,
+                journal.traded_volume,
+                journal.fees_collected,
+                journal.surplus,
+                journal.fill_counts,
+                journal.round_id
+            ),
+            "{:?}",
+            variant
+        );
+        let expected_status = (variant != JournalVariant::Abi).then_some(status);
+        assert_eq!(decoded.market_status, expected_status, "{:?}", variant);
+        let carries_rows = matches!(
+            variant,
+            JournalVariant::Full
+                | JournalVariant::Delta
+                | JournalVariant::Abi
+                | JournalVariant::Slim
+        );
+        let rows = rebuilt_rows(&decoded, &input.participants).map(Result::unwrap);
+        assert_eq!(rows.is_some(), carries_rows, "{:?}", variant);
+        if let Some(rows) = rows {
+            assert_eq!(words(&rows), words(&journal), "{:?}", variant);
+        }
+    }
+    tags.sort_unstable();
+    tags.dedup();
+    assert_eq!(tags.len(), 7);
+}
+
+fn greedy(max_trades: u32) -> AuctionConfig {
+    AuctionConfig {
+        algorithm: Algorithm::Greedy,
+        max_trades: Some(max_trades),
+        ..AuctionConfig::default()
+    }
+}
+
+proptest! {
+    #[test]
+    fn trade_list_reproduces_the_rows(
+        participants in book(),
+        trade in any::<prop::sample::Index>(),
+    ) {
+        let input = auction(participants, greedy(u32::MAX));
+        let (journal, list) = validate_input(&input)
+            .and_then(|()| clear_with_trades(&input, u32::MAX))
+            .map_err(|error| TestCaseError::fail(format!("clearing failed: {:?}", error)))?;
+        prop_assert_eq!(check_trades(&journal, &list.trades), Ok(()));
+        let volume: u64 = list.trades.iter().map(|trade| trade.quantity).sum();
+        prop_assert_eq!(volume, journal.traded_volume);
+        if list.trades.is_empty() {
+            prop_assert_eq!(&journal.out_energy, &journal.in_energy);
+            return Ok(());
+        }
+
+        let index = trade.index(list.trades.len());
+        let mut quantity = list.trades.clone();
+        quantity[index].quantity += 1;
+        prop_assert!(matches!(
+            check_trades(&journal, &quantity),
+            Err(AuctionError::MalformedJournal(_))
+        ));
+        let mut price = list.trades;
+        price[index].price += 1;
+        prop_assert!(matches!(
+            check_trades(&journal, &price),
+            Err(AuctionError::MalformedJournal(_))
+        ));
+    }
+}
+
+/// trade_list_N4.json: buyer 0 takes seller 2's four units at 35 and one
+/// of seller 3's at 40, buyer 1 the other five at 35
+#[test]
+fn trade_list_of_four_rows() {
+    let participants = vec![
+        order(0, 0, 50, 5, 1000, 0),
+        order(1, 0, 40, 5, 1000, 0),
+        order(2, 1, 20, 4, 0, 4),
+        order(3, 1, 30, 6, 0, 6),
+    ];
+    let input = auction(participants, greedy(3));
+    let (journal, list) = clear_with_trades(&input, 3).unwrap();
+    let trade = |buyer_id, seller_id, quantity, price| TradeRecord {
+        buyer_id,
+        seller_id,
+        quantity,
+        price,
+    };
+    assert_eq!(
+        list.trades,
+        [trade(0, 2, 4, 35), trade(0, 3, 1, 40), trade(1, 3, 5, 35)]
+    );
+    assert_eq!(journal.out_coin, [820, 825, 140, 215]);
+    assert_eq!(journal.out_energy, [5, 5, 0, 0]);
+
+    // Buyer 1's trade credited to buyer 0: row 0 is the first that differs
+    let mut tampered = list.trades.clone();
+    tampered[2].buyer_id = 0;
+    assert_eq!(
+        check_trades(&journal, &tampered),
+        Err(AuctionError::MalformedJournal(0))
+    );
+    // A trade between ids the journal does not list moves no row, only the volume
+    let mut unknown = list.trades;
+    unknown.push(trade(7, 8, 1, 35));
+    assert_eq!(
+        check_trades(&journal, &unknown),
+        Err(AuctionError::MalformedJournal(4))
+    );
+    assert_eq!(
+        clear_with_trades(&input, 2).unwrap_err(),
+        AuctionError::TooManyTrades(3)
+    );
+}
+
+/// One argument of Solidity's abi.encodePacked, by its declared type
+enum Packed<'a> {
+    Uint32(u32),
+    Bytes32(&'a [u8]),
+    Uint256(u64),
+    Uint32Array(&'a [u32]),
+    Uint256Array(&'a [u64]),
+}
+
+/// abi.encodePacked of the arguments, as a Solidity verifier computes it: a
+/// uint32 takes four bytes and a bytes32 or uint256 thirty-two, while every
+/// array element is padded to thirty-two bytes and no length word is written
+fn encode_packed(args: &[Packed]) -> Vec<u8> {
+    let padded = |value: u128| [[0u8; 16], value.to_be_bytes()].concat();
+    let mut bytes = Vec::new();
+    for arg in args {
+        match arg {
+            Packed::Uint32(value) => bytes.extend(value.to_be_bytes()),
+            Packed::Bytes32(value) => bytes.extend(*value),
+            Packed::Uint256(value) => bytes.extend(padded(*value as u128)),
+            Packed::Uint32Array(values) => {
+                bytes.extend(values.iter().flat_map(|&value| padded(value as u128)))
+            }
+            Packed::Uint256Array(values) => {
+                bytes.extend(values.iter().flat_map(|&value| padded(value as u128)))
+            }
+        }
+    }
+    bytes
+}
+
+/// The ABI journal a settlement contract expects: encodePacked of the fields
+fn packed_journal(journal: &PublicJournal) -> Vec<u8> {
+    encode_packed(&[
+        Packed::Uint32(ABI_JOURNAL_VERSION),
+        Packed::Bytes32(journal.input_digest.as_bytes()),
+        Packed::Uint32(journal.algorithm_id),
+        Packed::Bytes32(journal.config_digest.as_bytes()),
+        Packed::Uint32(journal.ids.len() as u32),
+        Packed::Uint32(journal.buyer_count),
+        Packed::Uint32(journal.seller_count),
+        Packed::Uint32Array(&journal.ids),
+        Packed::Uint256Array(&journal.in_coin),
+        Packed::Uint256Array(&journal.in_energy),
+        Packed::Uint256Array(&journal.out_coin),
+        Packed::Uint256Array(&journal.out_energy),
+        Packed::Uint256Array(&journal.allocation),
+        Packed::Uint32(journal.status),
+        Packed::Uint256(journal.clearing_price),
+        Packed::Uint256(journal.traded_volume),
+        Packed::Uint256(journal.fees_collected),
+        Packed::Uint256(journal.surplus),
+        Packed::Uint32(journal.fill_counts.buyers_filled),
+        Packed::Uint32(journal.fill_counts.buyers_partial),
+        Packed::Uint32(journal.fill_counts.buyers_unfilled),
+        Packed::Uint32(journal.fill_counts.sellers_filled),
+        Packed::Uint32(journal.fill_counts.sellers_partial),
+        Packed::Uint32(journal.fill_counts.sellers_unfilled),
+        Packed::Uint256(journal.round_id),
+    ])
+}
+
+/// Clear and bind a book as run does before committing its ABI journal
+fn bound_journal(input: &AuctionInput) -> Result<PublicJournal, TestCaseError> {
+    let mut journal = clear(input)?;
+    journal.bind(
+        input_digest(input),
+        config_digest(&input.config),
+        input.config.round_id,
+    );
+    Ok(journal)
+}
+
+proptest! {
+    #[test]
+    fn abi_journal_is_encode_packed(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+    ) {
+        let config = AuctionConfig {
+            algorithm,
+            pricing_rule,
+            journal_variant: JournalVariant::Abi,
+            ..AuctionConfig::default()
+        };
+        let journal = bound_journal(&auction(participants, config))?;
+        let bytes = abi_journal_bytes(&journal);
+        prop_assert_eq!(bytes.len(), 272 + 192 * journal.ids.len());
+        prop_assert_eq!(&bytes, &packed_journal(&journal));
+        let decoded = decode_abi_journal(&bytes).unwrap();
+        let words = |journal: &PublicJournal| risc0_zkvm::serde::to_vec(journal).unwrap();
+        prop_assert_eq!(words(&decoded), words(&journal));
+    }
+}
+
+/// A uint256 read at a fixed offset, as the contract reads it: the value
+/// must fit the last eight bytes
+fn abi_word_at(bytes: &[u8], offset: usize) -> u64 {
+    let word = &bytes[offset..offset + 32];
+    assert_eq!(word[..24], [0; 24], "word at {} exceeds u64", offset);
+    u64::from_be_bytes(word[24..].try_into().unwrap())
+}
+
+/// abi_journal_N4.json and abi_journal_no_crossing_N2.json: the guest's
+/// bytes equal the native encoding, and the fields sit at the offsets a
+/// contract reads them from (rows from byte 84, the clearing after them).
+/// The host's decode_abi_journal refuses bytes of the wrong length
+#[test]
+fn abi_journal_of_two_books() {
+    let crossing = AuctionInput {
+        config: AuctionConfig {
+            journal_variant: JournalVariant::Abi,
+            ..AuctionConfig::default()
+        },
+        ..marginal_inside_ask()
+    };
+    let journal = bound_journal(&crossing).unwrap();
+    let bytes = abi_journal_bytes(&journal);
+    assert_eq!(bytes, packed_journal(&journal));
+    assert_eq!(bytes.len(), 1040);
+    assert_eq!(bytes[..4], 0x0002_0009u32.to_be_bytes());
+    assert_eq!(bytes[4..36], *journal.input_digest.as_bytes());
+    assert_eq!(bytes[72..84], [0, 0, 0, 4, 0, 0, 0, 2, 0, 0, 0, 2]);
+    let column = |k: usize| -> Vec<u64> {
+        (0..4)
+            .map(|row| abi_word_at(&bytes, 84 + 32 * (4 * k + row)))
+            .collect()
+    };
+    assert_eq!(column(0), [0, 1, 2, 3]);
+    assert_eq!(column(3), [600, 1000, 400, 0]);
+    assert_eq!(column(4), [10, 0, 5, 10]);
+    assert_eq!(column(5), [10, 0, 10, 0]); // allocation
+    assert_eq!(bytes[852..856], STATUS_CLEARED.to_be_bytes());
+    assert_eq!(
+        (abi_word_at(&bytes, 856), abi_word_at(&bytes, 888)),
+        (40, 10)
+    );
+    assert_eq!(abi_word_at(&bytes, 920), 0); // fees_collected
+    assert_eq!(abi_word_at(&bytes, 952), 400); // surplus
+    let counts: Vec<u32> = (0..6)
+        .map(|k| u32::from_be_bytes(bytes[984 + 4 * k..988 + 4 * k].try_into().unwrap()))
+        .collect();
+    assert_eq!(counts, [1, 0, 1, 0, 1, 1]); // fill_counts, buyers then sellers
+    assert_eq!(abi_word_at(&bytes, 1008), 0); // round_id
+
+    let participants = vec![order(0, 0, 10, 5, 100, 0), order(1, 1, 20, 5, 0, 5)];
+    let config = AuctionConfig {
+        journal_variant: JournalVariant::Abi,
+        ..AuctionConfig::default()
+    };
+    let journal = bound_journal(&auction(participants, config)).unwrap();
+    let bytes = abi_journal_bytes(&journal);
+    assert_eq!(bytes, packed_journal(&journal));
+    assert_eq!(bytes.len(), 656);
+    assert_eq!(bytes[468..472], STATUS_NO_CROSSING.to_be_bytes());
+    assert_eq!((abi_word_at(&bytes, 472), abi_word_at(&bytes, 504)), (0, 0));
+    assert_eq!((abi_word_at(&bytes, 536), abi_word_at(&bytes, 568)), (0, 0));
+    assert_eq!(
+        bytes[600..624],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]
+    );
+    assert_eq!(abi_word_at(&bytes, 624), 0); // round_id
+    assert_eq!(
+        decode_abi_journal(&bytes[..655]).unwrap_err(),
+        "655 bytes for row_count 2 (expected 656)"
+    );
+    assert_eq!(
+        decode_abi_journal(&bytes[..100]).unwrap_err(),
+        "100 bytes, below the 272 of an empty book"
+    );
+}
+
+/// Round constants of keccak-f[1600], XORed into lane (0, 0) after each round
+const KECCAK_ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808A,
+    0x8000000080008000,
+    0x000000000000808B,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008A,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000A,
+    0x000000008000808B,
+    0x800000000000008B,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800A,
+    0x800000008000000A,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// Rotation offset of lane (x, y), at index x + 5y
+const KECCAK_ROTATIONS: [u32; 25] = [
+    0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14,
+];
+
+/// keccak-f[1600] step by step (θ, ρ and π, χ, ι), without the keccak crate
+fn keccak_permute(state: &mut [u64; 25]) {
+    for round_constant in KECCAK_ROUND_CONSTANTS {
+        let parity: [u64; 5] =
+            std::array::from_fn(|x| (0..5).fold(0, |acc, y| acc ^ state[x + 5 * y]));
+        for x in 0..5 {
+            let d = parity[(x + 4) % 5] ^ parity[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                state[x + 5 * y] ^= d;
+            }
+        }
+        let mut moved = [0u64; 25];
+        for x in 0..5 {
+            for y in 0..5 {
+                moved[y + 5 * ((2 * x + 3 * y) % 5)] =
+                    state[x + 5 * y].rotate_left(KECCAK_ROTATIONS[x + 5 * y]);
+            }
+        }
+        for x in 0..5 {
+            for y in 0..5 {
+                state[x + 5 * y] =
+                    moved[x + 5 * y] ^ (!moved[(x + 1) % 5 + 5 * y] & moved[(x + 2) % 5 + 5 * y]);
+            }
+        }
+        state[0] ^= round_constant;
+    }
+}
+
+/// Keccak-256 as the EVM's keccak256 opcode computes it: bytes XORed into the
+/// state one at a time, a rate of 136 bytes and the padding 0x01 ... 0x80
+fn native_keccak256(bytes: &[u8]) -> [u8; 32] {
+    let mut state = [0u64; 25];
+    let absorb = |position: usize, byte: u8, state: &mut [u64; 25]| {
+        state[position / 8] ^= (byte as u64) << (8 * (position % 8));
+        if position == 135 {
+            keccak_permute(state);
+        }
+    };
+    for (k, &byte) in bytes.iter().enumerate() {
+        absorb(k % 136, byte, &mut state);
+    }
+    let position = bytes.len() % 136;
+    state[position / 8] ^= 0x01 << (8 * (position % 8));
+    state[135 / 8] ^= 0x80 << 56;
+    keccak_permute(&mut state);
+    let mut digest = [0u8; 32];
+    for (k, byte) in digest.iter_mut().enumerate() {
+        *byte = (state[k / 8] >> (8 * (k % 8))) as u8;
+    }
+    digest
+}
+
+/// The known digests, and the shared keccak256 against the native one on
+/// every length from an empty message to three blocks (the padding byte
+/// lands on every position of the rate, alone in a block at 135)
+#[test]
+fn keccak256_matches_the_evm() {
+    let hex = |digest: [u8; 32]| {
+        digest
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    };
+    assert_eq!(
+        hex(native_keccak256(b"")),
+        "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+    );
+    assert_eq!(
+        hex(native_keccak256(b"abc")),
+        "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+    );
+    let message: Vec<u8> = (0..3 * 136).map(|k| (k * 7 + 3) as u8).collect();
+    for len in 0..=message.len() {
+        assert_eq!(
+            keccak256(&message[..len]),
+            native_keccak256(&message[..len]),
+            "length {}",
+            len
+        );
+    }
+}
+
+proptest! {
+    #[test]
+    fn journal_keccak_hashes_the_packed_journal(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+    ) {
+        let config = AuctionConfig {
+            algorithm,
+            pricing_rule,
+            keccak_digest: true,
+            ..AuctionConfig::default()
+        };
+        let journal = bound_journal(&auction(participants, config))?;
+        let digest = journal_keccak(&journal);
+        prop_assert_eq!(digest.as_bytes(), &native_keccak256(&packed_journal(&journal))[..]);
+    }
+}
+
+/// A sealed bid's commitment, hashed apart from the guest as a bidder does
+fn sealed(p: &Participant) -> Digest {
+    let (id, role) = (p.id.to_le_bytes(), p.role.to_le_bytes());
+    let (price, quantity) = (p.price.to_le_bytes(), p.quantity.to_le_bytes());
+    Digest::from(sha256(&[&p.salt, &id, &role, &price, &quantity]))
+}
+
+proptest! {
+    #[test]
+    fn sealed_bids_open_in_the_guest(
+        participants in book(),
+        salts in prop::collection::vec(any::<[u8; 32]>(), MAX_ROWS),
+        row in any::<prop::sample::Index>(),
+    ) {
+        let mut participants: Vec<Participant> = participants
+            .into_iter()
+            .zip(salts)
+            .map(|(p, salt)| Participant { salt, ..p })
+            .collect();
+        let commitments: Vec<Digest> = participants.iter().map(sealed).collect();
+        participants.reverse(); // The list follows the ids, not the rows
+        let input = AuctionInput {
+            commitments,
+            ..auction(participants, AuctionConfig::default())
+        };
+        prop_assert_eq!(validate_input(&input), Ok(()));
+        prop_assert!(clear(&input).is_ok());
+
+        // Row k of the reversed book has the k-th largest id
+        let k = row.index(input.participants.len());
+        let position = input.participants.len() - 1 - k;
+        let mut wrong_salt = input.clone();
+        wrong_salt.participants[k].salt[0] ^= 1;
+        prop_assert_eq!(
+            validate_input(&wrong_salt),
+            Err(AuctionError::CommitmentMismatch(position))
+        );
+        let mut raised = input.clone();
+        raised.participants[k].price += 1;
+        prop_assert_eq!(
+            validate_input(&raised),
+            Err(AuctionError::CommitmentMismatch(position))
+        );
+        let mut wrong_commitment = input.clone();
+        wrong_commitment.commitments[position] = Digest::from([7u32; 8]);
+        prop_assert_eq!(
+            validate_input(&wrong_commitment),
+            Err(AuctionError::CommitmentMismatch(position))
+        );
+        let mut missing = input;
+        missing.commitments.pop();
+        prop_assert_eq!(
+            validate_input(&missing),
+            Err(AuctionError::CommitmentMismatch(missing.commitments.len()))
+        );
+    }
+}
+
+proptest! {
+    #[test]
+    fn audit_journal_ranks_the_book(
+        participants in book(),
+        algorithm in algorithm(),
+        tie_break in prop_oneof![
+            Just(TieBreak::ById),
+            Just(TieBreak::ByQuantityDesc),
+            Just(TieBreak::ByQuantityAsc),
+            Just(TieBreak::Seeded),
+        ],
+        seed in any::<[u8; 32]>(),
+    ) {
+        let config = AuctionConfig { algorithm, tie_break, seed, audit: true, ..AuctionConfig::default() };
+        let input = auction(participants, config);
+        let journal = clear(&input)?;
+        let audit = audit_journal(&input);
+
+        let (buyers, sellers) = (journal.buyer_count as usize, journal.seller_count as usize);
+        prop_assert_eq!((audit.bid_prices.len(), audit.bid_quantities.len()), (buyers, buyers));
+        prop_assert_eq!((audit.ask_prices.len(), audit.ask_quantities.len()), (sellers, sellers));
+        prop_assert!(audit.bid_prices.windows(2).all(|pair| pair[0] >= pair[1]));
+        prop_assert!(audit.ask_prices.windows(2).all(|pair| pair[0] <= pair[1]));
+        let bids = audit.bid_prices.iter().zip(&audit.bid_quantities);
+        let asks = audit.ask_prices.iter().zip(&audit.ask_quantities);
+        for (row, (&price, &quantity)) in bids.chain(asks).enumerate() {
+            let p = input.participants.iter().find(|p| p.id == journal.ids[row]).unwrap();
+            prop_assert_eq!((p.price, p.quantity), (price, quantity), "row {}", row);
+        }
+        prop_assert_eq!(check_audit(&journal, &audit, &input.participants), Ok(()));
+
+        if sellers > 0 {
+            let mut missing = audit.clone();
+            missing.ask_prices.pop();
+            prop_assert_eq!(
+                check_audit(&journal, &missing, &input.participants),
+                Err(AuctionError::MalformedJournal(journal.ids.len()))
+            );
+        }
+        // Swapping the first and last bid breaks the order or names the wrong quote
+        if buyers > 1 && audit.bid_prices[0] != audit.bid_prices[buyers - 1] {
+            let mut swapped = audit.clone();
+            swapped.bid_prices.swap(0, buyers - 1);
+            prop_assert_eq!(
+                check_audit(&journal, &swapped, &input.participants),
+                Err(AuctionError::MalformedJournal(0))
+            );
+        }
+    }
+}
+
+/// audit_journal_N4.json (marginal_inside_ask_N4.json with audit set): the
+/// bids 60 and 30 for 10, the asks 20 for 15 and 50 for 10, and the demand
+/// and supply the host prints from them, crossing at the clearing price 40
+#[test]
+fn audit_journal_of_marginal_inside_ask() {
+    let input = AuctionInput {
+        config: AuctionConfig {
+            audit: true,
+            ..AuctionConfig::default()
+        },
+        ..marginal_inside_ask()
+    };
+    let journal = clear_book(&input).unwrap();
+    let audit = audit_journal(&input);
+    assert_eq!(audit.bid_prices, vec![60, 30]);
+    assert_eq!(audit.bid_quantities, vec![10, 10]);
+    assert_eq!(audit.ask_prices, vec![20, 50]);
+    assert_eq!(audit.ask_quantities, vec![15, 10]);
+    assert_eq!(check_audit(&journal, &audit, &input.participants), Ok(()));
+
+    let at = |price: u64| {
+        let demand: u64 = (audit.bid_prices.iter().zip(&audit.bid_quantities))
+            .filter(|(&bid, _)| bid >= price)
+            .map(|(_, &quantity)| quantity)
+            .sum();
+        let supply: u64 = (audit.ask_prices.iter().zip(&audit.ask_quantities))
+            .filter(|(&ask, _)| ask <= price)
+            .map(|(_, &quantity)| quantity)
+            .sum();
+        (demand, supply)
+    };
+    let table: Vec<(u64, u64)> = [20, 30, 40, 50, 60].into_iter().map(at).collect();
+    assert_eq!(
+        table,
+        vec![(20, 15), (20, 15), (10, 15), (10, 25), (10, 25)]
+    );
+    assert_eq!(journal.clearing_price, 40);
+
+    // Seller 2's quote committed as seller 3's: ids[2] names seller 2
+    let mut renamed = audit;
+    renamed.ask_quantities.swap(0, 1);
+    assert_eq!(
+        check_audit(&journal, &renamed, &input.participants),
+        Err(AuctionError::MalformedJournal(2))
+    );
+}
+
+/// How the guest's single-book branch ends on an input
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Exit(u8),    // Committed this error code
+    PassThrough, // Committed the no-trade journal
+    Cleared,     // Committed a journal in which some row traded
+}
+
+fn outcome(input: &AuctionInput) -> Outcome {
+    let cleared = validate_input(input).and_then(|()| match input.config.max_trades {
+        Some(max_trades) => clear_with_trades(input, max_trades).map(|(journal, _)| journal),
+        None => clear_book(input),
+    });
+    match cleared {
+        Err(error) => Outcome::Exit(error.exit_code()),
+        Ok(journal)
+            if journal.out_coin == journal.in_coin && journal.out_energy == journal.in_energy =>
+        {
+            Outcome::PassThrough
+        }
+        Ok(_) => Outcome::Cleared,
+    }
+}
+
+#[test]
+fn every_exit_code() {
+    use AuctionError::*;
+    // (error, exit code, a no-trade journal may stand in for it)
+    let table = [
+        (DuplicateId(0), 1, false),
+        (InvalidRole(0), 2, false),
+        (TooManyParticipants(MAX_PARTICIPANTS + 1), 3, false),
+        (TooManyOrders(MAX_EXPANDED_ORDERS + 1), 4, true),
+        (Overflow, 5, true),
+        (InsufficientCoin(0, 1), 6, true),
+        (InsufficientEnergy(0, 1), 6, true),
+        (SettlementBound, 7, false),
+        (Internal(0), 8, false),
+        (MalformedJournal(0), 9, true),
+        (PriceAboveMax(0), 10, false),
+        (Irrational(0), 11, true),
+        (Infeasible(0), 12, true),
+        (EmptyMarket, 13, false),
+        (TooManyTrades(2), 14, true),
+        (CommitmentMismatch(0), 15, false),
+        (UnsupportedMode, 16, false),
+        (InvalidKDouble, 17, false),
+    ];
+    for (error, code, passes_through) in &table {
+        assert_eq!(error.exit_code(), *code, "{:?}", error);
+        assert_eq!(error.passes_through(), *passes_through, "{:?}", error);
+    }
+    let mut codes: Vec<u8> = table.iter().map(|(_, code, _)| *code).collect();
+    codes.dedup();
+    assert_eq!(codes, (1..=17).collect::<Vec<u8>>());
+}
+
+#[test]
+fn every_input_exit_code() {
+    let big = u64::MAX / 2 + 2; // Two of these settle past i128 at price u64::MAX
+    let stepped = |id: u32| Participant {
+        steps: vec![(10, 1); MAX_STEPS],
+        ..order(id, 1, 10, MAX_STEPS as u64, 0, MAX_STEPS as u64)
+    };
+    // (input, outcome under strict, outcome under strict: false)
+    let table: Vec<(&str, Vec<Participant>, AuctionConfig, Outcome, Outcome)> = vec![
+        (
+            "duplicate id",
+            vec![order(0, 0, 20, 1, 100, 0), order(0, 1, 10, 1, 0, 1)],
+            AuctionConfig::default(),
+            Outcome::Exit(1),
+            Outcome::Exit(1),
+        ),
+        (
+            "unknown role",
+            vec![order(0, 0, 20, 1, 100, 0), order(1, 3, 10, 1, 0, 1)],
+            AuctionConfig::default(),
+            Outcome::Exit(2),
+            Outcome::Exit(2),
+        ),
+        (
+            "participant limit",
+            (0..=MAX_PARTICIPANTS as u32)
+                .map(|id| order(id, 1, 10, 1, 0, 1))
+                .collect(),
+            AuctionConfig::default(),
+            Outcome::Exit(3),
+            Outcome::Exit(3),
+        ),
+        (
+            "order limit",
+            (0..=(MAX_EXPANDED_ORDERS / MAX_STEPS) as u32)
+                .map(stepped)
+                .collect(),
+            AuctionConfig::default(),
+            Outcome::Exit(4),
+            Outcome::PassThrough,
+        ),
+        (
+            "seller coin past u64::MAX",
+            vec![
+                order(0, 0, 10, 100, 1000, 0),
+                order(1, 1, 10, 100, u64::MAX - 999, 100),
+            ],
+            AuctionConfig::default(),
+            Outcome::Exit(5),
+            Outcome::PassThrough,
+        ),
+        (
+            "settlement bound",
+            vec![
+                order(0, 0, u64::MAX, big, u64::MAX, 0),
+                order(1, 1, 1, big, 0, big),
+            ],
+            AuctionConfig {
+                max_price: u64::MAX,
+                ..AuctionConfig::default()
+            },
+            Outcome::Exit(7),
+            Outcome::Exit(7),
+        ),
+        (
+            "price above max_price",
+            vec![
+                order(0, 0, u64::MAX, 1, 100, 0),
+                order(1, 0, 20, 1, 100, 0),
+                order(2, 1, 10, 1, 0, 1),
+            ],
+            AuctionConfig::default(),
+            Outcome::Exit(10),
+            Outcome::Cleared,
+        ),
+        (
+            "empty market",
+            Vec::new(),
+            AuctionConfig::default(),
+            Outcome::Exit(13),
+            Outcome::Exit(13),
+        ),
+        (
+            "trade list over max_trades",
+            vec![
+                order(0, 0, 20, 1, 100, 0),
+                order(1, 0, 20, 1, 100, 0),
+                order(2, 1, 10, 2, 0, 2),
+            ],
+            greedy(1),
+            Outcome::Exit(14),
+            Outcome::PassThrough,
+        ),
+        (
+            "carbon pricing with losses",
+            vec![
+                order(0, 0, 20, 1, 100, 0),
+                Participant {
+                    carbon_per_unit: 3,
+                    ..order(1, 1, 10, 1, 0, 1)
+                },
+            ],
+            AuctionConfig {
+                loss_bps: 100,
+                loss_sink_id: Some(1),
+                ..AuctionConfig::default()
+            },
+            Outcome::Exit(16),
+            Outcome::Exit(16),
+        ),
+        (
+            "secondary round with a prosumer",
+            vec![
+                order(0, 0, 20, 1, 100, 0),
+                order(1, 1, 10, 1, 0, 1),
+                order(2, 2, 15, 1, 100, 1),
+            ],
+            AuctionConfig {
+                secondary_concession: Some(5),
+                ..AuctionConfig::default()
+            },
+            Outcome::Exit(16),
+            Outcome::Exit(16),
+        ),
+        (
+            "k-double weight above 1",
+            vec![order(0, 0, 20, 1, 100, 0), order(1, 1, 10, 1, 0, 1)],
+            AuctionConfig {
+                pricing_rule: PricingRule::KDouble,
+                k_numerator: 3,
+                k_denominator: 2,
+                ..AuctionConfig::default()
+            },
+            Outcome::Exit(17),
+            Outcome::Exit(17),
+        ),
+    ];
+    for (name, participants, config, strict, lenient) in table {
+        let input = auction(participants, config);
+        assert_eq!(outcome(&input), strict, "{} (strict)", name);
+        let lenient_input = auction(
+            input.participants,
+            AuctionConfig {
+                strict: false,
+                ..input.config
+            },
+        );
+        assert_eq!(outcome(&lenient_input), lenient, "{} (strict: false)", name);
+    }
+}
+
+/// The exit codes no valid input reaches through clearing, each triggered
+/// on crossed_book (cleared at 45) by the check that raises it: an
+/// allocation past buyer 0's coin (6), a journal of another layout
+/// version (9), buyer 0 charged 650 for units it bid 600 for (11), seller
+/// 3 gaining the unit buyer 0 lost (12) and a bid that does not open its
+/// commitment (15); every_input_exit_code triggers the rest but Internal
+/// (8), an invariant of the commodity and period ledgers no input breaks
+#[test]
+fn every_check_exit_code() {
+    use AuctionError::*;
+    let input = auction(crossed_book(), AuctionConfig::default());
+    let journal = clear_book(&input).unwrap();
+    let participants = &input.participants;
+    let config = &input.config;
+
+    let overspent = [((0, 0), 23), ((2, 1), 10)];
+    let mut old_version = journal.clone();
+    old_version.version = 0;
+    let mut overcharged = journal.clone();
+    overcharged.out_coin = vec![350, 1000, 650, 0];
+    let mut moved_back = journal.clone();
+    moved_back.out_energy = vec![9, 0, 0, 11];
+    let mut sealed = input.clone();
+    sealed.commitments = vec![Digest::from([7u32; 8]); 4];
+
+    let table = [
+        (
+            compute_outputs(participants, &overspent, (45, 45), config).map(|_| ()),
+            InsufficientCoin(0, 23),
+        ),
+        (
+            check_journal(&old_version, participants, config),
+            MalformedJournal(0),
+        ),
+        (
+            check_rationality(&overcharged, participants, config),
+            Irrational(0),
+        ),
+        (
+            check_feasibility(&moved_back, participants, config),
+            Infeasible(3),
+        ),
+        (validate_input(&sealed), CommitmentMismatch(0)),
+    ];
+    for ((result, error), code) in table.into_iter().zip([6, 9, 11, 12, 15]) {
+        assert_eq!(result, Err(error.clone()));
+        assert_eq!(error.exit_code(), code, "{:?}", error);
+    }
+    assert_eq!(check_journal(&journal, participants, config), Ok(()));
+}
+
+/// The words a cleared book commits: the version, the input digest (zero
+/// until run binds it, eight words), the algorithm id, the config digest
+/// (bound by run too), each column as its length
+/// and its values, one word per id or count and two per u64 (low first),
+/// then the status, clearing_price, traded_volume, fees_collected, surplus
+/// and the six fill counts
+#[test]
+fn journal_words_end_with_the_clearing() {
+    let input = auction(
+        vec![order(0, 0, 30, 1, 100, 0), order(1, 1, 10, 1, 0, 1)],
+        AuctionConfig::default(),
+    );
+    let journal = clear_book(&input).unwrap();
+    let words = risc0_zkvm::serde::to_vec(&journal).unwrap();
+    #[rustfmt::skip]
+    let expected = [
+        JOURNAL_VERSION, // version
+        0, 0, 0, 0,      // input_digest
+        0, 0, 0, 0,
+        ALGORITHM_ID,    // algorithm_id
+        0, 0, 0, 0,      // config_digest
+        0, 0, 0, 0,
+        2, 0, 1,         // ids
+        1, 1,            // buyer_count, seller_count
+        2, 100, 0, 0, 0, // in_coin
+        2, 0, 0, 1, 0,   // in_energy
+        2, 80, 0, 20, 0, // out_coin
+        2, 1, 0, 0, 0,   // out_energy
+        2, 1, 0, 1, 0,   // allocation
+        STATUS_CLEARED,  // status
+        20, 0,           // clearing_price (mid-point of 30 and 10)
+        1, 0,            // traded_volume
+        0, 0,            // fees_collected
+        20, 0,           // surplus (30 − 10 on one unit)
+        1, 0, 0,         // fill_counts: the buyer filled...
+        1, 0, 0,         // ...and the seller
+        0, 0,            // round_id
+    ];
+    assert_eq!(words, expected);
+    let decoded: PublicJournal = risc0_zkvm::serde::from_slice(&words).unwrap();
+    assert_eq!((decoded.clearing_price, decoded.traded_volume), (20, 1));
+}
+
+proptest! {
+    #[test]
+    fn round_id_is_echoed_into_every_journal(
+        participants in book(),
+        algorithm in algorithm(),
+        round_id in any::<u64>(),
+    ) {
+        let config = AuctionConfig { algorithm, round_id, ..AuctionConfig::default() };
+        let journal = bound_journal(&auction(participants, config))?;
+        prop_assert_eq!(journal.round_id, round_id);
+        let words = risc0_zkvm::serde::to_vec(&journal).unwrap();
+        prop_assert_eq!(&words[words.len() - 2..], &[round_id as u32, (round_id >> 32) as u32]);
+        let bytes = abi_journal_bytes(&journal);
+        prop_assert_eq!(abi_word_at(&bytes, bytes.len() - 32), round_id);
+        prop_assert_eq!(compact_journal(&journal).round_id, round_id);
+        prop_assert_eq!(delta_journal(&journal).unwrap().round_id, round_id);
+        prop_assert_eq!(quantized_journal(&journal, 1).unwrap().round_id, round_id);
+        prop_assert_eq!(slim_journal(&journal).round_id, round_id);
+        prop_assert_eq!(private_journal(&journal, &[0; 32]).round_id, round_id);
+    }
+}
+
+/// round_id_N4.json: marginal_inside_ask_N4.json for round 42 clears as
+/// the book does without a round and commits 42. Replayed for round 43,
+/// its receipt fails both checks the host runs: the round id is not the
+/// one --expect-round-id gives, and the config digest is not that of the
+/// round 43 config the host writes
+#[test]
+fn round_id_of_marginal_inside_ask() {
+    let input = AuctionInput {
+        config: AuctionConfig {
+            round_id: 42,
+            ..AuctionConfig::default()
+        },
+        ..marginal_inside_ask()
+    };
+    let mut journal = clear_book(&input).unwrap();
+    journal.bind(
+        input_digest(&input),
+        config_digest(&input.config),
+        input.config.round_id,
+    );
+    assert_eq!(journal.round_id, 42);
+    let unbound = clear_book(&marginal_inside_ask()).unwrap();
+    assert_eq!(
+        (&journal.out_coin, &journal.out_energy),
+        (&unbound.out_coin, &unbound.out_energy)
+    );
+    assert_eq!(unbound.round_id, 0);
+
+    let replayed = AuctionConfig {
+        round_id: 43,
+        ..input.config.clone()
+    };
+    assert_ne!(journal.round_id, replayed.round_id);
+    assert_ne!(journal.config_digest, config_digest(&replayed));
+    assert_eq!(journal.config_digest, config_digest(&input.config));
+}
+
+/// Id of the operator row the fee books add after the generated ids
+const OPERATOR: u32 = MAX_ROWS as u32;
+
+proptest! {
+    #[test]
+    fn fees_collected_matches_every_fee(
+        participants in book(),
+        algorithm in prop_oneof![
+            Just(Algorithm::UniformPrice),
+            Just(Algorithm::EnglishClock),
+            Just(Algorithm::Merge),
+        ],
+        pricing_rule in pricing_rule(),
+        fee_bps in 1u64..=500,
+    ) {
+        let mut participants = participants;
+        participants.push(order(OPERATOR, 1, 0, 0, 0, 0));
+        let config = AuctionConfig {
+            algorithm,
+            pricing_rule,
+            fee_bps,
+            operator_id: Some(OPERATOR),
+            ..AuctionConfig::default()
+        };
+        let input = auction(participants, config);
+        let journal = clear(&input)?;
+        prop_assert_eq!(check_journal(&journal, &input.participants, &input.config), Ok(()));
+
+        // Uniform settlement: every side pays its fee on clearing_price × units moved
+        let mut expected = 0u64;
+        let mut gain = 0i128;
+        for row in 0..journal.ids.len() {
+            let moved = journal.in_energy[row].abs_diff(journal.out_energy[row]);
+            if journal.ids[row] == OPERATOR {
+                gain = journal.out_coin[row] as i128 - journal.in_coin[row] as i128;
+            } else {
+                let notional = journal.clearing_price as u128 * moved as u128;
+                expected += (notional * fee_bps as u128 / 10_000) as u64;
+            }
+        }
+        prop_assert_eq!(journal.fees_collected, expected);
+        prop_assert_eq!(gain, expected as i128);
+    }
+}
+
+/// fee_bps_N4.json: notionals 98, 42 and 140 at 250 bps carry fees 2, 1 and
+/// 3, so 6 coins are collected where the exact fees sum to 7.0, and the
+/// operator (row 2 in protocol order) gains exactly those 6
+#[test]
+fn fee_bps_book_collects_floored_fees() {
+    let participants = vec![
+        order(0, 0, 20, 7, 1000, 0),
+        order(1, 0, 18, 3, 1000, 0),
+        order(2, 1, 10, 10, 0, 10),
+        order(3, 1, 0, 0, 0, 0),
+    ];
+    let config = AuctionConfig {
+        fee_bps: 250,
+        operator_id: Some(3),
+        ..AuctionConfig::default()
+    };
+    let input = auction(participants, config);
+    let journal = clear_book(&input).unwrap();
+    assert_eq!(journal.ids, [0, 1, 3, 2]);
+    assert_eq!(journal.out_coin, [900, 957, 6, 137]);
+    assert_eq!(journal.fees_collected, 6);
+
+    for miscount in [5, 7] {
+        let tampered = PublicJournal {
+            fees_collected: miscount,
+            ..journal.clone()
+        };
+        assert_eq!(
+            check_journal(&tampered, &input.participants, &input.config),
+            Err(AuctionError::MalformedJournal(2)),
+            "fees_collected {}",
+            miscount
+        );
+    }
+}
+
+/// mixed_balances_N5.json, mixed_balances_greedy_N4.json and
+/// mixed_balances_dutch_N3.json: buyers already hold energy and sellers coin,
+/// so settlement moves each traded row from what it holds rather than from
+/// zero; coin and energy are conserved and the untraded rows (buyer 1 and
+/// seller 3) end with exactly their inputs
+#[test]
+fn mixed_balances_settle_on_top_of_what_rows_hold() {
+    let book = vec![
+        order(0, 0, 30, 5, 500, 4),
+        order(1, 0, 12, 5, 300, 9),
+        order(2, 1, 10, 5, 70, 9),
+        order(3, 1, 40, 5, 25, 8),
+    ];
+    let mut with_operator = book.clone();
+    with_operator.push(order(4, 1, 0, 0, 40, 3));
+    let cases = [
+        (
+            with_operator,
+            AuctionConfig {
+                fee_bps: 100,
+                operator_id: Some(4),
+                ..AuctionConfig::default()
+            },
+            vec![399, 300, 42, 169, 25],
+            vec![9, 9, 3, 4, 8],
+        ),
+        (
+            book,
+            AuctionConfig {
+                algorithm: Algorithm::Greedy,
+                ..AuctionConfig::default()
+            },
+            vec![400, 300, 170, 25],
+            vec![9, 9, 4, 8],
+        ),
+        (
+            vec![
+                order(0, 0, 95, 10, 2000, 6),
+                order(1, 0, 40, 10, 500, 2),
+                order(2, 1, 50, 10, 30, 12),
+            ],
+            AuctionConfig {
+                algorithm: Algorithm::DutchClock,
+                clock_increment: 50,
+                clock_tick: 10,
+                ..AuctionConfig::default()
+            },
+            vec![1100, 500, 930],
+            vec![16, 2, 2],
+        ),
+    ];
+
+    for (participants, config, out_coin, out_energy) in cases {
+        let input = auction(participants, config);
+        let journal = clear_book(&input).unwrap();
+        assert_eq!(journal.out_coin, out_coin, "{:?}", input.config.algorithm);
+        assert_eq!(
+            journal.out_energy, out_energy,
+            "{:?}",
+            input.config.algorithm
+        );
+        assert_eq!(
+            journal.in_coin.iter().sum::<u64>(),
+            journal.out_coin.iter().sum::<u64>()
+        );
+        assert_eq!(
+            journal.in_energy.iter().sum::<u64>(),
+            journal.out_energy.iter().sum::<u64>()
+        );
+        for (row, &id) in journal.ids.iter().enumerate() {
+            if journal.allocation[row] == 0 && Some(id) != input.config.operator_id {
+                assert_eq!(journal.out_coin[row], journal.in_coin[row], "id {}", id);
+                assert_eq!(journal.out_energy[row], journal.in_energy[row], "id {}", id);
+            }
+        }
+        assert_eq!(
+            check_journal(&journal, &input.participants, &input.config),
+            Ok(())
+        );
+    }
+}
+
+proptest! {
+    #[test]
+    fn surplus_matches_the_rows(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+    ) {
+        let config = AuctionConfig { algorithm, pricing_rule, ..AuctionConfig::default() };
+        let input = auction(participants, config);
+        let journal = clear(&input)?;
+
+        // Unstepped orders: each row's units moved are its matched units
+        let mut expected = 0i128;
+        for (row, p) in protocol_order(&input.participants, &input.config).iter().enumerate() {
+            let moved = journal.in_energy[row].abs_diff(journal.out_energy[row]) as i128;
+            match p.role {
+                0 => expected += p.price as i128 * moved,
+                _ => expected -= p.price as i128 * moved,
+            }
+        }
+        prop_assert_eq!(journal.surplus as i128, expected);
+    }
+}
+
+/// Surplus of books computed by hand: marginal_inside_ask_N4.json (bids 60
+/// and 30 against asks 20 and 50, 10 units of 60 − 20), fee_bps_N4.json
+/// (20 × 7 + 18 × 3 − 10 × 10, fees do not count), steps_partial_N3.json
+/// (steps 50 × 10 then 40 × 15 against ask 20 × 25; the whole allocation at
+/// each step would give 1750) and a book that does not trade
+#[test]
+fn surplus_of_hand_computed_books() {
+    let stepped = Participant {
+        steps: vec![(50, 10), (40, 20)],
+        ..order(0, 0, 50, 30, 1000, 0)
+    };
+    let table = [
+        (
+            "marginal_inside_ask",
+            marginal_inside_ask().participants,
+            0,
+            400,
+        ),
+        (
+            "fee_bps",
+            vec![
+                order(0, 0, 20, 7, 1000, 0),
+                order(1, 0, 18, 3, 1000, 0),
+                order(2, 1, 10, 10, 0, 10),
+                order(3, 1, 0, 0, 0, 0),
+            ],
+            250,
+            94,
+        ),
+        (
+            "steps_partial",
+            vec![
+                stepped,
+                order(1, 0, 30, 10, 1000, 0),
+                order(2, 1, 20, 40, 0, 25),
+            ],
+            0,
+            600,
+        ),
+        (
+            "no crossing",
+            vec![order(0, 0, 10, 5, 100, 0), order(1, 1, 20, 5, 0, 5)],
+            0,
+            0,
+        ),
+    ];
+    for (name, participants, fee_bps, expected) in table {
+        let config = AuctionConfig {
+            fee_bps,
+            operator_id: (fee_bps > 0).then_some(3),
+            ..AuctionConfig::default()
+        };
+        let journal = clear_book(&auction(participants, config)).unwrap();
+        assert_eq!(journal.surplus, expected, "{}", name);
+        assert_eq!(journal.traded_volume == 0, expected == 0, "{}", name);
+    }
+}
+
+proptest! {
+    #[test]
+    fn fill_counts_match_the_rows(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+    ) {
+        let config = AuctionConfig { algorithm, pricing_rule, ..AuctionConfig::default() };
+        let input = auction(participants, config);
+        let journal = clear(&input)?;
+
+        // Unstepped orders, one per row: a row's units moved are its allocation
+        let mut counts = [[0u32; 3]; 2];
+        for (row, p) in protocol_order(&input.participants, &input.config).iter().enumerate() {
+            let moved = journal.in_energy[row].abs_diff(journal.out_energy[row]);
+            let class = match moved {
+                0 => 2,
+                moved if moved == p.quantity => 0,
+                _ => 1,
+            };
+            counts[p.role as usize][class] += 1;
+        }
+        let fills = journal.fill_counts;
+        prop_assert_eq!(
+            counts,
+            [
+                [fills.buyers_filled, fills.buyers_partial, fills.buyers_unfilled],
+                [fills.sellers_filled, fills.sellers_partial, fills.sellers_unfilled],
+            ]
+        );
+    }
+}
+
+/// Fill counts of books computed by hand: marginal_inside_ask_N4.json
+/// (buyer 0 takes its 10, seller 2 sells 10 of 15), fill_counts_N2.json
+/// (a bid for 10 with 100 coin buys 5 at the clearing price 20, so both
+/// rows are partial), steps_partial_N3.json (25 of the stepped bid's 30,
+/// and the seller's 25 energy of its 40) and a book that does not trade
+#[test]
+fn fill_counts_of_hand_computed_books() {
+    let stepped = Participant {
+        steps: vec![(50, 10), (40, 20)],
+        ..order(0, 0, 50, 30, 1000, 0)
+    };
+    let table = [
+        (
+            "marginal_inside_ask",
+            marginal_inside_ask().participants,
+            [1, 0, 1, 0, 1, 1],
+        ),
+        (
+            "buyer capped by its coin",
+            vec![order(0, 0, 30, 10, 100, 0), order(1, 1, 10, 10, 0, 10)],
+            [0, 1, 0, 0, 1, 0],
+        ),
+        (
+            "steps_partial",
+            vec![
+                stepped,
+                order(1, 0, 30, 10, 1000, 0),
+                order(2, 1, 20, 40, 0, 25),
+            ],
+            [0, 1, 1, 0, 1, 0],
+        ),
+        (
+            "no crossing",
+            vec![order(0, 0, 10, 5, 100, 0), order(1, 1, 20, 5, 0, 5)],
+            [0, 0, 1, 0, 0, 1],
+        ),
+    ];
+    for (name, participants, expected) in table {
+        let journal = clear_book(&auction(participants, AuctionConfig::default())).unwrap();
+        let fills = journal.fill_counts;
+        let counts = [
+            fills.buyers_filled,
+            fills.buyers_partial,
+            fills.buyers_unfilled,
+            fills.sellers_filled,
+            fills.sellers_partial,
+            fills.sellers_unfilled,
+        ];
+        assert_eq!(counts, expected, "{}", name);
+    }
+
+    // Counts short of a block, and a filled row in a book that did not trade
+    let input = marginal_inside_ask();
+    let journal = clear_book(&input).unwrap();
+    let short = PublicJournal {
+        fill_counts: FillCounts {
+            buyers_unfilled: 0,
+            ..journal.fill_counts
+        },
+        ..journal.clone()
+    };
+    assert_eq!(
+        check_journal(&short, &input.participants, &input.config),
+        Err(AuctionError::MalformedJournal(4))
+    );
+    let input = auction(
+        vec![order(0, 0, 10, 5, 100, 0), order(1, 1, 20, 5, 0, 5)],
+        AuctionConfig::default(),
+    );
+    let journal = clear_book(&input).unwrap();
+    let filled = PublicJournal {
+        fill_counts: FillCounts {
+            buyers_filled: 1,
+            buyers_unfilled: 0,
+            ..journal.fill_counts
+        },
+        ..journal
+    };
+    assert_eq!(
+        check_journal(&filled, &input.participants, &input.config),
+        Err(AuctionError::MalformedJournal(2))
+    );
+}
+
+proptest! {
+    #[test]
+    fn allocation_matches_the_rows(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+        row in any::<prop::sample::Index>(),
+    ) {
+        let config = AuctionConfig { algorithm, pricing_rule, ..AuctionConfig::default() };
+        let input = auction(participants, config);
+        let journal = clear(&input)?;
+
+        // No losses or prosumers: a row's allocation is the energy it moves
+        let moved: Vec<u64> = (0..journal.ids.len())
+            .map(|row| journal.in_energy[row].abs_diff(journal.out_energy[row]))
+            .collect();
+        prop_assert_eq!(&journal.allocation, &moved);
+        if journal.ids.is_empty() {
+            return Ok(());
+        }
+        let row = row.index(journal.ids.len());
+        let mut tampered = journal.clone();
+        tampered.allocation[row] += 1;
+        prop_assert_eq!(
+            check_journal(&tampered, &input.participants, &input.config),
+            Err(AuctionError::MalformedJournal(row))
+        );
+    }
+}
+
+/// Allocations of books computed by hand: marginal_inside_ask_N4.json
+/// under uniform settlement (buyer 0 and seller 2 trade 10 at 40),
+/// pay_as_bid_N5.json (every buyer and seller is allocated its 10 and
+/// settles at its own quote, the operator row nothing) and
+/// allocation_pay_as_bid_N3.json (6 of the bid's 10, limited by the
+/// seller's energy: the buyer pays 600, the seller gets 360)
+#[test]
+fn allocation_of_hand_computed_books() {
+    let pay_as_bid = |operator_id| AuctionConfig {
+        settlement: Settlement::PayAsBid,
+        operator_id: Some(operator_id),
+        ..AuctionConfig::default()
+    };
+    let table = [
+        (
+            "marginal_inside_ask",
+            marginal_inside_ask(),
+            vec![10, 0, 10, 0],
+            vec![600, 1000, 400, 0],
+        ),
+        (
+            "pay_as_bid",
+            auction(
+                vec![
+                    order(0, 0, 100, 10, 2000, 0),
+                    order(1, 0, 90, 10, 2000, 0),
+                    order(2, 1, 60, 10, 0, 10),
+                    order(3, 1, 70, 10, 0, 10),
+                    order(4, 1, 0, 0, 0, 0),
+                ],
+                pay_as_bid(4),
+            ),
+            vec![10, 10, 0, 10, 10],
+            vec![1000, 1100, 600, 600, 700],
+        ),
+        (
+            "allocation_pay_as_bid",
+            auction(
+                vec![
+                    order(0, 0, 100, 10, 2000, 0),
+                    order(1, 1, 60, 10, 0, 6),
+                    order(2, 1, 0, 0, 0, 0),
+                ],
+                pay_as_bid(2),
+            ),
+            vec![6, 0, 6],
+            vec![1400, 240, 360],
+        ),
+    ];
+    for (name, input, allocation, out_coin) in table {
+        let journal = clear_book(&input).unwrap();
+        assert_eq!(journal.allocation, allocation, "{}", name);
+        assert_eq!(journal.out_coin, out_coin, "{}", name);
+    }
+
+    // Ten coin moved from seller 2 to buyer 0 keeps both totals but not the price
+    let input = marginal_inside_ask();
+    let mut journal = clear_book(&input).unwrap();
+    journal.out_coin[0] += 10;
+    journal.out_coin[2] -= 10;
+    assert_eq!(
+        check_journal(&journal, &input.participants, &input.config),
+        Err(AuctionError::MalformedJournal(0))
+    );
+}
+
+/// The status of each way a book ends: status_*.json pins the middle three
+#[test]
+fn status_of_each_outcome() {
+    let table = [
+        (
+            "crossing",
+            marginal_inside_ask().participants,
+            AuctionConfig::default(),
+            STATUS_CLEARED,
+        ),
+        (
+            "best bid 10 below the ask 20",
+            vec![order(0, 0, 10, 5, 100, 0), order(1, 1, 20, 5, 0, 5)],
+            AuctionConfig::default(),
+            STATUS_NO_CROSSING,
+        ),
+        (
+            "crossing, but the buyer holds no coin",
+            vec![order(0, 0, 30, 5, 0, 0), order(1, 1, 10, 5, 0, 5)],
+            AuctionConfig::default(),
+            STATUS_ZERO_VOLUME,
+        ),
+        (
+            "fee without an operator",
+            vec![order(0, 0, 30, 5, 200, 0), order(1, 1, 10, 5, 0, 5)],
+            AuctionConfig {
+                fee_bps: 10,
+                ..AuctionConfig::default()
+            },
+            STATUS_NO_SETTLEMENT_ACCOUNT,
+        ),
+        (
+            "seller coin past u64::MAX, lenient",
+            vec![
+                order(0, 0, 10, 100, 1000, 0),
+                order(1, 1, 10, 100, u64::MAX - 999, 100),
+            ],
+            AuctionConfig {
+                strict: false,
+                ..AuctionConfig::default()
+            },
+            STATUS_SETTLED_AS_NO_TRADE,
+        ),
+    ];
+    for (name, participants, config, status) in table {
+        let journal = clear_book(&auction(participants, config)).unwrap();
+        assert_eq!(journal.status, status, "{}", name);
+        assert_eq!(
+            journal.traded_volume > 0,
+            status == STATUS_CLEARED,
+            "{}",
+            name
+        );
+    }
+
+    let input = marginal_inside_ask();
+    let mut journal = clear_book(&input).unwrap();
+    journal.traded_volume = 0;
+    assert_eq!(
+        check_journal(&journal, &input.participants, &input.config),
+        Err(AuctionError::MalformedJournal(4))
+    );
+}
+
+/// Words of a stored journal fixture: whitespace-separated, `#` lines are comments
+fn fixture_words(text: &str) -> Vec<u32> {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(str::split_whitespace)
+        .map(|word| word.parse().unwrap())
+        .collect()
+}
+
+/// marginal_inside_ask_N4.json, the book of the stored journal fixtures
+fn marginal_inside_ask() -> AuctionInput {
+    let participants = vec![
+        order(0, 0, 60, 10, 1000, 0),
+        order(1, 0, 30, 10, 1000, 0),
+        order(2, 1, 20, 15, 0, 15),
+        order(3, 1, 50, 10, 0, 10),
+    ];
+    auction(participants, AuctionConfig::default())
+}
+
+/// fixtures/public_journal_v1.txt to public_journal_v5.txt: the words
+/// marginal_inside_ask_N4.json committed under layout version 1, before the
+/// input digest, version 2, before the status, version 3, before the
+/// algorithm id and config digest, version 4, before fees_collected,
+/// version 5, before surplus, version 6, before fill_counts, version 7,
+/// before allocation, and version 8, before round_id. A decoder of the
+/// current layout must refuse them by their first word (read as version 9,
+/// the ids would fill a digest, and a version 8 journal ends two words
+/// short), and a journal that claims an old version fails check_journal at
+/// row 0
+#[test]
+fn stored_old_journals_are_refused() {
+    let fixtures = [
+        (1, include_str!("fixtures/public_journal_v1.txt")),
+        (2, include_str!("fixtures/public_journal_v2.txt")),
+        (3, include_str!("fixtures/public_journal_v3.txt")),
+        (4, include_str!("fixtures/public_journal_v4.txt")),
+        (5, include_str!("fixtures/public_journal_v5.txt")),
+        (6, include_str!("fixtures/public_journal_v6.txt")),
+        (7, include_str!("fixtures/public_journal_v7.txt")),
+        (8, include_str!("fixtures/public_journal_v8.txt")),
+    ];
+    for (expected, fixture) in fixtures {
+        let words = fixture_words(fixture);
+        let version: u32 = risc0_zkvm::serde::from_slice(&words).unwrap();
+        assert_eq!(
+            version, expected,
+            "the fixture is not a version {} journal",
+            expected
+        );
+        assert_ne!(version, JOURNAL_VERSION);
+
+        let input = marginal_inside_ask();
+        let mut journal = clear_book(&input).unwrap();
+        journal.version = version;
+        assert_eq!(
+            check_journal(&journal, &input.participants, &input.config),
+            Err(AuctionError::MalformedJournal(0))
+        );
+    }
+}
+
+/// fixtures/public_journal_v9.txt: the same book under layout version 9,
+/// as a consumer of the receipt stores it. The words decode to that book's
+/// balances, and while JOURNAL_VERSION is 9 the guest must commit exactly
+/// these words, bar the sixteen digest words, which follow the AuctionInput
+/// and AuctionConfig serialization rather than the journal layout
+#[test]
+fn stored_v9_journal_decodes() {
+    let words = fixture_words(include_str!("fixtures/public_journal_v9.txt"));
+    let stored: PublicJournal = risc0_zkvm::serde::from_slice(&words).unwrap();
+    assert_eq!(stored.version, 9, "the fixture is not a version 9 journal");
+    assert_eq!(stored.algorithm_id, ALGORITHM_ID);
+    assert_eq!(stored.ids, [0, 1, 2, 3]);
+    assert_eq!((stored.buyer_count, stored.seller_count), (2, 2));
+    assert_eq!(stored.out_coin, [600, 1000, 400, 0]);
+    assert_eq!(stored.out_energy, [10, 0, 5, 10]);
+    assert_eq!(stored.allocation, [10, 0, 10, 0]);
+    assert_eq!(stored.status, STATUS_CLEARED);
+    assert_eq!((stored.clearing_price, stored.traded_volume), (40, 10));
+    assert_eq!((stored.fees_collected, stored.surplus), (0, 400));
+    assert_eq!(
+        stored.fill_counts,
+        FillCounts {
+            buyers_filled: 1,
+            buyers_unfilled: 1,
+            sellers_partial: 1,
+            sellers_unfilled: 1,
+            ..FillCounts::default()
+        }
+    );
+    assert_eq!(stored.round_id, 0);
+
+    let input = marginal_inside_ask();
+    let mut journal = clear_book(&input).unwrap();
+    journal.bind(
+        input_digest(&input),
+        config_digest(&input.config),
+        input.config.round_id,
+    );
+    let committed = risc0_zkvm::serde::to_vec(&journal).unwrap();
+    let layout = |words: &[u32]| [&words[..1], &words[9..10], &words[18..]].concat();
+    assert_eq!(
+        layout(&committed),
+        layout(&words),
+        "the PublicJournal layout changed without a JOURNAL_VERSION bump"
+    );
+}
+
+/// A value through serde JSON and back, as a scenario file or journal.json carries it
+fn through_json<T: serde::Serialize + serde::de::DeserializeOwned>(value: &T) -> T {
+    serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap()
+}
+
+proptest! {
+    #[test]
+    fn borsh_agrees_with_json(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+    ) {
+        let config = AuctionConfig { algorithm, pricing_rule, ..AuctionConfig::default() };
+        let input = auction(participants, config);
+        let mut journal = clear(&input)?;
+        journal.bind(input_digest(&input), config_digest(&input.config), input.config.round_id);
+
+        // The Borsh bytes of a value and of its JSON round trip are the same...
+        let input_bytes = borsh::to_vec(&input).unwrap();
+        let journal_bytes = borsh::to_vec(&journal).unwrap();
+        prop_assert_eq!(&borsh::to_vec(&through_json(&input)).unwrap(), &input_bytes);
+        prop_assert_eq!(&borsh::to_vec(&through_json(&journal)).unwrap(), &journal_bytes);
+        // ...and decode to the value JSON carries
+        let input_back: AuctionInput = borsh::from_slice(&input_bytes).unwrap();
+        let journal_back: PublicJournal = borsh::from_slice(&journal_bytes).unwrap();
+        prop_assert_eq!(serde_json::to_string(&input_back).unwrap(), serde_json::to_string(&input).unwrap());
+        prop_assert_eq!(serde_json::to_string(&journal_back).unwrap(), serde_json::to_string(&journal).unwrap());
+    }
+}
+
+/// Bytes of a stored hex fixture: whitespace-separated, `#` lines are comments
+fn fixture_bytes(text: &str) -> Vec<u8> {
+    let hex: String = text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(str::split_whitespace)
+        .collect();
+    (0..hex.len())
+        .step_by(2)
+        .map(|at| u8::from_str_radix(&hex[at..at + 2], 16).unwrap())
+        .collect()
+}
+
+/// fixtures/public_journal_v9.borsh.txt: marginal_inside_ask_N4.json's
+/// journal as the host writes journal.borsh. The bytes decode to that
+/// book's balances, and Borsh of the journal the guest commits must give
+/// exactly these bytes, bar the two digests (bytes 4..36 and 40..72). The
+/// version 8 file, 8 bytes shorter, no longer decodes
+#[test]
+fn stored_borsh_journal_decodes() {
+    let old = fixture_bytes(include_str!("fixtures/public_journal_v8.borsh.txt"));
+    assert!(borsh::from_slice::<PublicJournal>(&old).is_err());
+
+    let bytes = fixture_bytes(include_str!("fixtures/public_journal_v9.borsh.txt"));
+    let stored: PublicJournal = borsh::from_slice(&bytes).unwrap();
+    assert_eq!(stored.version, 9, "the fixture is not a version 9 journal");
+    assert_eq!(stored.ids, [0, 1, 2, 3]);
+    assert_eq!(stored.out_coin, [600, 1000, 400, 0]);
+    assert_eq!(stored.out_energy, [10, 0, 5, 10]);
+    assert_eq!(stored.allocation, [10, 0, 10, 0]);
+    assert_eq!((stored.clearing_price, stored.traded_volume), (40, 10));
+
+    let input = marginal_inside_ask();
+    let mut journal = clear_book(&input).unwrap();
+    journal.bind(
+        input_digest(&input),
+        config_digest(&input.config),
+        input.config.round_id,
+    );
+    let encoded = borsh::to_vec(&journal).unwrap();
+    let layout = |bytes: &[u8]| [&bytes[..4], &bytes[36..40], &bytes[72..]].concat();
+    assert_eq!(
+        layout(&encoded),
+        layout(&bytes),
+        "the Borsh layout of PublicJournal changed"
+    );
+}
+
+/// The committed input digest is SHA-256 of the input's serde words, as the
+/// host hashes the words it writes, and binds the book: a deliberate
+/// mismatch, where the guest clears another book with the same aggregate
+/// balances (buyer 0 holds 200 coin more, buyer 1 200 less) than the file
+/// the host hashes, commits another digest and fails the host's comparison
+#[test]
+fn input_digest_binds_the_book() {
+    use sha2::Digest as _;
+    let input = marginal_inside_ask();
+    let words = risc0_zkvm::serde::to_vec(&input).unwrap();
+    let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    let host: [u8; 32] = sha2::Sha256::digest(&bytes).into();
+    assert_eq!(input_digest(&input).as_bytes(), host);
+
+    let mut proved = input.clone();
+    proved.participants[0].in_coin += 200;
+    proved.participants[1].in_coin -= 200;
+    assert_ne!(input_digest(&proved).as_bytes(), host);
+}
+
+/// The committed config digest is SHA-256 of the config's serde words, as the
+/// host hashes them, and names every parameter: changing any one field (the
+/// algorithm, the pricing rule, a fee, a flag, the seed) changes it, while
+/// a book with other participants under the same config keeps it
+#[test]
+fn config_digest_names_the_parameters() {
+    use sha2::Digest as _;
+    let config = AuctionConfig::default();
+    let words = risc0_zkvm::serde::to_vec(&config).unwrap();
+    let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    let host: [u8; 32] = sha2::Sha256::digest(&bytes).into();
+    assert_eq!(config_digest(&config).as_bytes(), host);
+
+    let changed = [
+        AuctionConfig {
+            algorithm: Algorithm::Greedy,
+            ..AuctionConfig::default()
+        },
+        AuctionConfig {
+            pricing_rule: PricingRule::BuyerBid,
+            ..AuctionConfig::default()
+        },
+        AuctionConfig {
+            fee_bps: 1,
+            ..AuctionConfig::default()
+        },
+        AuctionConfig {
+            strict: false,
+            ..AuctionConfig::default()
+        },
+        AuctionConfig {
+            seed: [1; 32],
+            ..AuctionConfig::default()
+        },
+        AuctionConfig {
+            max_trades: Some(0),
+            ..AuctionConfig::default()
+        },
+    ];
+    for other in &changed {
+        assert_ne!(config_digest(other).as_bytes(), host, "{:?}", other);
+    }
+
+    let mut input = marginal_inside_ask();
+    input.participants[0].price += 1;
+    assert_eq!(config_digest(&input.config).as_bytes(), host);
+    assert_ne!(input_digest(&input), input_digest(&marginal_inside_ask()));
+}
+
+/// journal_ids_ties_N5.json: rows matched to accounts by ids alone, under
+/// two tie-breaks that order the tied sellers differently
+#[test]
+fn ids_align_with_balances_under_ties() {
+    let participants = vec![
+        order(3, 0, 50, 10, 1000, 0),
+        order(2, 1, 20, 5, 0, 10),
+        order(1, 0, 50, 10, 700, 0),
+        order(4, 0, 30, 5, 600, 0),
+        order(0, 1, 20, 10, 5, 10),
+    ];
+    // Balances after the clearing (15 units at 35), by id
+    let out_coin = [355, 350, 175, 825, 600];
+    for (tie_break, ids) in [
+        (TieBreak::ById, [1, 3, 4, 0, 2]),
+        (TieBreak::ByQuantityAsc, [1, 3, 4, 2, 0]),
+    ] {
+        let config = AuctionConfig {
+            tie_break,
+            ..AuctionConfig::default()
+        };
+        let journal = clear_book(&auction(participants.clone(), config)).unwrap();
+        assert_eq!(journal.ids, ids, "{:?}", tie_break);
+        for (row, id) in journal.ids.iter().enumerate() {
+            let p = participants.iter().find(|p| p.id == *id).unwrap();
+            assert_eq!(
+                journal.in_coin[row], p.in_coin,
+                "{:?} row {}",
+                tie_break, row
+            );
+            assert_eq!(
+                journal.out_coin[row], out_coin[*id as usize],
+                "{:?} row {}",
+                tie_break, row
+            );
+        }
+    }
+}
+
+/// degenerate_buyers_only_N2.json and degenerate_sellers_only_N2.json: the
+/// committed counts decode to one empty block, the other covering every row
+#[test]
+fn one_sided_blocks_decode() {
+    let books = [
+        (
+            vec![order(0, 0, 50, 10, 1000, 0), order(1, 0, 40, 10, 1000, 0)],
+            (2, 0),
+        ),
+        (
+            vec![order(0, 1, 20, 10, 0, 10), order(1, 1, 30, 10, 0, 10)],
+            (0, 2),
+        ),
+    ];
+    for (participants, counts) in books {
+        let journal = clear_book(&auction(participants, AuctionConfig::default())).unwrap();
+        let words = risc0_zkvm::serde::to_vec(&journal).unwrap();
+        let decoded: PublicJournal = risc0_zkvm::serde::from_slice(&words).unwrap();
+        assert_eq!((decoded.buyer_count, decoded.seller_count), counts);
+        assert_eq!(
+            (decoded.buyer_count + decoded.seller_count) as usize,
+            decoded.in_coin.len()
+        );
+        assert_eq!(decoded.out_coin, decoded.in_coin);
+    }
+}
+
+#[test]
+fn rejects_empty_market() {
+    let input = auction(Vec::new(), AuctionConfig::default());
+    assert_eq!(validate_input(&input), Err(AuctionError::EmptyMarket));
+    assert_eq!(AuctionError::EmptyMarket.exit_code(), EXIT_EMPTY_MARKET);
+}
+
+/// Two bids (60 and 40 for 10) against two asks (30 and 50 for 10): the
+/// book crosses at the grid price 50, where bid 60 meets ask 30 on the
+/// last matched unit, bid 40 is the first excluded bid and no ask is
+fn crossed_book() -> Vec<Participant> {
+    vec![
+        order(0, 0, 60, 10, 1000, 0),
+        order(1, 0, 40, 10, 1000, 0),
+        order(2, 1, 30, 10, 0, 10),
+        order(3, 1, 50, 10, 0, 10),
+    ]
+}
+
+/// Each pricing rule on crossed_book, computed by hand: MidPoint floors
+/// (60 + 30) / 2 to 45, BuyerBid takes 60 and SellerAsk 30; buyer 0 buys
+/// 10 from seller 2 at that price, and a config without pricing_rule
+/// (as a scenario file leaves it out) clears at the mid-point
+#[test]
+fn pricing_rules_of_crossed_book() {
+    let table = [
+        (PricingRule::MidPoint, 45, vec![550, 1000, 450, 0]),
+        (PricingRule::BuyerBid, 60, vec![400, 1000, 600, 0]),
+        (PricingRule::SellerAsk, 30, vec![700, 1000, 300, 0]),
+    ];
+    for (pricing_rule, price, out_coin) in table {
+        let config = AuctionConfig {
+            pricing_rule,
+            ..AuctionConfig::default()
+        };
+        let journal = clear_book(&auction(crossed_book(), config)).unwrap();
+        assert_eq!(journal.clearing_price, price, "{:?}", pricing_rule);
+        assert_eq!(journal.traded_volume, 10, "{:?}", pricing_rule);
+        assert_eq!(journal.out_coin, out_coin, "{:?}", pricing_rule);
+        assert_eq!(journal.out_energy, vec![10, 0, 0, 10], "{:?}", pricing_rule);
+    }
+
+    let config: AuctionConfig = serde_json::from_str("{}").unwrap();
+    assert_eq!(config.pricing_rule, PricingRule::MidPoint);
+    let journal = clear_book(&auction(crossed_book(), config)).unwrap();
+    assert_eq!(journal.clearing_price, 45);
+}
+
+/// KDouble on crossed_book (b_marg 60, a_marg 30), computed by hand: the
+/// weighted price is floored toward the ask, so k = 1/4 prices 150 / 4 =
+/// 37.5 at 37 and k = 3/4 prices 210 / 4 = 52.5 at 52, k = 2/4 equals
+/// MidPoint and k = 0 and 1 are SellerAsk and BuyerBid; every coin the
+/// buyer pays reaches the seller. k = 0/0 and 3/2 are no weight:
+/// validate_input refuses them, and cleared unchecked the book does not
+/// trade rather than dividing by zero or wrapping 2 − 3
+#[test]
+fn k_double_rounds_toward_the_ask() {
+    let table = [
+        ((1, 4), Some(37)),
+        ((3, 4), Some(52)),
+        ((2, 4), Some(45)),
+        ((0, 1), Some(30)),
+        ((1, 1), Some(60)),
+        ((0, 0), None),
+        ((3, 2), None),
+    ];
+    for ((k_numerator, k_denominator), price) in table {
+        let config = AuctionConfig {
+            pricing_rule: PricingRule::KDouble,
+            k_numerator,
+            k_denominator,
+            ..AuctionConfig::default()
+        };
+        let input = auction(crossed_book(), config);
+        let journal = clear_book(&input).unwrap();
+        let k = (k_numerator, k_denominator);
+        let Some(price) = price else {
+            assert_eq!(
+                validate_input(&input),
+                Err(AuctionError::InvalidKDouble),
+                "k = {:?}",
+                k
+            );
+            assert_eq!(journal.out_coin, journal.in_coin, "k = {:?}", k);
+            assert_eq!(journal.traded_volume, 0, "k = {:?}", k);
+            continue;
+        };
+        assert_eq!(journal.clearing_price, price, "k = {:?}", k);
+        assert_eq!(
+            journal.out_coin,
+            vec![1000 - 10 * price, 1000, 10 * price, 0],
+            "k = {:?}",
+            k
+        );
+        assert_eq!(journal.out_coin.iter().sum::<u64>(), 2000, "k = {:?}", k);
+    }
+}
+
+proptest! {
+    /// Pay-as-bid on a generated book with an operator row: coin and energy
+    /// are conserved, every matched buyer pays exactly its bid per unit and
+    /// every matched seller receives exactly its ask, the operator keeps the
+    /// spread
+    #[test]
+    fn pay_as_bid_charges_each_quote(participants in book()) {
+        let operator = participants.len() as u32;
+        let mut participants = participants;
+        participants.push(order(operator, 1, 0, 0, 0, 0));
+        let config = AuctionConfig {
+            settlement: Settlement::PayAsBid,
+            operator_id: Some(operator),
+            ..AuctionConfig::default()
+        };
+        let input = auction(participants, config);
+        let journal = clear(&input)?;
+        let total = |column: &[u64]| column.iter().map(|&value| value as u128).sum::<u128>();
+        prop_assert_eq!(total(&journal.in_coin), total(&journal.out_coin));
+        prop_assert_eq!(total(&journal.in_energy), total(&journal.out_energy));
+
+        let mut spread = 0u64;
+        for (row, p) in protocol_order(&input.participants, &input.config).iter().enumerate() {
+            if p.id == operator {
+                continue;
+            }
+            let units = journal.in_energy[row].abs_diff(journal.out_energy[row]);
+            let coin = journal.in_coin[row].abs_diff(journal.out_coin[row]);
+            prop_assert_eq!(coin, p.price * units, "participant {}", p.id);
+            match p.role {
+                0 => spread += coin,
+                _ => spread -= coin,
+            }
+        }
+        let operator_row = journal.ids.iter().position(|&id| id == operator).unwrap();
+        prop_assert_eq!(journal.out_coin[operator_row], spread);
+    }
+}
+
+/// SecondPrice against MidPoint on textbook books, computed by hand: on
+/// crossed_book the first excluded bid 40 and the marginal ask 30 (no ask
+/// is excluded) price 35 where the mid-point is 45; with the ask of 50
+/// raised to 70, bid 40 and ask 70 price 55; excluded bid 10 and ask 30
+/// would price 20, below the marginal ask, so the price is clamped to 30;
+/// a book where every order clears falls back to the marginal pair, 45
+#[test]
+fn second_price_differs_from_mid_point() {
+    let mut high_ask = crossed_book();
+    high_ask[3].price = 70;
+    let low_bid = vec![
+        order(0, 0, 60, 10, 1000, 0),
+        order(1, 0, 10, 10, 1000, 0),
+        order(2, 1, 30, 10, 0, 10),
+    ];
+    let everyone = vec![order(0, 0, 60, 10, 1000, 0), order(1, 1, 30, 10, 0, 10)];
+    let table = [
+        ("crossed_book", crossed_book(), 35, 45),
+        ("high ask", high_ask, 55, 45),
+        ("low bid", low_bid, 30, 45),
+        ("everyone clears", everyone, 45, 45),
+    ];
+    for (name, participants, second_price, mid_point) in table {
+        for (pricing_rule, price) in [
+            (PricingRule::SecondPrice, second_price),
+            (PricingRule::MidPoint, mid_point),
+        ] {
+            let config = AuctionConfig {
+                pricing_rule,
+                ..AuctionConfig::default()
+            };
+            let journal = clear_book(&auction(participants.clone(), config)).unwrap();
+            assert_eq!(journal.clearing_price, price, "{} {:?}", name, pricing_rule);
+            assert_eq!(journal.traded_volume, 10, "{} {:?}", name, pricing_rule);
+            assert_eq!(
+                journal.out_coin[0],
+                1000 - 10 * price,
+                "{} {:?}",
+                name,
+                pricing_rule
+            );
+        }
+    }
+}
+
+/// A Dutch clock from 70 (ask 50 + increment 20) in ticks of 5, computed by
+/// hand: the best bids (60, buyers 1 and 3) stop it at 60, where both
+/// accept at the same tick and are served by id, buyer 1 taking its 8 and
+/// buyer 3 the 2 units left, whichever order the input lists them in;
+/// buyer 2 (bid 52) is never reached
+#[test]
+fn dutch_clock_ties_accept_by_id() {
+    let config = AuctionConfig {
+        algorithm: Algorithm::DutchClock,
+        clock_increment: 20,
+        clock_tick: 5,
+        ..AuctionConfig::default()
+    };
+    let book = vec![
+        order(0, 1, 50, 10, 0, 10),
+        order(1, 0, 60, 8, 1000, 0),
+        order(2, 0, 52, 5, 1000, 0),
+        order(3, 0, 60, 8, 1000, 0),
+    ];
+    let mut reversed = book.clone();
+    reversed.reverse();
+    for participants in [book, reversed] {
+        let journal = clear_book(&auction(participants, config.clone())).unwrap();
+        assert_eq!(journal.ids, vec![1, 3, 2, 0]);
+        assert_eq!((journal.clearing_price, journal.traded_volume), (60, 10));
+        assert_eq!(journal.out_energy, vec![8, 2, 0, 0]);
+        assert_eq!(journal.out_coin, vec![520, 880, 1000, 600]);
+    }
+}
+
+/// A Dutch clock whose first jump passes u64::MAX: the clock opens at
+/// u64::MAX (ask 1 + the whole increment) and falls towards the bid 2 in
+/// ticks of 2^63, two of which overflow; the guest exits with Overflow
+/// instead of wrapping the jump
+#[test]
+fn dutch_clock_jump_beyond_u64_overflows() {
+    let participants = vec![order(0, 0, 2, 10, 1000, 0), order(1, 1, 1, 10, 0, 10)];
+    let config = AuctionConfig {
+        algorithm: Algorithm::DutchClock,
+        clock_increment: u64::MAX,
+        clock_tick: 1 << 63,
+        ..AuctionConfig::default()
+    };
+    let input = auction(participants, config);
+    assert_eq!(validate_input(&input), Ok(()));
+    assert_eq!(
+        run_double_auction(&input).unwrap_err(),
+        AuctionError::Overflow
+    );
+}
+
+/// An English clock from the ask 10 in ticks of 5, computed by hand: a lone
+/// bid of 20 for 15 exceeds the seller's 10 until the clock passes 20 (one
+/// jump to 25), where no bid is left, so nothing trades; bids of 30 and 12
+/// for 6 exceed the 10 at 10, and one increment to 15 drops the bid of 12,
+/// so buyer 0 buys its 6 at 15
+#[test]
+fn english_clock_of_hand_computed_books() {
+    let config = AuctionConfig {
+        algorithm: Algorithm::EnglishClock,
+        clock_tick: 5,
+        ..AuctionConfig::default()
+    };
+    let past_every_bid = vec![order(0, 0, 20, 15, 1000, 0), order(1, 1, 10, 10, 0, 10)];
+    let journal = clear_book(&auction(past_every_bid, config.clone())).unwrap();
+    assert_eq!((journal.clearing_price, journal.traded_volume), (0, 0));
+    assert_eq!(journal.status, STATUS_ZERO_VOLUME);
+    assert_eq!(journal.out_coin, journal.in_coin);
+    assert_eq!(journal.out_energy, journal.in_energy);
+
+    let one_increment = vec![
+        order(0, 0, 30, 6, 1000, 0),
+        order(1, 0, 12, 6, 1000, 0),
+        order(2, 1, 10, 10, 0, 10),
+    ];
+    let journal = clear_book(&auction(one_increment, config)).unwrap();
+    assert_eq!((journal.clearing_price, journal.traded_volume), (15, 6));
+    assert_eq!(journal.out_coin, vec![910, 1000, 90]);
+    assert_eq!(journal.out_energy, vec![6, 0, 4]);
+}
+
+/// An English clock whose jump passes u64::MAX: from the ask 1, demand 10
+/// exceeds the 5 units until the price passes the bid u64::MAX − 1, two
+/// ticks of 2^63 + 1 away under a raised max_price; the guest exits with
+/// Overflow instead of wrapping the jump back below the bid
+#[test]
+fn english_clock_jump_beyond_u64_overflows() {
+    let participants = vec![
+        order(0, 0, u64::MAX - 1, 10, u64::MAX, 0),
+        order(1, 1, 1, 5, 0, 5),
+    ];
+    let config = AuctionConfig {
+        algorithm: Algorithm::EnglishClock,
+        clock_tick: (1 << 63) + 1,
+        max_price: u64::MAX,
+        ..AuctionConfig::default()
+    };
+    let input = auction(participants, config);
+    assert_eq!(validate_input(&input), Ok(()));
+    assert_eq!(
+        run_double_auction(&input).unwrap_err(),
+        AuctionError::Overflow
+    );
+}
+
+/// settle_bundles on deliberately broken fills, as a replaced bundle
+/// matcher could produce them: a buyer charged more coin than it holds, a
+/// seller drawn for more energy or more capacity than it holds, each named
+/// with its allocation instead of wrapping; the fill it can pay settles
+#[test]
+fn broken_bundle_fills_are_refused() {
+    let buyer = Participant {
+        capacity: 2,
+        ..order(0, 0, 10, 5, 100, 0)
+    };
+    let seller = Participant {
+        capacity: 2,
+        in_capacity: 2,
+        ..order(1, 1, 5, 5, 0, 5)
+    };
+    let participants = vec![buyer, seller];
+    let fills = |buyer: (u64, u64, u64), seller: (u64, u64, u64)| vec![(0, buyer), (1, seller)];
+
+    let (outputs, capacity) =
+        settle_bundles(&participants, &fills((70, 5, 2), (70, 5, 2))).unwrap();
+    assert_eq!(outputs, [(0, 30, 5), (1, 70, 0)]);
+    assert_eq!(capacity, [(0, 2), (1, 0)]);
+    assert_eq!(
+        settle_bundles(&participants, &fills((140, 5, 2), (140, 5, 2))),
+        Err(AuctionError::InsufficientCoin(0, 7))
+    );
+    assert_eq!(
+        settle_bundles(&participants, &fills((60, 6, 0), (60, 6, 0))),
+        Err(AuctionError::InsufficientEnergy(1, 6))
+    );
+    assert_eq!(
+        settle_bundles(&participants, &fills((50, 2, 3), (50, 2, 3))),
+        Err(AuctionError::InsufficientEnergy(1, 5))
+    );
+}
+
+/// Reserve prices on crossed_book, computed by hand: seller 2's floor of 50
+/// is violated by the mid-point 45, so it is excluded and the book
+/// recrosses at 50, where bid 60 and ask 50 price 55; seller 2 is not
+/// re-admitted although 55 would meet its floor. Buyer 0's ceiling of 44
+/// excludes it the same way, and bid 40 against ask 30 clears at 35. A
+/// participant without the field, as older scenario files list it, has no
+/// reserve
+#[test]
+fn reserve_prices_exclude_once_and_recross() {
+    let with_reserve = |id: usize, reserve| {
+        let mut participants = crossed_book();
+        participants[id].reserve_price = Some(reserve);
+        auction(participants, AuctionConfig::default())
+    };
+    let table = [
+        (
+            "seller floor",
+            with_reserve(2, 50),
+            55,
+            vec![450, 1000, 0, 550],
+            vec![10, 0, 10, 0],
+        ),
+        (
+            "buyer ceiling",
+            with_reserve(0, 44),
+            35,
+            vec![1000, 650, 350, 0],
+            vec![0, 10, 0, 10],
+        ),
+    ];
+    for (name, input, price, out_coin, out_energy) in table {
+        let journal = clear_book(&input).unwrap();
+        assert_eq!(
+            (journal.clearing_price, journal.traded_volume),
+            (price, 10),
+            "{}",
+            name
+        );
+        assert_eq!(journal.out_coin, out_coin, "{}", name);
+        assert_eq!(journal.out_energy, out_energy, "{}", name);
+    }
+
+    let participant: Participant = serde_json::from_str(
+        r#"{"id": 0, "role": 1, "price": 30, "quantity": 10, "in_coin": 0, "in_energy": 10}"#,
+    )
+    .unwrap();
+    assert_eq!(participant.reserve_price, None);
+}
+
+/// The price band, computed by hand: a band below every ask (cap 20) or
+/// above every bid (floor 70) leaves crossed_book nothing to trade under
+/// either policy. With seller 2 offering 5, the book prices 55 and buys 5
+/// from each seller; a cap of 45 clamps it to 45, where ask 50 no longer
+/// qualifies, so buyer 0 buys seller 2's 5 alone, or nothing under NoTrade
+#[test]
+fn price_band_clamps_or_refuses() {
+    let banded = |participants, price_floor, price_cap, band_policy| {
+        let config = AuctionConfig {
+            price_floor,
+            price_cap,
+            band_policy,
+            ..AuctionConfig::default()
+        };
+        clear_book(&auction(participants, config)).unwrap()
+    };
+    for band_policy in [BandPolicy::Clamp, BandPolicy::NoTrade] {
+        for (floor, cap) in [(None, Some(20)), (Some(70), None)] {
+            let journal = banded(crossed_book(), floor, cap, band_policy);
+            assert_eq!(
+                journal.traded_volume, 0,
+                "{:?} {:?} {:?}",
+                band_policy, floor, cap
+            );
+            assert_eq!(journal.out_coin, journal.in_coin);
+        }
+    }
+
+    let mut short_ask = crossed_book();
+    short_ask[2] = order(2, 1, 30, 5, 0, 5);
+    let journal = banded(short_ask.clone(), None, None, BandPolicy::Clamp);
+    assert_eq!((journal.clearing_price, journal.traded_volume), (55, 10));
+    assert_eq!(journal.allocation, vec![10, 0, 5, 5]);
+
+    let journal = banded(short_ask.clone(), None, Some(45), BandPolicy::Clamp);
+    assert_eq!((journal.clearing_price, journal.traded_volume), (45, 5));
+    assert_eq!(journal.allocation, vec![5, 0, 5, 0]);
+    assert_eq!(journal.out_coin, vec![775, 1000, 225, 0]);
+
+    let journal = banded(short_ask, None, Some(45), BandPolicy::NoTrade);
+    assert_eq!(journal.traded_volume, 0);
+}
+
+/// Fee rounding, computed by hand: 11 units at 7 are a notional of 77, whose
+/// 150 bps fee of 1.155 is floored to 1 on each side, so the buyer pays 78,
+/// the seller gets 76 and the 2 coins between them are the dust, also at
+/// the negative price -7. On crossed_book with an operator the fee on 450
+/// is 6.75 a side: rounding it to 7 would charge 14 coins the exact fees
+/// (13.5) do not cover, flooring collects 12, all credited to the operator
+#[test]
+fn floored_fees_neither_create_nor_destroy_coin() {
+    assert_eq!(settle(7, 11, 150), Some((78, 76, 2)));
+    assert_eq!(settle(-7, 11, 150), Some((-76, -78, 2)));
+    assert_eq!(settle(7, 11, 0), Some((77, 77, 0)));
+
+    let mut participants = crossed_book();
+    participants.push(order(4, 1, 0, 0, 0, 0));
+    let config = AuctionConfig {
+        fee_bps: 150,
+        operator_id: Some(4),
+        ..AuctionConfig::default()
+    };
+    let journal = clear_book(&auction(participants, config)).unwrap();
+    assert_eq!(journal.ids, vec![0, 1, 4, 2, 3]);
+    assert_eq!(journal.clearing_price, 45);
+    assert_eq!(journal.out_coin, vec![544, 1000, 12, 444, 0]);
+    assert_eq!(journal.fees_collected, 12);
+    assert_eq!(
+        journal.out_coin.iter().sum::<u64>(),
+        journal.in_coin.iter().sum::<u64>()
+    );
+}
+
+/// An all-or-nothing bid, computed by hand: bid 60 for 25 meets asks 30
+/// and 35 for 10 each, so the book clears at 47 with buyer 0 buying both
+/// sellers' 20; all-or-nothing, 20 of its 25 is a partial fill, so it is
+/// dropped and bid 40 for 10 recrosses at 35 with seller 2 alone, seller 3
+/// no longer trading. A participant without the field is not all-or-nothing
+#[test]
+fn dropped_all_or_nothing_bid_changes_the_sellers() {
+    let book = |all_or_nothing| {
+        let aon = Participant {
+            all_or_nothing,
+            ..order(0, 0, 60, 25, 2000, 0)
+        };
+        let participants = vec![
+            aon,
+            order(1, 0, 40, 10, 1000, 0),
+            order(2, 1, 30, 10, 0, 10),
+            order(3, 1, 35, 10, 0, 10),
+        ];
+        clear_book(&auction(participants, AuctionConfig::default())).unwrap()
+    };
+    let journal = book(false);
+    assert_eq!((journal.clearing_price, journal.traded_volume), (47, 20));
+    assert_eq!(journal.allocation, vec![20, 0, 10, 10]);
+    assert_eq!(journal.out_coin, vec![1060, 1000, 470, 470]);
+
+    let journal = book(true);
+    assert_eq!((journal.clearing_price, journal.traded_volume), (35, 10));
+    assert_eq!(journal.allocation, vec![0, 10, 10, 0]);
+    assert_eq!(journal.out_coin, vec![2000, 650, 350, 0]);
+
+    let participant: Participant = serde_json::from_str(
+        r#"{"id": 0, "role": 0, "price": 60, "quantity": 25, "in_coin": 2000, "in_energy": 0}"#,
+    )
+    .unwrap();
+    assert!(!participant.all_or_nothing);
+}
+
+proptest! {
+    /// Minimum fills on generated books: no row is allocated strictly between
+    /// 0 and its min_fill, and the buyer and seller blocks' allocations both
+    /// sum to the traded volume
+    #[test]
+    fn allocations_respect_min_fill(
+        participants in book(),
+        min_fills in prop::collection::vec(0..=MAX_QUANTITY, MAX_ROWS),
+        algorithm in prop_oneof![Just(Algorithm::UniformPrice), Just(Algorithm::Merge)],
+        pricing_rule in pricing_rule(),
+    ) {
+        let participants: Vec<Participant> = participants
+            .into_iter()
+            .zip(min_fills)
+            .map(|(p, min_fill)| Participant { min_fill, ..p })
+            .collect();
+        let config = AuctionConfig { algorithm, pricing_rule, ..AuctionConfig::default() };
+        let input = auction(participants, config);
+        let journal = clear(&input)?;
+        let rows = protocol_order(&input.participants, &input.config);
+        for (p, &allocated) in rows.iter().zip(&journal.allocation) {
+            prop_assert!(allocated == 0 || allocated >= p.min_fill, "participant {}", p.id);
+        }
+        let buyers = journal.buyer_count as usize;
+        let side = |range: &[u64]| range.iter().sum::<u64>();
+        prop_assert_eq!(side(&journal.allocation[..buyers]), journal.traded_volume);
+        prop_assert_eq!(side(&journal.allocation[buyers..]), journal.traded_volume);
+    }
+}
+
+/// A min_fill cascade, computed by hand: two bids of 60 for 10 against asks
+/// of 30 for 8 and 5 clear at 45, where rationing leaves buyer 1 3 units
+/// below its min_fill of 5; without it buyer 0 wants only 10, which leaves
+/// seller 3 2 units below its min_fill of 3, and without that seller 2's 8
+/// go to buyer 0, both sides still matching
+#[test]
+fn min_fill_cascade_of_hand_computed_book() {
+    let participants = vec![
+        order(0, 0, 60, 10, 1000, 0),
+        Participant {
+            min_fill: 5,
+            ..order(1, 0, 60, 10, 1000, 0)
+        },
+        order(2, 1, 30, 8, 0, 8),
+        Participant {
+            min_fill: 3,
+            ..order(3, 1, 30, 5, 0, 5)
+        },
+    ];
+    let journal = clear_book(&auction(participants, AuctionConfig::default())).unwrap();
+    assert_eq!((journal.clearing_price, journal.traded_volume), (45, 8));
+    assert_eq!(journal.allocation, vec![8, 0, 8, 0]);
+    assert_eq!(journal.out_coin, vec![640, 1000, 360, 0]);
+    assert_eq!(journal.out_energy, vec![8, 0, 0, 5]);
+}
+
+/// A clearing price between ticks, computed by hand: on a 5 tick and 10 lot,
+/// bid 60 and ask 35 have the mid-point 47, rounded down to 45, toward the
+/// seller. A bid of 72 is off the tick and dropped, leaving the price at 45;
+/// with round_to_grid it bids 70 instead and, alone at the crossing, buys
+/// the 10 units at 52 rounded to 50
+#[test]
+fn clearing_price_between_ticks_rounds_down() {
+    let config = |round_to_grid| AuctionConfig {
+        price_tick: 5,
+        quantity_lot: 10,
+        round_to_grid,
+        ..AuctionConfig::default()
+    };
+    let on_grid = vec![order(0, 0, 60, 10, 1000, 0), order(1, 1, 35, 10, 0, 10)];
+    let journal = clear_book(&auction(on_grid.clone(), config(false))).unwrap();
+    assert_eq!((journal.clearing_price, journal.traded_volume), (45, 10));
+
+    let mut off_tick = on_grid;
+    off_tick.push(order(2, 0, 72, 10, 1000, 0));
+    let journal = clear_book(&auction(off_tick.clone(), config(false))).unwrap();
+    assert_eq!(journal.ids, vec![2, 0, 1]);
+    assert_eq!((journal.clearing_price, journal.traded_volume), (45, 10));
+    assert_eq!(journal.out_coin, vec![1000, 550, 450]);
+
+    let journal = clear_book(&auction(off_tick, config(true))).unwrap();
+    assert_eq!((journal.clearing_price, journal.traded_volume), (50, 10));
+    assert_eq!(journal.out_coin, vec![500, 1000, 500]);
+}
+
+/// Offset-encoded prices around zero, computed by hand with price_offset
+/// 100: bid -10 and ask -30 (encoded 90 and 70) clear at -20, so the
+/// seller pays the buyer 200 for its 10 units; bid +30 and ask -10 clear at
+/// +10 across zero, and bid +20 and ask -20 at exactly 0, where the energy
+/// moves and no coin does
+#[test]
+fn negative_prices_reverse_the_coin() {
+    let config = AuctionConfig {
+        price_offset: 100,
+        ..AuctionConfig::default()
+    };
+    let table = [
+        ("negative", (90, 70), 80, [1200, 800]),
+        ("across zero", (130, 90), 110, [900, 1100]),
+        ("zero", (120, 80), 100, [1000, 1000]),
+    ];
+    for (name, (bid, ask), price, out_coin) in table {
+        let participants = vec![
+            order(0, 0, bid, 10, 1000, 0),
+            order(1, 1, ask, 10, 1000, 10),
+        ];
+        let journal = clear_book(&auction(participants, config.clone())).unwrap();
+        assert_eq!(journal.status, STATUS_CLEARED, "{}", name);
+        assert_eq!(
+            (journal.clearing_price, journal.traded_volume),
+            (price, 10),
+            "{}",
+            name
+        );
+        assert_eq!(journal.out_coin, out_coin, "{}", name);
+        assert_eq!(journal.out_energy, [10, 0], "{}", name);
+    }
+}
+
+/// The traded-volume cap, computed by hand: bids 60 and 50 for 10 against
+/// an ask of 30 for 20 clear all 20 at 40; a cap of 0 trades nothing, a cap
+/// of 20 changes nothing, and a cap of 15 cuts the marginal buyer 1 to 5
+/// units at the same price, the seller selling the 15
+#[test]
+fn volume_cap_rations_both_sides() {
+    let participants = vec![
+        order(0, 0, 60, 10, 1000, 0),
+        order(1, 0, 50, 10, 1000, 0),
+        order(2, 1, 30, 20, 0, 20),
+    ];
+    let table = [
+        (u64::MAX, 40, vec![10, 10, 20], vec![600, 600, 800]),
+        (20, 40, vec![10, 10, 20], vec![600, 600, 800]),
+        (15, 40, vec![10, 5, 15], vec![600, 800, 600]),
+        (0, 0, vec![0, 0, 0], vec![1000, 1000, 0]),
+    ];
+    for (max_traded_volume, price, allocation, out_coin) in table {
+        let config = AuctionConfig {
+            max_traded_volume,
+            ..AuctionConfig::default()
+        };
+        let journal = clear_book(&auction(participants.clone(), config)).unwrap();
+        let cap = max_traded_volume;
+        assert_eq!(journal.clearing_price, price, "cap {}", cap);
+        assert_eq!(journal.traded_volume, allocation[2], "cap {}", cap);
+        assert_eq!(journal.allocation, allocation, "cap {}", cap);
+        assert_eq!(journal.out_coin, out_coin, "cap {}", cap);
+    }
+}
+
+/// Delivery losses at 250 bps, computed by hand: a 1-unit trade delivers
+/// floor(1 × 9750 / 10000) = 0 units, so the sink (participant 2) receives
+/// the whole unit the buyer paid 45 for; a 10-unit trade delivers 9.75,
+/// floored to 9, and the sink absorbs the remaining 1
+#[test]
+fn delivery_losses_land_on_the_sink() {
+    let config = AuctionConfig {
+        loss_bps: 250,
+        loss_sink_id: Some(2),
+        ..AuctionConfig::default()
+    };
+    for (quantity, delivered, out_coin) in [(1, 0, [955, 0, 45]), (10, 9, [550, 0, 450])] {
+        let participants = vec![
+            order(0, 0, 60, quantity, 1000, 0),
+            order(1, 1, 30, quantity, 0, quantity),
+            order(2, 1, 0, 0, 0, 0),
+        ];
+        let journal = clear_book(&auction(participants, config.clone())).unwrap();
+        assert_eq!(journal.ids, vec![0, 2, 1], "{} units", quantity);
+        assert_eq!(
+            (journal.clearing_price, journal.traded_volume),
+            (45, quantity)
+        );
+        assert_eq!(journal.out_coin, out_coin, "{} units", quantity);
+        assert_eq!(
+            journal.out_energy,
+            [delivered, quantity - delivered, 0],
+            "{} units",
+            quantity
+        );
+    }
+}
+
+/// Priority dispatch, computed by hand: a bid of 60 for 10 against asks of
+/// 30 for 5 and 40 for 10 clears at 50 with both asks qualified; by price
+/// the cheap seller 1 sells its 5 and seller 2 the other 5, while a
+/// priority seller 2 is served first and sells all 10, displacing seller 1
+#[test]
+fn priority_seller_displaces_a_cheaper_one() {
+    for (priority, allocation, out_coin) in [
+        (false, [10, 5, 5], [500, 250, 250]),
+        (true, [10, 0, 10], [500, 0, 500]),
+    ] {
+        let participants = vec![
+            order(0, 0, 60, 10, 1000, 0),
+            order(1, 1, 30, 5, 0, 5),
+            Participant {
+                priority,
+                ..order(2, 1, 40, 10, 0, 10)
+            },
+        ];
+        let journal = clear_book(&auction(participants, AuctionConfig::default())).unwrap();
+        assert_eq!((journal.clearing_price, journal.traded_volume), (50, 10));
+        assert_eq!(journal.allocation, allocation, "priority {}", priority);
+        assert_eq!(journal.out_coin, out_coin, "priority {}", priority);
+    }
+}
+
+/// Flexible demand, computed by hand: bids of 60 and 50 for 10 against an
+/// ask of 30 for 20 that holds only 10 energy clear at 40 with both bids
+/// qualified; by price buyer 0 buys the 10, but flexible it is served
+/// after the inflexible buyer 1, which takes all 10. With 200 coin buyer 1
+/// affords 5 units, and the flexible buyer 0 receives the other 5
+#[test]
+fn flexible_buyer_is_served_last() {
+    for (flexible, coin, allocation, out_coin) in [
+        (false, 1000, [10, 0, 10], [600, 1000, 400]),
+        (true, 1000, [0, 10, 10], [1000, 600, 400]),
+        (true, 200, [5, 5, 10], [800, 0, 400]),
+    ] {
+        let participants = vec![
+            Participant {
+                flexible,
+                ..order(0, 0, 60, 10, 1000, 0)
+            },
+            order(1, 0, 50, 10, coin, 0),
+            order(2, 1, 30, 20, 0, 10),
+        ];
+        let journal = clear_book(&auction(participants, AuctionConfig::default())).unwrap();
+        let case = (flexible, coin);
+        assert_eq!(
+            (journal.clearing_price, journal.traded_volume),
+            (40, 10),
+            "{:?}",
+            case
+        );
+        assert_eq!(journal.allocation, allocation, "{:?}", case);
+        assert_eq!(journal.out_coin, out_coin, "{:?}", case);
+    }
+}
+
+/// Carbon co-clearing only trims a plain uniform-price book, so every
+/// feature it would settle wrongly or drop is refused before clearing; the
+/// plain book trades its 2 units at 15, 6 carbon within the buyer's budget
+/// of 10
+#[test]
+fn carbon_co_clearing_refuses_what_it_cannot_settle() {
+    let carbon_book = || {
+        vec![
+            Participant {
+                carbon_budget: Some(10),
+                ..order(0, 0, 20, 2, 100, 0)
+            },
+            Participant {
+                carbon_per_unit: 3,
+                ..order(1, 1, 10, 2, 0, 2)
+            },
+        ]
+    };
+    let refused = |participants: Vec<Participant>, config: AuctionConfig| {
+        validate_input(&auction(participants, config)) == Err(AuctionError::UnsupportedMode)
+    };
+    assert!(!refused(carbon_book(), AuctionConfig::default()));
+    let (journal, carbon) =
+        run_carbon_auction(&auction(carbon_book(), AuctionConfig::default())).unwrap();
+    assert_eq!(journal.out_coin, vec![70, 30]);
+    assert_eq!((carbon.carbon, carbon.total_carbon), (vec![6, 6], 6));
+
+    let configs = [
+        AuctionConfig {
+            algorithm: Algorithm::McAfee,
+            ..AuctionConfig::default()
+        },
+        AuctionConfig {
+            settlement: Settlement::Vcg,
+            ..AuctionConfig::default()
+        },
+        AuctionConfig {
+            loss_bps: 100,
+            loss_sink_id: Some(1),
+            ..AuctionConfig::default()
+        },
+        AuctionConfig {
+            price_tick: 5,
+            ..AuctionConfig::default()
+        },
+        AuctionConfig {
+            quantity_lot: 2,
+            ..AuctionConfig::default()
+        },
+        AuctionConfig {
+            bundles: true,
+            ..AuctionConfig::default()
+        },
+    ];
+    for config in configs {
+        assert!(refused(carbon_book(), config.clone()), "{:?}", config);
+    }
+
+    let mut stepped = carbon_book();
+    stepped[0].steps = vec![(20, 1), (15, 1)];
+    let mut prosumer = carbon_book();
+    prosumer.push(order(2, 2, 15, 1, 100, 1));
+    let mut dirty_buyer = carbon_book();
+    dirty_buyer[0].carbon_per_unit = 1;
+    let mut budgeted_seller = carbon_book();
+    budgeted_seller[1].carbon_budget = Some(1);
+    let mut zonal = carbon_book();
+    zonal[1].zone = 1;
+    for book in [stepped, prosumer, dirty_buyer, budgeted_seller, zonal] {
+        assert!(refused(book, AuctionConfig::default()));
+    }
+}
+
+/// Pro-rata rationing, computed by hand: three bids of 60 for 3, 3 and 4
+/// share a seller's 7 units at 45; the floors 2.1, 2.1 and 2.8 hand out 6
+/// and the largest remainder gives the last unit to buyer 2, where priority
+/// serves 3, 3 and 1. Two bids for 5 each floor 3.5 to 3, and the tied
+/// remainders give the last unit to the lower id
+#[test]
+fn pro_rata_remainder_decides_the_last_unit() {
+    let table = [
+        (vec![3, 3, 4], vec![2, 2, 3, 7], vec![3, 3, 1, 7]),
+        (vec![5, 5], vec![4, 3, 7], vec![5, 2, 7]),
+    ];
+    for (quantities, pro_rata, priority) in table {
+        let seller = quantities.len() as u32;
+        let mut participants: Vec<Participant> = (0..seller)
+            .map(|id| order(id, 0, 60, quantities[id as usize], 1000, 0))
+            .collect();
+        participants.push(order(seller, 1, 30, 10, 0, 7));
+        for (allocation_rule, allocation) in [
+            (AllocationRule::ProRata, &pro_rata),
+            (AllocationRule::Priority, &priority),
+        ] {
+            let config = AuctionConfig {
+                allocation_rule,
+                ..AuctionConfig::default()
+            };
+            let journal = clear_book(&auction(participants.clone(), config)).unwrap();
+            assert_eq!((journal.clearing_price, journal.traded_volume), (45, 7));
+            assert_eq!(
+                &journal.allocation, allocation,
+                "{:?} {:?}",
+                quantities, allocation_rule
+            );
+        }
+    }
+}
+
+/// Max-min rationing against priority, computed by hand: bids of 60 for 2,
+/// 6 and 6 share a seller's 10 units at 45; water-filling fills buyer 0's
+/// 2 and raises the others to 4 each, where priority serves 2, 6 and 2.
+/// With 11 units the level stays 4 and the unit left over goes to the
+/// lower id above it, buyer 1; both rules hand out the same total
+#[test]
+fn max_min_levels_where_priority_ranks() {
+    for (energy, max_min, priority) in [
+        (10, vec![2, 4, 4, 10], vec![2, 6, 2, 10]),
+        (11, vec![2, 5, 4, 11], vec![2, 6, 3, 11]),
+    ] {
+        let participants = vec![
+            order(0, 0, 60, 2, 1000, 0),
+            order(1, 0, 60, 6, 1000, 0),
+            order(2, 0, 60, 6, 1000, 0),
+            order(3, 1, 30, 14, 0, energy),
+        ];
+        for (allocation_rule, allocation) in [
+            (AllocationRule::MaxMin, &max_min),
+            (AllocationRule::Priority, &priority),
+        ] {
+            let config = AuctionConfig {
+                allocation_rule,
+                ..AuctionConfig::default()
+            };
+            let journal = clear_book(&auction(participants.clone(), config)).unwrap();
+            assert_eq!(
+                (journal.clearing_price, journal.traded_volume),
+                (45, energy)
+            );
+            assert_eq!(
+                &journal.allocation, allocation,
+                "{} {:?}",
+                energy, allocation_rule
+            );
+            assert_eq!(journal.allocation[..3].iter().sum::<u64>(), energy);
+        }
+    }
+}
+
+/// Three bids of 60 for 4, 6 and 2 (ids 0, 1, 2) share a seller's 7 units
+/// at 45, computed by hand under each tie-break: by id 4 and 3, by
+/// descending quantity 6 and 1, by ascending quantity 2, 4 and 1; the
+/// journal lists the buyers in the same order the allocation served them
+#[test]
+fn tie_breaks_order_the_journal_and_the_allocation() {
+    let participants = vec![
+        order(0, 0, 60, 4, 1000, 0),
+        order(1, 0, 60, 6, 1000, 0),
+        order(2, 0, 60, 2, 1000, 0),
+        order(3, 1, 30, 12, 0, 7),
+    ];
+    let table = [
+        (TieBreak::ById, [0, 1, 2, 3], [4, 3, 0, 7]),
+        (TieBreak::ByQuantityDesc, [1, 0, 2, 3], [6, 1, 0, 7]),
+        (TieBreak::ByQuantityAsc, [2, 0, 1, 3], [2, 4, 1, 7]),
+    ];
+    for (tie_break, ids, allocation) in table {
+        let config = AuctionConfig {
+            tie_break,
+            ..AuctionConfig::default()
+        };
+        let journal = clear_book(&auction(participants.clone(), config)).unwrap();
+        assert_eq!(journal.ids, ids, "{:?}", tie_break);
+        assert_eq!(journal.allocation, allocation, "{:?}", tie_break);
+        let paid: Vec<u64> = allocation[..3]
+            .iter()
+            .map(|units| 1000 - 45 * units)
+            .collect();
+        assert_eq!(journal.out_coin[..3], paid[..], "{:?}", tie_break);
+    }
+}
+
+/// Seeded ties: three bids of 60 for 4 share a seller's 7 units, ranked by
+/// SHA-256(seed || id) computed apart from the guest; seed [1; 32] ranks
+/// them 0, 2, 1 and seed [3; 32] 1, 0, 2, so participant 1 buys nothing
+/// under the first and 4 units under the second, and clearing under either
+/// seed again commits the same journal
+#[test]
+fn seeds_reorder_the_marginal_tier() {
+    let participants = vec![
+        order(0, 0, 60, 4, 1000, 0),
+        order(1, 0, 60, 4, 1000, 0),
+        order(2, 0, 60, 4, 1000, 0),
+        order(3, 1, 30, 12, 0, 7),
+    ];
+    for (seed, ranked, bought_by_1) in [([1; 32], [0, 2, 1], 0), ([3; 32], [1, 0, 2], 4)] {
+        let mut by_rank = vec![0u32, 1, 2];
+        by_rank.sort_by_key(|id| sha256(&[&seed, &id.to_le_bytes()]));
+        assert_eq!(by_rank, ranked);
+
+        let config = AuctionConfig {
+            tie_break: TieBreak::Seeded,
+            seed,
+            ..AuctionConfig::default()
+        };
+        let input = auction(participants.clone(), config);
+        let journal = clear_book(&input).unwrap();
+        assert_eq!(journal.ids, [ranked[0], ranked[1], ranked[2], 3]);
+        assert_eq!(journal.allocation, [4, 3, 0, 7]);
+        let row = journal.ids.iter().position(|&id| id == 1).unwrap();
+        assert_eq!(journal.allocation[row], bought_by_1);
+
+        let again = clear_book(&input).unwrap();
+        assert_eq!(
+            risc0_zkvm::serde::to_vec(&again).unwrap(),
+            risc0_zkvm::serde::to_vec(&journal).unwrap()
+        );
+    }
+}
+
+/// The three objectives on one pro-rata book, computed by hand: bids 100
+/// and 20 for 10 against asks 10 for 10 and 15 for 5. At the grid price
+/// 15 both bids share the 15 units at 17 (8 and 7, surplus 765), the
+/// largest volume; at 10 only bid 100 stays, buying seller 2's 10 at 55
+/// (surplus 900), the largest surplus; FirstCrossing stops at 100, where
+/// bid 100 buys 7 and 3 from both sellers at 55 (surplus 885). The
+/// objective journal reports the price, volume and surplus each chose
+#[test]
+fn objectives_pick_different_prices() {
+    let participants = vec![
+        order(0, 0, 100, 10, 2000, 0),
+        order(1, 0, 20, 10, 2000, 0),
+        order(2, 1, 10, 10, 0, 10),
+        order(3, 1, 15, 5, 0, 5),
+    ];
+    let table = [
+        (Objective::MaxVolume, 17, 15, 765, [8, 7, 10, 5]),
+        (Objective::MaxSurplus, 55, 10, 900, [10, 0, 10, 0]),
+        (Objective::FirstCrossing, 55, 10, 885, [10, 0, 7, 3]),
+    ];
+    for (objective, price, volume, surplus, allocation) in table {
+        let config = AuctionConfig {
+            allocation_rule: AllocationRule::ProRata,
+            objective,
+            ..AuctionConfig::default()
+        };
+        let input = auction(participants.clone(), config);
+        let journal = clear_book(&input).unwrap();
+        assert_eq!(journal.clearing_price, price, "{:?}", objective);
+        assert_eq!(
+            (journal.traded_volume, journal.surplus),
+            (volume, surplus),
+            "{:?}",
+            objective
+        );
+        assert_eq!(journal.allocation, allocation, "{:?}", objective);
+
+        let summary = build_objective_journal(&input, &journal);
+        assert_eq!(summary.objective, objective);
+        assert_eq!(
+            (summary.clearing_price, summary.volume, summary.surplus),
+            (price, volume, surplus as u128),
+            "{:?}",
+            objective
+        );
+    }
+}
+
+/// Objectives on a stepped book: buyer 0 bids 100 for 10, buyer 1 steps of
+/// 10 at 50 and 10 at 45, against seller 2's 12 at 10 and seller 3's 10 at
+/// 60, under ProRata. Grid price 10 shares seller 2's 12 units 4 / 4 / 4
+/// over the three orders (volume 12; buyer 1's 8 valued best step first,
+/// surplus 400 + 400 − 120 = 680), grid price 50 leaves buyer 1's second
+/// step out and shares them 6 / 6 (volume 12, surplus 780), grid price 60
+/// gives buyer 0 its 10 from seller 2 (volume 10, surplus 900). Valuing
+/// each step at buyer 1's whole allocation made grid price 50 worth 1050
+/// and grid price 10 a volume of 20; counted per participant, MaxSurplus
+/// picks the crossing at 60 and MaxVolume grid price 10, and the summary
+/// commits their true values
+#[test]
+fn objectives_value_a_stepped_bid_step_by_step() {
+    let participants = vec![
+        order(0, 0, 100, 10, 10_000, 0),
+        Participant {
+            steps: vec![(50, 10), (45, 10)],
+            ..order(1, 0, 50, 20, 10_000, 0)
+        },
+        order(2, 1, 10, 12, 0, 12),
+        order(3, 1, 60, 10, 0, 10),
+    ];
+    let summary = |objective| {
+        let config = AuctionConfig {
+            allocation_rule: AllocationRule::ProRata,
+            objective,
+            ..AuctionConfig::default()
+        };
+        let input = auction(participants.clone(), config);
+        let journal = clear_book(&input).unwrap();
+        (journal.clone(), build_objective_journal(&input, &journal))
+    };
+
+    let (journal, surplus) = summary(Objective::MaxSurplus);
+    assert_eq!(journal.out_energy, [10, 0, 2, 10]);
+    assert_eq!((surplus.volume, surplus.surplus), (10, 900));
+
+    let (journal, volume) = summary(Objective::MaxVolume);
+    assert_eq!(journal.out_energy, [4, 8, 0, 10]);
+    assert_eq!((volume.volume, volume.surplus), (12, 680));
+}
+
+/// An iceberg bid, computed by hand: bid 70 for 1000 showing 10 and two
+/// bids of 60 for 15 share a seller's 60 units at 45. The first pass
+/// serves the 10 shown and both smaller bids in full, 40 units, and two
+/// more slices of 10 use up the rest, so buyer 0 buys 30, where without
+/// the display it would take all 60; both sides sum to the 60 sold
+#[test]
+fn iceberg_bid_shares_the_supply() {
+    for (display_quantity, allocation, out_coin) in [
+        (Some(10), [30, 15, 15, 60], [98_650, 325, 325, 2700]),
+        (None, [60, 0, 0, 60], [97_300, 1000, 1000, 2700]),
+    ] {
+        let participants = vec![
+            Participant {
+                display_quantity,
+                ..order(0, 0, 70, 1000, 100_000, 0)
+            },
+            order(1, 0, 60, 15, 1000, 0),
+            order(2, 0, 60, 15, 1000, 0),
+            order(3, 1, 30, 2000, 0, 60),
+        ];
+        let journal = clear_book(&auction(participants, AuctionConfig::default())).unwrap();
+        assert_eq!((journal.clearing_price, journal.traded_volume), (45, 60));
+        assert_eq!(journal.allocation, allocation, "{:?}", display_quantity);
+        assert_eq!(journal.out_coin, out_coin, "{:?}", display_quantity);
+        assert_eq!(
+            journal.allocation[..3].iter().sum::<u64>(),
+            journal.allocation[3]
+        );
+        assert_eq!(
+            journal.out_coin.iter().sum::<u64>(),
+            journal.in_coin.iter().sum::<u64>()
+        );
+    }
+}
+
+/// Self-trade prevention, computed by hand on bids 60 and 50 and asks 30
+/// and 50 for 10 each. Uniform price clears all 20 at 50, with the marginal
+/// ask 50 and bid 60 owned by one owner: its 10 bought and 10 sold net to
+/// nothing and are withdrawn, leaving bid 50 buying ask 30's 10 at 50.
+/// Greedy with bid 60 and ask 30 of one owner pairs bid 60 with ask 50 at
+/// 55 and bid 50 with ask 30 at 40, where without owners they pair 60
+/// with 30 at 45 and 50 with 50
+#[test]
+fn one_owner_never_trades_with_itself() {
+    let book = |buyer_owner, seller: usize, seller_owner| {
+        let mut participants = vec![
+            Participant {
+                owner: buyer_owner,
+                ..order(0, 0, 60, 10, 1000, 0)
+            },
+            order(1, 0, 50, 10, 1000, 0),
+            order(2, 1, 30, 10, 0, 10),
+            order(3, 1, 50, 10, 0, 10),
+        ];
+        participants[seller].owner = seller_owner;
+        participants
+    };
+    let uniform = clear_book(&auction(
+        book(Some(7), 3, Some(7)),
+        AuctionConfig::default(),
+    ))
+    .unwrap();
+    assert_eq!((uniform.clearing_price, uniform.traded_volume), (50, 10));
+    assert_eq!(uniform.allocation, [0, 10, 10, 0]);
+    assert_eq!(uniform.out_coin, [1000, 500, 500, 0]);
+
+    let greedy = AuctionConfig {
+        algorithm: Algorithm::Greedy,
+        ..AuctionConfig::default()
+    };
+    let journal = clear_book(&auction(book(Some(7), 2, Some(7)), greedy.clone())).unwrap();
+    assert_eq!(journal.allocation, [10, 10, 10, 10]);
+    assert_eq!(journal.out_coin, [450, 600, 400, 550]);
+    let journal = clear_book(&auction(book(None, 2, None), greedy)).unwrap();
+    assert_eq!(journal.out_coin, [550, 500, 450, 500]);
+}
+
+/// A budget bid at the margin, computed by hand: bid 60 for 10 and a bid
+/// of 50 with a budget of 300 against an ask of 30 for 20. At 30 the
+/// budget buys 10, so demand meets the 20 offered and the budget bid holds
+/// the last matched unit, pricing the book at (50 + 30) / 2 = 40; there
+/// its 300 buys 7 units for 280, and the seller keeps 3 of its 20
+#[test]
+fn budget_bid_is_the_marginal_buyer() {
+    let participants = vec![
+        order(0, 0, 60, 10, 1000, 0),
+        Participant {
+            budget: 300,
+            ..order(1, 0, 50, 0, 1000, 0)
+        },
+        order(2, 1, 30, 20, 0, 20),
+    ];
+    let journal = clear_book(&auction(participants, AuctionConfig::default())).unwrap();
+    assert_eq!((journal.clearing_price, journal.traded_volume), (40, 17));
+    assert_eq!(journal.allocation, [10, 7, 17]);
+    assert_eq!(journal.out_coin, [600, 720, 680]);
+    assert_eq!(journal.out_energy, [10, 7, 3]);
+}
+
+/// Congestion rent, computed by hand: a bid of 60 and an ask of 50 for 20
+/// each, with the grid capped at 10 units, leave both sides unserved at
+/// the cap, so the buyer pays its bid 60 and the seller receives its ask 50
+/// for the 10 units, and the operator (participant 2) collects exactly
+/// (60 − 50) × 10 = 100; without the cap both trade 20 at 55
+#[test]
+fn congestion_rent_goes_to_the_operator() {
+    let participants = vec![
+        order(0, 0, 60, 20, 2000, 0),
+        order(1, 1, 50, 20, 0, 20),
+        order(2, 1, 0, 0, 0, 0),
+    ];
+    let table = [
+        (10, 60, 10, [1400, 100, 500]),
+        (u64::MAX, 55, 20, [900, 0, 1100]),
+    ];
+    for (max_traded_volume, price, volume, out_coin) in table {
+        let config = AuctionConfig {
+            congestion_rent: true,
+            max_traded_volume,
+            operator_id: Some(2),
+            ..AuctionConfig::default()
+        };
+        let journal = clear_book(&auction(participants.clone(), config)).unwrap();
+        assert_eq!(journal.ids, [0, 2, 1]);
+        assert_eq!(
+            (journal.clearing_price, journal.traded_volume),
+            (price, volume)
+        );
+        assert_eq!(journal.out_coin, out_coin, "cap {}", max_traded_volume);
+    }
+}
+
+/// Curtailment compensation, computed by hand: a bid of 60 for 4 clears at
+/// 45 against three asks of 30, priority sellers 2 (6 units) and 3 (3
+/// units) served first, so seller 2 sells 4 and pays a 10% funding fee of
+/// 18 on its 180. Priority sellers 2 and 3 are left with 2 and 3 units
+/// curtailed: at 2 coins a unit the pool pays the 4 and 6 owed and the
+/// operator keeps 8; at 10 a unit the 20 and 30 owed exceed the pool, so
+/// they are scaled down to floor(18 × 20 / 50) = 7 and floor(18 × 30 / 50)
+/// = 10, and the operator receives the 1 coin of dust
+#[test]
+fn curtailment_compensation_scales_and_leaves_dust_to_the_operator() {
+    let participants = vec![
+        order(0, 0, 60, 4, 1000, 0),
+        order(1, 1, 30, 10, 0, 10),
+        Participant {
+            priority: true,
+            ..order(2, 1, 30, 6, 0, 6)
+        },
+        Participant {
+            priority: true,
+            ..order(3, 1, 30, 3, 0, 3)
+        },
+        order(4, 1, 0, 0, 0, 0),
+    ];
+    for (curtailment_comp_per_unit, out_coin) in
+        [(2, [820, 8, 0, 166, 6]), (10, [820, 1, 0, 169, 10])]
+    {
+        let config = AuctionConfig {
+            curtailment_comp_per_unit,
+            funding_fee_bps: 1000,
+            operator_id: Some(4),
+            ..AuctionConfig::default()
+        };
+        let journal = clear_book(&auction(participants.clone(), config)).unwrap();
+        assert_eq!(journal.ids, [0, 4, 1, 2, 3]);
+        assert_eq!((journal.clearing_price, journal.traded_volume), (45, 4));
+        assert_eq!(journal.allocation, [4, 0, 0, 4, 0]);
+        assert_eq!(
+            journal.out_coin, out_coin,
+            "{} a unit",
+            curtailment_comp_per_unit
+        );
+    }
+}
+
+/// Position limits, computed by hand under every allocation rule: buyer 0
+/// holds 4 units under a limit of 10, so it buys 6 though its coin affords
+/// 22 at 45, next to buyer 1's 10 from a seller offering 20; a limit of 0
+/// excludes buyer 0, and buyer 1 buys a seller's 10 alone
+#[test]
+fn position_limits_cap_the_buyer() {
+    let books = [
+        (Some(10), 4, 20, [6, 10, 16], [10, 10, 4]),
+        (Some(0), 0, 10, [0, 10, 10], [0, 10, 0]),
+    ];
+    for (limit, held, offered, allocation, out_energy) in books {
+        let participants = vec![
+            Participant {
+                max_position_energy: limit,
+                ..order(0, 0, 60, 10, 1000, held)
+            },
+            order(1, 0, 60, 10, 1000, 0),
+            order(2, 1, 30, offered, 0, offered),
+        ];
+        for allocation_rule in [
+            AllocationRule::Priority,
+            AllocationRule::ProRata,
+            AllocationRule::MaxMin,
+        ] {
+            let config = AuctionConfig {
+                allocation_rule,
+                ..AuctionConfig::default()
+            };
+            let journal = clear_book(&auction(participants.clone(), config)).unwrap();
+            let case = (limit, allocation_rule);
+            assert_eq!(journal.clearing_price, 45, "{:?}", case);
+            assert_eq!(journal.allocation, allocation, "{:?}", case);
+            assert_eq!(journal.out_energy, out_energy, "{:?}", case);
+        }
+    }
+}
+
+/// The secondary round carries each primary row's energy change over as
+/// the quantity it traded, so whatever breaks that is refused before
+/// clearing; the plain book trades its 2 units at 15
+#[test]
+fn secondary_round_refuses_what_it_cannot_carry_over() {
+    let book = || vec![order(0, 0, 20, 2, 100, 0), order(1, 1, 10, 2, 0, 2)];
+    let concession = AuctionConfig {
+        secondary_concession: Some(5),
+        ..AuctionConfig::default()
+    };
+    let refused = |participants: Vec<Participant>, config: AuctionConfig| {
+        validate_input(&auction(participants, config)) == Err(AuctionError::UnsupportedMode)
+    };
+    assert!(!refused(book(), concession.clone()));
+    let (journal, summary) = run_secondary_round(&auction(book(), concession.clone())).unwrap();
+    assert_eq!(journal.out_coin, [70, 30]);
+    assert_eq!((summary.primary_price, summary.secondary_volume), (15, 0));
+
+    let configs = [
+        AuctionConfig {
+            algorithm: Algorithm::McAfee,
+            ..concession.clone()
+        },
+        AuctionConfig {
+            settlement: Settlement::PayAsBid,
+            ..concession.clone()
+        },
+        AuctionConfig {
+            objective: Objective::MaxVolume,
+            ..concession.clone()
+        },
+        AuctionConfig {
+            fee_bps: 10,
+            operator_id: Some(1),
+            ..concession.clone()
+        },
+        AuctionConfig {
+            loss_bps: 100,
+            loss_sink_id: Some(1),
+            ..concession.clone()
+        },
+        AuctionConfig {
+            price_offset: 1,
+            ..concession.clone()
+        },
+        AuctionConfig {
+            congestion_rent: true,
+            ..concession.clone()
+        },
+        AuctionConfig {
+            bundles: true,
+            ..concession.clone()
+        },
+    ];
+    for config in configs {
+        assert!(refused(book(), config.clone()), "{:?}", config);
+    }
+
+    let mut stepped = book();
+    stepped[0].steps = vec![(20, 1), (15, 1)];
+    let mut budget_bid = book();
+    budget_bid[0].quantity = 0;
+    budget_bid[0].budget = 40;
+    let mut prosumer = book();
+    prosumer.push(order(2, 2, 15, 1, 100, 1));
+    let mut carbon = book();
+    carbon[1].carbon_per_unit = 1;
+    let mut zonal = book();
+    zonal[1].zone = 1;
+    for participants in [stepped, budget_bid, prosumer, carbon, zonal] {
+        assert!(refused(participants, concession.clone()));
+    }
+
+    let fee = AuctionConfig {
+        fee_bps: 10,
+        operator_id: Some(1),
+        ..concession
+    };
+    let (journal, summary) = run_secondary_round(&auction(book(), fee)).unwrap();
+    assert_eq!(journal.out_coin, journal.in_coin);
+    assert_eq!((summary.primary_price, summary.secondary_price), (0, 0));
+}
+
+/// An external settlement price on crossed_book, whose overlap is [30, 60]
+/// (marginal ask and bid): 50 and both edges settle buyer 0's 10 units at
+/// the index, while 29 and 61 lie outside and nothing trades
+#[test]
+fn settlement_price_outside_the_overlap_is_refused() {
+    for (index, traded) in [(50, true), (30, true), (60, true), (29, false), (61, false)] {
+        let config = AuctionConfig {
+            settlement_price: Some(index),
+            ..AuctionConfig::default()
+        };
+        let journal = clear_book(&auction(crossed_book(), config)).unwrap();
+        if traded {
+            assert_eq!((journal.clearing_price, journal.traded_volume), (index, 10));
+            assert_eq!(journal.out_coin, [1000 - 10 * index, 1000, 10 * index, 0]);
+        } else {
+            assert_eq!(
+                (journal.clearing_price, journal.traded_volume),
+                (0, 0),
+                "index {}",
+                index
+            );
+            assert_eq!(journal.out_coin, journal.in_coin, "index {}", index);
+        }
+    }
+}
+
+/// The settlement account, computed by hand on a bid of 60 and an ask of
+/// 30 for 10: pay-as-bid leaves a surplus of 600 − 300 credited to the
+/// account; VCG charges the buyer its externality 300 and pays the seller
+/// 600, a deficit of 300 the account covers from 500 coin, while from 299
+/// it would go negative, so the book does not trade
+#[test]
+fn settlement_account_absorbs_a_surplus_and_refuses_a_deficit() {
+    let table = [
+        (Settlement::PayAsBid, 0, Some([400, 300, 300])),
+        (Settlement::Vcg, 500, Some([700, 200, 600])),
+        (Settlement::Vcg, 300, Some([700, 0, 600])),
+        (Settlement::Vcg, 299, None),
+    ];
+    for (settlement, account_coin, out_coin) in table {
+        let participants = vec![
+            order(0, 0, 60, 10, 1000, 0),
+            order(1, 1, 30, 10, 0, 10),
+            order(2, 1, 0, 0, account_coin, 0),
+        ];
+        let config = AuctionConfig {
+            settlement,
+            operator_id: Some(2),
+            ..AuctionConfig::default()
+        };
+        let journal = clear_book(&auction(participants, config)).unwrap();
+        let case = (settlement, account_coin);
+        assert_eq!(journal.ids, [0, 2, 1]);
+        match out_coin {
+            Some(out_coin) => {
+                assert_eq!(journal.traded_volume, 10, "{:?}", case);
+                assert_eq!(journal.out_coin, out_coin, "{:?}", case);
+            }
+            None => {
+                assert_eq!(journal.traded_volume, 0, "{:?}", case);
+                assert_eq!(journal.out_coin, journal.in_coin, "{:?}", case);
+            }
+        }
+    }
+}
+
+/// Milli-unit quantities near u64::MAX: at quantity_scale 1000, 10^7 units
+/// are 10^10 raw units, which at 10^9 coin a raw unit cost 10^19 coin,
+/// computed in u128. A seller holding u64::MAX − 10^19 ends at exactly
+/// u64::MAX, one coin more is an Overflow, not a wrap, and a buyer one
+/// coin short of 10^19 affords one raw unit fewer, keeping 10^9 − 1
+#[test]
+fn scaled_quantities_settle_up_to_u64_max() {
+    const RAW_UNITS: u64 = 10_000_000_000;
+    const PRICE: u64 = 1_000_000_000;
+    const NOTIONAL: u64 = 10_000_000_000_000_000_000;
+    let book = |buyer_coin, seller_coin| {
+        let participants = vec![
+            order(0, 0, PRICE, RAW_UNITS, buyer_coin, 0),
+            order(1, 1, PRICE, RAW_UNITS, seller_coin, RAW_UNITS),
+        ];
+        let config = AuctionConfig {
+            quantity_scale: 1000,
+            ..AuctionConfig::default()
+        };
+        clear_book(&auction(participants, config))
+    };
+
+    let journal = book(u64::MAX, u64::MAX - NOTIONAL).unwrap();
+    assert_eq!(
+        (journal.clearing_price, journal.traded_volume),
+        (PRICE, RAW_UNITS)
+    );
+    assert_eq!(journal.out_coin, [u64::MAX - NOTIONAL, u64::MAX]);
+
+    assert_eq!(
+        book(u64::MAX, u64::MAX - NOTIONAL + 1).unwrap_err(),
+        AuctionError::Overflow
+    );
+
+    let journal = book(NOTIONAL - 1, 0).unwrap();
+    assert_eq!(journal.traded_volume, RAW_UNITS - 1);
+    assert_eq!(journal.out_coin, [PRICE - 1, NOTIONAL - PRICE]);
+}
+
+/// Payments near the u64 boundary, computed by hand: 10^10 units quoted at
+/// a wholesale 2 × 10^9 are 2 × 10^19 coin, past u64::MAX, so a buyer
+/// holding u64::MAX affords floor(u64::MAX / (2 × 10^9)) = 9_223_372_036
+/// units; it pays 18_446_744_072_000_000_000 and keeps 1_709_551_615, the
+/// seller receiving exactly that. A notional or fee past i128 and u128 is
+/// refused rather than wrapped
+#[test]
+fn payments_near_u64_max_are_exact_or_refused() {
+    let participants = vec![
+        order(0, 0, 2_000_000_000, 10_000_000_000, u64::MAX, 0),
+        order(1, 1, 2_000_000_000, 10_000_000_000, 0, 10_000_000_000),
+    ];
+    let journal = clear_book(&auction(participants, AuctionConfig::default())).unwrap();
+    assert_eq!(journal.traded_volume, 9_223_372_036);
+    assert_eq!(
+        journal.out_coin,
+        [1_709_551_615, 18_446_744_072_000_000_000]
+    );
+    assert_eq!(journal.out_energy, [9_223_372_036, 776_627_964]);
+
+    assert_eq!(
+        affordable_units(u64::MAX, 1, &AuctionConfig::default()),
+        u64::MAX
+    );
+    assert_eq!(settle(i128::MAX, 2, 0), None);
+    assert_eq!(trade_fee(u128::MAX, 2), None);
+    assert_eq!(
+        trade_fee(u128::MAX / 10_000, 10_000),
+        Some(u128::MAX / 10_000)
+    );
+}
+
+/// Sums past u64::MAX: two buyers each bidding 2 for u64::MAX units with
+/// u64::MAX coin demand 2 × u64::MAX, summed in u128, against a seller's
+/// 10 at 1; the book clears at 1 with buyer 0 buying the 10, and the
+/// journal, whose coin column also sums past u64::MAX, passes the
+/// conservation check
+#[test]
+fn sums_past_u64_max_stay_exact() {
+    let participants = vec![
+        order(0, 0, 2, u64::MAX, u64::MAX, 0),
+        order(1, 0, 2, u64::MAX, u64::MAX, 0),
+        order(2, 1, 1, 10, 0, 10),
+    ];
+    let input = auction(participants, AuctionConfig::default());
+    let (buyers, sellers) = separate_and_sort(&input.participants, &input.config);
+    assert_eq!(
+        demand_supply_at(&buyers, &sellers, 1, &input.config),
+        (2 * u64::MAX as u128, 10)
+    );
+
+    let journal = clear_book(&input).unwrap();
+    assert_eq!((journal.clearing_price, journal.traded_volume), (1, 10));
+    assert_eq!(journal.out_coin, [u64::MAX - 10, u64::MAX, 10]);
+    assert_eq!(journal.out_energy, [10, 0, 0]);
+    assert_eq!(
+        check_journal(&journal, &input.participants, &input.config),
+        Ok(())
+    );
+}
+
+/// Duplicate ids: a buyer and a seller sharing id 5 next to distinct ids
+/// are refused as DuplicateId(5), exit code 1, in either row order, before
+/// clearing could merge their rows; with ids 7 and 3 both shared the
+/// smallest is named, and one id on two commodities' books is not a
+/// duplicate
+#[test]
+fn buyer_and_seller_sharing_an_id_are_refused() {
+    let shared = vec![
+        order(1, 0, 60, 10, 1000, 0),
+        order(5, 0, 50, 10, 1000, 0),
+        order(2, 1, 30, 10, 0, 10),
+        order(5, 1, 40, 10, 0, 10),
+    ];
+    let mut reversed = shared.clone();
+    reversed.reverse();
+    for participants in [shared, reversed] {
+        let error = validate_input(&auction(participants, AuctionConfig::default())).unwrap_err();
+        assert_eq!(error, AuctionError::DuplicateId(5));
+        assert_eq!(error.exit_code(), EXIT_DUPLICATE_ID);
+    }
+
+    let twice = vec![
+        order(7, 0, 60, 10, 1000, 0),
+        order(3, 0, 50, 10, 1000, 0),
+        order(7, 1, 30, 10, 0, 10),
+        order(3, 1, 40, 10, 0, 10),
+    ];
+    assert_eq!(
+        validate_input(&auction(twice, AuctionConfig::default())),
+        Err(AuctionError::DuplicateId(3))
+    );
+
+    let commodities = vec![
+        order(5, 0, 60, 10, 1000, 0),
+        order(6, 1, 30, 10, 0, 10),
+        Participant {
+            commodity: 1,
+            ..order(5, 1, 30, 10, 0, 10)
+        },
+        Participant {
+            commodity: 1,
+            ..order(7, 0, 60, 10, 1000, 0)
+        },
+    ];
+    assert_eq!(
+        validate_input(&auction(commodities, AuctionConfig::default())),
+        Ok(())
+    );
+}
+
+/// Degenerate markets: no participants is Empty and refused with
+/// EmptyMarket, buyers only is NoSellers, sellers only NoBuyers, and a
+/// book whose every order quotes 0 is ZeroQuantity, however its prices
+/// cross; each of the last three commits its balances unchanged with the
+/// status no crossing, and a book that trades is TwoSided
+#[test]
+fn degenerate_markets_have_their_own_status() {
+    let empty = auction(Vec::new(), AuctionConfig::default());
+    assert_eq!(market_status(&empty), MarketStatus::Empty);
+    assert_eq!(validate_input(&empty), Err(AuctionError::EmptyMarket));
+
+    let table = [
+        (
+            "buyers only",
+            vec![order(0, 0, 50, 10, 1000, 0), order(1, 0, 40, 10, 1000, 0)],
+            MarketStatus::NoSellers,
+        ),
+        (
+            "sellers only",
+            vec![order(0, 1, 20, 10, 0, 10), order(1, 1, 30, 10, 0, 10)],
+            MarketStatus::NoBuyers,
+        ),
+        (
+            "zero quantity",
+            vec![order(0, 0, 50, 0, 1000, 0), order(1, 1, 20, 0, 0, 10)],
+            MarketStatus::ZeroQuantity,
+        ),
+    ];
+    for (name, participants, status) in table {
+        let input = auction(participants, AuctionConfig::default());
+        assert_eq!(market_status(&input), status, "{}", name);
+        let journal = clear_book(&input).unwrap();
+        assert_eq!(journal.status, STATUS_NO_CROSSING, "{}", name);
+        assert_eq!(journal.out_coin, journal.in_coin, "{}", name);
+        assert_eq!(journal.out_energy, journal.in_energy, "{}", name);
+    }
+    assert_eq!(
+        market_status(&auction(crossed_book(), AuctionConfig::default())),
+        MarketStatus::TwoSided
+    );
+}
+
+/// The participant limit: MAX_PARTICIPANTS rows are accepted and one more
+/// is refused as TooManyParticipants, exit code 3, counting the rows of
+/// every round's book with the first
+#[test]
+fn participant_limit_at_and_one_over() {
+    let sellers = |first: usize, count: usize| -> Vec<Participant> {
+        (first..first + count)
+            .map(|id| order(id as u32, 1, 10, 1, 0, 1))
+            .collect()
+    };
+    let at_limit = auction(sellers(0, MAX_PARTICIPANTS), AuctionConfig::default());
+    assert_eq!(validate_input(&at_limit), Ok(()));
+    let over = auction(sellers(0, MAX_PARTICIPANTS + 1), AuctionConfig::default());
+    let error = validate_input(&over).unwrap_err();
+    assert_eq!(
+        error,
+        AuctionError::TooManyParticipants(MAX_PARTICIPANTS + 1)
+    );
+    assert_eq!(error.exit_code(), EXIT_PARTICIPANT_LIMIT);
+
+    for (round_rows, accepted) in [(1, true), (2, false)] {
+        let mut input = auction(sellers(0, MAX_PARTICIPANTS - 1), AuctionConfig::default());
+        input.rounds = vec![RoundInput {
+            participants: sellers(0, round_rows),
+        }];
+        assert_eq!(
+            validate_input(&input).is_ok(),
+            accepted,
+            "{} round rows",
+            round_rows
+        );
+    }
+}
+
+/// Zero-quantity rows on crossed_book: a buyer bidding 90 and a seller
+/// asking 1 for nothing would be the marginal pair if they cleared, but
+/// they pass through, so every rule prices as on crossed_book alone and
+/// both keep their balances in the journal (the bid of 90 ranks first in
+/// protocol order, the ask of 1 first among the sellers)
+#[test]
+fn zero_quantity_quotes_leave_the_price_alone() {
+    let mut participants = crossed_book();
+    participants.push(order(4, 0, 90, 0, 500, 0));
+    participants.push(order(5, 1, 1, 0, 0, 7));
+    let table = [
+        (PricingRule::MidPoint, 45, vec![500, 550, 1000, 0, 450, 0]),
+        (PricingRule::BuyerBid, 60, vec![500, 400, 1000, 0, 600, 0]),
+        (PricingRule::SellerAsk, 30, vec![500, 700, 1000, 0, 300, 0]),
+    ];
+    for (pricing_rule, price, out_coin) in table {
+        let config = AuctionConfig {
+            pricing_rule,
+            ..AuctionConfig::default()
+        };
+        let journal = clear_book(&auction(participants.clone(), config)).unwrap();
+        assert_eq!(journal.clearing_price, price, "{:?}", pricing_rule);
+        assert_eq!(journal.traded_volume, 10, "{:?}", pricing_rule);
+        assert_eq!(journal.out_coin, out_coin, "{:?}", pricing_rule);
+        assert_eq!(journal.ids, vec![4, 0, 1, 5, 2, 3], "{:?}", pricing_rule);
+        assert_eq!(
+            journal.allocation,
+            vec![0, 10, 0, 0, 10, 0],
+            "{:?}",
+            pricing_rule
+        );
+        assert_eq!(
+            journal.out_energy,
+            vec![0, 10, 0, 7, 0, 10],
+            "{:?}",
+            pricing_rule
+        );
+    }
+}
+
+/// The carbon journal goes through the checks clear_book makes: seller 1's
+/// proceeds of 10 overflow its coin, which strict mode refuses as Overflow
+/// and lenient mode settles as no trade, without any carbon. A seller
+/// holding no energy stays out of the book, so the buyer trades with seller
+/// 2 at the mid-point of 20 and 12
+#[test]
+fn carbon_co_clearing_passes_the_clear_book_checks() {
+    let participants = vec![
+        Participant {
+            carbon_budget: Some(10),
+            ..order(0, 0, 20, 1, 100, 0)
+        },
+        Participant {
+            carbon_per_unit: 3,
+            ..order(1, 1, 10, 1, u64::MAX - 5, 1)
+        },
+    ];
+    let strict = auction(participants.clone(), AuctionConfig::default());
+    assert_eq!(
+        run_carbon_auction(&strict).err(),
+        Some(AuctionError::Overflow)
+    );
+    let lenient = auction(
+        participants,
+        AuctionConfig {
+            strict: false,
+            ..AuctionConfig::default()
+        },
+    );
+    let (journal, carbon) = run_carbon_auction(&lenient).unwrap();
+    assert_eq!(journal.status, STATUS_SETTLED_AS_NO_TRADE);
+    assert_eq!((carbon.carbon, carbon.total_carbon), (vec![0, 0], 0));
+
+    let participants = vec![
+        Participant {
+            carbon_budget: Some(10),
+            ..order(0, 0, 20, 1, 100, 0)
+        },
+        Participant {
+            carbon_per_unit: 1,
+            ..order(1, 1, 1, 1, 0, 0)
+        },
+        Participant {
+            carbon_per_unit: 4,
+            ..order(2, 1, 12, 1, 0, 1)
+        },
+    ];
+    let (journal, carbon) =
+        run_carbon_auction(&auction(participants, AuctionConfig::default())).unwrap();
+    assert_eq!(journal.out_coin, vec![84, 0, 16]);
+    assert_eq!(journal.out_energy, vec![1, 0, 0]);
+    assert_eq!((carbon.carbon, carbon.total_carbon), (vec![4, 0, 4], 4));
+}
+
+/// A broken allocation vector, as a replaced clearing algorithm might
+/// hand compute_outputs on crossed_book at 45: buyer 0 filled 23 would pay
+/// 1035 of its 1000 coin and seller 2 filled 11 would deliver more than
+/// its 10 energy, each refused naming that participant and allocation
+/// (exit code 5) instead of underflowing; the honest vector settles
+#[test]
+fn broken_allocation_vector_names_the_participant() {
+    let participants = crossed_book();
+    let config = AuctionConfig::default();
+    let table = [
+        (
+            vec![((0, 0), 23), ((2, 1), 10)],
+            AuctionError::InsufficientCoin(0, 23),
+        ),
+        (
+            vec![((0, 0), 10), ((2, 1), 11)],
+            AuctionError::InsufficientEnergy(2, 11),
+        ),
+    ];
+    for (allocations, expected) in table {
+        let error = compute_outputs(&participants, &allocations, (45, 45), &config).unwrap_err();
+        assert_eq!(error, expected, "{:?}", allocations);
+        assert_eq!(error.exit_code(), EXIT_INSUFFICIENT_BALANCE);
+    }
+
+    let honest = [((0, 0), 10), ((2, 1), 10)];
+    let (outputs, fees) = compute_outputs(&participants, &honest, (45, 45), &config)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        outputs,
+        vec![(0, 550, 10), (1, 1000, 0), (2, 450, 0), (3, 0, 10)]
+    );
+    assert_eq!(fees, 0);
+}
+
+/// Allocations keyed by (id, role) where participant 7 bids and asks with
+/// one balance of 1000 coin and 10 energy: listed seller side first, a
+/// lookup by id alone would hand the buyer side the seller side's 4 units
+/// and net to nothing, but at 45 it pays 450 for its own 10, is paid 180
+/// for 4, and nets 730 coin and 16 energy while seller 8 is paid 270 for 6
+#[test]
+fn colliding_id_settles_each_role_its_own_allocation() {
+    let participants = vec![
+        order(7, 0, 60, 10, 1000, 10),
+        order(7, 1, 30, 10, 1000, 10),
+        order(8, 1, 40, 10, 0, 10),
+    ];
+    let allocations = [((7, 1), 4), ((8, 1), 6), ((7, 0), 10)];
+    assert_eq!(allocation_of(&allocations, &participants[0]), 10);
+    assert_eq!(allocation_of(&allocations, &participants[1]), 4);
+
+    let (outputs, fees) = compute_outputs(
+        &participants,
+        &allocations,
+        (45, 45),
+        &AuctionConfig::default(),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(outputs, vec![(7, 730, 16), (8, 270, 4)]);
+    assert_eq!(fees, 0);
+}
+
+/// The saturation boundaries of the saturation_*.json books, computed by
+/// hand (max_price raised to u64::MAX): u64::MAX units at 1 and one unit
+/// at u64::MAX each trade in full; a bid of u64::MAX against an ask of 1
+/// for 2^63 units bounds the settlement at 2^127 − 2^63 inside i128 and
+/// clears at 2^63, where the buyer affords one unit, and one more unit on
+/// each side passes i128::MAX and is refused with exit code 7
+#[test]
+fn saturation_boundaries_are_exact_or_refused() {
+    let config = AuctionConfig {
+        max_price: u64::MAX,
+        ..AuctionConfig::default()
+    };
+    let max = u64::MAX;
+    let half = 1u64 << 63;
+    let table = [
+        (1, 1, max, max, [0, max], [max, 0]),
+        (max, max, 1, 1, [0, max], [1, 0]),
+        (max, 1, half, half, [half - 1, half], [1, half - 1]),
+    ];
+    for (bid, ask, quantity, energy, out_coin, out_energy) in table {
+        let participants = vec![
+            order(0, 0, bid, quantity, max, 0),
+            order(1, 1, ask, quantity, 0, energy),
+        ];
+        let journal = clear_book(&auction(participants, config.clone())).unwrap();
+        assert_eq!(journal.out_coin, out_coin, "bid {} ask {}", bid, ask);
+        assert_eq!(journal.out_energy, out_energy, "bid {} ask {}", bid, ask);
+    }
+
+    let at_limit = vec![
+        order(0, 0, max, half, max, 0),
+        order(1, 1, 1, half, 0, half),
+    ];
+    assert_eq!(
+        settlement_bound(&at_limit, &config),
+        Some((1u128 << 127) - (1u128 << 63))
+    );
+    let over = vec![
+        order(0, 0, max, half + 1, max, 0),
+        order(1, 1, 1, half + 1, 0, half + 1),
+    ];
+    assert!(settlement_bound(&over, &config).unwrap() > i128::MAX as u128);
+    let error = validate_input(&auction(over, config)).unwrap_err();
+    assert_eq!(error, AuctionError::SettlementBound);
+    assert_eq!(error.exit_code(), EXIT_SETTLEMENT_BOUND);
+}
+
+/// Textbook marginal pairs, worked by hand on the marginal_*_N4.json books:
+/// demand 15 meeting supply 15 at 20 prices the last matched unit, bid 50
+/// against ask 20, at 35; a crossing inside seller 2's 15-unit ask pairs
+/// 60 with 20, not the highest qualified ask 50, at 40; and under
+/// MaxVolume a crossing inside buyer 0's 30-unit bid pairs 60 with 10,
+/// not the lowest qualified bid 20, at 35
+#[test]
+fn marginal_pair_holds_the_last_matched_unit() {
+    let table = [
+        (
+            vec![
+                order(0, 0, 60, 10, 1000, 0),
+                order(1, 0, 50, 5, 1000, 0),
+                order(2, 1, 20, 15, 0, 15),
+                order(3, 1, 70, 10, 0, 10),
+            ],
+            Objective::FirstCrossing,
+            35,
+            15,
+            vec![650, 825, 525, 0],
+            vec![10, 5, 0, 10],
+        ),
+        (
+            marginal_inside_ask().participants,
+            Objective::FirstCrossing,
+            40,
+            10,
+            vec![600, 1000, 400, 0],
+            vec![10, 0, 5, 10],
+        ),
+        (
+            vec![
+                order(0, 0, 60, 30, 1000, 0),
+                order(1, 0, 20, 10, 1000, 0),
+                order(2, 1, 10, 10, 0, 10),
+                order(3, 1, 70, 10, 0, 10),
+            ],
+            Objective::MaxVolume,
+            35,
+            10,
+            vec![650, 1000, 350, 0],
+            vec![10, 0, 0, 10],
+        ),
+    ];
+    for (participants, objective, price, volume, out_coin, out_energy) in table {
+        let config = AuctionConfig {
+            objective,
+            ..AuctionConfig::default()
+        };
+        let journal = clear_book(&auction(participants, config)).unwrap();
+        assert_eq!(journal.clearing_price, price, "{:?}", objective);
+        assert_eq!(journal.traded_volume, volume, "{:?}", objective);
+        assert_eq!(journal.out_coin, out_coin, "{:?}", objective);
+        assert_eq!(journal.out_energy, out_energy, "{:?}", objective);
+    }
+}
+
+/// Crossing edges of the crossing_*_N*.json books, worked by hand: a bid
+/// of 50 against an ask of 50 crosses at the single grid price; bids 60
+/// and 50 against asks 55 and 60 cross only at the top grid price 60,
+/// where buyer 0 buys from seller 2 at 57; and a bid of 60 for 20 against
+/// an ask of 50 for 10 never crosses, so the top price clears it with the
+/// buyer rationed to the 10 units at 55
+#[test]
+fn crossing_at_one_price_point_trades() {
+    let table = [
+        (
+            vec![order(0, 0, 50, 10, 1000, 0), order(1, 1, 50, 10, 0, 10)],
+            50,
+            vec![500, 500],
+            vec![10, 0],
+        ),
+        (
+            vec![
+                order(0, 0, 60, 10, 1000, 0),
+                order(1, 0, 50, 10, 1000, 0),
+                order(2, 1, 55, 10, 0, 10),
+                order(3, 1, 60, 10, 0, 10),
+            ],
+            57,
+            vec![430, 1000, 570, 0],
+            vec![10, 0, 0, 10],
+        ),
+        (
+            vec![order(0, 0, 60, 20, 5000, 0), order(1, 1, 50, 10, 0, 10)],
+            55,
+            vec![4450, 550],
+            vec![10, 0],
+        ),
+    ];
+    for (participants, price, out_coin, out_energy) in table {
+        let journal = clear_book(&auction(participants, AuctionConfig::default())).unwrap();
+        assert_eq!(journal.status, STATUS_CLEARED, "price {}", price);
+        assert_eq!(journal.clearing_price, price);
+        assert_eq!(journal.traded_volume, 10, "price {}", price);
+        assert_eq!(journal.out_coin, out_coin, "price {}", price);
+        assert_eq!(journal.out_energy, out_energy, "price {}", price);
+    }
+}
+
+/// expiry_best_bid_N3.json: buyer 0's bid of 100 expired after round 2, so in
+/// round 3 it neither sets the clearing price nor trades; buyer 1 and seller
+/// 2 clear at (60 + 20) / 2 = 40, the price without buyer 0 at all, while in
+/// round 2 (expiry_still_valid_N3.json) buyer 0 takes all 10 at 60
+#[test]
+fn expired_best_bid_does_not_set_the_price() {
+    let participants = vec![
+        Participant {
+            valid_until_round: 2,
+            ..order(0, 0, 100, 10, 2000, 0)
+        },
+        order(1, 0, 60, 10, 2000, 0),
+        order(2, 1, 20, 10, 0, 10),
+    ];
+    let in_round = |current_round: u32| AuctionConfig {
+        current_round,
+        ..AuctionConfig::default()
+    };
+
+    let input = auction(participants.clone(), in_round(3));
+    let journal = clear_book(&input).unwrap();
+    assert_eq!((journal.clearing_price, journal.traded_volume), (40, 10));
+    assert_eq!(journal.out_coin, [2000, 1600, 400]);
+    assert_eq!(journal.out_energy, [0, 10, 0]);
+    assert_eq!(journal.allocation, [0, 10, 10]);
+    let without_expired = clear_book(&auction(participants[1..].to_vec(), in_round(3))).unwrap();
+    assert_eq!(journal.clearing_price, without_expired.clearing_price);
+
+    let journal = clear_book(&auction(participants, in_round(2))).unwrap();
+    assert_eq!((journal.clearing_price, journal.traded_volume), (60, 10));
+    assert_eq!(journal.out_coin, [1400, 2000, 600]);
+    assert_eq!(journal.out_energy, [10, 0, 0]);
+}
+
+/// zero_bid_*.json: a bid of 0 qualifies only when the crossing price p* is
+/// 0. Against an ask of 0 it buys 5 units free, even without coin, and an
+/// ask of 10 that does not cross leaves that outcome alone; against an ask of
+/// 1 nothing trades; with demand at 0 above the supply p* rises to buyer 0's
+/// 20 and the zero bid is priced out though seller 2 has units left. Clock
+/// prices start at 1, so the clocks never fill a zero bid
+#[test]
+fn zero_bids_buy_only_at_a_crossing_price_of_zero() {
+    let free = vec![order(0, 0, 0, 5, 100, 0), order(1, 1, 0, 5, 0, 5)];
+    let mut irrelevant_ask = free.clone();
+    irrelevant_ask.push(order(2, 1, 10, 5, 0, 5));
+    let cases = [
+        (free.clone(), (0, 5), vec![100, 0], vec![5, 0]),
+        (
+            vec![order(0, 0, 0, 5, 0, 0), order(1, 1, 0, 5, 0, 5)],
+            (0, 5),
+            vec![0, 0],
+            vec![5, 0],
+        ),
+        (irrelevant_ask, (0, 5), vec![100, 0, 0], vec![5, 0, 5]),
+        (
+            vec![
+                order(0, 0, 0, 5, 100, 0),
+                order(1, 0, 0, 5, 100, 0),
+                order(2, 1, 1, 10, 0, 10),
+            ],
+            (0, 0),
+            vec![100, 100, 0],
+            vec![0, 0, 10],
+        ),
+        (
+            vec![
+                order(0, 0, 20, 5, 1000, 0),
+                order(1, 0, 0, 5, 1000, 0),
+                order(2, 1, 0, 8, 0, 10),
+            ],
+            (10, 5),
+            vec![950, 1000, 50],
+            vec![5, 0, 5],
+        ),
+    ];
+    for (participants, (price, volume), out_coin, out_energy) in cases {
+        let input = auction(participants, AuctionConfig::default());
+        let journal = clear_book(&input).unwrap();
+        assert_eq!(
+            (journal.clearing_price, journal.traded_volume),
+            (price, volume)
+        );
+        assert_eq!(journal.out_coin, out_coin);
+        assert_eq!(journal.out_energy, out_energy);
+    }
+
+    for algorithm in [Algorithm::DutchClock, Algorithm::EnglishClock] {
+        let config = AuctionConfig {
+            algorithm,
+            ..AuctionConfig::default()
+        };
+        let journal = clear_book(&auction(free.clone(), config)).unwrap();
+        assert_eq!(journal.traded_volume, 0, "{:?}", algorithm);
+        assert_eq!(journal.out_energy, [0, 5], "{:?}", algorithm);
+    }
+}
+
+proptest! {
+    /// The same input cleared twice commits the same words: the native
+    /// counterpart of the host's --check-determinism, which executes the
+    /// guest twice and needs the zkVM
+    #[test]
+    fn clearing_is_reproducible(
+        participants in book(),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+    ) {
+        let config = AuctionConfig { algorithm, pricing_rule, ..AuctionConfig::default() };
+        let input = auction(participants, config);
+        let committed = |input: &AuctionInput| -> Result<Vec<u32>, TestCaseError> {
+            let journal = clear(input)?;
+            Ok(risc0_zkvm::serde::to_vec(&(&journal, market_status(input))).unwrap())
+        };
+        prop_assert_eq!(committed(&input)?, committed(&input.clone())?);
+    }
+
+    /// Shuffling the participants leaves the journal unchanged: the rows are
+    /// committed in protocol order, whatever order the input lists them in
+    #[test]
+    fn clearing_is_permutation_invariant(
+        (participants, shuffled) in book()
+            .prop_flat_map(|book| (Just(book.clone()), Just(book).prop_shuffle())),
+        algorithm in algorithm(),
+        pricing_rule in pricing_rule(),
+        allocation_rule in prop_oneof![
+            Just(AllocationRule::Priority),
+            Just(AllocationRule::ProRata),
+            Just(AllocationRule::MaxMin),
+        ],
+        tie_break in prop_oneof![
+            Just(TieBreak::ById),
+            Just(TieBreak::ByQuantityDesc),
+            Just(TieBreak::ByQuantityAsc),
+            Just(TieBreak::Seeded),
+        ],
+    ) {
+        let config = AuctionConfig {
+            algorithm,
+            pricing_rule,
+            allocation_rule,
+            tie_break,
+            seed: [7; 32],
+            ..AuctionConfig::default()
+        };
+        let words = |participants: Vec<Participant>| {
+            let input = auction(participants, config.clone());
+            clear_book(&input)
+                .map(|journal| risc0_zkvm::serde::to_vec(&(&journal, market_status(&input))).unwrap())
+        };
+        prop_assert_eq!(words(participants), words(shuffled));
+    }
+}
+
+/// The journals committed in place of a PublicJournal lead with a tag of
+/// their own, so a decoder checks the layout before any other field: one
+/// small book each in commodities and zones (rounds and periods read the
+/// zkVM cycle counter, which a native build lacks), every tag apart from
+/// the others and from the journal variants
+#[test]
+fn multi_book_journals_lead_with_their_version() {
+    let book = || vec![order(0, 0, 30, 1, 100, 0), order(1, 1, 10, 1, 0, 1)];
+    let first_word = |words: Vec<u32>| words[0];
+
+    let mut commodities = book();
+    commodities.push(Participant {
+        commodity: 1,
+        ..order(2, 1, 10, 1, 0, 1)
+    });
+    let journal = run_commodities(&auction(commodities, AuctionConfig::default())).unwrap();
+    let commodities = first_word(risc0_zkvm::serde::to_vec(&journal).unwrap());
+
+    let mut zones = book();
+    zones[1].zone = 1;
+    let journal = run_zones(&auction(zones, AuctionConfig::default())).unwrap();
+    let zones = first_word(risc0_zkvm::serde::to_vec(&journal).unwrap());
+
+    assert_eq!(
+        [commodities, zones],
+        [COMMODITY_JOURNAL_VERSION, ZONAL_JOURNAL_VERSION]
+    );
+    let mut tags = vec![
+        JOURNAL_VERSION,
+        COMPACT_JOURNAL_VERSION,
+        DELTA_JOURNAL_VERSION,
+        ABI_JOURNAL_VERSION,
+        PRIVATE_JOURNAL_VERSION,
+        QUANTIZED_JOURNAL_VERSION,
+        SLIM_JOURNAL_VERSION,
+        ROUNDS_JOURNAL_VERSION,
+        PERIODS_JOURNAL_VERSION,
+        commodities,
+        zones,
+    ];
+    tags.sort();
+    tags.dedup();
+    assert_eq!(tags.len(), 11);
+}
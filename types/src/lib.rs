@@ -0,0 +1,1705 @@
+// ═══════════════════════════════════════════════════════════════════════════
+// Protocol Types - Shared by the Guest, Host, Properties and Fuzz
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// The input the host sends, the journals the guest commits, the codes it
+// exits with and the limits it enforces, defined once. The guest, the host,
+// the property tests and the fuzz target all build against this crate, so
+// the words one side writes are the words the other side reads.
+//
+// CONSTRAINTS:
+//   • Field order is the wire order (risc0 serde words and Borsh bytes)
+//   • Every change to a journal's fields, order or types bumps JOURNAL_VERSION
+//   • Serde defaults apply to scenario files only: the host always sends
+//     every field of the input
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use risc0_zkvm::sha::{Digest, Impl, Sha256};
+use serde::{Deserialize, Serialize};
+
+// ═══════════════════════════════════════════════════════════════════════════
+// INPUT AND JOURNALS
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Participant in the auction (received from host)
+#[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct Participant {
+    pub id: u32,          // Unique ID (0..N-1)
+    pub role: u32,        // 0=BUY, 1=SELL, 2=PROSUMER (buy at price, sell at sell_price)
+    pub price: u64,       // Bid (buyers) or Ask (sellers)
+    pub quantity: u64,    // Desired trade amount
+    pub in_coin: u64,     // Initial coin balance
+    pub in_energy: u64,   // Initial energy balance
+    #[serde(default)]
+    pub capacity: u64,    // Bundle capacity wanted/offered (bundle mode only)
+    #[serde(default)]
+    pub in_capacity: u64, // Initial capacity balance (bundle mode only)
+    #[serde(default)]
+    pub reserve_price: Option<u64>, // Seller floor / buyer ceiling on the clearing price
+    #[serde(default)]
+    pub all_or_nothing: bool,       // Fill-or-kill: full effective cap or nothing
+    #[serde(default)]
+    pub min_fill: u64,              // Smallest non-zero allocation accepted
+    #[serde(default)]
+    pub steps: Vec<(u64, u64)>,     // (price, quantity) steps; empty = price/quantity
+    #[serde(default)]
+    pub commodity: u32,             // Product traded; coin is shared across commodities
+    #[serde(default)]
+    pub sell_price: u64,            // Prosumer ask (role 2, must exceed its bid)
+    #[serde(default)]
+    pub sell_quantity: u64,         // Prosumer sell quantity (role 2)
+    #[serde(default)]
+    pub zone: u32,                  // Grid zone; zones are coupled by transfer links
+    #[serde(default)]
+    pub priority: bool,             // Seller dispatched before non-priority sellers
+    #[serde(default)]
+    pub flexible: bool,             // Buyer served after all inflexible buyers
+    #[serde(default)]
+    pub carbon_per_unit: u64,       // Seller carbon intensity per energy unit
+    #[serde(default)]
+    pub carbon_budget: Option<u64>, // Buyer limit on embedded carbon (None = no limit)
+    #[serde(default = "always_valid")]
+    pub valid_until_round: u32,     // Last round the order may clear in
+    #[serde(default)]
+    pub display_quantity: Option<u64>, // Iceberg slice served per pass (None = all displayed)
+    #[serde(default)]
+    pub owner: Option<u32>,         // Legal entity; its orders never trade with each other
+    #[serde(default)]
+    pub fixed_cost: u64,            // Seller startup cost, must be covered to be committed
+    #[serde(default)]
+    pub budget: u64,                // Buyer spend for a budget bid (quantity 0)
+    #[serde(default)]
+    pub max_position_energy: Option<u64>, // Position limit (None = config default)
+    #[serde(default)]
+    pub periods: Vec<(u64, u64)>,   // Hourly (price, quantity) bids, up to 24; coin is shared
+    #[serde(default)]
+    pub salt: [u8; 32],             // Blinds the sealed bid (see bid_commitment)
+}
+
+/// Orders without valid_until_round never expire
+fn always_valid() -> u32 {
+    u32::MAX
+}
+
+/// Rule used to settle the uniform price between the marginal bid and ask
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub enum PricingRule {
+    #[default]
+    MidPoint,    // (b_marg + a_marg) / 2
+    BuyerBid,    // b_marg
+    SellerAsk,   // a_marg
+    KDouble,     // (k * b_marg + (1 - k) * a_marg), k = k_numerator / k_denominator
+    SecondPrice, // (b_next + a_next) / 2 of first excluded orders, within [a_marg, b_marg]
+}
+
+/// Which candidate price find_crossing picks from the price grid
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub enum Objective {
+    #[default]
+    FirstCrossing, // Lowest grid price where supply >= demand (reference)
+    MaxVolume,     // Full scan, largest matched volume
+    MaxSurplus,    // Full scan, largest Σ bid × qty − Σ ask × qty
+}
+
+/// How matched participants are charged once allocations are fixed
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub enum Settlement {
+    #[default]
+    Uniform,  // Everyone trades at the clearing price
+    PayAsBid, // Buyers pay their bid, sellers receive their ask, spread to operator
+    Vcg,      // Winners pay/receive their externality, budget imbalance to operator
+}
+
+/// Clearing algorithm run by run_double_auction
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub enum Algorithm {
+    #[default]
+    UniformPrice, // Supply-demand crossing with marginal pricing (reference)
+    McAfee,       // McAfee trade-reduction double auction
+    DutchClock,   // Descending-price clock, single seller
+    EnglishClock, // Ascending-price clock, single seller
+    Greedy,       // Greedy bilateral matching, mid-point price per pair
+    Merge,        // Merge walk of the sorted book to the crossing, marginal pricing
+}
+
+/// What find_clearing_price does with a price outside [price_floor, price_cap]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub enum BandPolicy {
+    #[default]
+    Clamp,   // Move the price to the nearest band edge and re-qualify the book
+    NoTrade, // Declare no trade
+}
+
+/// How the long side is rationed when supply and demand caps differ
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub enum AllocationRule {
+    #[default]
+    Priority, // Serve orders in price/id priority until the volume runs out
+    ProRata,  // Share the volume in proportion to effective caps (largest remainder)
+    MaxMin,   // Water-filling: raise the smallest allocations first (ties by id)
+}
+
+/// How orders at the same price are ranked (book, allocation and journal order)
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub enum TieBreak {
+    #[default]
+    ById,           // Ascending id
+    ByQuantityDesc, // Larger quantity first, then ascending id
+    ByQuantityAsc,  // Smaller quantity first, then ascending id
+    Seeded,         // Pseudo-random: ascending SHA-256(seed || id), then ascending id
+}
+
+/// Which journal a single book commits (see run)
+///
+/// Every variant commits its version tag first: JOURNAL_VERSION, with a bit
+/// of its own set for every variant but Full (the *_JOURNAL_VERSION
+/// constants), so a decoder picks the layout from the first word alone.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub enum JournalVariant {
+    #[default]
+    Full,           // The PublicJournal (reference)
+    Compact,        // A Merkle root of the rows instead of the rows (see CompactJournal)
+    Delta,          // Net balance changes instead of the rows (see DeltaJournal)
+    Abi,            // The rows as abi.encodePacked bytes instead of serde words (see abi_journal_bytes)
+    Private,        // Aggregates and a salted digest of the rows only (see PrivateJournal)
+    Quantized(u64), // Balances in units of this scale, as u32 (see QuantizedJournal)
+    Slim,           // The rows without their input balances (see SlimJournal)
+}
+
+/// Auction parameters (received from host, defaults reproduce the reference mechanism)
+#[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+#[serde(default)]
+pub struct AuctionConfig {
+    pub algorithm: Algorithm,
+    pub pricing_rule: PricingRule,
+    pub k_numerator: u64,         // KDouble weight on the marginal bid (k <= 1)
+    pub k_denominator: u64,       // KDouble weight denominator (> 0)
+    pub settlement: Settlement,
+    #[serde(alias = "settlement_account")]
+    pub operator_id: Option<u32>, // Participant credited with any coin spread
+    pub clock_increment: u64,     // Dutch clock start above the seller's ask
+    pub clock_tick: u64,          // Clock price step (> 0)
+    pub bundles: bool,            // Energy + capacity bundle auction
+    pub price_floor: Option<u64>, // Regulated minimum clearing price
+    pub price_cap: Option<u64>,   // Regulated maximum clearing price
+    pub band_policy: BandPolicy,
+    pub fee_bps: u64,             // Per-side trade fee in basis points, credited to the operator
+    pub price_tick: u64,          // Prices must be multiples of this (> 0)
+    pub quantity_lot: u64,        // Quantities must be multiples of this (> 0)
+    pub round_to_grid: bool,      // Round non-conforming orders instead of dropping them
+    pub price_offset: u64,        // Prices are encoded as real price + price_offset
+    pub transfer_capacities: Vec<(u32, u32, u64)>, // (from_zone, to_zone, capacity) links
+    pub max_traded_volume: u64,   // Grid capacity per clearing (u64::MAX = unconstrained)
+    pub loss_bps: u64,            // Delivery loss on energy bought, in basis points
+    pub loss_sink_id: Option<u32>, // Participant credited with the lost energy
+    pub allocation_rule: AllocationRule,
+    pub tie_break: TieBreak,
+    pub seed: [u8; 32],           // Permutation seed for TieBreak::Seeded
+    pub objective: Objective,
+    pub current_round: u32,       // Orders with valid_until_round below this are expired
+    pub net_owners: bool,         // Clear one synthetic order per owner and side
+    pub congestion_rent: bool,    // Split prices when max_traded_volume binds
+    pub curtailment_comp_per_unit: u64, // Paid per curtailed unit of a priority seller
+    pub funding_fee_bps: u64,     // Seller-side fee on matched sales funding the compensation
+    pub max_position_energy: Option<u64>, // Default position limit (None = unlimited)
+    pub secondary_concession: Option<u64>, // Second pass over the remainders (None = off)
+    pub settlement_price: Option<u64>, // External index price to verify and settle at
+    pub quantity_scale: u64,      // Raw quantity units per energy unit (1000 = milli-units)
+    pub effective_crossing: bool, // Search p* over affordable and deliverable quantities
+    pub max_price: u64,           // Highest price an order may quote (see price_above_max)
+    pub check_rationality: bool,  // Reject settlements past a trader's own quote (see rationality_violation)
+    pub strict: bool,             // Exit on every violation; false settles a failed book as no trade (see clear_book)
+    pub journal_variant: JournalVariant, // Which journal a single book commits (see JournalVariant)
+    pub max_trades: Option<u32>,  // Commit Greedy's trade list, at most this long (None = off, see TradesJournal)
+    pub keccak_digest: bool,      // Commit keccak256 of the packed journal last (see journal_keccak)
+    pub round_id: u64,            // Settlement round the receipt is for, echoed into the journal (see bind)
+    pub audit: bool,              // Commit the ranked bids and asks after the journal (see AuditJournal)
+}
+
+/// Default config.max_price: every quoted price, and so every real price, fits i64
+pub const DEFAULT_MAX_PRICE: u64 = i64::MAX as u64;
+
+impl Default for AuctionConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::UniformPrice,
+            pricing_rule: PricingRule::MidPoint,
+            k_numerator: 1,
+            k_denominator: 2,
+            settlement: Settlement::Uniform,
+            operator_id: None,
+            clock_increment: 0,
+            clock_tick: 1,
+            bundles: false,
+            price_floor: None,
+            price_cap: None,
+            band_policy: BandPolicy::Clamp,
+            fee_bps: 0,
+            price_tick: 1,
+            quantity_lot: 1,
+            round_to_grid: false,
+            price_offset: 0,
+            transfer_capacities: Vec::new(),
+            max_traded_volume: u64::MAX,
+            loss_bps: 0,
+            loss_sink_id: None,
+            allocation_rule: AllocationRule::Priority,
+            tie_break: TieBreak::ById,
+            seed: [0; 32],
+            objective: Objective::FirstCrossing,
+            current_round: 0,
+            net_owners: false,
+            congestion_rent: false,
+            curtailment_comp_per_unit: 0,
+            funding_fee_bps: 0,
+            max_position_energy: None,
+            secondary_concession: None,
+            settlement_price: None,
+            quantity_scale: 1,
+            effective_crossing: false,
+            max_price: DEFAULT_MAX_PRICE,
+            check_rationality: true,
+            strict: true,
+            journal_variant: JournalVariant::Full,
+            max_trades: None,
+            keccak_digest: false,
+            round_id: 0,
+            audit: false,
+        }
+    }
+}
+
+/// Bid set of one round in a multi-round auction (balances come from the carried state)
+#[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct RoundInput {
+    pub participants: Vec<Participant>,
+}
+
+/// Input to the auction algorithm
+#[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct AuctionInput {
+    pub participants: Vec<Participant>,
+    pub config: AuctionConfig,
+    pub rounds: Vec<RoundInput>, // Empty = single auction over participants
+    pub commitments: Vec<Digest>, // Sealed bids, one per participant in ascending id (empty = off)
+    pub output_salt: [u8; 32],    // Blinds outputs_digest under JournalVariant::Private
+}
+
+/// Layout version committed first in every PublicJournal
+///
+/// Bump it with every change to the PublicJournal fields, their order or
+/// their types, so a decoder refuses a layout it does not know instead of
+/// misreading its words (the host checks it before decoding the rest).
+pub const JOURNAL_VERSION: u32 = 9;
+
+/// Version word of a DeltaJournal: JOURNAL_VERSION with bit 16 set, so a
+/// decoder tells the two variants apart by their first word
+pub const DELTA_JOURNAL_VERSION: u32 = JOURNAL_VERSION | 1 << 16;
+
+/// Version word of an ABI journal: JOURNAL_VERSION with bit 17 set, its
+/// first four bytes (big-endian, as Solidity reads a uint32)
+pub const ABI_JOURNAL_VERSION: u32 = JOURNAL_VERSION | 1 << 17;
+
+/// Version word of a RoundsJournal: JOURNAL_VERSION with bit 22 set
+///
+/// Each journal committed in place of a PublicJournal leads with a tag
+/// of its own, so a decoder checks the layout from the first word instead
+/// of decoding the fields before it under a guessed layout.
+pub const ROUNDS_JOURNAL_VERSION: u32 = JOURNAL_VERSION | 1 << 22;
+
+/// Version word of a PeriodsJournal: JOURNAL_VERSION with bit 23 set
+pub const PERIODS_JOURNAL_VERSION: u32 = JOURNAL_VERSION | 1 << 23;
+
+/// Version word of a CommodityJournal: JOURNAL_VERSION with bit 24 set
+pub const COMMODITY_JOURNAL_VERSION: u32 = JOURNAL_VERSION | 1 << 24;
+
+/// Version word of a ZonalJournal: JOURNAL_VERSION with bit 25 set
+pub const ZONAL_JOURNAL_VERSION: u32 = JOURNAL_VERSION | 1 << 25;
+
+/// Version word of a PrivateJournal: JOURNAL_VERSION with bit 18 set
+pub const PRIVATE_JOURNAL_VERSION: u32 = JOURNAL_VERSION | 1 << 18;
+
+/// Version word of a QuantizedJournal: JOURNAL_VERSION with bit 19 set
+pub const QUANTIZED_JOURNAL_VERSION: u32 = JOURNAL_VERSION | 1 << 19;
+
+/// Version word of a SlimJournal: JOURNAL_VERSION with bit 20 set
+pub const SLIM_JOURNAL_VERSION: u32 = JOURNAL_VERSION | 1 << 20;
+
+/// Version word of a CompactJournal: JOURNAL_VERSION with bit 21 set
+pub const COMPACT_JOURNAL_VERSION: u32 = JOURNAL_VERSION | 1 << 21;
+
+/// Output journal committed to zkVM receipt
+/// CRITICAL: Arrays must be sorted [buyers DESC by price, sellers ASC by price]
+#[derive(Clone, Debug, Serialize, Deserialize, BorshSerialize, BorshDeserialize)]
+pub struct PublicJournal {
+    pub version: u32,            // JOURNAL_VERSION of this layout
+    pub input_digest: Digest,    // SHA-256 of the whole AuctionInput (see input_digest)
+    pub algorithm_id: u32,       // ALGORITHM_ID of the implementation that cleared the book
+    pub config_digest: Digest,   // SHA-256 of the AuctionConfig alone (see config_digest)
+    pub ids: Vec<u32>,           // Participant id of each row (protocol order)
+    pub buyer_count: u32,        // Rows 0..buyer_count are buyers
+    pub seller_count: u32,       // The next seller_count rows are sellers, prosumers follow
+    pub in_coin: Vec<u64>,       // Input balances (protocol order)
+    pub in_energy: Vec<u64>,     // Input balances (protocol order)
+    pub out_coin: Vec<u64>,      // Output balances (YOUR ALGORITHM)
+    pub out_energy: Vec<u64>,    // Output balances (YOUR ALGORITHM)
+    pub allocation: Vec<u64>,    // Units each row traded (protocol order, see check_allocation)
+    pub status: u32,             // How the book ended, STATUS_* (0 = cleared)
+    pub clearing_price: u64,     // Price the book cleared at (see Clearing)
+    pub traded_volume: u64,      // Energy the sellers delivered (0 = no trade, price 0 too)
+    pub fees_collected: u64,     // config.fee_bps fees both sides paid the operator (see check_fees)
+    pub surplus: u64,            // Σ (bid − ask) over the matched units (see matched_surplus)
+    pub fill_counts: FillCounts, // Buyer and seller rows by how much of their order traded
+    pub round_id: u64,           // config.round_id, verbatim (see bind)
+}
+
+/// Buyer and seller rows by their allocation against their order (see fill_counts)
+///
+/// Each side sums to its block count: buyer_count, seller_count. Prosumer
+/// rows, which trade on both sides, are not classified.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub struct FillCounts {
+    pub buyers_filled: u32,    // Allocated their whole quantity
+    pub buyers_partial: u32,   // Allocated some of it: rationed, or capped by their coin
+    pub buyers_unfilled: u32,  // Allocated nothing
+    pub sellers_filled: u32,   // Allocated their whole quantity
+    pub sellers_partial: u32,  // Allocated some of it: rationed, or capped by their energy
+    pub sellers_unfilled: u32, // Allocated nothing
+}
+
+/// PublicJournal.status: some energy traded (the only status with traded_volume > 0)
+pub const STATUS_CLEARED: u32 = 0;
+
+/// PublicJournal.status: no bid reaches an ask, or a side has no tradable order
+pub const STATUS_NO_CROSSING: u32 = 1;
+
+/// PublicJournal.status: the best bid reaches the best ask, but no unit trades
+/// at the price (budgets, balances, caps or self-trade prevention)
+pub const STATUS_ZERO_VOLUME: u32 = 2;
+
+/// PublicJournal.status: the operator or loss sink the settlement needs is missing
+pub const STATUS_NO_SETTLEMENT_ACCOUNT: u32 = 3;
+
+/// PublicJournal.status: the clearing failed and config.strict is off, so the
+/// book was settled as no trade (see clear_book)
+pub const STATUS_SETTLED_AS_NO_TRADE: u32 = 4;
+
+/// Second-good section committed after PublicJournal in bundle mode only
+/// (same protocol order), so single-good journals stay byte-identical
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CapacityJournal {
+    pub in_capacity: Vec<u64>,  // Input balances (protocol order)
+    pub out_capacity: Vec<u64>, // Output balances (protocol order)
+}
+
+/// Carbon section committed after PublicJournal when carbon is priced
+/// (same protocol order)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CarbonJournal {
+    pub carbon: Vec<u64>,  // Embedded (buyers) or emitted (sellers) carbon
+    pub total_carbon: u64, // Σ seller allocation × carbon_per_unit
+}
+
+/// Clearing summary committed after PublicJournal when an objective is selected
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ObjectiveJournal {
+    pub objective: Objective,
+    pub clearing_price: u64, // Chosen price (0 with volume 0 = no trade)
+    pub volume: u64,         // Matched volume (the MaxVolume objective value)
+    pub surplus: u128,       // Σ bid × qty − Σ ask × qty (the MaxSurplus objective value)
+}
+
+/// Secondary round summary committed after PublicJournal when a concession is set
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SecondaryJournal {
+    pub primary_price: u64,    // Primary clearing price (0 = no trade)
+    pub secondary_price: u64,  // Price of the second pass over the remainders (0 = no trade)
+    pub secondary_volume: u64, // Energy traded in the second pass
+}
+
+/// One bilateral trade of the trade list: quantity moves seller to buyer at price
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TradeRecord {
+    pub buyer_id: u32,
+    pub seller_id: u32,
+    pub quantity: u64,
+    pub price: u64, // Per unit, encoded (see price_offset)
+}
+
+/// Trade list committed after PublicJournal when config.max_trades is set
+/// (Greedy only), in matching order
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradesJournal {
+    pub trades: Vec<TradeRecord>, // Reproduce every row's change (see check_trades)
+}
+
+/// Sealed-bid commitments committed after PublicJournal when input.commitments is set
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommitmentsJournal {
+    pub commitments: Vec<Digest>, // Ascending id, each checked against its bid (see check_commitments)
+}
+
+/// The ranked order book committed after PublicJournal when config.audit is set
+///
+/// One entry per buyer row and per seller row, in the journal's protocol
+/// order: bid k belongs to ids[k], ask k to ids[buyer_count + k]. Each is
+/// the stated price and quantity the row is ranked by; a stepped or
+/// budget bid's steps and budget are in the input the digest binds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditJournal {
+    pub bid_prices: Vec<u64>,     // Non-increasing, buyer_count entries
+    pub bid_quantities: Vec<u64>, // Of the same rows
+    pub ask_prices: Vec<u64>,     // Non-decreasing, seller_count entries
+    pub ask_quantities: Vec<u64>, // Of the same rows
+}
+
+/// Journal of a compact single-book auction (committed instead of PublicJournal)
+///
+/// The rows stay off-chain: outputs_root commits them as a Merkle tree over
+/// (id, out_coin, out_energy) leaves in protocol order (see outputs_root),
+/// so the journal has the same size for any book.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CompactJournal {
+    pub version: u32,            // COMPACT_JOURNAL_VERSION of this layout
+    pub input_digest: Digest,    // SHA-256 of the whole AuctionInput (see input_digest)
+    pub algorithm_id: u32,       // As in PublicJournal
+    pub config_digest: Digest,   // As in PublicJournal
+    pub outputs_root: Digest,    // Merkle root of the output rows
+    pub row_count: u32,          // Leaves under the root (one per buyer, seller and prosumer)
+    pub buyer_count: u32,        // Leaves 0..buyer_count are buyers
+    pub seller_count: u32,       // The next seller_count leaves are sellers, prosumers follow
+    pub total_coin: u128,        // Σ out_coin (== Σ in_coin)
+    pub total_energy: u128,      // Σ out_energy (== Σ in_energy)
+    pub status: u32,             // As in PublicJournal
+    pub clearing_price: u64,     // As in PublicJournal
+    pub traded_volume: u64,      // As in PublicJournal
+    pub fees_collected: u64,     // As in PublicJournal
+    pub surplus: u64,            // As in PublicJournal
+    pub fill_counts: FillCounts, // As in PublicJournal
+    pub round_id: u64,           // As in PublicJournal
+}
+
+/// Journal of a single-book auction with private outputs (committed instead of PublicJournal)
+///
+/// Only aggregates are public; outputs_digest binds the rows, which the host
+/// keeps in private_outputs.json for whoever may see them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrivateJournal {
+    pub version: u32,            // PRIVATE_JOURNAL_VERSION
+    pub input_digest: Digest,    // As in PublicJournal
+    pub algorithm_id: u32,       // As in PublicJournal
+    pub config_digest: Digest,   // As in PublicJournal
+    pub outputs_digest: Digest,  // SHA-256(output_salt || rows), see outputs_digest
+    pub row_count: u32,          // Rows behind the digest (one per buyer, seller and prosumer)
+    pub buyer_count: u32,        // As in PublicJournal
+    pub seller_count: u32,       // As in PublicJournal
+    pub turnover: u128,          // Coin that changed hands: Σ max(in_coin − out_coin, 0)
+    pub status: u32,             // As in PublicJournal
+    pub clearing_price: u64,     // As in PublicJournal
+    pub traded_volume: u64,      // As in PublicJournal
+    pub fees_collected: u64,     // As in PublicJournal
+    pub surplus: u64,            // As in PublicJournal
+    pub fill_counts: FillCounts, // As in PublicJournal
+    pub round_id: u64,           // As in PublicJournal
+}
+
+/// Journal of a delta-encoded single-book auction (committed instead of PublicJournal)
+///
+/// A verifier that submitted the input balances rebuilds every output as
+/// in + delta; the two delta columns replace the four balance columns.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeltaJournal {
+    pub version: u32,            // DELTA_JOURNAL_VERSION
+    pub input_digest: Digest,    // As in PublicJournal
+    pub algorithm_id: u32,       // As in PublicJournal
+    pub config_digest: Digest,   // As in PublicJournal
+    pub ids: Vec<u32>,           // Participant id of each row (protocol order)
+    pub buyer_count: u32,        // As in PublicJournal
+    pub seller_count: u32,       // As in PublicJournal
+    pub coin_delta: Vec<i64>,    // out_coin − in_coin per row (Σ = 0)
+    pub energy_delta: Vec<i64>,  // out_energy − in_energy per row (Σ = 0)
+    pub allocation: Vec<u64>,    // As in PublicJournal
+    pub status: u32,             // As in PublicJournal
+    pub clearing_price: u64,     // As in PublicJournal
+    pub traded_volume: u64,      // As in PublicJournal
+    pub fees_collected: u64,     // As in PublicJournal
+    pub surplus: u64,            // As in PublicJournal
+    pub fill_counts: FillCounts, // As in PublicJournal
+    pub round_id: u64,           // As in PublicJournal
+}
+
+/// Journal of a single-book auction with quantized balances (committed instead of PublicJournal)
+///
+/// Lossy by design: each balance column holds ⌊balance / scale⌋ as a u32,
+/// one serde word instead of two, while the clearing stays exact.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuantizedJournal {
+    pub version: u32,            // QUANTIZED_JOURNAL_VERSION
+    pub input_digest: Digest,    // As in PublicJournal
+    pub algorithm_id: u32,       // As in PublicJournal
+    pub config_digest: Digest,   // As in PublicJournal
+    pub scale: u64,              // JournalVariant::Quantized's: each balance is at most scale − 1 above units × scale
+    pub ids: Vec<u32>,           // As in PublicJournal
+    pub buyer_count: u32,        // As in PublicJournal
+    pub seller_count: u32,       // As in PublicJournal
+    pub in_coin: Vec<u32>,       // ⌊in_coin / scale⌋ per row
+    pub in_energy: Vec<u32>,     // ⌊in_energy / scale⌋ per row
+    pub out_coin: Vec<u32>,      // ⌊out_coin / scale⌋ per row
+    pub out_energy: Vec<u32>,    // ⌊out_energy / scale⌋ per row
+    pub status: u32,             // As in PublicJournal
+    pub clearing_price: u64,     // As in PublicJournal
+    pub traded_volume: u64,      // As in PublicJournal
+    pub fees_collected: u64,     // As in PublicJournal
+    pub surplus: u64,            // As in PublicJournal
+    pub fill_counts: FillCounts, // As in PublicJournal
+    pub round_id: u64,           // As in PublicJournal
+}
+
+/// Journal of a single-book auction without its input balances (committed instead of PublicJournal)
+///
+/// For a verifier that built the input itself: input_digest binds the
+/// in_coin and in_energy it holds, so the journal leaves them out.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SlimJournal {
+    pub version: u32,            // SLIM_JOURNAL_VERSION
+    pub input_digest: Digest,    // As in PublicJournal: binds the input balances left out
+    pub algorithm_id: u32,       // As in PublicJournal
+    pub config_digest: Digest,   // As in PublicJournal
+    pub ids: Vec<u32>,           // As in PublicJournal
+    pub buyer_count: u32,        // As in PublicJournal
+    pub seller_count: u32,       // As in PublicJournal
+    pub out_coin: Vec<u64>,      // As in PublicJournal
+    pub out_energy: Vec<u64>,    // As in PublicJournal
+    pub allocation: Vec<u64>,    // As in PublicJournal
+    pub status: u32,             // As in PublicJournal
+    pub clearing_price: u64,     // As in PublicJournal
+    pub traded_volume: u64,      // As in PublicJournal
+    pub fees_collected: u64,     // As in PublicJournal
+    pub surplus: u64,            // As in PublicJournal
+    pub fill_counts: FillCounts, // As in PublicJournal
+    pub round_id: u64,           // As in PublicJournal
+}
+
+/// Shape of a single-book market, committed last after its journal sections
+///
+/// Tells "the auction ran" apart from books that cannot trade by construction.
+/// A degenerate book still runs and commits its balances unchanged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MarketStatus {
+    TwoSided,     // Buyers and sellers quote positive quantities; the auction ran
+    Empty,        // No participants (never committed: the guest exits with EmptyMarket)
+    ZeroQuantity, // No order quotes a positive quantity
+    NoBuyers,     // Only sellers quote a positive quantity
+    NoSellers,    // Only buyers quote a positive quantity
+}
+
+/// Journal of a multi-round auction (committed instead of PublicJournal)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoundsJournal {
+    pub version: u32,               // ROUNDS_JOURNAL_VERSION
+    pub rounds: Vec<PublicJournal>, // Per round, in that round's protocol order
+    pub ids: Vec<u32>,              // Final state order (ascending id)
+    pub final_coin: Vec<u64>,       // Balances after the last round
+    pub final_energy: Vec<u64>,     // Balances after the last round
+}
+
+/// Journal of a multi-period auction (committed instead of PublicJournal)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PeriodsJournal {
+    pub version: u32,              // PERIODS_JOURNAL_VERSION
+    pub input_digest: Digest,      // SHA-256 of the whole AuctionInput (see input_digest)
+    pub algorithm_id: u32,         // As in PublicJournal
+    pub config_digest: Digest,     // As in PublicJournal
+    pub counts: Vec<u32>,          // Rows per period (one per participant)
+    pub ids: Vec<u32>,             // Row order of every vector (ascending id)
+    pub out_energy: Vec<Vec<u64>>, // Per period, energy after that period's clearing
+    pub final_coin: Vec<u64>,      // Coin after the last period
+    pub round_id: u64,             // As in PublicJournal
+}
+
+/// Journal of a multi-commodity auction (committed instead of PublicJournal)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommodityJournal {
+    pub version: u32,                // COMMODITY_JOURNAL_VERSION
+    pub commodities: Vec<u32>,       // Ascending commodity ids (clearing order)
+    pub counts: Vec<u32>,            // Rows per commodity
+    pub markets: Vec<PublicJournal>, // Per commodity, in that market's protocol order
+}
+
+/// Journal of a zonal auction (committed instead of PublicJournal)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ZonalJournal {
+    pub version: u32,           // ZONAL_JOURNAL_VERSION
+    pub zones: Vec<u32>,        // Ascending zone ids
+    pub prices: Vec<u64>,       // Clearing price per zone (0 = no trade)
+    pub flows: Vec<u64>,        // Energy moved per transfer link (config order)
+    pub journal: PublicJournal, // All participants, protocol order
+}
+
+/// Why the guest rejected an input or stopped a clearing
+///
+/// Committed as the whole journal before the guest exits with
+/// exit_code(), so a failing run still proves which check fired.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AuctionError {
+    DuplicateId(u32),             // Id listed twice in one book (see duplicate_id)
+    InvalidRole(u32),             // Id whose role is not 0 / 1 / 2 (see unknown_role)
+    TooManyParticipants(usize),   // Rows over input.participants and the rounds
+    TooManyOrders(usize),         // Orders after step and prosumer expansion
+    Overflow,                     // A payment or balance left its integer range
+    InsufficientCoin(u32, u64),   // (id, allocation): its payment exceeds the coin
+    InsufficientEnergy(u32, u64), // (id, allocation): more than the seller holds
+    SettlementBound,              // A book could settle more than i128 holds (see settlement_bound)
+    Internal(u32),                // Id a guest invariant failed for (a bug, not an input error)
+    MalformedJournal(usize),      // First journal row out of shape or order (see check_journal)
+    PriceAboveMax(u32),           // Id quoting a price above config.max_price (see price_above_max)
+    Irrational(u32),              // Id settled past its own quote (see rationality_violation)
+    Infeasible(u32),              // Id whose row its allocation cannot reach (see infeasible_row)
+    EmptyMarket,                  // No participants: nothing to clear or commit
+    TooManyTrades(usize),         // Trades in the list, over config.max_trades
+    CommitmentMismatch(usize),    // First commitment, in ascending id, its bid does not open
+    UnsupportedMode,              // Features no clearing mode settles together (see unsupported_mode)
+    InvalidKDouble,               // KDouble weight k_numerator / k_denominator outside [0, 1]
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// JOURNAL DECODING
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// How the host reads a single-book journal back: by the version tag it
+// commits first, into the fields every variant shares, and for the delta and
+// slim variants the full rows rebuilt from the input they were proven for.
+// The property tests call these same functions.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// The ABI journal: abi.encodePacked of the PublicJournal fields, as the guest commits them
+///
+/// uint32 fields take four big-endian bytes, bytes32 and uint256 fields and
+/// every array element thirty-two; row_count precedes the unlengthed arrays.
+pub fn abi_journal_bytes(journal: &PublicJournal) -> Vec<u8> {
+    let word = |value: u64| {
+        let mut word = [0u8; 32];
+        word[24..].copy_from_slice(&value.to_be_bytes());
+        word
+    };
+    let mut bytes = ABI_JOURNAL_VERSION.to_be_bytes().to_vec();
+    bytes.extend_from_slice(journal.input_digest.as_bytes());
+    bytes.extend_from_slice(&journal.algorithm_id.to_be_bytes());
+    bytes.extend_from_slice(journal.config_digest.as_bytes());
+    for count in [
+        journal.ids.len() as u32,
+        journal.buyer_count,
+        journal.seller_count,
+    ] {
+        bytes.extend_from_slice(&count.to_be_bytes());
+    }
+    bytes.extend(journal.ids.iter().flat_map(|&id| word(id as u64)));
+    for column in [
+        &journal.in_coin,
+        &journal.in_energy,
+        &journal.out_coin,
+        &journal.out_energy,
+        &journal.allocation,
+    ] {
+        bytes.extend(column.iter().flat_map(|&value| word(value)));
+    }
+    bytes.extend_from_slice(&journal.status.to_be_bytes());
+    bytes.extend_from_slice(&word(journal.clearing_price));
+    bytes.extend_from_slice(&word(journal.traded_volume));
+    bytes.extend_from_slice(&word(journal.fees_collected));
+    bytes.extend_from_slice(&word(journal.surplus));
+    let fills = &journal.fill_counts;
+    for count in [
+        fills.buyers_filled,
+        fills.buyers_partial,
+        fills.buyers_unfilled,
+        fills.sellers_filled,
+        fills.sellers_partial,
+        fills.sellers_unfilled,
+    ] {
+        bytes.extend_from_slice(&count.to_be_bytes());
+    }
+    bytes.extend_from_slice(&word(journal.round_id));
+    bytes
+}
+
+/// Read an ABI journal's fields at their fixed offsets, as a settlement contract does
+///
+/// Only the low bytes of each word are read; comparing abi_journal_bytes of
+/// the result with the committed bytes refuses any other padding.
+pub fn decode_abi_journal(bytes: &[u8]) -> Result<PublicJournal, String> {
+    let u32_at = |offset: usize| u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let u64_at =
+        |offset: usize| u64::from_be_bytes(bytes[offset + 24..offset + 32].try_into().unwrap());
+    let digest_at = |offset: usize| Digest::try_from(&bytes[offset..offset + 32]).unwrap();
+    if bytes.len() < 272 {
+        return Err(format!(
+            "{} bytes, below the 272 of an empty book",
+            bytes.len()
+        ));
+    }
+    let rows = u32_at(72) as usize;
+    if bytes.len() != 272 + 192 * rows {
+        return Err(format!(
+            "{} bytes for row_count {} (expected {})",
+            bytes.len(),
+            rows,
+            272 + 192 * rows
+        ));
+    }
+    let column = |k: usize| -> Vec<u64> {
+        (0..rows)
+            .map(|row| u64_at(84 + 32 * (k * rows + row)))
+            .collect()
+    };
+    let end = 84 + 192 * rows;
+    Ok(PublicJournal {
+        version: JOURNAL_VERSION,
+        input_digest: digest_at(4),
+        algorithm_id: u32_at(36),
+        config_digest: digest_at(40),
+        ids: column(0).into_iter().map(|id| id as u32).collect(),
+        buyer_count: u32_at(76),
+        seller_count: u32_at(80),
+        in_coin: column(1),
+        in_energy: column(2),
+        out_coin: column(3),
+        out_energy: column(4),
+        allocation: column(5),
+        status: u32_at(end),
+        clearing_price: u64_at(end + 4),
+        traded_volume: u64_at(end + 36),
+        fees_collected: u64_at(end + 68),
+        surplus: u64_at(end + 100),
+        fill_counts: FillCounts {
+            buyers_filled: u32_at(end + 132),
+            buyers_partial: u32_at(end + 136),
+            buyers_unfilled: u32_at(end + 140),
+            sellers_filled: u32_at(end + 144),
+            sellers_partial: u32_at(end + 148),
+            sellers_unfilled: u32_at(end + 152),
+        },
+        round_id: u64_at(end + 156),
+    })
+}
+
+/// A single-book journal as the guest committed it, one arm per JournalVariant
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum CommittedJournal {
+    Full(PublicJournal),
+    Compact(CompactJournal),
+    Delta(DeltaJournal),
+    Abi(PublicJournal),
+    Private(PrivateJournal),
+    Quantized(QuantizedJournal),
+    Slim(SlimJournal),
+}
+
+/// The fields every JournalVariant commits, normalized for printing and checks
+#[derive(Debug)]
+pub struct DecodedJournal {
+    pub variant: JournalVariant, // Quantized carries the committed scale
+    pub input_digest: Digest,
+    pub algorithm_id: u32,
+    pub config_digest: Digest,
+    pub round_id: u64,
+    pub row_count: usize,
+    pub buyer_count: u32,
+    pub seller_count: u32,
+    pub status: u32,
+    pub clearing_price: u64,
+    pub traded_volume: u64,
+    pub fees_collected: u64,
+    pub surplus: u64,
+    pub fill_counts: FillCounts,
+    pub market_status: Option<MarketStatus>, // None for Abi, which commits the packed fields alone
+    pub committed: CommittedJournal,
+}
+
+/// Decode a single-book journal of any JournalVariant, dispatching on its version tag
+///
+/// The serde variants commit the tag as their first little-endian word and
+/// end with the market status; the ABI journal commits it as its first four
+/// big-endian bytes. A trailing keccak digest must be stripped first.
+pub fn decode_any_journal(bytes: &[u8]) -> Result<DecodedJournal, String> {
+    fn words<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<(T, MarketStatus), String> {
+        risc0_zkvm::serde::from_slice(bytes).map_err(|e| e.to_string())
+    }
+    let tag =
+        |order: fn([u8; 4]) -> u32| bytes.get(..4).map(|word| order(word.try_into().unwrap()));
+    let (committed, market_status) = match tag(u32::from_le_bytes) {
+        Some(JOURNAL_VERSION) => words(bytes).map(|(j, s)| (CommittedJournal::Full(j), Some(s)))?,
+        Some(COMPACT_JOURNAL_VERSION) => {
+            words(bytes).map(|(j, s)| (CommittedJournal::Compact(j), Some(s)))?
+        }
+        Some(DELTA_JOURNAL_VERSION) => {
+            words(bytes).map(|(j, s)| (CommittedJournal::Delta(j), Some(s)))?
+        }
+        Some(PRIVATE_JOURNAL_VERSION) => {
+            words(bytes).map(|(j, s)| (CommittedJournal::Private(j), Some(s)))?
+        }
+        Some(QUANTIZED_JOURNAL_VERSION) => {
+            words(bytes).map(|(j, s)| (CommittedJournal::Quantized(j), Some(s)))?
+        }
+        Some(SLIM_JOURNAL_VERSION) => {
+            words(bytes).map(|(j, s)| (CommittedJournal::Slim(j), Some(s)))?
+        }
+        _ if tag(u32::from_be_bytes) == Some(ABI_JOURNAL_VERSION) => {
+            (CommittedJournal::Abi(decode_abi_journal(bytes)?), None)
+        }
+        found => return Err(format!("unknown version tag {:?}", found)),
+    };
+    // Copy the common fields out before the journal moves into its arm
+    macro_rules! normalize {
+        ($arm:path, $variant:expr, $journal:expr, $rows:expr) => {
+            DecodedJournal {
+                variant: $variant,
+                input_digest: $journal.input_digest,
+                algorithm_id: $journal.algorithm_id,
+                config_digest: $journal.config_digest,
+                round_id: $journal.round_id,
+                row_count: $rows,
+                buyer_count: $journal.buyer_count,
+                seller_count: $journal.seller_count,
+                status: $journal.status,
+                clearing_price: $journal.clearing_price,
+                traded_volume: $journal.traded_volume,
+                fees_collected: $journal.fees_collected,
+                surplus: $journal.surplus,
+                fill_counts: $journal.fill_counts,
+                market_status,
+                committed: $arm($journal),
+            }
+        };
+    }
+    Ok(match committed {
+        CommittedJournal::Full(j) => {
+            normalize!(CommittedJournal::Full, JournalVariant::Full, j, j.ids.len())
+        }
+        CommittedJournal::Compact(j) => normalize!(
+            CommittedJournal::Compact,
+            JournalVariant::Compact,
+            j,
+            j.row_count as usize
+        ),
+        CommittedJournal::Delta(j) => {
+            normalize!(
+                CommittedJournal::Delta,
+                JournalVariant::Delta,
+                j,
+                j.ids.len()
+            )
+        }
+        CommittedJournal::Abi(j) => {
+            normalize!(CommittedJournal::Abi, JournalVariant::Abi, j, j.ids.len())
+        }
+        CommittedJournal::Private(j) => normalize!(
+            CommittedJournal::Private,
+            JournalVariant::Private,
+            j,
+            j.row_count as usize
+        ),
+        CommittedJournal::Quantized(j) => normalize!(
+            CommittedJournal::Quantized,
+            JournalVariant::Quantized(j.scale),
+            j,
+            j.ids.len()
+        ),
+        CommittedJournal::Slim(j) => {
+            normalize!(CommittedJournal::Slim, JournalVariant::Slim, j, j.ids.len())
+        }
+    })
+}
+
+/// Rebuild the absolute rows of a delta journal from the scenario's input balances
+///
+/// Every row's id must name one scenario participant, whose in_coin and
+/// in_energy the deltas apply to; the deltas must sum to zero and land
+/// every output inside u64. The result is the PublicJournal the guest
+/// would have committed under JournalVariant::Full.
+pub fn absolute_outputs(
+    delta: &DeltaJournal,
+    participants: &[Participant],
+) -> Result<PublicJournal, String> {
+    let rows = delta.ids.len();
+    if delta.coin_delta.len() != rows
+        || delta.energy_delta.len() != rows
+        || delta.allocation.len() != rows
+    {
+        return Err(format!(
+            "{} ids but {} coin and {} energy deltas and {} allocations",
+            rows,
+            delta.coin_delta.len(),
+            delta.energy_delta.len(),
+            delta.allocation.len()
+        ));
+    }
+    let total = |column: &[i64]| column.iter().map(|&change| change as i128).sum::<i128>();
+    if total(&delta.coin_delta) != 0 || total(&delta.energy_delta) != 0 {
+        return Err(format!(
+            "deltas sum to {} coin and {} energy, not zero",
+            total(&delta.coin_delta),
+            total(&delta.energy_delta)
+        ));
+    }
+    let apply = |id: u32, balance: u64, change: i64| {
+        u64::try_from(balance as i128 + change as i128)
+            .map_err(|_| format!("participant {}: {} {:+} leaves u64", id, balance, change))
+    };
+    let mut journal = PublicJournal {
+        version: JOURNAL_VERSION,
+        input_digest: delta.input_digest,
+        algorithm_id: delta.algorithm_id,
+        config_digest: delta.config_digest,
+        ids: delta.ids.clone(),
+        buyer_count: delta.buyer_count,
+        seller_count: delta.seller_count,
+        in_coin: Vec::with_capacity(rows),
+        in_energy: Vec::with_capacity(rows),
+        out_coin: Vec::with_capacity(rows),
+        out_energy: Vec::with_capacity(rows),
+        allocation: delta.allocation.clone(),
+        status: delta.status,
+        clearing_price: delta.clearing_price,
+        traded_volume: delta.traded_volume,
+        fees_collected: delta.fees_collected,
+        surplus: delta.surplus,
+        fill_counts: delta.fill_counts,
+        round_id: delta.round_id,
+    };
+    for (row, &id) in delta.ids.iter().enumerate() {
+        let p = participants
+            .iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| format!("row {}: participant {} is not in the scenario", row, id))?;
+        journal.in_coin.push(p.in_coin);
+        journal.in_energy.push(p.in_energy);
+        journal
+            .out_coin
+            .push(apply(id, p.in_coin, delta.coin_delta[row])?);
+        journal
+            .out_energy
+            .push(apply(id, p.in_energy, delta.energy_delta[row])?);
+    }
+    Ok(journal)
+}
+
+/// Rebuild the full journal of a slim journal from the scenario's input balances
+///
+/// Every row's id must name one scenario participant, whose in_coin and
+/// in_energy fill the row; the committed input digest has already bound
+/// the scenario, so these are the balances the guest cleared, and the
+/// outputs must conserve them. The result is the PublicJournal the guest
+/// would have committed under JournalVariant::Full.
+pub fn full_journal(
+    slim: &SlimJournal,
+    participants: &[Participant],
+) -> Result<PublicJournal, String> {
+    let rows = slim.ids.len();
+    if slim.out_coin.len() != rows || slim.out_energy.len() != rows || slim.allocation.len() != rows
+    {
+        return Err(format!(
+            "{} ids but {} coin, {} energy and {} allocation rows",
+            rows,
+            slim.out_coin.len(),
+            slim.out_energy.len(),
+            slim.allocation.len()
+        ));
+    }
+    let mut journal = PublicJournal {
+        version: JOURNAL_VERSION,
+        input_digest: slim.input_digest,
+        algorithm_id: slim.algorithm_id,
+        config_digest: slim.config_digest,
+        ids: slim.ids.clone(),
+        buyer_count: slim.buyer_count,
+        seller_count: slim.seller_count,
+        in_coin: Vec::with_capacity(rows),
+        in_energy: Vec::with_capacity(rows),
+        out_coin: slim.out_coin.clone(),
+        out_energy: slim.out_energy.clone(),
+        allocation: slim.allocation.clone(),
+        status: slim.status,
+        clearing_price: slim.clearing_price,
+        traded_volume: slim.traded_volume,
+        fees_collected: slim.fees_collected,
+        surplus: slim.surplus,
+        fill_counts: slim.fill_counts,
+        round_id: slim.round_id,
+    };
+    for (row, &id) in slim.ids.iter().enumerate() {
+        let p = participants
+            .iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| format!("row {}: participant {} is not in the scenario", row, id))?;
+        journal.in_coin.push(p.in_coin);
+        journal.in_energy.push(p.in_energy);
+    }
+    let total = |column: &[u64]| column.iter().map(|&balance| balance as u128).sum::<u128>();
+    for (name, ins, outs) in [
+        ("coin", &journal.in_coin, &journal.out_coin),
+        ("energy", &journal.in_energy, &journal.out_energy),
+    ] {
+        if total(ins) != total(outs) {
+            return Err(format!(
+                "{}: the scenario's inputs sum to {}, the committed outputs to {}",
+                name,
+                total(ins),
+                total(outs)
+            ));
+        }
+    }
+    Ok(journal)
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// EXIT CODES AND LIMITS
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Exit code: an id appears twice in one book (see duplicate_id)
+pub const EXIT_DUPLICATE_ID: u8 = 1;
+
+/// Exit code: a role other than 0 / 1 / 2 (see unknown_role)
+pub const EXIT_UNKNOWN_ROLE: u8 = 2;
+
+/// Exit code: more than MAX_PARTICIPANTS rows in the input
+pub const EXIT_PARTICIPANT_LIMIT: u8 = 3;
+
+/// Exit code: more than MAX_EXPANDED_ORDERS orders in one book
+pub const EXIT_TOO_MANY_ORDERS: u8 = 4;
+
+/// Exit code: a settlement step left its integer range
+pub const EXIT_OVERFLOW: u8 = 5;
+
+/// Exit code: an allocation debits more coin or energy than a participant holds
+pub const EXIT_INSUFFICIENT_BALANCE: u8 = 6;
+
+/// Exit code: a book's largest possible settlement exceeds the i128 intermediates
+pub const EXIT_SETTLEMENT_BOUND: u8 = 7;
+
+/// Exit code: an internal invariant failed, e.g. a carried balance went missing
+pub const EXIT_INTERNAL: u8 = 8;
+
+/// Exit code: a journal failed its structural checks before commit
+pub const EXIT_MALFORMED_JOURNAL: u8 = 9;
+
+/// Exit code: an order quotes a price above config.max_price
+pub const EXIT_PRICE_ABOVE_MAX: u8 = 10;
+
+/// Exit code: a trader would pay above its bid or receive below its ask
+pub const EXIT_IRRATIONAL: u8 = 11;
+
+/// Exit code: a journal row is not reachable from its inputs by its allocation
+pub const EXIT_INFEASIBLE: u8 = 12;
+
+/// Exit code: the input has no participants
+pub const EXIT_EMPTY_MARKET: u8 = 13;
+
+/// Exit code: the trade list is longer than config.max_trades
+pub const EXIT_TOO_MANY_TRADES: u8 = 14;
+
+/// Exit code: a sealed bid does not match its commitment (see check_commitments)
+pub const EXIT_COMMITMENT_MISMATCH: u8 = 15;
+
+/// Exit code: the input combines features no clearing mode settles together
+pub const EXIT_UNSUPPORTED_MODE: u8 = 16;
+
+/// Exit code: KDouble pricing with a k_denominator of 0 or k above 1
+pub const EXIT_INVALID_K_DOUBLE: u8 = 17;
+
+/// Maximum participant rows, input.participants and every round's book together
+pub const MAX_PARTICIPANTS: usize = 1024;
+
+/// Maximum price-quantity steps read per participant (further steps ignored)
+pub const MAX_STEPS: usize = 8;
+
+/// Maximum virtual orders after step expansion (more is AuctionError::TooManyOrders)
+pub const MAX_EXPANDED_ORDERS: usize = 1024;
+
+/// Day-ahead markets clear 24 hourly products
+pub const MAX_PERIODS: usize = 24;
+
+impl AuctionError {
+    /// Whether a book's no-trade journal may stand in for this failure without config.strict
+    ///
+    /// These stop one book's clearing after its input was accepted, so every
+    /// row can keep its input balances. The others leave no journal a
+    /// verifier could trust (ids, roles, the participant limit, the
+    /// settlement bound, an empty market, a broken seal) or signal a lost
+    /// balance.
+    pub fn passes_through(&self) -> bool {
+        matches!(
+            self,
+            AuctionError::TooManyOrders(_)
+                | AuctionError::TooManyTrades(_)
+                | AuctionError::Overflow
+                | AuctionError::InsufficientCoin(..)
+                | AuctionError::InsufficientEnergy(..)
+                | AuctionError::MalformedJournal(_)
+                | AuctionError::Irrational(_)
+                | AuctionError::Infeasible(_)
+        )
+    }
+
+    /// Code the guest exits with (see the EXIT_* constants)
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            AuctionError::DuplicateId(_) => EXIT_DUPLICATE_ID,
+            AuctionError::InvalidRole(_) => EXIT_UNKNOWN_ROLE,
+            AuctionError::TooManyParticipants(_) => EXIT_PARTICIPANT_LIMIT,
+            AuctionError::TooManyOrders(_) => EXIT_TOO_MANY_ORDERS,
+            AuctionError::Overflow => EXIT_OVERFLOW,
+            AuctionError::InsufficientCoin(..) | AuctionError::InsufficientEnergy(..) => {
+                EXIT_INSUFFICIENT_BALANCE
+            }
+            AuctionError::SettlementBound => EXIT_SETTLEMENT_BOUND,
+            AuctionError::Internal(_) => EXIT_INTERNAL,
+            AuctionError::MalformedJournal(_) => EXIT_MALFORMED_JOURNAL,
+            AuctionError::PriceAboveMax(_) => EXIT_PRICE_ABOVE_MAX,
+            AuctionError::Irrational(_) => EXIT_IRRATIONAL,
+            AuctionError::Infeasible(_) => EXIT_INFEASIBLE,
+            AuctionError::EmptyMarket => EXIT_EMPTY_MARKET,
+            AuctionError::TooManyTrades(_) => EXIT_TOO_MANY_TRADES,
+            AuctionError::CommitmentMismatch(_) => EXIT_COMMITMENT_MISMATCH,
+            AuctionError::UnsupportedMode => EXIT_UNSUPPORTED_MODE,
+            AuctionError::InvalidKDouble => EXIT_INVALID_K_DOUBLE,
+        }
+    }
+}
+
+impl PublicJournal {
+    /// Bind a journal to the input and config it cleared, before commit
+    ///
+    /// round_id is copied verbatim from the config, so a settlement layer
+    /// can refuse a receipt proven for another round without the input.
+    pub fn bind(&mut self, input_digest: Digest, config_digest: Digest, round_id: u64) {
+        self.input_digest = input_digest;
+        self.config_digest = config_digest;
+        self.round_id = round_id;
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// SHARED PROTOCOL FUNCTIONS
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// What the guest computes from an input and the host must compute alike:
+// the layout a book selects, its settlement bound and bid commitments
+// before proving, and the digests a journal commits after it. Hashes use
+// the zkVM SHA accelerator in the guest and software SHA-256 natively.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Whether any participant bids per period (the PeriodsJournal layout)
+pub fn is_multi_period(participants: &[Participant]) -> bool {
+    participants.iter().any(|p| !p.periods.is_empty())
+}
+
+/// Periods a multi-period book clears: its longest list, at most MAX_PERIODS
+pub fn period_count(participants: &[Participant]) -> usize {
+    participants
+        .iter()
+        .map(|p| p.periods.len())
+        .max()
+        .unwrap_or(0)
+        .min(MAX_PERIODS)
+}
+
+/// One period's book: each bid for that period, quantity 0 past a participant's last entry
+pub fn period_participants(participants: &[Participant], period: usize) -> Vec<Participant> {
+    participants
+        .iter()
+        .map(|p| {
+            let (price, quantity) = p.periods.get(period).copied().unwrap_or((p.price, 0));
+            Participant {
+                price,
+                quantity,
+                periods: Vec::new(),
+                ..p.clone()
+            }
+        })
+        .collect()
+}
+
+/// Whether participants span more than one commodity (the multi-commodity layout)
+pub fn is_multi_commodity(participants: &[Participant]) -> bool {
+    participants
+        .iter()
+        .any(|p| p.commodity != participants[0].commodity)
+}
+
+/// Whether participants span more than one zone (the zonal layout)
+pub fn is_zonal(participants: &[Participant]) -> bool {
+    participants.iter().any(|p| p.zone != participants[0].zone)
+}
+
+/// Whether any participant declares carbon data (a CarbonJournal section follows)
+pub fn is_carbon_priced(participants: &[Participant]) -> bool {
+    participants
+        .iter()
+        .any(|p| p.carbon_per_unit > 0 || p.carbon_budget.is_some())
+}
+
+/// Basis points in one unit (fee_bps = 10_000 is a 100% fee)
+pub const BPS_DENOMINATOR: u128 = 10_000;
+
+/// Every price an order quotes: bid or ask, prosumer ask, steps and periods
+pub fn quoted_prices(p: &Participant) -> impl Iterator<Item = u64> + '_ {
+    let quoted = p.steps.iter().chain(&p.periods).map(|(price, _)| *price);
+    [p.price, p.sell_price].into_iter().chain(quoted)
+}
+
+/// Largest coin amount one book can settle, fees included
+///
+/// The highest price quoted anywhere in the book (bids, asks, steps,
+/// periods, prosumer asks) times the smaller side's total quantity (steps,
+/// budgets and bundle capacity included; a period row counts its largest
+/// hourly quantity), plus the larger of the trade and funding fees on it.
+/// Every settlement intermediate (a notional, a fee, a side's total, VCG
+/// welfare) is at most this amount, so a book whose bound fits i128 settles
+/// exactly: a balance is either committed or reported as Overflow when it
+/// leaves u64, never wrapped.
+///
+/// Returns: None if the bound overflows u128
+pub fn settlement_bound(participants: &[Participant], config: &AuctionConfig) -> Option<u128> {
+    let price = participants
+        .iter()
+        .flat_map(quoted_prices)
+        .max()
+        .unwrap_or(0);
+
+    let (mut demand, mut supply) = (0u128, 0u128); // Sums of u64 fields, far below u128::MAX
+    for p in participants {
+        let stepped: u128 = p.steps.iter().map(|(_, quantity)| *quantity as u128).sum();
+        let hourly = p
+            .periods
+            .iter()
+            .map(|(_, quantity)| *quantity)
+            .max()
+            .unwrap_or(0);
+        let quantity = stepped.max(p.quantity.max(hourly) as u128) + p.capacity as u128;
+        match p.role {
+            0 => demand += quantity.max(p.budget as u128),
+            1 => supply += quantity,
+            _ => {
+                demand += quantity;
+                supply += p.sell_quantity as u128;
+            }
+        }
+    }
+
+    let notional = (price as u128).checked_mul(demand.min(supply))?;
+    let fee_bps = config.fee_bps.max(config.funding_fee_bps) as u128;
+    notional.checked_add(notional.checked_mul(fee_bps)? / BPS_DENOMINATOR)
+}
+
+/// SHA-256 of the input's risc0 serde words (the bytes the host writes)
+///
+/// Binds the receipt to the exact book it cleared: another participant set
+/// with the same aggregate balances hashes differently. Serializing the
+/// input cannot fail; if it did, the digest of no words would not match the
+/// host's.
+pub fn input_digest(input: &AuctionInput) -> Digest {
+    let words = risc0_zkvm::serde::to_vec(input).unwrap_or_default();
+    *Impl::hash_words(&words)
+}
+
+/// SHA-256 of the config's risc0 serde words alone, as input_digest hashes the input
+///
+/// Names the mechanism's parameters (algorithm, pricing rule, fees, ...)
+/// apart from the book, so a verifier can check them against a config it
+/// expects without knowing the participants; any field change changes it.
+pub fn config_digest(config: &AuctionConfig) -> Digest {
+    let words = risc0_zkvm::serde::to_vec(config).unwrap_or_default();
+    *Impl::hash_words(&words)
+}
+
+/// SHA-256(salt || id || role || price || quantity), little-endian
+pub fn bid_commitment(p: &Participant) -> Digest {
+    let mut bytes = [0u8; 56];
+    bytes[..32].copy_from_slice(&p.salt);
+    bytes[32..36].copy_from_slice(&p.id.to_le_bytes());
+    bytes[36..40].copy_from_slice(&p.role.to_le_bytes());
+    bytes[40..48].copy_from_slice(&p.price.to_le_bytes());
+    bytes[48..].copy_from_slice(&p.quantity.to_le_bytes());
+    sha256(&bytes)
+}
+
+/// private_outputs.json: the rows behind a PrivateJournal's outputs_digest
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrivateOutputsFile {
+    pub outputs_digest: String, // Hex, as committed in the PrivateJournal
+    pub salt: String,           // Hex output_salt, which opens the digest
+    pub rows: Vec<PrivateRow>,  // Protocol order, as in PublicJournal
+}
+
+/// One row under a PrivateJournal's outputs_digest
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrivateRow {
+    pub id: u32,
+    pub in_coin: u64,
+    pub in_energy: u64,
+    pub out_coin: u64,
+    pub out_energy: u64,
+}
+
+/// A journal's rows as the outputs digest covers them, in protocol order
+pub fn private_rows(journal: &PublicJournal) -> Vec<PrivateRow> {
+    (0..journal.ids.len())
+        .map(|row| PrivateRow {
+            id: journal.ids[row],
+            in_coin: journal.in_coin[row],
+            in_energy: journal.in_energy[row],
+            out_coin: journal.out_coin[row],
+            out_energy: journal.out_energy[row],
+        })
+        .collect()
+}
+
+/// SHA-256(salt || rows), each row id || in_coin || in_energy || out_coin || out_energy
+pub fn private_outputs_digest(salt: &[u8], rows: &[PrivateRow]) -> Digest {
+    let mut bytes = salt.to_vec();
+    for row in rows {
+        bytes.extend_from_slice(&row.id.to_le_bytes());
+        for value in [row.in_coin, row.in_energy, row.out_coin, row.out_energy] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    sha256(&bytes)
+}
+
+/// Rate of Keccak-256 in bytes (1600-bit state, 512-bit capacity)
+pub const KECCAK_RATE: usize = 136;
+
+/// Keccak-256 as the EVM computes it: the original Keccak padding, not SHA3-256's
+pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    let mut padded = bytes.to_vec();
+    padded.push(0x01);
+    padded.resize(padded.len().div_ceil(KECCAK_RATE) * KECCAK_RATE, 0);
+    if let Some(last) = padded.last_mut() {
+        *last |= 0x80;
+    }
+    let mut state = [0u64; 25];
+    for block in padded.chunks(KECCAK_RATE) {
+        for (lane, word) in state.iter_mut().zip(block.chunks(8)) {
+            *lane ^= word
+                .iter()
+                .rev()
+                .fold(0u64, |lane, &byte| lane << 8 | byte as u64);
+        }
+        keccak::f1600(&mut state);
+    }
+    let mut digest = [0u8; 32];
+    for (out, lane) in digest.chunks_mut(8).zip(&state) {
+        out.copy_from_slice(&lane.to_le_bytes());
+    }
+    digest
+}
+
+/// The audit vectors must follow the journal's blocks in price priority
+///
+/// Each side holds one price and one quantity per row of its block, bids
+/// non-increasing and asks non-decreasing, each the stated quote of the
+/// participant its row names. MalformedJournal names the first row that
+/// differs, or the row count when a vector's length is not its block's.
+pub fn check_audit(
+    journal: &PublicJournal,
+    audit: &AuditJournal,
+    participants: &[Participant],
+) -> Result<(), AuctionError> {
+    let buyers = journal.buyer_count as usize;
+    let sellers = journal.seller_count as usize;
+    let rows = journal.ids.len();
+    if audit.bid_prices.len() != buyers
+        || audit.bid_quantities.len() != buyers
+        || audit.ask_prices.len() != sellers
+        || audit.ask_quantities.len() != sellers
+        || buyers + sellers > rows
+    {
+        return Err(AuctionError::MalformedJournal(rows));
+    }
+    let bids = audit.bid_prices.iter().zip(&audit.bid_quantities);
+    let asks = audit.ask_prices.iter().zip(&audit.ask_quantities);
+    for (row, (&price, &quantity)) in bids.chain(asks).enumerate() {
+        let id = journal.ids[row];
+        let stated = participants
+            .iter()
+            .find(|p| p.id == id)
+            .map(|p| (p.price, p.quantity));
+        let ranked = match row {
+            0 => true,
+            row if row < buyers => audit.bid_prices[row - 1] >= price,
+            row if row == buyers => true,
+            row => audit.ask_prices[row - buyers - 1] <= price,
+        };
+        if stated != Some((price, quantity)) || !ranked {
+            return Err(AuctionError::MalformedJournal(row));
+        }
+    }
+    Ok(())
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// OUTPUTS TREE
+// ═══════════════════════════════════════════════════════════════════════════
+//
+// The Merkle tree over the rows of a compact journal, as outputs.json carries
+// it: the host builds it from the rows the guest wrote and checks it against
+// the committed outputs_root, and a participant folds its own path up to it.
+//
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// outputs.json of a compact journal: every row as a leaf under outputs_root
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutputsFile {
+    pub outputs_root: String, // Hex, as committed in the CompactJournal
+    pub leaves: Vec<OutputLeaf>,
+}
+
+/// One row of a compact journal with the path that proves it against the root
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutputLeaf {
+    pub row: usize, // Protocol order, as in PublicJournal
+    pub id: u32,
+    pub out_coin: u64,
+    pub out_energy: u64,
+    pub leaf: String,          // Hex SHA-256(0x00 || id || out_coin || out_energy)
+    pub path: Vec<MerkleStep>, // Siblings from the leaf up to the root
+}
+
+/// A sibling on a Merkle path (levels where the node has none are skipped)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MerkleStep {
+    pub sibling: String, // Hex
+    pub left: bool,      // The sibling is the left child: node = SHA-256(0x01 || sibling || node)
+}
+
+/// SHA-256 of the bytes (the zkVM accelerator in the guest, software natively)
+fn sha256(bytes: &[u8]) -> Digest {
+    *Impl::hash_bytes(bytes)
+}
+
+/// Leaf of one row of the outputs tree: SHA-256(0x00 || id || out_coin || out_energy)
+pub fn output_leaf(id: u32, out_coin: u64, out_energy: u64) -> Digest {
+    let mut bytes = [0u8; 21];
+    bytes[1..5].copy_from_slice(&id.to_le_bytes());
+    bytes[5..13].copy_from_slice(&out_coin.to_le_bytes());
+    bytes[13..].copy_from_slice(&out_energy.to_le_bytes());
+    sha256(&bytes)
+}
+
+/// Inner node of the outputs tree: SHA-256(0x01 || left || right)
+pub fn merkle_node(left: &Digest, right: &Digest) -> Digest {
+    let mut bytes = [1u8; 65];
+    bytes[1..33].copy_from_slice(left.as_bytes());
+    bytes[33..].copy_from_slice(right.as_bytes());
+    sha256(&bytes)
+}
+
+/// Every level of the outputs tree, leaves first and the root last
+///
+/// As in the guest's outputs_root, a node without a sibling moves up a
+/// level unchanged.
+pub fn merkle_levels(leaves: Vec<Digest>) -> Vec<Vec<Digest>> {
+    let mut levels = vec![leaves];
+    while levels[levels.len() - 1].len() > 1 {
+        let next = levels[levels.len() - 1]
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => merkle_node(left, right),
+                _ => pair[0],
+            })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+/// Siblings of one leaf from the bottom level up (levels where it has none are skipped)
+pub fn merkle_path(levels: &[Vec<Digest>], mut index: usize) -> Vec<MerkleStep> {
+    let mut path = Vec::new();
+    for level in &levels[..levels.len() - 1] {
+        let sibling = index ^ 1;
+        if sibling < level.len() {
+            path.push(MerkleStep {
+                sibling: level[sibling].to_string(),
+                left: sibling < index,
+            });
+        }
+        index /= 2;
+    }
+    path
+}
+
+/// Whether a hex leaf and its path fold up to the root; hex that does not parse fails
+pub fn verify_merkle_path(leaf: &str, path: &[MerkleStep], root: &Digest) -> bool {
+    let parse = |hex: &str| Digest::try_from(hex::decode(hex).ok()?.as_slice()).ok();
+    let Some(mut node) = parse(leaf) else {
+        return false;
+    };
+    for step in path {
+        let Some(sibling) = parse(&step.sibling) else {
+            return false;
+        };
+        node = if step.left {
+            merkle_node(&sibling, &node)
+        } else {
+            merkle_node(&node, &sibling)
+        };
+    }
+    node == *root
+}
+
+/// The leaves and paths of the rows the guest wrote, which must hash to the committed root
+///
+/// The rows come over the guest's stdout, outside the proof: only a match
+/// with outputs_root (and the committed counts and totals) makes them the
+/// rows the receipt proves. The error says how the rows differ.
+pub fn outputs_file_of(
+    compact: &CompactJournal,
+    rows: &PublicJournal,
+) -> Result<OutputsFile, String> {
+    let leaves: Vec<Digest> = (0..rows.ids.len())
+        .map(|row| output_leaf(rows.ids[row], rows.out_coin[row], rows.out_energy[row]))
+        .collect();
+    let levels = merkle_levels(leaves);
+    let root = levels[levels.len() - 1]
+        .first()
+        .copied()
+        .unwrap_or(Digest::ZERO);
+    if root != compact.outputs_root {
+        return Err(format!(
+            "hash to {}, not to the committed outputs_root {}",
+            root, compact.outputs_root
+        ));
+    }
+    let totals = (
+        rows.out_coin.iter().map(|&coin| coin as u128).sum::<u128>(),
+        rows.out_energy
+            .iter()
+            .map(|&energy| energy as u128)
+            .sum::<u128>(),
+    );
+    if rows.ids.len() != compact.row_count as usize
+        || (rows.buyer_count, rows.seller_count) != (compact.buyer_count, compact.seller_count)
+        || totals != (compact.total_coin, compact.total_energy)
+    {
+        return Err("do not match the committed counts and totals".into());
+    }
+    Ok(OutputsFile {
+        outputs_root: compact.outputs_root.to_string(),
+        leaves: (0..rows.ids.len())
+            .map(|row| OutputLeaf {
+                row,
+                id: rows.ids[row],
+                out_coin: rows.out_coin[row],
+                out_energy: rows.out_energy[row],
+                leaf: levels[0][row].to_string(),
+                path: merkle_path(&levels, row),
+            })
+            .collect(),
+    })
+}